@@ -0,0 +1,194 @@
+use serde::Serialize;
+
+use crate::tempo;
+
+/// Sample rate both takes are decoded at, so their peaks line up bucket for
+/// bucket.
+pub const COMPARE_SAMPLE_RATE: u32 = 22050;
+
+/// Largest offset between two takes `compare_waveforms` searches unless told
+/// otherwise, in seconds.
+pub const DEFAULT_MAX_OFFSET_SECS: f64 = 10.0;
+
+/// Length of the frames the onset envelopes are measured over, in seconds.
+/// It limits how finely the offset is found, before interpolation.
+const FRAME_SECS: f64 = 0.005;
+
+/// Frames on either side the onset envelope is averaged over.
+const SMOOTHING_FRAMES: usize = 2;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Alignment {
+    /// Seconds by which the second take lags the first: what happens at `t`
+    /// in the first happens at `t + offset` in the second. Negative when the
+    /// second take is ahead.
+    pub offset: f64,
+    /// How alike the takes' onsets are at `offset`, from 0 (unrelated) to 1
+    /// (the same).
+    pub confidence: f64,
+}
+
+/// Measures the rise in log energy from each frame to the next, which peaks
+/// where notes start, with the mean taken out so silence doesn't correlate.
+///
+/// A short moving average spreads each onset over a few frames, so onsets
+/// that fall at different points within a frame in the two takes still
+/// overlap.
+fn onset_envelope(samples: &[f32], frame: usize) -> Vec<f64> {
+    let log_energy: Vec<f64> = samples
+        .chunks_exact(frame)
+        .map(|chunk| {
+            let energy = chunk.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / frame as f64;
+            (1.0 + 1e4 * energy).ln()
+        })
+        .collect();
+    let rises: Vec<f64> = log_energy
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).max(0.0))
+        .collect();
+    let smoothed: Vec<f64> = (0..rises.len())
+        .map(|i| {
+            let window = &rises
+                [i.saturating_sub(SMOOTHING_FRAMES)..(i + SMOOTHING_FRAMES + 1).min(rises.len())];
+            window.iter().sum::<f64>() / window.len() as f64
+        })
+        .collect();
+    let mean = smoothed.iter().sum::<f64>() / smoothed.len().max(1) as f64;
+    smoothed.into_iter().map(|rise| rise - mean).collect()
+}
+
+/// Finds the offset between two takes of the same music, mono `first` and
+/// `second` at `sample_rate`, searching up to `max_offset` seconds either
+/// way.
+///
+/// The onset envelopes of the takes are cross-correlated, so the takes need
+/// not sound the same, only start their notes at the same places. The best
+/// lag is refined between frames by fitting a parabola through its
+/// neighbours. Takes too short or quiet to compare give an offset of 0 with
+/// a confidence of 0.
+pub fn estimate_offset(
+    first: &[f32],
+    second: &[f32],
+    sample_rate: u32,
+    max_offset: f64,
+) -> Alignment {
+    let unknown = Alignment {
+        offset: 0.0,
+        confidence: 0.0,
+    };
+    let frame = ((sample_rate as f64 * FRAME_SECS).round() as usize).max(1);
+    let a = onset_envelope(first, frame);
+    let b = onset_envelope(second, frame);
+    let energy = |values: &[f64]| values.iter().map(|v| v * v).sum::<f64>();
+    let scale = (energy(&a) * energy(&b)).sqrt();
+    if a.len() < 3 || b.len() < 3 || scale <= f64::EPSILON {
+        return unknown;
+    }
+
+    // correlation[lag] = sum of a[i] * b[i + lag], with negative lags
+    // wrapped to the end. Padding to the combined length keeps the circular
+    // correlation from mixing up positive and negative lags.
+    let n = (a.len() + b.len()).next_power_of_two();
+    let (mut a_re, mut a_im) = (a.clone(), vec![0.0; n]);
+    let (mut b_re, mut b_im) = (b.clone(), vec![0.0; n]);
+    a_re.resize(n, 0.0);
+    b_re.resize(n, 0.0);
+    tempo::fft(&mut a_re, &mut a_im);
+    tempo::fft(&mut b_re, &mut b_im);
+    // conj(A) * B, conjugated so the forward transform inverts it.
+    let mut re: Vec<f64> = (0..n)
+        .map(|k| a_re[k] * b_re[k] + a_im[k] * b_im[k])
+        .collect();
+    let mut im: Vec<f64> = (0..n)
+        .map(|k| -(a_re[k] * b_im[k] - a_im[k] * b_re[k]))
+        .collect();
+    tempo::fft(&mut re, &mut im);
+    let correlation = |lag: i64| re[lag.rem_euclid(n as i64) as usize] / n as f64;
+
+    let frame_secs = frame as f64 / sample_rate as f64;
+    let reach = (max_offset.max(0.0) / frame_secs).floor() as i64;
+    let max_lag = reach.min(b.len() as i64 - 1);
+    let min_lag = -reach.min(a.len() as i64 - 1);
+    let best = (min_lag..=max_lag)
+        .max_by(|x, y| correlation(*x).total_cmp(&correlation(*y)))
+        .unwrap_or(0);
+    let peak = correlation(best);
+    if peak <= 0.0 {
+        return unknown;
+    }
+
+    let shift = if best > min_lag && best < max_lag {
+        let (before, after) = (correlation(best - 1), correlation(best + 1));
+        let curvature = before - 2.0 * peak + after;
+        if curvature < 0.0 {
+            0.5 * (before - after) / curvature
+        } else {
+            0.0
+        }
+    } else {
+        0.0
+    };
+    Alignment {
+        offset: (best as f64 + shift) * frame_secs,
+        confidence: (peak / scale).clamp(0.0, 1.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decaying notes at irregular times, so no lag but the right one
+    /// lines them all up.
+    fn take(seconds: f64) -> Vec<f32> {
+        let rate = COMPARE_SAMPLE_RATE as f64;
+        let onsets = [0.3, 0.9, 1.25, 2.1, 2.4, 3.35, 4.0, 4.2, 5.15];
+        (0..(rate * seconds) as usize)
+            .map(|i| {
+                let t = i as f64 / rate;
+                onsets
+                    .iter()
+                    .filter(|&&onset| onset <= t)
+                    .map(|onset| {
+                        let since = t - onset;
+                        0.3 * (-since * 8.0).exp()
+                            * (2.0 * std::f64::consts::PI * 330.0 * since).sin()
+                    })
+                    .sum::<f64>() as f32
+            })
+            .collect()
+    }
+
+    fn delayed(samples: &[f32], seconds: f64) -> Vec<f32> {
+        let mut delayed = vec![0.0; (seconds * COMPARE_SAMPLE_RATE as f64) as usize];
+        delayed.extend_from_slice(samples);
+        delayed
+    }
+
+    #[test]
+    fn finds_the_delay_of_a_copy() {
+        let original = take(6.0);
+        let late = delayed(&original, 0.437);
+        let alignment = estimate_offset(&original, &late, COMPARE_SAMPLE_RATE, 2.0);
+        assert!((alignment.offset - 0.437).abs() < 0.005, "{:?}", alignment);
+        assert!(alignment.confidence > 0.9, "{:?}", alignment);
+
+        let alignment = estimate_offset(&late, &original, COMPARE_SAMPLE_RATE, 2.0);
+        assert!((alignment.offset + 0.437).abs() < 0.005, "{:?}", alignment);
+    }
+
+    #[test]
+    fn only_searches_up_to_the_max_offset() {
+        let original = take(6.0);
+        let late = delayed(&original, 1.5);
+        let alignment = estimate_offset(&original, &late, COMPARE_SAMPLE_RATE, 0.5);
+        assert!(alignment.offset.abs() <= 0.5, "{:?}", alignment);
+    }
+
+    #[test]
+    fn silence_cannot_be_aligned() {
+        let silence = vec![0.0; COMPARE_SAMPLE_RATE as usize];
+        let alignment = estimate_offset(&take(2.0), &silence, COMPARE_SAMPLE_RATE, 1.0);
+        assert_eq!((alignment.offset, alignment.confidence), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,71 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+
+use crate::error::AppError;
+
+/// Largest embedded picture we are willing to load into memory.
+pub const MAX_COVER_ART_BYTES: usize = 10 * 1024 * 1024;
+
+/// Width video frames are scaled down to unless the caller asks otherwise.
+pub const DEFAULT_FRAME_WIDTH: u32 = 640;
+
+/// Widest frame `extract_frame` returns, 4K UHD.
+pub const MAX_FRAME_WIDTH: u32 = 3840;
+
+/// Most thumbnails `generate_timeline_thumbnails` takes in one call.
+pub const MAX_TIMELINE_THUMBNAILS: u32 = 500;
+
+/// Widest timeline thumbnail; larger ones make for an unwieldy sprite sheet.
+pub const MAX_THUMBNAIL_WIDTH: u32 = 640;
+
+/// Thumbnails per row of a sprite sheet.
+pub const SPRITE_COLUMNS: u32 = 10;
+
+/// Timeline thumbnails, either as separate images or tiled into one PNG.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TimelineThumbnails {
+    /// JPEG `data:` URLs in timeline order.
+    Frames { thumbnails: Vec<String> },
+    /// A PNG with `count` thumbnails laid out left to right, top to bottom,
+    /// in a grid `columns` wide.
+    Sprite {
+        path: String,
+        count: u32,
+        columns: u32,
+        rows: u32,
+    },
+}
+
+/// Returns `count` timestamps spaced `duration / count` apart from the start,
+/// so each thumbnail shows the beginning of an equal share of the file.
+pub fn thumbnail_times(duration: f64, count: u32) -> Vec<f64> {
+    let interval = duration / count as f64;
+    (0..count).map(|index| index as f64 * interval).collect()
+}
+
+/// Columns and rows of a sprite sheet holding `count` thumbnails.
+pub fn sprite_grid(count: u32) -> (u32, u32) {
+    let columns = count.clamp(1, SPRITE_COLUMNS);
+    (columns, count.div_ceil(columns).max(1))
+}
+
+/// Identifies an image from its leading magic bytes.
+pub fn image_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some("image/png")
+    } else {
+        None
+    }
+}
+
+/// Encodes an image as a `data:` URL the frontend can use as an `<img>` src.
+pub fn to_data_url(bytes: &[u8]) -> Result<String, AppError> {
+    let mime = image_mime(bytes).ok_or_else(|| {
+        AppError::ParseFailed("Embedded picture is neither JPEG nor PNG".to_string())
+    })?;
+    Ok(format!("data:{};base64,{}", mime, STANDARD.encode(bytes)))
+}
@@ -0,0 +1,60 @@
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+use tokio::sync::Notify;
+
+/// Payload of the `batch-state` event sent when batches are paused or
+/// resumed.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BatchState {
+    pub paused: bool,
+}
+
+/// Managed state letting the user pause batch work, e.g. to free the CPU
+/// for a call.
+///
+/// Batch workers call [`BatchController::wait_if_paused`] before starting
+/// each file, so jobs that are already running finish and only new ones are
+/// held back. Batches started while paused wait as well.
+#[derive(Default)]
+pub struct BatchController {
+    paused: AtomicBool,
+    resumed: Notify,
+}
+
+impl BatchController {
+    pub fn state(&self) -> BatchState {
+        BatchState {
+            paused: self.paused.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Holds back new batch jobs. Returns `false` if already paused.
+    pub fn pause(&self) -> bool {
+        !self.paused.swap(true, Ordering::SeqCst)
+    }
+
+    /// Lets waiting batch jobs start. Returns `false` if not paused.
+    pub fn resume(&self) -> bool {
+        let was_paused = self.paused.swap(false, Ordering::SeqCst);
+        if was_paused {
+            self.resumed.notify_waiters();
+        }
+        was_paused
+    }
+
+    /// Returns once batches aren't paused, straight away if they aren't now.
+    pub async fn wait_if_paused(&self) {
+        loop {
+            // Register for the wakeup before checking the flag, so a resume
+            // in between isn't missed.
+            let mut resumed = pin!(self.resumed.notified());
+            resumed.as_mut().enable();
+            if !self.paused.load(Ordering::SeqCst) {
+                return;
+            }
+            resumed.await;
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use serde::Serialize;
+
+use crate::error::AppError;
+
+/// Most lines [`grid_lines`] returns, about an hour of sixteenths at 400 BPM.
+pub const MAX_GRID_LINES: usize = 100_000;
+
+/// One line of a beat grid.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GridLine {
+    pub time: f64,
+    /// Whether the line falls on a beat rather than between beats.
+    pub beat: bool,
+    /// Whether the line is the first beat of a bar.
+    pub downbeat: bool,
+}
+
+/// Returns the spacing in seconds of a grid with `subdivision` lines per beat
+/// at `bpm`.
+pub fn grid_step(bpm: f64, subdivision: u32) -> Result<f64, AppError> {
+    if !bpm.is_finite() || bpm <= 0.0 {
+        return Err(AppError::InvalidInput(format!(
+            "Tempo must be a positive BPM, got {}",
+            bpm
+        )));
+    }
+    if subdivision == 0 {
+        return Err(AppError::InvalidInput(
+            "Subdivision must be at least 1".to_string(),
+        ));
+    }
+    Ok(60.0 / bpm / subdivision as f64)
+}
+
+/// Snaps `time` to the nearest line of a grid spaced `step` seconds apart
+/// that passes through `offset`. The grid extends in both directions, so an
+/// offset later than `time`, or further away than a whole step, still works.
+/// The result is never negative.
+pub fn snap(time: f64, step: f64, offset: f64) -> f64 {
+    let phase = offset.rem_euclid(step);
+    let snapped = phase + ((time - phase) / step).round() * step;
+    if snapped < 0.0 {
+        snapped + step
+    } else {
+        snapped
+    }
+}
+
+/// Snaps a loop's `start` and `end` to the beat grid described by `bpm`,
+/// the time of a downbeat `offset` and `subdivision` lines per beat (1 for
+/// quarter notes at 4/4, 4 for sixteenths).
+///
+/// Fails if both ends land on the same grid line, since that would leave an
+/// empty loop.
+pub fn quantize(
+    start: f64,
+    end: f64,
+    bpm: f64,
+    offset: f64,
+    subdivision: u32,
+) -> Result<(f64, f64), AppError> {
+    let step = grid_step(bpm, subdivision)?;
+    if !offset.is_finite() {
+        return Err(AppError::InvalidInput(format!(
+            "Beat offset must be a number, got {}",
+            offset
+        )));
+    }
+
+    let snapped = (snap(start, step, offset), snap(end, step, offset));
+    // Half a step comfortably separates distinct grid lines from float noise.
+    if snapped.1 - snapped.0 < step / 2.0 {
+        return Err(AppError::InvalidRange(format!(
+            "Both ends of the loop ({} - {}) snap to the same beat at {:.3}s",
+            start, end, snapped.0
+        )));
+    }
+    Ok(snapped)
+}
+
+/// Shrinks `start..end` to the grid lines just inside it, like [`quantize`]
+/// but rounding the start up and the end down, so the result never reaches
+/// past the original range.
+///
+/// Fails if no whole grid step fits inside the range.
+pub fn quantize_within(
+    start: f64,
+    end: f64,
+    bpm: f64,
+    offset: f64,
+    subdivision: u32,
+) -> Result<(f64, f64), AppError> {
+    let step = grid_step(bpm, subdivision)?;
+    if !offset.is_finite() {
+        return Err(AppError::InvalidInput(format!(
+            "Beat offset must be a number, got {}",
+            offset
+        )));
+    }
+
+    // As in grid_lines, ends a hair off a line still land on it.
+    let tolerance = step * 1e-9;
+    let first = ((start - offset - tolerance) / step).ceil();
+    let last = ((end - offset + tolerance) / step).floor();
+    if last <= first {
+        return Err(AppError::InvalidRange(format!(
+            "No whole step of the grid ({:.3}s) fits inside {} - {}",
+            step, start, end
+        )));
+    }
+    Ok(((offset + first * step).max(0.0), offset + last * step))
+}
+
+/// Lists the lines between `start` and `end` inclusive of a grid at `bpm`
+/// with `subdivision` lines per beat, whose downbeats are `beats_per_bar`
+/// beats apart starting at `offset`.
+///
+/// Lines are counted from `offset` and each time is computed from its index,
+/// so float error doesn't build up over a long range.
+pub fn grid_lines(
+    bpm: f64,
+    offset: f64,
+    start: f64,
+    end: f64,
+    subdivision: u32,
+    beats_per_bar: u32,
+) -> Result<Vec<GridLine>, AppError> {
+    let step = grid_step(bpm, subdivision)?;
+    if !offset.is_finite() {
+        return Err(AppError::InvalidInput(format!(
+            "Beat offset must be a number, got {}",
+            offset
+        )));
+    }
+    if beats_per_bar == 0 {
+        return Err(AppError::InvalidInput(
+            "A bar must have at least 1 beat".to_string(),
+        ));
+    }
+
+    // Lines a hair outside the range still count, so ends that are exact
+    // multiples of the step aren't lost to rounding.
+    let tolerance = step * 1e-9;
+    let first = ((start - offset - tolerance) / step).ceil() as i64;
+    let last = ((end - offset + tolerance) / step).floor() as i64;
+    let count = (last - first + 1).max(0) as usize;
+    if count > MAX_GRID_LINES {
+        return Err(AppError::InvalidRange(format!(
+            "The range holds {} grid lines, more than the {} allowed. Pick a shorter range or a coarser grid.",
+            count, MAX_GRID_LINES
+        )));
+    }
+
+    let per_beat = subdivision as i64;
+    let per_bar = per_beat * beats_per_bar as i64;
+    Ok((first..=last)
+        .map(|index| GridLine {
+            time: offset + index as f64 * step,
+            beat: index.rem_euclid(per_beat) == 0,
+            downbeat: index.rem_euclid(per_bar) == 0,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-9 && (actual.1 - expected.1).abs() < 1e-9,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn snaps_to_whole_beats() {
+        // 120 BPM puts a beat every half second.
+        assert_close(quantize(1.2, 3.9, 120.0, 0.0, 1).unwrap(), (1.0, 4.0));
+    }
+
+    #[test]
+    fn snaps_to_subdivisions_around_the_offset() {
+        // Sixteenths at 120 BPM are 0.125s apart, shifted by the offset.
+        assert_close(quantize(1.2, 3.9, 120.0, 0.05, 4).unwrap(), (1.175, 3.925));
+    }
+
+    #[test]
+    fn the_grid_wraps_around_a_late_offset() {
+        // A downbeat at 10.1s still places beats at 0.1, 0.6, 1.1...
+        assert_close(quantize(0.7, 1.9, 120.0, 10.1, 1).unwrap(), (0.6, 2.1));
+        // The grid line before zero is skipped rather than going negative.
+        assert_close(quantize(0.0, 1.0, 120.0, 0.4, 1).unwrap(), (0.4, 0.9));
+    }
+
+    #[test]
+    fn refuses_ends_on_the_same_beat() {
+        assert!(matches!(
+            quantize(1.1, 1.2, 120.0, 0.0, 1),
+            Err(AppError::InvalidRange(_))
+        ));
+    }
+
+    #[test]
+    fn refuses_an_invalid_grid() {
+        assert!(quantize(0.0, 4.0, 0.0, 0.0, 1).is_err());
+        assert!(quantize(0.0, 4.0, 120.0, 0.0, 0).is_err());
+        assert!(quantize(0.0, 4.0, 120.0, f64::NAN, 1).is_err());
+    }
+
+    fn line(time: f64, beat: bool, downbeat: bool) -> GridLine {
+        GridLine {
+            time,
+            beat,
+            downbeat,
+        }
+    }
+
+    #[test]
+    fn tags_beats_and_downbeats() {
+        // 120 BPM in 2/4 with eighth notes: a line every 0.25s.
+        assert_eq!(
+            grid_lines(120.0, 0.0, 0.0, 1.25, 2, 2).unwrap(),
+            [
+                line(0.0, true, true),
+                line(0.25, false, false),
+                line(0.5, true, false),
+                line(0.75, false, false),
+                line(1.0, true, true),
+                line(1.25, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_bars_from_the_offset_in_both_directions() {
+        // The downbeat at 2s also puts one at 0s, two bars of 2/4 earlier.
+        let lines = grid_lines(120.0, 2.0, 0.0, 2.0, 1, 2).unwrap();
+        let downbeats: Vec<f64> = lines
+            .iter()
+            .filter(|l| l.downbeat)
+            .map(|l| l.time)
+            .collect();
+        assert_eq!(downbeats, [0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn non_integer_tempos_do_not_drift() {
+        let bpm = 123.7;
+        let step = 60.0 / bpm;
+        let lines = grid_lines(bpm, 0.3, 0.0, 3600.0, 1, 4).unwrap();
+        assert_eq!(lines.len(), ((3600.0 - 0.3) / step).floor() as usize + 1);
+        let last = lines.last().unwrap();
+        let index = (lines.len() - 1) as f64;
+        assert!((last.time - (0.3 + index * step)).abs() < 1e-9);
+        assert_eq!(last.downbeat, (lines.len() - 1).is_multiple_of(4));
+    }
+
+    #[test]
+    fn refuses_too_many_lines() {
+        assert!(matches!(
+            grid_lines(400.0, 0.0, 0.0, 36_000.0, 16, 4),
+            Err(AppError::InvalidRange(_))
+        ));
+    }
+}
@@ -0,0 +1,457 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::key::KeyEstimate;
+use crate::pcm::PcmFormat;
+use crate::tempo::TempoEstimate;
+use crate::util;
+use crate::Chapter;
+
+const ANALYSIS_CACHE_DIR: &str = "analysis";
+const CHAPTER_CACHE_DIR: &str = "chapters";
+const WAVEFORM_CACHE_DIR: &str = "waveforms";
+
+/// Bumped whenever chapter extraction changes, so entries written by an
+/// older version are re-extracted instead of served.
+const CHAPTER_CACHE_VERSION: u32 = 4;
+
+/// Bumped whenever tempo or key detection changes, so older results are
+/// recomputed.
+const ANALYSIS_CACHE_VERSION: u32 = 1;
+
+/// Identifies one version of a media file on disk.
+///
+/// Any change to the file's size or modification time produces a different
+/// fingerprint, so stale entries are never served after the file is edited.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FileFingerprint {
+    pub path: String,
+    pub size: u64,
+    pub mtime_nanos: u128,
+}
+
+impl FileFingerprint {
+    pub fn of(path: &str) -> Result<Self, AppError> {
+        let metadata = fs::metadata(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                AppError::FileNotFound(format!("File not found: {}", path))
+            }
+            _ => AppError::Io(format!("Failed to read metadata for {}: {}", path, e)),
+        })?;
+        let mtime_nanos = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        Ok(Self {
+            path: path.to_string(),
+            size: metadata.len(),
+            mtime_nanos,
+        })
+    }
+
+    /// Hex digest used as the cache file name.
+    pub fn key(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChapterCacheEntry {
+    #[serde(default)]
+    version: u32,
+    fingerprint: FileFingerprint,
+    /// Title language the chapters were picked for.
+    #[serde(default)]
+    preferred_language: Option<String>,
+    chapters: Vec<Chapter>,
+}
+
+/// Returns (creating if needed) the `name` subdirectory of the app cache dir.
+pub fn cache_dir(handle: &AppHandle, name: &str) -> Result<PathBuf, AppError> {
+    let dir = handle
+        .path()
+        .app_cache_dir()
+        .map_err(|e| AppError::Io(format!("Failed to resolve app cache directory: {}", e)))?
+        .join(name);
+    fs::create_dir_all(&dir).map_err(|e| {
+        AppError::Io(format!(
+            "Failed to create cache directory {}: {}",
+            dir.display(),
+            e
+        ))
+    })?;
+    Ok(dir)
+}
+
+fn chapter_entry_path(
+    handle: &AppHandle,
+    fingerprint: &FileFingerprint,
+) -> Result<PathBuf, AppError> {
+    Ok(cache_dir(handle, CHAPTER_CACHE_DIR)?.join(format!("{}.json", fingerprint.key())))
+}
+
+/// Looks up previously extracted chapters for the current version of a file,
+/// as long as they were picked for the same title language.
+pub fn load_chapters(
+    handle: &AppHandle,
+    fingerprint: &FileFingerprint,
+    preferred_language: Option<&str>,
+) -> Option<Vec<Chapter>> {
+    let path = chapter_entry_path(handle, fingerprint).ok()?;
+    let contents = fs::read_to_string(path).ok()?;
+    let entry: ChapterCacheEntry = serde_json::from_str(&contents).ok()?;
+    // Guard against hash collisions by checking the full fingerprint.
+    (entry.version == CHAPTER_CACHE_VERSION
+        && entry.fingerprint == *fingerprint
+        && entry.preferred_language.as_deref() == preferred_language)
+        .then_some(entry.chapters)
+}
+
+pub fn store_chapters(
+    handle: &AppHandle,
+    fingerprint: &FileFingerprint,
+    preferred_language: Option<&str>,
+    chapters: &[Chapter],
+) -> Result<(), AppError> {
+    let path = chapter_entry_path(handle, fingerprint)?;
+    let entry = ChapterCacheEntry {
+        version: CHAPTER_CACHE_VERSION,
+        fingerprint: fingerprint.clone(),
+        preferred_language: preferred_language.map(str::to_string),
+        chapters: chapters.to_vec(),
+    };
+    let json = serde_json::to_string(&entry).map_err(|e| {
+        AppError::ParseFailed(format!("Failed to serialize chapter cache entry: {}", e))
+    })?;
+    fs::write(&path, json).map_err(|e| {
+        AppError::Io(format!(
+            "Failed to write chapter cache {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Tempo and key of one stretch of a file, `end` `None` running to the end
+/// of the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeAnalysis {
+    pub start: f64,
+    pub end: Option<f64>,
+    pub tempo: TempoEstimate,
+    pub key: KeyEstimate,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AnalysisCacheEntry {
+    version: u32,
+    fingerprint: FileFingerprint,
+    ranges: Vec<RangeAnalysis>,
+}
+
+fn analysis_entry_path(
+    handle: &AppHandle,
+    fingerprint: &FileFingerprint,
+) -> Result<PathBuf, AppError> {
+    Ok(cache_dir(handle, ANALYSIS_CACHE_DIR)?.join(format!("{}.json", fingerprint.key())))
+}
+
+/// Loads the ranges of the current version of a file analyzed so far.
+pub fn load_analysis(handle: &AppHandle, fingerprint: &FileFingerprint) -> Vec<RangeAnalysis> {
+    let entry = analysis_entry_path(handle, fingerprint)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str::<AnalysisCacheEntry>(&contents).ok());
+    match entry {
+        Some(entry)
+            if entry.version == ANALYSIS_CACHE_VERSION && entry.fingerprint == *fingerprint =>
+        {
+            entry.ranges
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Stores the analyzed `ranges` of a file, replacing what was stored.
+pub fn store_analysis(
+    handle: &AppHandle,
+    fingerprint: &FileFingerprint,
+    ranges: &[RangeAnalysis],
+) -> Result<(), AppError> {
+    let path = analysis_entry_path(handle, fingerprint)?;
+    let entry = AnalysisCacheEntry {
+        version: ANALYSIS_CACHE_VERSION,
+        fingerprint: fingerprint.clone(),
+        ranges: ranges.to_vec(),
+    };
+    let json = serde_json::to_string(&entry).map_err(|e| {
+        AppError::ParseFailed(format!("Failed to serialize analysis cache entry: {}", e))
+    })?;
+    util::write_atomic(&path, json.as_bytes())
+}
+
+/// Deletes every entry in the `name` cache directory.
+pub fn clear(handle: &AppHandle, name: &str) -> Result<(), AppError> {
+    let dir = cache_dir(handle, name)?;
+    remove_dir_contents(&dir)
+}
+
+fn remove_dir_contents(dir: &Path) -> Result<(), AppError> {
+    let entries = fs::read_dir(dir).map_err(|e| {
+        AppError::Io(format!(
+            "Failed to read cache directory {}: {}",
+            dir.display(),
+            e
+        ))
+    })?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_file() {
+            fs::remove_file(&path)
+                .map_err(|e| AppError::Io(format!("Failed to remove {}: {}", path.display(), e)))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn clear_chapters(handle: &AppHandle) -> Result<(), AppError> {
+    clear(handle, CHAPTER_CACHE_DIR)
+}
+
+/// Cache key for the peaks of one file at one zoom level and channel,
+/// decoded as `format`. Keys for the default `f32le` have no format suffix,
+/// as they did before the format could be chosen.
+pub fn waveform_key(
+    fingerprint: &FileFingerprint,
+    samples_per_pixel: u32,
+    channel: Option<u8>,
+    format: PcmFormat,
+) -> String {
+    let channel = channel.map_or("mix".to_string(), |c| c.to_string());
+    let key = format!("{}-{}-{}", fingerprint.key(), samples_per_pixel, channel);
+    match format {
+        PcmFormat::F32le => key,
+        other => format!("{}-{}", key, other.name()),
+    }
+}
+
+fn waveform_entry_path(handle: &AppHandle, key: &str) -> Result<PathBuf, AppError> {
+    Ok(cache_dir(handle, WAVEFORM_CACHE_DIR)?.join(format!("{}.peaks", key)))
+}
+
+/// Loads cached peaks, stored as little-endian `f32`s.
+pub fn load_waveform(handle: &AppHandle, key: &str) -> Option<Vec<f32>> {
+    let path = waveform_entry_path(handle, key).ok()?;
+    let bytes = fs::read(&path).ok()?;
+    if bytes.len() % 4 != 0 {
+        return None;
+    }
+    // Bump the modification time so eviction drops least recently used entries.
+    if let Ok(file) = fs::File::options().append(true).open(&path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+    Some(decode_peaks(&bytes))
+}
+
+fn decode_peaks(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+fn encode_peaks(peaks: &[f32]) -> Vec<u8> {
+    peaks.iter().flat_map(|p| p.to_le_bytes()).collect()
+}
+
+/// Stores peaks under `key`, then evicts old entries beyond `limit_bytes`.
+pub fn store_waveform(
+    handle: &AppHandle,
+    key: &str,
+    peaks: &[f32],
+    limit_bytes: u64,
+) -> Result<(), AppError> {
+    let path = waveform_entry_path(handle, key)?;
+    fs::write(&path, encode_peaks(peaks)).map_err(|e| {
+        AppError::Io(format!(
+            "Failed to write waveform cache {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    evict_waveforms(handle, limit_bytes)
+}
+
+fn waveform_checkpoint_path(handle: &AppHandle, key: &str) -> Result<PathBuf, AppError> {
+    Ok(cache_dir(handle, WAVEFORM_CACHE_DIR)?.join(format!("{}.partial", key)))
+}
+
+/// Loads the raw peaks an unfinished computation of `key` checkpointed, or
+/// `None` if there is no usable checkpoint. They are whole `[min, max]`
+/// pairs, each covering exactly `samples_per_pixel` samples from the start
+/// of the file.
+pub fn load_waveform_checkpoint(handle: &AppHandle, key: &str) -> Option<Vec<f32>> {
+    decode_checkpoint(&fs::read(waveform_checkpoint_path(handle, key).ok()?).ok()?)
+}
+
+/// Reads checkpointed peaks, rejecting a file cut short of a whole pair.
+fn decode_checkpoint(bytes: &[u8]) -> Option<Vec<f32>> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(8) {
+        return None;
+    }
+    Some(decode_peaks(bytes))
+}
+
+/// Replaces the checkpoint of `key` with `peaks`. Checkpoints count towards
+/// the cache limit like finished entries, and may be evicted with them.
+pub fn store_waveform_checkpoint(
+    handle: &AppHandle,
+    key: &str,
+    peaks: &[f32],
+) -> Result<(), AppError> {
+    util::write_atomic(
+        &waveform_checkpoint_path(handle, key)?,
+        &encode_peaks(peaks),
+    )
+}
+
+/// Removes the checkpoint of `key`, once its peaks are cached.
+pub fn remove_waveform_checkpoint(handle: &AppHandle, key: &str) -> Result<(), AppError> {
+    let path = waveform_checkpoint_path(handle, key)?;
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(AppError::Io(format!(
+            "Failed to remove {}: {}",
+            path.display(),
+            e
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Lists cache files with their size and modification time.
+fn entries_with_metadata(dir: &Path) -> Result<Vec<(PathBuf, u64, SystemTime)>, AppError> {
+    let entries = fs::read_dir(dir).map_err(|e| {
+        AppError::Io(format!(
+            "Failed to read cache directory {}: {}",
+            dir.display(),
+            e
+        ))
+    })?;
+    Ok(entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+            Some((entry.path(), metadata.len(), modified))
+        })
+        .collect())
+}
+
+/// Deletes the oldest waveform entries until the cache fits in `limit_bytes`.
+pub fn evict_waveforms(handle: &AppHandle, limit_bytes: u64) -> Result<(), AppError> {
+    let mut entries = entries_with_metadata(&cache_dir(handle, WAVEFORM_CACHE_DIR)?)?;
+    let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    entries.sort_by_key(|(_, _, modified)| *modified);
+
+    for (path, size, _) in entries {
+        if total <= limit_bytes {
+            break;
+        }
+        fs::remove_file(&path)
+            .map_err(|e| AppError::Io(format!("Failed to remove {}: {}", path.display(), e)))?;
+        total -= size;
+    }
+    Ok(())
+}
+
+/// Total bytes used by cached waveforms.
+pub fn waveform_cache_size(handle: &AppHandle) -> Result<u64, AppError> {
+    let entries = entries_with_metadata(&cache_dir(handle, WAVEFORM_CACHE_DIR)?)?;
+    Ok(entries.iter().map(|(_, size, _)| size).sum())
+}
+
+pub fn clear_waveforms(handle: &AppHandle) -> Result<(), AppError> {
+    clear(handle, WAVEFORM_CACHE_DIR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::waveform::{self, PeakAccumulator};
+
+    const SAMPLES_PER_PIXEL: usize = 300;
+
+    fn pcm(len: usize) -> Vec<u8> {
+        (0..len)
+            .flat_map(|i| {
+                let t = i as f32;
+                ((t * 0.021).sin() * (t * 0.0009).cos() * 0.9).to_le_bytes()
+            })
+            .collect()
+    }
+
+    /// Streams `bytes` in `piece` sized reads on top of `peaks`, the way
+    /// `stream_waveform` does, stopping after `reads` reads if given.
+    fn stream(bytes: &[u8], piece: usize, mut peaks: Vec<f32>, reads: Option<usize>) -> Vec<f32> {
+        let mut accumulator = PeakAccumulator::new(SAMPLES_PER_PIXEL, PcmFormat::F32le);
+        for chunk in bytes.chunks(piece).take(reads.unwrap_or(usize::MAX)) {
+            peaks.extend(accumulator.push(chunk));
+        }
+        if reads.is_none() {
+            peaks.extend(accumulator.finish());
+        }
+        peaks
+    }
+
+    #[test]
+    fn a_resumed_waveform_matches_an_uninterrupted_one() {
+        let bytes = pcm(100_003);
+        let mut uninterrupted = stream(&bytes, 4099, Vec::new(), None);
+        waveform::normalize_peaks(&mut uninterrupted);
+
+        let path = std::env::temp_dir().join(format!(
+            "guitar-looper-cache-{}-checkpoint.partial",
+            std::process::id()
+        ));
+        // Interrupted part way through a bucket, and part way through a
+        // sample.
+        for reads in [1, 7, 50] {
+            let partial = stream(&bytes, 4099, Vec::new(), Some(reads));
+            util::write_atomic(&path, &encode_peaks(&partial)).unwrap();
+
+            let checkpoint = decode_checkpoint(&fs::read(&path).unwrap()).unwrap();
+            assert_eq!(checkpoint, partial);
+            let skip_samples = checkpoint.len() / 2 * SAMPLES_PER_PIXEL;
+            let rest = &bytes[skip_samples * PcmFormat::F32le.bytes_per_sample()..];
+            let mut resumed = stream(rest, 4099, checkpoint, None);
+            waveform::normalize_peaks(&mut resumed);
+
+            assert_eq!(
+                encode_peaks(&resumed),
+                encode_peaks(&uninterrupted),
+                "resumed after {} reads",
+                reads
+            );
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn a_truncated_checkpoint_is_ignored() {
+        let bytes = encode_peaks(&[-0.5, 0.5, -0.25, 0.25]);
+        assert_eq!(decode_checkpoint(&bytes).unwrap().len(), 4);
+        assert_eq!(decode_checkpoint(&bytes[..12]), None);
+        assert_eq!(decode_checkpoint(&[]), None);
+    }
+}
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::{chapters, Chapter, ChapterSource};
+
+/// Version written by [`write`], and the newest [`parse`] understands.
+pub const FORMAT_VERSION: u64 = 1;
+
+/// A plain JSON chapter list for exchanging chapters with other players:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "source": "song.mp3",
+///   "chapters": [
+///     { "title": "Intro", "start_ms": 0, "end_ms": 15000 },
+///     { "title": "Solo", "start_ms": 95250, "end_ms": null }
+///   ]
+/// }
+/// ```
+///
+/// Times are whole milliseconds so they survive round trips through other
+/// tools exactly. `source` is the name of the media file the chapters
+/// belong to, if known, and `end_ms` is `null` for a chapter that runs to
+/// the next one or to the end of the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChapterFile {
+    version: u64,
+    source: Option<String>,
+    chapters: Vec<ChapterEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChapterEntry {
+    title: String,
+    start_ms: u64,
+    end_ms: Option<u64>,
+}
+
+fn to_ms(seconds: f64) -> u64 {
+    (seconds.max(0.0) * 1000.0).round() as u64
+}
+
+/// Serializes `chapters`, in start order, as a version [`FORMAT_VERSION`]
+/// chapter file naming `source` as the media file.
+pub fn write(chapters: &[Chapter], source: Option<&str>) -> Result<String, AppError> {
+    let mut ordered: Vec<&Chapter> = chapters.iter().collect();
+    ordered.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let file = ChapterFile {
+        version: FORMAT_VERSION,
+        source: source.map(str::to_string),
+        chapters: ordered
+            .iter()
+            .map(|chapter| ChapterEntry {
+                title: chapter.title.clone(),
+                start_ms: to_ms(chapter.start),
+                end_ms: chapter.end.map(to_ms),
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&file)
+        .map_err(|e| AppError::ParseFailed(format!("Failed to serialize chapters: {}", e)))
+}
+
+/// Parses a chapter file written by [`write`] or by hand into chapters in
+/// start order.
+///
+/// The version is checked before anything else, so a file from a newer
+/// release is reported as such instead of failing on whatever changed.
+pub fn parse(contents: &str) -> Result<Vec<Chapter>, AppError> {
+    let document: Value = serde_json::from_str(contents.trim_start_matches('\u{feff}'))
+        .map_err(|e| AppError::ParseFailed(format!("The file is not valid JSON: {}", e)))?;
+    let version = document["version"].as_u64().ok_or_else(|| {
+        AppError::ParseFailed(
+            "The file has no numeric 'version', so it is not a chapter file".to_string(),
+        )
+    })?;
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(AppError::ParseFailed(format!(
+            "The chapter file is version {}, but only versions up to {} are supported. It may have been written by a newer release.",
+            version, FORMAT_VERSION
+        )));
+    }
+    let file: ChapterFile = serde_json::from_value(document)
+        .map_err(|e| AppError::ParseFailed(format!("Invalid chapter file: {}", e)))?;
+
+    for entry in &file.chapters {
+        if let Some(end) = entry.end_ms.filter(|&end| end <= entry.start_ms) {
+            return Err(AppError::InvalidRange(format!(
+                "Chapter '{}' ends at {} ms, which is not after its start at {} ms",
+                entry.title, end, entry.start_ms
+            )));
+        }
+    }
+
+    let marks = file
+        .chapters
+        .into_iter()
+        .map(|entry| {
+            let start = entry.start_ms as f64 / 1000.0;
+            let end = entry.end_ms.map(|end| end as f64 / 1000.0);
+            (start, entry.title, end)
+        })
+        .collect();
+    Ok(chapters::to_chapters(marks, ChapterSource::ChapterJson))
+}
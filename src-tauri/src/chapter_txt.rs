@@ -0,0 +1,164 @@
+use crate::error::AppError;
+use crate::{chapters, timestamps, Chapter, ChapterSource};
+
+/// Parses an `HH:MM:SS` timestamp. Unlike a video description, the file
+/// always spells out the hours, so `MM:SS` is rejected.
+fn parse_timestamp(field: &str) -> Option<f64> {
+    if field.split(':').count() != 3 {
+        return None;
+    }
+    timestamps::parse_timestamp(field)
+}
+
+/// Formats seconds as `HH:MM:SS`, rounding to the nearest second.
+fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        total / 3600,
+        total / 60 % 60,
+        total % 60
+    )
+}
+
+/// Parses a plain chapter file as written by podcast tools, one
+/// `HH:MM:SS Title` line per chapter.
+///
+/// Each chapter ends where the next one starts and the last one has no end.
+/// Timestamps must be in order, and any line that isn't blank and doesn't
+/// start with a well-formed timestamp is an error naming its line number.
+pub fn parse(contents: &str) -> Result<Vec<Chapter>, AppError> {
+    let mut entries: Vec<(f64, &str)> = Vec::new();
+    // Notepad saves UTF-8 with a byte order mark.
+    let contents = contents.trim_start_matches('\u{feff}');
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (timestamp, title) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let start = parse_timestamp(timestamp).ok_or_else(|| {
+            AppError::ParseFailed(format!(
+                "Line {} does not start with an HH:MM:SS timestamp: {}",
+                number + 1,
+                line
+            ))
+        })?;
+        if let Some(&(previous, _)) = entries.last().filter(|(previous, _)| start <= *previous) {
+            return Err(AppError::InvalidRange(format!(
+                "Line {} starts at {}, which is not after the previous chapter at {}",
+                number + 1,
+                timestamp,
+                format_timestamp(previous)
+            )));
+        }
+        entries.push((start, title.trim()));
+    }
+    if entries.is_empty() {
+        return Err(AppError::ParseFailed(
+            "The file contains no chapters".to_string(),
+        ));
+    }
+
+    let marks = entries
+        .into_iter()
+        .map(|(start, title)| (start, title.to_string(), None))
+        .collect();
+    Ok(chapters::to_chapters(marks, ChapterSource::ChapterTxt))
+}
+
+/// Writes `chapters` in start order as `HH:MM:SS Title` lines.
+///
+/// The format only keeps whole-second starts, so chapter ends are dropped
+/// and two chapters starting within the same second are rejected, as the
+/// file couldn't be read back.
+pub fn write(chapters: &[Chapter]) -> Result<String, AppError> {
+    let mut ordered: Vec<&Chapter> = chapters.iter().collect();
+    ordered.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let mut file = String::new();
+    let mut previous: Option<(&Chapter, String)> = None;
+    for chapter in ordered {
+        let timestamp = format_timestamp(chapter.start);
+        if let Some((other, _)) = previous.as_ref().filter(|(_, last)| *last == timestamp) {
+            return Err(AppError::InvalidRange(format!(
+                "'{}' and '{}' both start at {}, and the format only stores whole seconds",
+                other.title, chapter.title, timestamp
+            )));
+        }
+        file.push_str(&format!(
+            "{} {}\n",
+            timestamp,
+            chapter.title.replace(['\r', '\n'], " ").trim()
+        ));
+        previous = Some((chapter, timestamp));
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn starts_and_titles(chapters: &[Chapter]) -> Vec<(f64, Option<f64>, &str)> {
+        chapters
+            .iter()
+            .map(|c| (c.start, c.end, c.title.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_one_chapter_per_line() {
+        let chapters = parse("\u{feff}00:00:00 Intro\n\n00:01:30   Verse one \n1:02:03\n").unwrap();
+        assert_eq!(
+            starts_and_titles(&chapters),
+            [
+                (0.0, Some(90.0), "Intro"),
+                (90.0, Some(3723.0), "Verse one"),
+                (3723.0, None, "Chapter 3"),
+            ]
+        );
+        assert_eq!(chapters[1].id, "chapter-1");
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let file = "00:00:00 Intro\n00:01:30 Verse\n01:02:03 Outro\n";
+        assert_eq!(write(&parse(file).unwrap()).unwrap(), file);
+    }
+
+    #[test]
+    fn malformed_lines_name_their_line_number() {
+        for (contents, line) in [
+            ("00:00:00 Intro\n\n0:1:30 Verse\n", "Line 3 "),
+            ("1:30 Intro\n", "Line 1 "),
+            ("00:00:00 Intro\n00:61:00 Late\n", "Line 2 "),
+            ("00:00:00 Intro\nIntro again\n", "Line 2 "),
+            ("00:00:00 Intro\n00:00:1x Verse\n", "Line 2 "),
+            ("00:00:00:00 Intro\n", "Line 1 "),
+        ] {
+            match parse(contents) {
+                Err(AppError::ParseFailed(message)) => {
+                    assert!(message.starts_with(line), "{} for {:?}", message, contents)
+                }
+                other => panic!("{:?} for {:?}", other, contents),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_order_and_empty_files() {
+        assert!(matches!(
+            parse("00:01:00 Verse\n00:01:00 Chorus\n"),
+            Err(AppError::InvalidRange(message)) if message.starts_with("Line 2 ")
+        ));
+        assert!(matches!(parse(" \n\n"), Err(AppError::ParseFailed(_))));
+    }
+
+    #[test]
+    fn rejects_chapters_in_the_same_second() {
+        let mut chapters = parse("00:00:00 Intro\n00:00:10 Verse\n").unwrap();
+        chapters[1].start = 0.4;
+        assert!(matches!(write(&chapters), Err(AppError::InvalidRange(_))));
+    }
+}
@@ -0,0 +1,1612 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::util;
+use crate::{Chapter, ChapterSource};
+
+/// Largest gap in seconds between two chapters that still counts as
+/// contiguous, to absorb rounding in chapter timestamps.
+const CONTIGUITY_TOLERANCE: f64 = 0.05;
+
+/// A chapter read from `source`, identified by its `index` among the ones
+/// read, with none of the settings only the user adds.
+pub fn imported(
+    index: usize,
+    title: String,
+    start: f64,
+    end: Option<f64>,
+    source: ChapterSource,
+) -> Chapter {
+    Chapter {
+        id: format!("chapter-{}", index),
+        title,
+        start,
+        end,
+        source: Some(source),
+        duration: end.map(|end| end - start),
+        ..Default::default()
+    }
+}
+
+/// Builds chapters in start order from `(start, title, end)` marks, ending
+/// each one where the next begins unless it has its own end. Untitled marks
+/// are named `Chapter N` after their place in that order.
+pub fn to_chapters(
+    mut marks: Vec<(f64, String, Option<f64>)>,
+    source: ChapterSource,
+) -> Vec<Chapter> {
+    marks.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let starts: Vec<f64> = marks.iter().map(|mark| mark.0).collect();
+    marks
+        .into_iter()
+        .enumerate()
+        .map(|(index, (start, title, end))| {
+            let end = end.or_else(|| starts.get(index + 1).copied());
+            let title = if title.trim().is_empty() {
+                format!("Chapter {}", index + 1)
+            } else {
+                title
+            };
+            imported(index, title, start, end, source)
+        })
+        .collect()
+}
+
+/// Replaces the chapters named by `ids` with a single chapter titled `title`
+/// spanning all of them.
+///
+/// The merged chapter keeps the id and color of the earliest chapter, takes
+/// its place in the list and has the tags of all of them. The chapters must
+/// follow each other in time with no other chapter or gap between them; a
+/// chapter without an end is treated as running up to the next one.
+pub fn merge(chapters: &[Chapter], ids: &[String], title: &str) -> Result<Vec<Chapter>, AppError> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err(AppError::InvalidInput(
+            "The merged chapter needs a title".to_string(),
+        ));
+    }
+    if let Some(missing) = ids.iter().find(|id| !chapters.iter().any(|c| &c.id == *id)) {
+        return Err(AppError::InvalidInput(format!(
+            "No chapter with id {}",
+            missing
+        )));
+    }
+
+    let mut ordered: Vec<&Chapter> = chapters.iter().collect();
+    ordered.sort_by(|a, b| a.start.total_cmp(&b.start));
+    let selected: Vec<usize> = ordered
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| ids.contains(&c.id))
+        .map(|(i, _)| i)
+        .collect();
+    if selected.len() < 2 {
+        return Err(AppError::InvalidInput(
+            "Select at least two chapters to merge".to_string(),
+        ));
+    }
+
+    let run = &ordered[selected[0]..=selected[selected.len() - 1]];
+    if run.len() != selected.len() {
+        return Err(AppError::InvalidRange(
+            "Only chapters that follow each other can be merged".to_string(),
+        ));
+    }
+    for pair in run.windows(2) {
+        if let Some(end) = pair[0].end {
+            if pair[1].start - end > CONTIGUITY_TOLERANCE {
+                return Err(AppError::InvalidRange(format!(
+                    "There is a gap between '{}' and '{}'",
+                    pair[0].title, pair[1].title
+                )));
+            }
+        }
+    }
+
+    let first = run[0];
+    // An open-ended last chapter keeps the merged chapter open-ended too.
+    let end = match run[run.len() - 1].end {
+        Some(_) => run.iter().filter_map(|c| c.end).reduce(f64::max),
+        None => None,
+    };
+    // The tags of every merged chapter, each once.
+    let mut tags: Vec<String> = Vec::new();
+    for tag in run.iter().flat_map(|c| &c.tags) {
+        if !tags.iter().any(|t| t.to_lowercase() == tag.to_lowercase()) {
+            tags.push(tag.clone());
+        }
+    }
+    let merged = Chapter {
+        id: first.id.clone(),
+        title: title.to_string(),
+        start: first.start,
+        end,
+        source: first.source,
+        duration: end.map(|end| end - first.start),
+        loop_settings: first.loop_settings.clone(),
+        // The merged title is new, so its language is unknown.
+        language: None,
+        color: first.color.clone(),
+        tags,
+    };
+
+    Ok(chapters
+        .iter()
+        .filter_map(|c| {
+            if c.id == first.id {
+                Some(merged.clone())
+            } else if ids.contains(&c.id) {
+                None
+            } else {
+                Some(c.clone())
+            }
+        })
+        .collect())
+}
+
+/// Returns `base` if no chapter uses it yet, otherwise `base-2`, `base-3`...
+fn unused_id(chapters: &[Chapter], base: String) -> String {
+    let taken = |id: &str| chapters.iter().any(|c| c.id == id);
+    if !taken(&base) {
+        return base;
+    }
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|id| !taken(id))
+        .expect("ran out of chapter ids")
+}
+
+/// Replaces the chapter `chapter_id` with two chapters meeting at `split_at`.
+///
+/// The parts get the ids `<id>-1` and `<id>-2` (bumped if already taken) and
+/// titles "<title> (Part 1)" and "<title> (Part 2)". For a chapter without
+/// an end, `split_at` must come before the start of the next chapter.
+pub fn split(
+    chapters: &[Chapter],
+    chapter_id: &str,
+    split_at: f64,
+) -> Result<Vec<Chapter>, AppError> {
+    let index = chapters
+        .iter()
+        .position(|c| c.id == chapter_id)
+        .ok_or_else(|| AppError::InvalidInput(format!("No chapter with id {}", chapter_id)))?;
+    let target = &chapters[index];
+
+    let limit = target.end.or_else(|| {
+        chapters
+            .iter()
+            .map(|c| c.start)
+            .filter(|start| *start > target.start)
+            .reduce(f64::min)
+    });
+    let inside = split_at.is_finite()
+        && split_at > target.start
+        && limit.is_none_or(|limit| split_at < limit);
+    if !inside {
+        return Err(AppError::InvalidRange(format!(
+            "Split point {} is outside '{}' ({} - {})",
+            split_at,
+            target.title,
+            target.start,
+            limit.map_or("end".to_string(), |limit| limit.to_string())
+        )));
+    }
+
+    let first = Chapter {
+        id: unused_id(chapters, format!("{}-1", target.id)),
+        title: format!("{} (Part 1)", target.title),
+        start: target.start,
+        end: Some(split_at),
+        source: target.source,
+        duration: Some(split_at - target.start),
+        loop_settings: target.loop_settings.clone(),
+        language: target.language.clone(),
+        color: target.color.clone(),
+        tags: target.tags.clone(),
+    };
+    let second = Chapter {
+        id: unused_id(chapters, format!("{}-2", target.id)),
+        title: format!("{} (Part 2)", target.title),
+        start: split_at,
+        end: target.end,
+        source: target.source,
+        duration: target.end.map(|end| end - split_at),
+        loop_settings: target.loop_settings.clone(),
+        language: target.language.clone(),
+        color: target.color.clone(),
+        tags: target.tags.clone(),
+    };
+
+    let mut result = Vec::with_capacity(chapters.len() + 1);
+    result.extend_from_slice(&chapters[..index]);
+    result.push(first);
+    result.push(second);
+    result.extend_from_slice(&chapters[index + 1..]);
+    Ok(result)
+}
+
+/// Formats `seconds` as `m:ss`, or `h:mm:ss` from an hour on, for titles.
+fn clock(seconds: f64) -> String {
+    let total = seconds.max(0.0).round() as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Suggests a title for a new chapter spanning `start..end` from where its
+/// middle falls among the existing `ordered` chapters: inside a chapter,
+/// in the gap between two, or before the first or after the last one.
+fn suggest_title(ordered: &[&Chapter], start: f64, end: f64) -> String {
+    let middle = (start + end) / 2.0;
+    let Some(first) = ordered.first() else {
+        return format!("Loop {} - {}", clock(start), clock(end));
+    };
+    if middle < first.start {
+        return format!("Before {}", first.title);
+    }
+    for (index, chapter) in ordered.iter().enumerate() {
+        let next = ordered.get(index + 1);
+        // A chapter without an end runs up to the next one.
+        let chapter_end = chapter.end.or(next.map(|next| next.start));
+        if chapter_end.is_none_or(|chapter_end| middle < chapter_end) {
+            return format!("{} (loop)", chapter.title);
+        }
+        match next {
+            Some(next) if middle < next.start => {
+                return format!("Between {} and {}", chapter.title, next.title);
+            }
+            Some(_) => {}
+            None => return format!("After {}", chapter.title),
+        }
+    }
+    unreachable!("the last chapter always returns")
+}
+
+/// Adds a chapter spanning `start..end`, such as a loop the user wants to
+/// keep, to `chapters`.
+///
+/// Without a `title` one is suggested from the chapters around it, e.g.
+/// "Between Verse and Chorus" or "Solo (loop)". Returns the chapters in
+/// start order with the new one after any starting at the same time, all
+/// with fresh ids `chapter-0`, `chapter-1`... in that order.
+pub fn insert_range(
+    chapters: &[Chapter],
+    start: f64,
+    end: f64,
+    title: Option<&str>,
+) -> Result<Vec<Chapter>, AppError> {
+    util::validate_range(start, Some(end))?;
+    let mut ordered: Vec<&Chapter> = chapters.iter().collect();
+    ordered.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let title = match title.map(str::trim).filter(|title| !title.is_empty()) {
+        Some(title) => title.to_string(),
+        None => suggest_title(&ordered, start, end),
+    };
+    let chapter = Chapter {
+        id: String::new(),
+        title,
+        start,
+        end: Some(end),
+        duration: Some(end - start),
+        ..Default::default()
+    };
+
+    let position = ordered.partition_point(|c| c.start <= start);
+    let mut result: Vec<Chapter> = ordered.into_iter().cloned().collect();
+    result.insert(position, chapter);
+    for (index, chapter) in result.iter_mut().enumerate() {
+        chapter.id = format!("chapter-{}", index);
+    }
+    Ok(result)
+}
+
+/// Ends the last chapter at `file_duration` if it has no end of its own,
+/// then sets every chapter's duration from its start and end.
+pub fn fill_durations(chapters: &mut [Chapter], file_duration: Option<f64>) {
+    let last = chapters
+        .iter_mut()
+        .max_by(|a, b| a.start.total_cmp(&b.start));
+    if let Some(last) = last {
+        if last.end.is_none() {
+            last.end = file_duration.filter(|&duration| duration > last.start);
+        }
+    }
+    for chapter in chapters.iter_mut() {
+        chapter.duration = chapter.end.map(|end| end - chapter.start);
+    }
+}
+
+/// Puts a chapter list from any source into the shape the rest of the app
+/// expects, so every importer ends up with the same timestamps:
+///
+/// - chapters whose start isn't a number are dropped, and negative times
+///   clamped to zero;
+/// - chapters are sorted by start, keeping the listed order on ties;
+/// - repeats of a chapter with the same title, start and end, to within
+///   rounding, are dropped;
+/// - an end that is missing, or not after the start, becomes the next
+///   later start, or `file_duration` for the last chapter;
+/// - ids are renumbered `chapter-0`, `chapter-1`, ... and durations set.
+///
+/// Titles, sources and user data are kept as they are.
+pub fn normalize(chapters: &[Chapter], file_duration: Option<f64>) -> Vec<Chapter> {
+    let mut normalized: Vec<Chapter> = chapters
+        .iter()
+        .filter(|chapter| chapter.start.is_finite())
+        .cloned()
+        .map(|mut chapter| {
+            chapter.start = chapter.start.max(0.0);
+            chapter.end = chapter
+                .end
+                .filter(|end| end.is_finite())
+                .map(|end| end.max(0.0))
+                .filter(|&end| end > chapter.start);
+            chapter
+        })
+        .collect();
+    normalized.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let same_time = |a: f64, b: f64| (a - b).abs() <= CONTIGUITY_TOLERANCE;
+    let mut unique: Vec<Chapter> = Vec::with_capacity(normalized.len());
+    for chapter in normalized {
+        let repeated = unique.iter().rev().any(|kept| {
+            same_time(kept.start, chapter.start)
+                && kept.title == chapter.title
+                && match (kept.end, chapter.end) {
+                    (Some(a), Some(b)) => same_time(a, b),
+                    (a, b) => a.is_none() && b.is_none(),
+                }
+        });
+        if !repeated {
+            unique.push(chapter);
+        }
+    }
+
+    let starts: Vec<f64> = unique.iter().map(|chapter| chapter.start).collect();
+    for (index, chapter) in unique.iter_mut().enumerate() {
+        if chapter.end.is_none() {
+            chapter.end = starts[index + 1..]
+                .iter()
+                .copied()
+                .find(|&start| start > chapter.start)
+                .or(file_duration.filter(|&duration| duration > chapter.start));
+        }
+        chapter.id = format!("chapter-{}", index);
+        chapter.duration = chapter.end.map(|end| end - chapter.start);
+    }
+    unique
+}
+
+/// Which neighbouring chapter takes over the gap between two chapters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GapFillMode {
+    /// The earlier chapter runs on until the next one starts.
+    ExtendPrevious,
+    /// The later chapter starts where the previous one ends.
+    ExtendNext,
+    /// Both chapters meet in the middle of the gap.
+    SplitEvenly,
+}
+
+/// Closes the gaps between `chapters` as `mode` says, so playing them in a
+/// row skips no audio. Returns them in start order.
+///
+/// Gaps at the file boundaries have only one neighbour, which takes them
+/// whatever the mode: the first chapter is moved back to the start of the
+/// file and the last one extended to `file_duration`, if known. Chapters
+/// without an end already run up to the next one and overlaps are left
+/// alone.
+pub fn fill_gaps(
+    chapters: &[Chapter],
+    mode: GapFillMode,
+    file_duration: Option<f64>,
+) -> Vec<Chapter> {
+    let mut filled = chapters.to_vec();
+    filled.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    if let Some(first) = filled.first_mut() {
+        first.start = 0.0;
+    }
+    for index in 1..filled.len() {
+        let next_start = filled[index].start;
+        let Some(end) = filled[index - 1].end.filter(|&end| end < next_start) else {
+            continue;
+        };
+        let meet = match mode {
+            GapFillMode::ExtendPrevious => next_start,
+            GapFillMode::ExtendNext => end,
+            GapFillMode::SplitEvenly => (end + next_start) / 2.0,
+        };
+        filled[index - 1].end = Some(meet);
+        filled[index].start = meet;
+    }
+    if let (Some(last), Some(file_duration)) = (filled.last_mut(), file_duration) {
+        if let Some(end) = last.end.as_mut() {
+            *end = end.max(file_duration);
+        }
+    }
+
+    for chapter in &mut filled {
+        chapter.duration = chapter.end.map(|end| end - chapter.start);
+    }
+    filled
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShiftedChapters {
+    pub chapters: Vec<Chapter>,
+    /// How many chapters fell entirely outside the file and were removed.
+    pub dropped: usize,
+}
+
+/// Moves every chapter by `offset` seconds, earlier when negative, to fix
+/// markers that are consistently out of sync with the audio.
+///
+/// Shifted times are clamped to `0..=file_duration`; chapters left with no
+/// length inside the file are dropped.
+pub fn shift(
+    chapters: &[Chapter],
+    offset: f64,
+    file_duration: f64,
+) -> Result<ShiftedChapters, AppError> {
+    if !offset.is_finite() {
+        return Err(AppError::InvalidInput(format!(
+            "Offset must be a number, got {}",
+            offset
+        )));
+    }
+    if !file_duration.is_finite() || file_duration <= 0.0 {
+        return Err(AppError::InvalidInput(format!(
+            "File duration must be positive, got {}",
+            file_duration
+        )));
+    }
+
+    let clamp = |time: f64| (time + offset).clamp(0.0, file_duration);
+    let shifted: Vec<Chapter> = chapters
+        .iter()
+        .filter_map(|chapter| {
+            let start = clamp(chapter.start);
+            let end = chapter.end.map(clamp);
+            if end.unwrap_or(file_duration) <= start {
+                return None;
+            }
+            Some(Chapter {
+                start,
+                end,
+                duration: end.map(|end| end - start),
+                ..chapter.clone()
+            })
+        })
+        .collect();
+    Ok(ShiftedChapters {
+        dropped: chapters.len() - shifted.len(),
+        chapters: shifted,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterWarningKind {
+    /// The chapter runs into `other_chapter_id`, which starts before it ends.
+    Overlap,
+    /// The chapter ends where it starts, or before.
+    ZeroLength,
+    /// The chapter starts or ends after the end of the file.
+    PastEnd,
+    /// The chapter starts before `other_chapter_id`, which comes before it
+    /// in the list.
+    OutOfOrder,
+}
+
+/// Something about a chapter that will likely play back wrong.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterWarning {
+    pub kind: ChapterWarningKind,
+    pub chapter_id: String,
+    /// The other chapter involved in an overlap or ordering problem.
+    pub other_chapter_id: Option<String>,
+    pub message: String,
+}
+
+/// Lists what is wrong with `chapters` in a file `file_duration` seconds
+/// long, in list order. Differences within the rounding of chapter
+/// timestamps aren't reported, and a chapter without an end is taken to run
+/// up to the next one, so it never overlaps.
+pub fn validate(chapters: &[Chapter], file_duration: f64) -> Vec<ChapterWarning> {
+    let warning =
+        |kind, chapter: &Chapter, other: Option<&Chapter>, message: String| ChapterWarning {
+            kind,
+            chapter_id: chapter.id.clone(),
+            other_chapter_id: other.map(|other| other.id.clone()),
+            message,
+        };
+    let mut warnings = Vec::new();
+    for (index, chapter) in chapters.iter().enumerate() {
+        if let Some(previous) = chapters[..index]
+            .iter()
+            .rev()
+            .find(|previous| previous.start > chapter.start)
+        {
+            warnings.push(warning(
+                ChapterWarningKind::OutOfOrder,
+                chapter,
+                Some(previous),
+                format!(
+                    "'{}' starts before '{}', which is listed ahead of it",
+                    chapter.title, previous.title
+                ),
+            ));
+        }
+
+        let next_start = chapters
+            .iter()
+            .map(|other| other.start)
+            .filter(|&start| start > chapter.start)
+            .reduce(f64::min);
+        let end = chapter.end.or(next_start).unwrap_or(file_duration);
+        if end - chapter.start <= CONTIGUITY_TOLERANCE {
+            warnings.push(warning(
+                ChapterWarningKind::ZeroLength,
+                chapter,
+                None,
+                format!(
+                    "'{}' has no length ({} - {})",
+                    chapter.title, chapter.start, end
+                ),
+            ));
+        }
+        if chapter.start.max(end) - file_duration > CONTIGUITY_TOLERANCE {
+            warnings.push(warning(
+                ChapterWarningKind::PastEnd,
+                chapter,
+                None,
+                format!(
+                    "'{}' runs to {}, past the end of the file at {}",
+                    chapter.title,
+                    chapter.start.max(end),
+                    file_duration
+                ),
+            ));
+        }
+
+        let Some(end) = chapter.end else {
+            continue;
+        };
+        for (other_index, other) in chapters.iter().enumerate() {
+            // Chapters starting together overlap both ways; report it once.
+            let later = other.start > chapter.start
+                || (other.start == chapter.start && other_index > index);
+            if other_index == index || !later {
+                continue;
+            }
+            let overlap = end.min(other.end.unwrap_or(f64::INFINITY)) - other.start;
+            if overlap > CONTIGUITY_TOLERANCE {
+                warnings.push(warning(
+                    ChapterWarningKind::Overlap,
+                    chapter,
+                    Some(other),
+                    format!(
+                        "'{}' overlaps '{}' by {:.2}s",
+                        chapter.title, other.title, overlap
+                    ),
+                ));
+            }
+        }
+    }
+    warnings
+}
+
+/// Sums the lengths of `chapters`, ending chapters without an end at
+/// `file_duration`.
+///
+/// With `dedupe_overlaps`, time covered by several chapters counts once, so
+/// overlapping selections aren't double counted.
+pub fn total_duration(
+    chapters: &[Chapter],
+    file_duration: f64,
+    dedupe_overlaps: bool,
+) -> Result<f64, AppError> {
+    let mut spans = Vec::with_capacity(chapters.len());
+    for chapter in chapters {
+        let end = chapter.end.unwrap_or(file_duration);
+        if !chapter.start.is_finite() || !end.is_finite() || end < chapter.start {
+            return Err(AppError::InvalidRange(format!(
+                "Chapter '{}' ends ({}) before it starts ({})",
+                chapter.title, end, chapter.start
+            )));
+        }
+        spans.push((chapter.start, end));
+    }
+    if !dedupe_overlaps {
+        return Ok(spans.iter().map(|(start, end)| end - start).sum());
+    }
+
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut total = 0.0;
+    let mut current: Option<(f64, f64)> = None;
+    for (start, end) in spans {
+        current = match current {
+            Some((open_start, open_end)) if start <= open_end => {
+                Some((open_start, open_end.max(end)))
+            }
+            Some((open_start, open_end)) => {
+                total += open_end - open_start;
+                Some((start, end))
+            }
+            None => Some((start, end)),
+        };
+    }
+    if let Some((start, end)) = current {
+        total += end - start;
+    }
+    Ok(total)
+}
+
+/// Group for chapters whose title has no group prefix.
+const UNGROUPED: &str = "Ungrouped";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterGroup {
+    pub group: String,
+    pub chapters: Vec<Chapter>,
+}
+
+/// Groups chapters by the part of their title before the first
+/// `separator`, so "Set 1 - Song A" lands in "Set 1". Later separators stay
+/// part of the song, and titles without a prefix go into "Ungrouped".
+///
+/// Groups are listed in order of their first chapter and keep their
+/// chapters in input order.
+pub fn group(chapters: &[Chapter], separator: &str) -> Result<Vec<ChapterGroup>, AppError> {
+    if separator.is_empty() {
+        return Err(AppError::InvalidInput(
+            "The group separator can't be empty".to_string(),
+        ));
+    }
+
+    let mut groups: Vec<ChapterGroup> = Vec::new();
+    for chapter in chapters {
+        let name = chapter
+            .title
+            .split_once(separator)
+            .map(|(prefix, _)| prefix.trim())
+            .filter(|prefix| !prefix.is_empty())
+            .unwrap_or(UNGROUPED);
+        match groups.iter_mut().find(|group| group.group == name) {
+            Some(group) => group.chapters.push(chapter.clone()),
+            None => groups.push(ChapterGroup {
+                group: name.to_string(),
+                chapters: vec![chapter.clone()],
+            }),
+        }
+    }
+    Ok(groups)
+}
+
+/// Whether a chapter must have any or all of the tags filtered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMatch {
+    Any,
+    All,
+}
+
+/// Returns the chapters tagged with any or all of `tags`, as `mode` says,
+/// in input order. Tags are compared ignoring case and surrounding
+/// whitespace. Without any tags to filter by, every chapter is returned.
+pub fn filter_by_tags(chapters: &[Chapter], tags: &[String], mode: TagMatch) -> Vec<Chapter> {
+    let normalize = |tag: &str| tag.trim().to_lowercase();
+    let wanted: Vec<String> = tags
+        .iter()
+        .map(|tag| normalize(tag))
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    if wanted.is_empty() {
+        return chapters.to_vec();
+    }
+
+    chapters
+        .iter()
+        .filter(|chapter| {
+            let has: Vec<String> = chapter.tags.iter().map(|tag| normalize(tag)).collect();
+            match mode {
+                TagMatch::Any => wanted.iter().any(|tag| has.contains(tag)),
+                TagMatch::All => wanted.iter().all(|tag| has.contains(tag)),
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// How `clean_titles` tidies chapter titles. Every step is on by default
+/// except title casing.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct TitleCleanup {
+    /// Remove leading track numbers such as `01 - `, `2. ` or `03)`.
+    pub strip_track_numbers: bool,
+    pub replace_underscores: bool,
+    /// Turn runs of whitespace into single spaces.
+    pub collapse_whitespace: bool,
+    /// Capitalize each word, leaving short joining words like "of" lower
+    /// case unless they start or end the title.
+    pub title_case: bool,
+}
+
+impl Default for TitleCleanup {
+    fn default() -> Self {
+        Self {
+            strip_track_numbers: true,
+            replace_underscores: true,
+            collapse_whitespace: true,
+            title_case: false,
+        }
+    }
+}
+
+/// Longest number taken for a track number, so a title starting with a
+/// year keeps it.
+const MAX_TRACK_NUMBER_DIGITS: usize = 3;
+
+/// Characters that can separate a track number from the title.
+const TRACK_NUMBER_SEPARATORS: [char; 5] = ['.', '-', ')', ':', '–'];
+
+/// Words title casing leaves in lower case inside a title.
+const MINOR_WORDS: [&str; 15] = [
+    "a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or", "the", "to",
+];
+
+/// Returns `title` without one leading track number, or `None` if it
+/// doesn't start with one. The number has to be followed by whitespace or a
+/// separator and then more text.
+fn strip_track_number(title: &str) -> Option<&str> {
+    let digits = title.len() - title.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 || digits > MAX_TRACK_NUMBER_DIGITS {
+        return None;
+    }
+    let after = &title[digits..];
+    let rest = after.trim_start();
+    let rest = match rest.strip_prefix(TRACK_NUMBER_SEPARATORS) {
+        Some(rest) => rest.trim_start(),
+        None if rest.len() < after.len() => rest,
+        None => return None,
+    };
+    (!rest.is_empty()).then_some(rest)
+}
+
+fn title_case(title: &str) -> String {
+    let words: Vec<&str> = title.split(' ').collect();
+    let last = words.len().saturating_sub(1);
+    words
+        .iter()
+        .enumerate()
+        .map(|(index, word)| {
+            let lower = word.to_lowercase();
+            if index != 0 && index != last && MINOR_WORDS.contains(&lower.as_str()) {
+                return lower;
+            }
+            let mut chars = lower.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => lower,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Tidies one title as `options` asks, always trimming it. A title that
+/// would be left empty, e.g. one that is only a number, is kept as it was
+/// apart from the trimming.
+///
+/// Cleaning is idempotent: a cleaned title comes out of a second pass with
+/// the same options unchanged. That's why every leading track number is
+/// stripped, not just the first, so "01 - 02 - Solo" doesn't lose one
+/// number per run.
+pub fn clean_title(title: &str, options: &TitleCleanup) -> String {
+    let mut cleaned = title.trim().to_string();
+    if options.replace_underscores {
+        cleaned = cleaned.replace('_', " ").trim().to_string();
+    }
+    if options.strip_track_numbers {
+        let mut rest = cleaned.as_str();
+        while let Some(stripped) = strip_track_number(rest) {
+            rest = stripped;
+        }
+        cleaned = rest.to_string();
+    }
+    if options.collapse_whitespace {
+        cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    }
+    if options.title_case {
+        cleaned = title_case(&cleaned);
+    }
+    if cleaned.is_empty() {
+        title.trim().to_string()
+    } else {
+        cleaned
+    }
+}
+
+/// Returns `chapters` with their titles cleaned by [`clean_title`].
+pub fn clean_titles(chapters: &[Chapter], options: &TitleCleanup) -> Vec<Chapter> {
+    chapters
+        .iter()
+        .map(|chapter| Chapter {
+            title: clean_title(&chapter.title, options),
+            ..chapter.clone()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(id: &str, start: f64, end: Option<f64>) -> Chapter {
+        Chapter {
+            id: id.to_string(),
+            title: id.to_uppercase(),
+            start,
+            end,
+            duration: end.map(|end| end - start),
+            ..Default::default()
+        }
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn merges_contiguous_chapters() {
+        let chapters = [
+            chapter("a", 0.0, Some(10.0)),
+            chapter("b", 10.0, Some(25.0)),
+            chapter("c", 25.0, Some(40.0)),
+        ];
+        let merged = merge(&chapters, &ids(&["a", "b"]), "Verse").unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].id, "a");
+        assert_eq!(merged[0].title, "Verse");
+        assert_eq!((merged[0].start, merged[0].end), (0.0, Some(25.0)));
+        assert_eq!(merged[1].id, "c");
+    }
+
+    #[test]
+    fn open_ended_chapters_merge_into_an_open_end() {
+        let chapters = [chapter("a", 0.0, None), chapter("b", 10.0, None)];
+        let merged = merge(&chapters, &ids(&["b", "a"]), "Song").unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!((merged[0].start, merged[0].end), (0.0, None));
+    }
+
+    #[test]
+    fn an_open_ended_middle_chapter_runs_to_the_next() {
+        let chapters = [chapter("a", 0.0, None), chapter("b", 10.0, Some(20.0))];
+        let merged = merge(&chapters, &ids(&["a", "b"]), "Song").unwrap();
+        assert_eq!(merged[0].end, Some(20.0));
+    }
+
+    #[test]
+    fn refuses_to_skip_a_chapter() {
+        let chapters = [
+            chapter("a", 0.0, Some(10.0)),
+            chapter("b", 10.0, Some(20.0)),
+            chapter("c", 20.0, Some(30.0)),
+        ];
+        assert!(matches!(
+            merge(&chapters, &ids(&["a", "c"]), "Both"),
+            Err(AppError::InvalidRange(_))
+        ));
+    }
+
+    #[test]
+    fn refuses_a_gap() {
+        let chapters = [
+            chapter("a", 0.0, Some(10.0)),
+            chapter("b", 12.0, Some(20.0)),
+        ];
+        assert!(matches!(
+            merge(&chapters, &ids(&["a", "b"]), "Both"),
+            Err(AppError::InvalidRange(_))
+        ));
+    }
+
+    #[test]
+    fn splits_a_chapter_in_two() {
+        let chapters = [
+            chapter("a", 0.0, Some(10.0)),
+            chapter("b", 10.0, Some(20.0)),
+        ];
+        let split = split(&chapters, "a", 4.0).unwrap();
+        let parts: Vec<(&str, &str, f64, Option<f64>)> = split
+            .iter()
+            .map(|c| (c.id.as_str(), c.title.as_str(), c.start, c.end))
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                ("a-1", "A (Part 1)", 0.0, Some(4.0)),
+                ("a-2", "A (Part 2)", 4.0, Some(10.0)),
+                ("b", "B", 10.0, Some(20.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_ids_avoid_existing_ones() {
+        let chapters = [chapter("a", 0.0, Some(10.0)), chapter("a-1", 10.0, None)];
+        let split = split(&chapters, "a", 5.0).unwrap();
+        assert_eq!(split[0].id, "a-1-2");
+        assert_eq!(split[1].id, "a-2");
+    }
+
+    #[test]
+    fn refuses_to_split_outside_the_chapter() {
+        let chapters = [chapter("a", 0.0, Some(10.0))];
+        for split_at in [0.0, 10.0, 12.0, -1.0, f64::NAN] {
+            assert!(matches!(
+                split(&chapters, "a", split_at),
+                Err(AppError::InvalidRange(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn an_open_ended_chapter_splits_before_the_next() {
+        let chapters = [chapter("a", 0.0, None), chapter("b", 30.0, None)];
+        assert!(split(&chapters, "a", 20.0).is_ok());
+        assert!(matches!(
+            split(&chapters, "a", 35.0),
+            Err(AppError::InvalidRange(_))
+        ));
+    }
+
+    #[test]
+    fn sums_chapters_ending_open_ones_at_the_file_end() {
+        let chapters = [chapter("a", 0.0, Some(30.0)), chapter("b", 30.0, None)];
+        assert_eq!(total_duration(&chapters, 100.0, false).unwrap(), 100.0);
+    }
+
+    #[test]
+    fn overlaps_count_once_when_deduped() {
+        // Out of order, with "b" inside "c" and "c" overlapping "a".
+        let chapters = [
+            chapter("c", 20.0, Some(60.0)),
+            chapter("a", 0.0, Some(30.0)),
+            chapter("b", 25.0, Some(40.0)),
+            chapter("d", 80.0, Some(90.0)),
+        ];
+        assert_eq!(total_duration(&chapters, 100.0, false).unwrap(), 95.0);
+        assert_eq!(total_duration(&chapters, 100.0, true).unwrap(), 70.0);
+    }
+
+    #[test]
+    fn refuses_a_chapter_ending_before_it_starts() {
+        let chapters = [chapter("a", 50.0, None)];
+        assert!(matches!(
+            total_duration(&chapters, 40.0, false),
+            Err(AppError::InvalidRange(_))
+        ));
+    }
+
+    fn titled(title: &str) -> Chapter {
+        Chapter {
+            title: title.to_string(),
+            ..chapter(title, 0.0, None)
+        }
+    }
+
+    fn group_titles(groups: &[ChapterGroup]) -> Vec<(&str, Vec<&str>)> {
+        groups
+            .iter()
+            .map(|g| {
+                let titles = g.chapters.iter().map(|c| c.title.as_str()).collect();
+                (g.group.as_str(), titles)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn groups_by_title_prefix_in_order() {
+        let chapters = [
+            titled("Set 1 - Song A"),
+            titled("Encore"),
+            titled("Set 2 - Song C"),
+            titled("Set 1 - Song B"),
+        ];
+        assert_eq!(
+            group_titles(&group(&chapters, " - ").unwrap()),
+            [
+                ("Set 1", vec!["Set 1 - Song A", "Set 1 - Song B"]),
+                ("Ungrouped", vec!["Encore"]),
+                ("Set 2", vec!["Set 2 - Song C"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn later_separators_stay_in_the_song() {
+        let chapters = [titled("Set 1 - Medley - Part 2")];
+        assert_eq!(group(&chapters, " - ").unwrap()[0].group, "Set 1");
+    }
+
+    #[test]
+    fn an_empty_prefix_is_ungrouped() {
+        let chapters = [titled(" - Untitled")];
+        assert_eq!(group(&chapters, " - ").unwrap()[0].group, "Ungrouped");
+        assert!(group(&[], " - ").unwrap().is_empty());
+        assert!(group(&chapters, "").is_err());
+    }
+
+    fn spans(chapters: &[Chapter]) -> Vec<(&str, f64, Option<f64>)> {
+        chapters
+            .iter()
+            .map(|c| (c.id.as_str(), c.start, c.end))
+            .collect()
+    }
+
+    /// Chapters with gaps at both file boundaries and between them, out of
+    /// order.
+    fn gappy() -> [Chapter; 2] {
+        [
+            chapter("b", 14.0, Some(20.0)),
+            chapter("a", 2.0, Some(10.0)),
+        ]
+    }
+
+    #[test]
+    fn extends_the_previous_chapter() {
+        let filled = fill_gaps(&gappy(), GapFillMode::ExtendPrevious, Some(30.0));
+        assert_eq!(
+            spans(&filled),
+            [("a", 0.0, Some(14.0)), ("b", 14.0, Some(30.0))]
+        );
+        assert_eq!(filled[0].duration, Some(14.0));
+    }
+
+    #[test]
+    fn extends_the_next_chapter() {
+        let filled = fill_gaps(&gappy(), GapFillMode::ExtendNext, Some(30.0));
+        assert_eq!(
+            spans(&filled),
+            [("a", 0.0, Some(10.0)), ("b", 10.0, Some(30.0))]
+        );
+    }
+
+    #[test]
+    fn splits_gaps_evenly() {
+        let filled = fill_gaps(&gappy(), GapFillMode::SplitEvenly, None);
+        // Without the file length the tail gap stays.
+        assert_eq!(
+            spans(&filled),
+            [("a", 0.0, Some(12.0)), ("b", 12.0, Some(20.0))]
+        );
+    }
+
+    #[test]
+    fn moves_a_late_first_chapter_to_the_start() {
+        let chapters = [chapter("a", 12.0, Some(20.0)), chapter("b", 20.0, None)];
+        let filled = fill_gaps(&chapters, GapFillMode::ExtendNext, None);
+        assert_eq!(spans(&filled), [("a", 0.0, Some(20.0)), ("b", 20.0, None)]);
+        assert_eq!(filled[0].duration, Some(20.0));
+    }
+
+    #[test]
+    fn leaves_overlaps_and_open_ends_alone() {
+        let chapters = [
+            chapter("a", 0.0, Some(12.0)),
+            chapter("b", 10.0, None),
+            chapter("c", 20.0, None),
+        ];
+        let filled = fill_gaps(&chapters, GapFillMode::SplitEvenly, Some(30.0));
+        assert_eq!(
+            spans(&filled),
+            [("a", 0.0, Some(12.0)), ("b", 10.0, None), ("c", 20.0, None)]
+        );
+    }
+
+    #[test]
+    fn a_negative_shift_clamps_the_first_chapter_to_zero() {
+        let chapters = [
+            chapter("a", 0.5, Some(10.0)),
+            chapter("b", 10.0, Some(20.0)),
+        ];
+        let shifted = shift(&chapters, -1.0, 60.0).unwrap();
+        assert_eq!(
+            spans(&shifted.chapters),
+            [("a", 0.0, Some(9.0)), ("b", 9.0, Some(19.0))]
+        );
+        assert_eq!(shifted.chapters[0].duration, Some(9.0));
+        assert_eq!(shifted.dropped, 0);
+    }
+
+    #[test]
+    fn drops_chapters_shifted_out_of_the_file() {
+        let chapters = [
+            chapter("a", 0.0, Some(0.8)),
+            chapter("b", 0.8, Some(30.0)),
+            chapter("c", 30.0, None),
+        ];
+        let shifted = shift(&chapters, -1.0, 60.0).unwrap();
+        assert_eq!(
+            spans(&shifted.chapters),
+            [("b", 0.0, Some(29.0)), ("c", 29.0, None)]
+        );
+        assert_eq!(shifted.dropped, 1);
+
+        let shifted = shift(&chapters, 45.0, 60.0).unwrap();
+        assert_eq!(
+            spans(&shifted.chapters),
+            [("a", 45.0, Some(45.8)), ("b", 45.8, Some(60.0))]
+        );
+        assert_eq!(shifted.dropped, 1);
+    }
+
+    #[test]
+    fn refuses_an_invalid_shift() {
+        let chapters = [chapter("a", 0.0, None)];
+        assert!(shift(&chapters, f64::NAN, 60.0).is_err());
+        assert!(shift(&chapters, 1.0, 0.0).is_err());
+    }
+
+    fn kinds(warnings: &[ChapterWarning]) -> Vec<(ChapterWarningKind, &str, Option<&str>)> {
+        warnings
+            .iter()
+            .map(|w| (w.kind, w.chapter_id.as_str(), w.other_chapter_id.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn a_clean_list_has_no_warnings() {
+        let chapters = [
+            chapter("a", 0.0, Some(10.0)),
+            // Rounding in the timestamps isn't an overlap.
+            chapter("b", 9.99, None),
+            chapter("c", 20.0, Some(60.0)),
+        ];
+        assert!(validate(&chapters, 60.0).is_empty());
+    }
+
+    #[test]
+    fn reports_overlaps_once() {
+        let chapters = [
+            chapter("a", 0.0, Some(15.0)),
+            chapter("b", 10.0, Some(20.0)),
+            chapter("c", 10.0, Some(20.0)),
+        ];
+        assert_eq!(
+            kinds(&validate(&chapters, 60.0)),
+            [
+                (ChapterWarningKind::Overlap, "a", Some("b")),
+                (ChapterWarningKind::Overlap, "a", Some("c")),
+                (ChapterWarningKind::Overlap, "b", Some("c")),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_zero_length_chapters() {
+        let chapters = [chapter("a", 5.0, Some(5.0)), chapter("b", 8.0, Some(7.0))];
+        assert_eq!(
+            kinds(&validate(&chapters, 60.0)),
+            [
+                (ChapterWarningKind::ZeroLength, "a", None),
+                (ChapterWarningKind::ZeroLength, "b", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_chapters_past_the_end() {
+        let chapters = [chapter("a", 0.0, Some(65.0)), chapter("b", 70.0, None)];
+        assert_eq!(
+            kinds(&validate(&chapters, 60.0)),
+            [
+                (ChapterWarningKind::PastEnd, "a", None),
+                (ChapterWarningKind::ZeroLength, "b", None),
+                (ChapterWarningKind::PastEnd, "b", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_out_of_order_chapters() {
+        let chapters = [
+            chapter("a", 20.0, Some(30.0)),
+            chapter("b", 0.0, Some(10.0)),
+            chapter("c", 10.0, Some(20.0)),
+        ];
+        assert_eq!(
+            kinds(&validate(&chapters, 60.0)),
+            [
+                (ChapterWarningKind::OutOfOrder, "b", Some("a")),
+                (ChapterWarningKind::OutOfOrder, "c", Some("a")),
+            ]
+        );
+    }
+
+    const NO_CLEANUP: TitleCleanup = TitleCleanup {
+        strip_track_numbers: false,
+        replace_underscores: false,
+        collapse_whitespace: false,
+        title_case: false,
+    };
+
+    #[test]
+    fn strips_track_numbers() {
+        let options = TitleCleanup {
+            strip_track_numbers: true,
+            ..NO_CLEANUP
+        };
+        for (title, cleaned) in [
+            ("01 - Intro", "Intro"),
+            ("2. Verse", "Verse"),
+            ("03) Solo", "Solo"),
+            ("04 Outro", "Outro"),
+            ("01 - 02 - Solo", "Solo"),
+            ("1999 Live", "1999 Live"),
+            ("7", "7"),
+            ("3rd Verse", "3rd Verse"),
+        ] {
+            assert_eq!(clean_title(title, &options), cleaned, "from '{}'", title);
+        }
+    }
+
+    #[test]
+    fn replaces_underscores() {
+        let options = TitleCleanup {
+            replace_underscores: true,
+            ..NO_CLEANUP
+        };
+        assert_eq!(clean_title("_guitar_solo_", &options), "guitar solo");
+        assert_eq!(clean_title("02_SOLO", &options), "02 SOLO");
+    }
+
+    #[test]
+    fn collapses_whitespace() {
+        let options = TitleCleanup {
+            collapse_whitespace: true,
+            ..NO_CLEANUP
+        };
+        assert_eq!(clean_title("  big \t  riff  ", &options), "big riff");
+    }
+
+    #[test]
+    fn title_cases_all_but_minor_words() {
+        let options = TitleCleanup {
+            title_case: true,
+            ..NO_CLEANUP
+        };
+        assert_eq!(
+            clean_title("return OF the king of", &options),
+            "Return of the King Of"
+        );
+        assert_eq!(clean_title("the end", &options), "The End");
+    }
+
+    #[test]
+    fn only_trims_with_every_step_off() {
+        assert_eq!(
+            clean_title("  01 - intro__a  ", &NO_CLEANUP),
+            "01 - intro__a"
+        );
+    }
+
+    #[test]
+    fn combines_every_step() {
+        let options = TitleCleanup {
+            title_case: true,
+            ..TitleCleanup::default()
+        };
+        let chapters = [
+            titled("01 - intro  "),
+            titled("02_SOLO"),
+            titled("3.  the   bridge_of_doom"),
+        ];
+        let titles: Vec<String> = clean_titles(&chapters, &options)
+            .into_iter()
+            .map(|c| c.title)
+            .collect();
+        assert_eq!(titles, ["Intro", "Solo", "The Bridge of Doom"]);
+        assert_eq!(
+            clean_title("01 - intro  ", &TitleCleanup::default()),
+            "intro"
+        );
+    }
+
+    #[test]
+    fn cleaning_twice_changes_nothing() {
+        let all = TitleCleanup {
+            title_case: true,
+            ..TitleCleanup::default()
+        };
+        for options in [NO_CLEANUP, TitleCleanup::default(), all] {
+            for title in [
+                "01 - 02 - Solo",
+                " 7 ",
+                "3_ _4 _ x",
+                "a_b  c",
+                "01 -  the_END  ",
+            ] {
+                let once = clean_title(title, &options);
+                assert_eq!(clean_title(&once, &options), once, "from '{}'", title);
+            }
+        }
+    }
+
+    /// Three chapters out of order, with a gap before the first and
+    /// between the last two.
+    fn song() -> Vec<Chapter> {
+        vec![
+            chapter("chorus", 40.0, Some(50.0)),
+            chapter("intro", 10.0, Some(20.0)),
+            chapter("verse", 20.0, Some(30.0)),
+        ]
+    }
+
+    fn titles(chapters: &[Chapter]) -> Vec<(&str, &str)> {
+        chapters
+            .iter()
+            .map(|c| (c.id.as_str(), c.title.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn inserts_a_range_at_the_start() {
+        let chapters = insert_range(&song(), 2.0, 8.0, None).unwrap();
+        assert_eq!(
+            titles(&chapters),
+            [
+                ("chapter-0", "Before INTRO"),
+                ("chapter-1", "INTRO"),
+                ("chapter-2", "VERSE"),
+                ("chapter-3", "CHORUS"),
+            ]
+        );
+        assert_eq!((chapters[0].start, chapters[0].end), (2.0, Some(8.0)));
+        assert_eq!(chapters[0].duration, Some(6.0));
+    }
+
+    #[test]
+    fn inserts_a_range_in_the_middle() {
+        let chapters = insert_range(&song(), 32.0, 38.0, None).unwrap();
+        assert_eq!(
+            titles(&chapters),
+            [
+                ("chapter-0", "INTRO"),
+                ("chapter-1", "VERSE"),
+                ("chapter-2", "Between VERSE and CHORUS"),
+                ("chapter-3", "CHORUS"),
+            ]
+        );
+
+        let chapters = insert_range(&song(), 22.0, 26.0, None).unwrap();
+        assert_eq!(chapters[2].title, "VERSE (loop)");
+        assert_eq!(chapters[2].start, 22.0);
+    }
+
+    #[test]
+    fn inserts_a_range_at_the_end() {
+        let chapters = insert_range(&song(), 55.0, 60.0, Some("  Outro jam ")).unwrap();
+        assert_eq!(
+            titles(&chapters),
+            [
+                ("chapter-0", "INTRO"),
+                ("chapter-1", "VERSE"),
+                ("chapter-2", "CHORUS"),
+                ("chapter-3", "Outro jam"),
+            ]
+        );
+        let chapters = insert_range(&song(), 55.0, 60.0, Some(" ")).unwrap();
+        assert_eq!(chapters[3].title, "After CHORUS");
+    }
+
+    #[test]
+    fn a_range_goes_after_a_chapter_starting_with_it() {
+        let chapters = insert_range(&song(), 20.0, 24.0, None).unwrap();
+        assert_eq!(
+            titles(&chapters)[1..3],
+            [("chapter-1", "VERSE"), ("chapter-2", "VERSE (loop)")]
+        );
+    }
+
+    #[test]
+    fn names_a_range_by_time_without_chapters() {
+        let chapters = insert_range(&[], 65.0, 3725.0, None).unwrap();
+        assert_eq!(titles(&chapters), [("chapter-0", "Loop 1:05 - 1:02:05")]);
+    }
+
+    #[test]
+    fn rejects_an_empty_range() {
+        assert!(matches!(
+            insert_range(&song(), 30.0, 30.0, None),
+            Err(AppError::InvalidRange(_))
+        ));
+    }
+
+    fn tagged(id: &str, tags: &[&str]) -> Chapter {
+        Chapter {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..chapter(id, 0.0, None)
+        }
+    }
+
+    fn tag_filter(tags: &[&str], mode: TagMatch) -> Vec<String> {
+        let chapters = [
+            tagged("a", &["Hard", "solo"]),
+            tagged("b", &["solo"]),
+            tagged("c", &[]),
+            tagged("d", &["rhythm", " hard "]),
+        ];
+        let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+        filter_by_tags(&chapters, &tags, mode)
+            .into_iter()
+            .map(|c| c.id)
+            .collect()
+    }
+
+    #[test]
+    fn any_mode_needs_one_tag() {
+        assert_eq!(tag_filter(&["solo"], TagMatch::Any), ids(&["a", "b"]));
+        assert_eq!(
+            tag_filter(&["hard", "solo"], TagMatch::Any),
+            ids(&["a", "b", "d"])
+        );
+        assert!(tag_filter(&["lead"], TagMatch::Any).is_empty());
+    }
+
+    #[test]
+    fn all_mode_needs_every_tag() {
+        assert_eq!(tag_filter(&["hard", "solo"], TagMatch::All), ids(&["a"]));
+        assert_eq!(tag_filter(&["HARD "], TagMatch::All), ids(&["a", "d"]));
+        assert!(tag_filter(&["hard", "lead"], TagMatch::All).is_empty());
+    }
+
+    #[test]
+    fn no_tags_match_everything() {
+        let all = ids(&["a", "b", "c", "d"]);
+        assert_eq!(tag_filter(&[], TagMatch::Any), all);
+        assert_eq!(tag_filter(&[" "], TagMatch::All), all);
+    }
+
+    #[test]
+    fn chapters_without_color_or_tags_still_load() {
+        let old: Chapter =
+            serde_json::from_str(r#"{"id": "chapter-0", "title": "Intro", "start": 0.0}"#).unwrap();
+        assert_eq!((old.color, old.tags), (None, Vec::<String>::new()));
+
+        let json = serde_json::to_value(tagged("a", &["solo"])).unwrap();
+        assert!(json.get("color").is_none());
+        assert_eq!(json["tags"], serde_json::json!(["solo"]));
+    }
+
+    fn normalized(
+        chapters: &[Chapter],
+        file_duration: Option<f64>,
+    ) -> Vec<(String, f64, Option<f64>)> {
+        normalize(chapters, file_duration)
+            .into_iter()
+            .map(|c| (c.title, c.start, c.end))
+            .collect()
+    }
+
+    fn row(title: &str, start: f64, end: Option<f64>) -> (String, f64, Option<f64>) {
+        (title.to_string(), start, end)
+    }
+
+    #[test]
+    fn normalize_sorts_and_fills_missing_ends() {
+        // As a CUE sheet gives them: starts only, not always in order.
+        let chapters = [
+            chapter("b", 60.0, None),
+            chapter("a", 0.0, None),
+            chapter("c", 120.0, None),
+        ];
+        assert_eq!(
+            normalized(&chapters, Some(200.0)),
+            [
+                row("A", 0.0, Some(60.0)),
+                row("B", 60.0, Some(120.0)),
+                row("C", 120.0, Some(200.0)),
+            ]
+        );
+        assert_eq!(normalized(&chapters, Some(100.0))[2], row("C", 120.0, None));
+        assert_eq!(normalized(&chapters, None)[2], row("C", 120.0, None));
+    }
+
+    #[test]
+    fn normalize_clamps_and_drops_bad_times() {
+        // As label tracks and hand-edited files can have them.
+        let chapters = [
+            chapter("early", -0.5, Some(10.0)),
+            chapter("nan", f64::NAN, Some(20.0)),
+            chapter("backwards", 20.0, Some(15.0)),
+            chapter("infinite", 30.0, Some(f64::INFINITY)),
+            chapter("last", 40.0, Some(-1.0)),
+        ];
+        assert_eq!(
+            normalized(&chapters, Some(50.0)),
+            [
+                row("EARLY", 0.0, Some(10.0)),
+                row("BACKWARDS", 20.0, Some(30.0)),
+                row("INFINITE", 30.0, Some(40.0)),
+                row("LAST", 40.0, Some(50.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn normalize_drops_repeated_chapters() {
+        // As pasting a description twice, or rounding, gives them.
+        let chapters = [
+            chapter("intro", 0.0, None),
+            chapter("verse", 30.0, Some(60.0)),
+            chapter("intro", 0.0, None),
+            chapter("verse", 30.0005, Some(59.9995)),
+            // Same start but another title, or another end, is kept.
+            Chapter {
+                title: "Riff".to_string(),
+                ..chapter("riff", 30.0, Some(60.0))
+            },
+            chapter("verse", 30.0, Some(45.0)),
+        ];
+        assert_eq!(
+            normalized(&chapters, None),
+            [
+                row("INTRO", 0.0, Some(30.0)),
+                row("VERSE", 30.0, Some(60.0)),
+                row("Riff", 30.0, Some(60.0)),
+                row("VERSE", 30.0, Some(45.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn normalize_renumbers_and_keeps_user_data() {
+        let tagged = Chapter {
+            color: Some("#f80".to_string()),
+            tags: vec!["solo".to_string()],
+            source: Some(crate::ChapterSource::Cue),
+            ..chapter("z", 30.0, None)
+        };
+        let chapters = normalize(&[tagged, chapter("y", 0.0, Some(10.0))], Some(40.0));
+        assert_eq!(
+            spans(&chapters),
+            [
+                ("chapter-0", 0.0, Some(10.0)),
+                ("chapter-1", 30.0, Some(40.0))
+            ]
+        );
+        assert_eq!(chapters[0].duration, Some(10.0));
+        assert_eq!(chapters[1].duration, Some(10.0));
+        assert_eq!(chapters[1].color.as_deref(), Some("#f80"));
+        assert_eq!(chapters[1].tags, ["solo"]);
+        assert_eq!(chapters[1].source, Some(crate::ChapterSource::Cue));
+    }
+
+    #[test]
+    fn normalizing_twice_changes_nothing() {
+        let chapters = [
+            chapter("b", 60.0, None),
+            chapter("a", -3.0, Some(70.0)),
+            chapter("b", 60.0, None),
+            chapter("c", 60.0, Some(50.0)),
+        ];
+        let once = normalize(&chapters, Some(90.0));
+        let twice = normalize(&once, Some(90.0));
+        assert_eq!(spans(&once), spans(&twice));
+        assert_eq!(
+            once.iter().map(|c| &c.title).collect::<Vec<_>>(),
+            twice.iter().map(|c| &c.title).collect::<Vec<_>>()
+        );
+    }
+}
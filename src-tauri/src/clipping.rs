@@ -0,0 +1,114 @@
+use serde::Serialize;
+
+/// Default number of consecutive full-scale samples that count as clipping.
+/// A single sample at full scale is often just a loud, clean peak.
+pub const DEFAULT_MIN_RUN: usize = 3;
+
+/// Magnitude treated as full scale. Positive 16-bit full scale decodes to
+/// 32767/32768, just under 1.0, and lossy decoders overshoot it.
+const FULL_SCALE: f32 = 0.9999;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClippingReport {
+    /// Samples, across all channels, that are part of a clipped run.
+    pub clipped_sample_count: u64,
+    /// `(start, end)` times of the clipped runs of any channel, merged where
+    /// they overlap.
+    pub clipped_regions: Vec<(f64, f64)>,
+}
+
+/// Finds runs of at least `min_run` consecutive full-scale samples in
+/// interleaved `samples` of `channels` channels at `sample_rate`.
+///
+/// Region times are offset by `start`, the time of the first sample.
+pub fn detect(
+    samples: &[f32],
+    channels: usize,
+    sample_rate: u32,
+    min_run: usize,
+    start: f64,
+) -> ClippingReport {
+    let channels = channels.max(1);
+    let min_run = min_run.max(1);
+    let frames = samples.len() / channels;
+
+    let mut clipped_sample_count = 0;
+    // Clipped runs as (first frame, frame after the last) pairs.
+    let mut runs = Vec::new();
+    for channel in 0..channels {
+        let mut run_start = None;
+        for frame in 0..=frames {
+            let clipped = frame < frames && samples[frame * channels + channel].abs() >= FULL_SCALE;
+            match (clipped, run_start) {
+                (true, None) => run_start = Some(frame),
+                (false, Some(first)) => {
+                    if frame - first >= min_run {
+                        clipped_sample_count += (frame - first) as u64;
+                        runs.push((first, frame));
+                    }
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    runs.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (first, end) in runs {
+        match merged.last_mut() {
+            Some(last) if first <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((first, end)),
+        }
+    }
+
+    let rate = sample_rate as f64;
+    ClippingReport {
+        clipped_sample_count,
+        clipped_regions: merged
+            .into_iter()
+            .map(|(first, end)| (start + first as f64 / rate, start + end as f64 / rate))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_a_clipped_run() {
+        let mut samples = vec![0.5f32; 100];
+        samples[40..50].fill(1.0);
+        // A lone full-scale peak is too short to count.
+        samples[70] = -1.0;
+        let report = detect(&samples, 1, 100, DEFAULT_MIN_RUN, 10.0);
+        assert_eq!(report.clipped_sample_count, 10);
+        assert_eq!(report.clipped_regions, [(10.4, 10.5)]);
+    }
+
+    #[test]
+    fn merges_overlapping_runs_across_channels() {
+        // Stereo: the left channel clips in frames 10..20, the right in 15..30.
+        let mut samples = vec![0.0f32; 200];
+        for frame in 10..20 {
+            samples[frame * 2] = 1.0;
+        }
+        for frame in 15..30 {
+            samples[frame * 2 + 1] = -1.0;
+        }
+        let report = detect(&samples, 2, 100, 3, 0.0);
+        assert_eq!(report.clipped_sample_count, 25);
+        assert_eq!(report.clipped_regions, [(0.1, 0.3)]);
+    }
+
+    #[test]
+    fn a_run_at_the_end_is_counted() {
+        let mut samples = vec![0.0f32; 10];
+        samples[6..].fill(1.0);
+        assert_eq!(
+            detect(&samples, 1, 10, 4, 0.0).clipped_regions,
+            [(0.6, 1.0)]
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::Path;
+
+/// Builds the input list for ffmpeg's concat demuxer, one `file` line per
+/// segment in playback order.
+pub fn concat_list(segments: &[impl AsRef<Path>]) -> String {
+    segments
+        .iter()
+        .map(|segment| {
+            // Paths are single-quoted; a quote inside one is closed, escaped
+            // and reopened.
+            let path = segment.as_ref().to_string_lossy().replace('\'', "'\\''");
+            format!("file '{}'\n", path)
+        })
+        .collect()
+}
+
+/// Arguments for cutting `start..end` out of `file_path` into an
+/// uncompressed segment, so joining segments never lands on a codec frame
+/// boundary.
+pub fn segment_args(file_path: &str, start: f64, end: f64, segment_path: &str) -> Vec<String> {
+    vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        start.to_string(),
+        "-to".to_string(),
+        end.to_string(),
+        "-i".to_string(),
+        file_path.to_string(),
+        "-vn".to_string(),
+        "-c:a".to_string(),
+        "pcm_f32le".to_string(),
+        segment_path.to_string(),
+    ]
+}
+
+/// Filter graph joining input 0 and input 1, gained by `gain_db`, with
+/// `gap` seconds of silence between them. Both are resampled to
+/// `sample_rate` and converted to stereo so concat accepts them. The result
+/// is labelled `[ab]`.
+pub fn ab_comparison_graph(sample_rate: u32, gain_db: f64, gap: f64) -> String {
+    let format = format!(
+        "aresample={},aformat=sample_fmts=fltp:channel_layouts=stereo",
+        sample_rate
+    );
+    format!(
+        "[0:a]{format}[a];[1:a]volume={}dB,{format}[b];\
+         anullsrc=r={}:cl=stereo,atrim=duration={},{format}[gap];\
+         [a][gap][b]concat=n=3:v=0:a=1[ab]",
+        gain_db,
+        sample_rate,
+        gap,
+        format = format
+    )
+}
+
+/// Whether audio at these sample rates can't be joined as is. Unknown
+/// rates, reported as 0, are ignored.
+pub fn mixed_sample_rates(rates: &[u32]) -> bool {
+    let mut known = rates.iter().filter(|rate| **rate > 0);
+    known
+        .next()
+        .is_some_and(|first| known.any(|rate| rate != first))
+}
+
+/// Rate to resample audio at `rates` to before joining it: the highest, so
+/// no input loses its upper frequencies. `None` if no rate is known.
+pub fn target_sample_rate(rates: &[u32]) -> Option<u32> {
+    rates.iter().copied().filter(|rate| *rate > 0).max()
+}
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::retry;
+use crate::settings;
+use crate::sidecar::{BundledTools, BundledToolsState};
+
+/// Default number of seconds ffmpeg or ffprobe may go without producing any
+/// output before it is treated as hung.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+/// Largest accepted `threads` setting.
+pub const MAX_THREADS: u32 = 256;
+
+/// External tools the app shells out to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Ffmpeg,
+    Ffprobe,
+}
+
+impl Tool {
+    /// Executable name used for a PATH lookup.
+    pub fn default_program(self) -> &'static str {
+        match self {
+            Tool::Ffmpeg => "ffmpeg",
+            Tool::Ffprobe => "ffprobe",
+        }
+    }
+}
+
+/// Where the program launched for a tool comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolSource {
+    /// A path the user set.
+    Configured,
+    /// A sidecar binary shipped with the app.
+    Bundled,
+    /// Looked up on PATH.
+    Path,
+}
+
+/// Where to find the ffmpeg binaries and how long to wait on them. `None`
+/// paths fall back to the binaries bundled with the app, then to PATH.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FfmpegConfig {
+    pub ffmpeg_path: Option<String>,
+    pub ffprobe_path: Option<String>,
+    /// Seconds without output before a process is killed, or `None` for
+    /// [`DEFAULT_TIMEOUT_SECS`].
+    pub timeout_secs: Option<u64>,
+    /// Threads ffmpeg may use per run, or `None`/0 to let it decide.
+    ///
+    /// Mostly speeds up decoding video and encoding. Common audio decoders
+    /// (MP3, AAC, FLAC) and most of the audio filters used here, such as
+    /// `atempo`, `areverse`, `loudnorm`, `silencedetect` and
+    /// `silenceremove`, run on one thread regardless.
+    pub threads: Option<u32>,
+    /// Times a read that failed with a transient I/O error is retried, or
+    /// `None` for [`retry::DEFAULT_RETRIES`].
+    pub retries: Option<u32>,
+    /// Sidecar binaries found with the app. Not a setting, so not saved;
+    /// [`current`] fills it in.
+    #[serde(skip)]
+    pub bundled: BundledTools,
+}
+
+impl FfmpegConfig {
+    fn configured(&self, tool: Tool) -> Option<&String> {
+        match tool {
+            Tool::Ffmpeg => self.ffmpeg_path.as_ref(),
+            Tool::Ffprobe => self.ffprobe_path.as_ref(),
+        }
+    }
+
+    /// Returns the program to launch for `tool`: the configured path, else
+    /// the bundled binary, else the name to look up on PATH.
+    pub fn program(&self, tool: Tool) -> String {
+        if let Some(path) = self.configured(tool) {
+            return path.clone();
+        }
+        match self.bundled.get(tool) {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => tool.default_program().to_string(),
+        }
+    }
+
+    /// Returns where [`Self::program`] comes from.
+    pub fn source(&self, tool: Tool) -> ToolSource {
+        if self.configured(tool).is_some() {
+            ToolSource::Configured
+        } else if self.bundled.get(tool).is_some() {
+            ToolSource::Bundled
+        } else {
+            ToolSource::Path
+        }
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries.unwrap_or(retry::DEFAULT_RETRIES)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS))
+    }
+}
+
+/// Returns the path of `file_name` in the app config directory, creating the
+/// directory if needed.
+pub(crate) fn config_path(handle: &AppHandle, file_name: &str) -> Result<PathBuf, AppError> {
+    let dir = handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| AppError::Io(format!("Failed to resolve app config directory: {}", e)))?;
+    fs::create_dir_all(&dir).map_err(|e| {
+        AppError::Io(format!(
+            "Failed to create config directory {}: {}",
+            dir.display(),
+            e
+        ))
+    })?;
+    Ok(dir.join(file_name))
+}
+
+/// Returns a snapshot of the current ffmpeg settings, with the bundled
+/// binaries filled in.
+pub fn current(handle: &AppHandle) -> FfmpegConfig {
+    FfmpegConfig {
+        bundled: handle.state::<BundledToolsState>().get(),
+        ..settings::current(handle).ffmpeg
+    }
+}
@@ -0,0 +1,251 @@
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::{chapters, Chapter, ChapterSource};
+
+/// CD audio frames per second, the unit of the last field in `MM:SS:FF`.
+const FRAMES_PER_SECOND: f64 = 75.0;
+
+struct CueTrack {
+    number: u32,
+    title: Option<String>,
+    start: Option<f64>,
+    /// Index of the `FILE` directive the track belongs to.
+    file: usize,
+}
+
+/// Parses `MM:SS:FF` into seconds.
+pub fn parse_timestamp(value: &str) -> Option<f64> {
+    let mut parts = value.split(':');
+    let minutes: u32 = parts.next()?.parse().ok()?;
+    let seconds: u32 = parts.next()?.parse().ok()?;
+    let frames: u32 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || seconds >= 60 || frames >= FRAMES_PER_SECOND as u32 {
+        return None;
+    }
+    Some(minutes as f64 * 60.0 + seconds as f64 + frames as f64 / FRAMES_PER_SECOND)
+}
+
+/// Formats seconds as `MM:SS:FF`, rounding to the nearest CD frame.
+pub fn format_timestamp(seconds: f64) -> String {
+    let total_frames = (seconds.max(0.0) * FRAMES_PER_SECOND).round() as u64;
+    let frames_per_second = FRAMES_PER_SECOND as u64;
+    let frames = total_frames % frames_per_second;
+    let total_seconds = total_frames / frames_per_second;
+    format!(
+        "{:02}:{:02}:{:02}",
+        total_seconds / 60,
+        total_seconds % 60,
+        frames
+    )
+}
+
+/// Strips the surrounding quotes from a CUE string argument.
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+        .to_string()
+}
+
+/// Counts the `FILE` directives of a CUE sheet. Times restart in each, so
+/// the tracks of a sheet naming several files don't share one timeline.
+pub fn file_count(contents: &str) -> usize {
+    contents
+        .lines()
+        .filter(|line| {
+            let line = line.trim().trim_start_matches('\u{feff}');
+            let command = line.split(char::is_whitespace).next().unwrap_or(line);
+            command.eq_ignore_ascii_case("FILE")
+        })
+        .count()
+}
+
+/// Parses the contents of a CUE sheet into chapters.
+///
+/// Each track starts at its `INDEX 01` and ends where the next track starts.
+/// Index times restart in every `FILE` of a multi-file sheet, so the last
+/// track of each file is left open-ended rather than borrowing a start time
+/// from the next file.
+pub fn parse(contents: &str) -> Result<Vec<Chapter>, AppError> {
+    let mut tracks: Vec<CueTrack> = Vec::new();
+    let mut file = 0;
+    let mut seen_file = false;
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match command.to_ascii_uppercase().as_str() {
+            "FILE" => {
+                if seen_file {
+                    file += 1;
+                }
+                seen_file = true;
+            }
+            "TRACK" => {
+                let number = rest
+                    .split_whitespace()
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| {
+                        AppError::ParseFailed(format!(
+                            "Line {}: invalid TRACK number",
+                            line_number + 1
+                        ))
+                    })?;
+                tracks.push(CueTrack {
+                    number,
+                    title: None,
+                    start: None,
+                    file,
+                });
+            }
+            // A TITLE before the first TRACK names the whole disc.
+            "TITLE" => {
+                if let Some(track) = tracks.last_mut() {
+                    track.title = Some(unquote(rest));
+                }
+            }
+            "INDEX" => {
+                let mut fields = rest.split_whitespace();
+                if fields.next() != Some("01") {
+                    continue;
+                }
+                let start = fields.next().and_then(parse_timestamp).ok_or_else(|| {
+                    AppError::ParseFailed(format!(
+                        "Line {}: invalid INDEX timestamp, expected MM:SS:FF",
+                        line_number + 1
+                    ))
+                })?;
+                if let Some(track) = tracks.last_mut() {
+                    track.start = Some(start);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let tracks: Vec<(CueTrack, f64)> = tracks
+        .into_iter()
+        .filter_map(|track| track.start.map(|start| (track, start)))
+        .collect();
+
+    let chapters = tracks
+        .iter()
+        .enumerate()
+        .map(|(index, (track, start))| {
+            let end = tracks
+                .get(index + 1)
+                .filter(|(next, _)| next.file == track.file)
+                .map(|(_, next_start)| *next_start);
+            let title = track
+                .title
+                .clone()
+                .unwrap_or_else(|| format!("Track {:02}", track.number));
+            chapters::imported(index, title, *start, end, ChapterSource::Cue)
+        })
+        .collect();
+    Ok(chapters)
+}
+
+/// The CUE spec only allows track numbers 01 through 99.
+const MAX_TRACKS: usize = 99;
+
+/// Picks the `FILE` type keyword for a source file from its extension.
+fn file_type(file_path: &str) -> &'static str {
+    let extension = Path::new(file_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("mp3") => "MP3",
+        Some("aif") | Some("aiff") => "AIFF",
+        _ => "WAVE",
+    }
+}
+
+/// Renders `chapters` as a CUE sheet referencing `file_name`.
+///
+/// Tracks are written in ascending start order regardless of input order.
+pub fn write(chapters: &[Chapter], file_name: &str) -> Result<String, AppError> {
+    if chapters.len() > MAX_TRACKS {
+        return Err(AppError::InvalidInput(format!(
+            "A CUE sheet can hold at most {} tracks, got {}",
+            MAX_TRACKS,
+            chapters.len()
+        )));
+    }
+
+    let mut sorted: Vec<&Chapter> = chapters.iter().collect();
+    sorted.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    // Quotes can't be escaped inside CUE strings.
+    let quote = |value: &str| format!("\"{}\"", value.replace('"', "'"));
+
+    let mut sheet = format!("FILE {} {}\n", quote(file_name), file_type(file_name));
+    for (index, chapter) in sorted.iter().enumerate() {
+        sheet.push_str(&format!("  TRACK {:02} AUDIO\n", index + 1));
+        sheet.push_str(&format!("    TITLE {}\n", quote(&chapter.title)));
+        sheet.push_str(&format!(
+            "    INDEX 01 {}\n",
+            format_timestamp(chapter.start)
+        ));
+    }
+    Ok(sheet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_frame_timing() {
+        assert_eq!(parse_timestamp("01:02:15"), Some(62.2));
+        assert_eq!(parse_timestamp("00:00:75"), None);
+    }
+
+    #[test]
+    fn ignores_rem_comments() {
+        let sheet = r#"REM GENRE Rock
+REM DATE 1977
+PERFORMER "The Band"
+TITLE "Live"
+FILE "live.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "Opener"
+    REM COMPOSER "Someone"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    REM INDEX 01 09:99:99
+    INDEX 01 03:30:00
+"#;
+        let chapters = parse(sheet).unwrap();
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].title, "Opener");
+        assert_eq!(chapters[0].start, 0.0);
+        assert_eq!(chapters[0].end, Some(210.0));
+        assert_eq!(chapters[1].title, "Track 02");
+        assert_eq!(chapters[1].end, None);
+    }
+
+    #[test]
+    fn ends_each_file_open() {
+        let sheet = r#"FILE "disc1.wav" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    INDEX 01 02:00:00
+FILE "disc2.wav" WAVE
+  TRACK 03 AUDIO
+    INDEX 01 00:00:00
+  TRACK 04 AUDIO
+    INDEX 01 01:00:00
+"#;
+        assert_eq!(file_count(sheet), 2);
+        let chapters = parse(sheet).unwrap();
+        let ends: Vec<Option<f64>> = chapters.iter().map(|c| c.end).collect();
+        assert_eq!(ends, [Some(120.0), None, Some(60.0), None]);
+    }
+}
@@ -0,0 +1,125 @@
+use serde::Serialize;
+
+/// Offset below which a channel is reported as clean, about -60 dBFS. Even
+/// cheap converters rarely sit lower than that, and it is far too small to
+/// matter for headroom or clicks.
+pub const SIGNIFICANT_OFFSET: f64 = 0.001;
+
+/// Filter exports add to take out a DC offset. A high pass this low leaves
+/// even a low B on a five-string bass untouched, and needs no measurement,
+/// so it also follows an offset that drifts.
+pub const REMOVE_FILTER: &str = "highpass=f=10";
+
+/// Level given to an offset of exactly zero, in dB.
+const FLOOR_DB: f64 = -120.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DcOffsetReport {
+    /// Mean sample value of each channel, from -1 to 1.
+    pub offsets: Vec<f64>,
+    /// The largest offset of any channel in dBFS.
+    pub max_offset_db: f64,
+    /// Whether any channel is off by more than [`SIGNIFICANT_OFFSET`].
+    pub significant: bool,
+}
+
+/// Measures the DC offset of each channel of interleaved `samples` of
+/// `channels` channels. Trailing samples that don't fill a frame are left
+/// out, and empty input reads as no offset.
+pub fn measure(samples: &[f32], channels: usize) -> DcOffsetReport {
+    let channels = channels.max(1);
+    let frames = samples.len() / channels;
+    let mut sums = vec![0.0f64; channels];
+    for frame in samples.chunks_exact(channels) {
+        for (sum, sample) in sums.iter_mut().zip(frame) {
+            *sum += *sample as f64;
+        }
+    }
+    let offsets: Vec<f64> = sums
+        .into_iter()
+        .map(|sum| if frames > 0 { sum / frames as f64 } else { 0.0 })
+        .collect();
+
+    let max = offsets
+        .iter()
+        .fold(0.0f64, |max, offset| max.max(offset.abs()));
+    DcOffsetReport {
+        max_offset_db: if max > 0.0 {
+            (20.0 * max.log10()).max(FLOOR_DB)
+        } else {
+            FLOOR_DB
+        },
+        significant: max > SIGNIFICANT_OFFSET,
+        offsets,
+    }
+}
+
+/// Puts [`REMOVE_FILTER`] in front of the audio `filter`, if any, when
+/// `remove_dc` is set. It has to come first so fades end at true silence.
+pub fn with_removal(remove_dc: Option<bool>, filter: Option<String>) -> Option<String> {
+    let filters: Vec<String> = remove_dc
+        .unwrap_or(false)
+        .then(|| REMOVE_FILTER.to_string())
+        .into_iter()
+        .chain(filter)
+        .collect();
+    (!filters.is_empty()).then(|| filters.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whole periods of a 100-sample sine in each of two channels, shifted
+    /// by `left` and `right`.
+    fn offset_sine(left: f32, right: f32) -> Vec<f32> {
+        (0..48_000)
+            .flat_map(|i| {
+                let wave = 0.5 * (2.0 * std::f32::consts::PI * i as f32 / 100.0).sin();
+                [wave + left, -wave + right]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn measures_a_known_offset_per_channel() {
+        let report = measure(&offset_sine(0.05, -0.01), 2);
+        assert_eq!(report.offsets.len(), 2);
+        assert!((report.offsets[0] - 0.05).abs() < 1e-6, "{:?}", report);
+        assert!((report.offsets[1] + 0.01).abs() < 1e-6, "{:?}", report);
+        assert!((report.max_offset_db - 20.0 * 0.05f64.log10()).abs() < 1e-3);
+        assert!(report.significant);
+    }
+
+    #[test]
+    fn a_centred_signal_has_no_offset() {
+        let report = measure(&offset_sine(0.0, 0.0), 2);
+        assert!(report.offsets.iter().all(|offset| offset.abs() < 1e-6));
+        assert!(!report.significant);
+
+        let empty = measure(&[], 2);
+        assert_eq!(empty.offsets, [0.0, 0.0]);
+        assert_eq!(empty.max_offset_db, FLOOR_DB);
+    }
+
+    #[test]
+    fn ignores_a_partial_frame() {
+        let report = measure(&[0.2, 0.4, 0.2, 0.4, 1.0], 2);
+        assert_eq!(report.offsets, [0.2f32 as f64, 0.4f32 as f64]);
+    }
+
+    #[test]
+    fn removal_goes_before_other_filters() {
+        let fade = Some("afade=t=out:st=4:d=1".to_string());
+        assert_eq!(
+            with_removal(Some(true), fade.clone()).as_deref(),
+            Some("highpass=f=10,afade=t=out:st=4:d=1")
+        );
+        assert_eq!(with_removal(None, fade.clone()), fade);
+        assert_eq!(
+            with_removal(Some(true), None).as_deref(),
+            Some(REMOVE_FILTER)
+        );
+        assert_eq!(with_removal(Some(false), None), None);
+    }
+}
@@ -0,0 +1,628 @@
+use crate::error::AppError;
+
+/// Largest pitch shift, in either direction, that [`pitch_shift_filter`] accepts.
+pub const MAX_SEMITONES: i32 = 12;
+
+/// Slowest playback rate accepted by [`atempo_chain`] callers.
+pub const MIN_TEMPO_RATE: f64 = 0.25;
+/// Fastest playback rate accepted by [`atempo_chain`] callers.
+pub const MAX_TEMPO_RATE: f64 = 2.0;
+
+/// Default longest segment `reverse_segment` accepts, in seconds.
+/// `areverse` holds the whole segment in memory as raw samples, about 21 MB
+/// per minute of 44.1 kHz stereo.
+pub const DEFAULT_MAX_REVERSE_SECS: f64 = 300.0;
+
+/// Builds an `atempo` chain that changes speed by `rate` without affecting
+/// pitch.
+///
+/// A single `atempo` instance only accepts factors between 0.5 and 2.0, so
+/// rates outside that window are split into several filters whose product
+/// is `rate`, e.g. 0.25 becomes `atempo=0.5,atempo=0.5`.
+pub fn atempo_chain(rate: f64) -> String {
+    let mut remaining = rate;
+    let mut factors = Vec::new();
+    while remaining < 0.5 {
+        factors.push(0.5);
+        remaining /= 0.5;
+    }
+    while remaining > 2.0 {
+        factors.push(2.0);
+        remaining /= 2.0;
+    }
+    factors.push(remaining);
+
+    factors
+        .iter()
+        .map(|factor| format!("atempo={}", factor))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Builds a filter chain that shifts pitch by `semitones` without changing
+/// the tempo of audio recorded at `sample_rate`.
+///
+/// `asetrate` relabels the sample rate, which raises or lowers pitch and
+/// speed together; `aresample` converts back to the original rate and
+/// `atempo` undoes the speed change. This works with every ffmpeg build,
+/// unlike the `rubberband` filter, but formants move with the pitch, so
+/// large shifts sound noticeably "chipmunk"-like or muddy.
+pub fn pitch_shift_filter(semitones: i32, sample_rate: u32) -> Result<String, AppError> {
+    if !(-MAX_SEMITONES..=MAX_SEMITONES).contains(&semitones) {
+        return Err(AppError::InvalidInput(format!(
+            "Pitch shift of {} semitones is out of range ({}..={})",
+            semitones, -MAX_SEMITONES, MAX_SEMITONES
+        )));
+    }
+    if sample_rate == 0 {
+        return Err(AppError::InvalidInput(
+            "Cannot pitch shift audio with an unknown sample rate".to_string(),
+        ));
+    }
+
+    let ratio = 2f64.powf(semitones as f64 / 12.0);
+    Ok(format!(
+        "asetrate={},aresample={},{}",
+        (sample_rate as f64 * ratio).round() as u32,
+        sample_rate,
+        atempo_chain(1.0 / ratio)
+    ))
+}
+
+/// Builds `afade` filters fading the first `fade_in` and last `fade_out`
+/// seconds of a segment `length` seconds long, or `None` when neither fade
+/// is requested. Zero-length fades are ignored.
+pub fn fade_filter(
+    fade_in: Option<f64>,
+    fade_out: Option<f64>,
+    length: f64,
+) -> Result<Option<String>, AppError> {
+    let mut filters = Vec::new();
+    for (name, fade) in [("Fade in", fade_in), ("Fade out", fade_out)] {
+        if let Some(fade) = fade {
+            if !fade.is_finite() || fade < 0.0 || fade > length {
+                return Err(AppError::InvalidInput(format!(
+                    "{} of {}s doesn't fit the {:.3}s segment",
+                    name, fade, length
+                )));
+            }
+        }
+    }
+
+    if let Some(fade) = fade_in.filter(|&fade| fade > 0.0) {
+        filters.push(format!("afade=t=in:st=0:d={}", fade));
+    }
+    if let Some(fade) = fade_out.filter(|&fade| fade > 0.0) {
+        filters.push(format!("afade=t=out:st={}:d={}", length - fade, fade));
+    }
+    Ok((!filters.is_empty()).then(|| filters.join(",")))
+}
+
+/// Longest filtergraph [`validate_custom_filter`] accepts.
+const MAX_CUSTOM_FILTER_LEN: usize = 1024;
+
+/// Filters that read or write files, load plugins or listen on the network.
+/// A custom filtergraph may only transform the audio it is given.
+const FORBIDDEN_FILTERS: [&str; 15] = [
+    "amovie",
+    "movie",
+    "asendcmd",
+    "sendcmd",
+    "azmq",
+    "zmq",
+    "ladspa",
+    "lv2",
+    "ametadata",
+    "metadata",
+    "arnndn",
+    "sofalizer",
+    "dnn_processing",
+    "signature",
+    "vidstabdetect",
+];
+
+/// Checks a user supplied `-af` filtergraph before it is handed to ffmpeg.
+///
+/// The filtergraph is only ever passed as a single argument to ffmpeg, never
+/// through a shell, so it can't add arguments or run commands. On top of
+/// that:
+/// - only characters that filtergraph syntax needs are allowed, which keeps
+///   out `$`, backticks, quotes other than `'`, `&`, `<`, `>`, `\`, newlines
+///   and other control characters. `|` is allowed because `pan` and channel
+///   layouts need it;
+/// - filters that can touch files, plugins or the network (see
+///   [`FORBIDDEN_FILTERS`]) are rejected by name.
+pub fn validate_custom_filter(filter: &str) -> Result<(), AppError> {
+    let filter = filter.trim();
+    if filter.is_empty() {
+        return Err(AppError::InvalidInput("The filter is empty".to_string()));
+    }
+    if filter.len() > MAX_CUSTOM_FILTER_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "The filter is longer than {} characters",
+            MAX_CUSTOM_FILTER_LEN
+        )));
+    }
+    if let Some(c) = filter
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || " =:,;._-+*/()[]'%|@".contains(c)))
+    {
+        return Err(AppError::InvalidInput(format!(
+            "The filter contains a character that isn't allowed: {:?}",
+            c
+        )));
+    }
+
+    // Splitting on every separator, quoted or not, finds every place a
+    // filter name can start. ffmpeg drops quotes inside names, so do the same.
+    for segment in filter.split([',', ';']) {
+        let name = segment_name(segment).replace('\'', "");
+        if let Some(name) = FORBIDDEN_FILTERS
+            .iter()
+            .find(|forbidden| name.eq_ignore_ascii_case(forbidden))
+        {
+            return Err(AppError::InvalidInput(format!(
+                "The '{}' filter can access files or the network and isn't allowed",
+                name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the filter name at the start of one filtergraph segment, skipping
+/// any leading `[label]`s. Instance names such as `volume@boost` are cut at
+/// the `@`.
+fn segment_name(segment: &str) -> &str {
+    let mut rest = segment.trim_start();
+    while let Some(after) = rest.strip_prefix('[') {
+        rest = after.split_once(']').map_or("", |(_, r)| r).trim_start();
+    }
+    let end = rest.find(['=', '@', '[', ' ']).unwrap_or(rest.len());
+    &rest[..end]
+}
+
+/// Gain of the center and surround channels when folding them into the
+/// front pair, -3 dB as in ITU-R BS.775.
+const DOWNMIX_GAIN: f64 = 0.707;
+
+/// Builds a `pan` filter folding 5.1 or 7.1 audio of `channels` channels
+/// down to stereo, or `None` for other layouts, which are left to ffmpeg's
+/// built-in `-ac 2` downmix.
+///
+/// Channels are addressed by index in ffmpeg's order (FL FR FC LFE, then the
+/// surround pairs), so it works the same for the "back" and "side" variants
+/// of 5.1. LFE is dropped, as the standard downmix does, and `<` scales the
+/// gains down so the folded channels can't clip.
+pub fn downmix_filter(channels: u8) -> Option<String> {
+    let surrounds: &[(u8, u8)] = match channels {
+        6 => &[(4, 5)],
+        8 => &[(4, 5), (6, 7)],
+        _ => return None,
+    };
+    let side = |front: u8, pick: fn(&(u8, u8)) -> u8| {
+        let mut gains = format!("c{}+{}*c2", front, DOWNMIX_GAIN);
+        for pair in surrounds {
+            gains.push_str(&format!("+{}*c{}", DOWNMIX_GAIN, pick(pair)));
+        }
+        gains
+    };
+    Some(format!(
+        "pan=stereo|FL<{}|FR<{}",
+        side(0, |pair| pair.0),
+        side(1, |pair| pair.1)
+    ))
+}
+
+/// Builds a `pan` filter that takes `center_attenuation` (0 to 1) of the
+/// mid signal, what both stereo channels have in common, out of each
+/// channel. Vocals and lead lines are usually mixed to the center, so at 1
+/// only the side signal is left: the left channel keeps `(L - R) / 2` and
+/// the right its inverse. Bass and kick drum sit in the center too and go
+/// with it.
+pub fn karaoke_filter(center_attenuation: f64) -> Result<String, AppError> {
+    if !(0.0..=1.0).contains(&center_attenuation) {
+        return Err(AppError::InvalidInput(format!(
+            "Center attenuation must be between 0 and 1, got {}",
+            center_attenuation
+        )));
+    }
+    let cross = center_attenuation / 2.0;
+    let own = 1.0 - cross;
+    Ok(format!(
+        "pan=stereo|FL={}*c0-{}*c1|FR={}*c1-{}*c0",
+        own, cross, own, cross
+    ))
+}
+
+/// Most repeats `loop_graph` accepts.
+pub const MAX_LOOP_REPEATS: u32 = 100;
+
+/// Longest looped output `loop_graph` accepts, in seconds.
+pub const MAX_LOOPED_SECS: f64 = 3600.0;
+
+/// Longest crossfade `acrossfade` supports, in seconds.
+const MAX_CROSSFADE_SECS: f64 = 60.0;
+
+/// Longest segment `loop_graph` repeats with `aloop`, in seconds. `aloop`
+/// buffers the segment as raw samples, so longer ones are decoded again for
+/// every repeat instead.
+const MAX_IN_MEMORY_LOOP_SECS: f64 = 300.0;
+
+/// A `-filter_complex` graph repeating a segment, whose output is labelled
+/// `[looped]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopGraph {
+    pub filter: String,
+    /// How many inputs the graph reads, `[0:a:0]`, `[1:a:0]` and so on. All
+    /// are the segment unless the builder says otherwise.
+    pub inputs: u32,
+    /// Length of the looped output in seconds.
+    pub length: f64,
+}
+
+/// Builds a graph playing a segment `length` seconds long, recorded at
+/// `sample_rate`, `repeat_count` times in a row.
+///
+/// Without a crossfade the segment is decoded once and repeated sample
+/// accurately by `aloop`, which holds it in memory; segments longer than
+/// [`MAX_IN_MEMORY_LOOP_SECS`], or of unknown sample rate, are instead
+/// decoded once per repeat and joined with `concat`. With a crossfade every
+/// repeat is its own input, overlapped with the previous one by `crossfade`
+/// seconds, so the output is that much shorter per join.
+pub fn loop_graph(
+    repeat_count: u32,
+    crossfade: Option<f64>,
+    length: f64,
+    sample_rate: u32,
+) -> Result<LoopGraph, AppError> {
+    if !(1..=MAX_LOOP_REPEATS).contains(&repeat_count) {
+        return Err(AppError::InvalidInput(format!(
+            "Repeat count must be between 1 and {}, got {}",
+            MAX_LOOP_REPEATS, repeat_count
+        )));
+    }
+    let crossfade = crossfade.unwrap_or(0.0);
+    if !(0.0..=MAX_CROSSFADE_SECS).contains(&crossfade) || (crossfade > 0.0 && crossfade >= length)
+    {
+        return Err(AppError::InvalidInput(format!(
+            "A crossfade of {}s doesn't fit the {:.3}s loop (at most {}s)",
+            crossfade, length, MAX_CROSSFADE_SECS
+        )));
+    }
+    let joins = (repeat_count - 1) as f64;
+    let looped_length = length * repeat_count as f64 - crossfade * joins;
+    if looped_length > MAX_LOOPED_SECS {
+        return Err(AppError::InvalidInput(format!(
+            "Repeating the {:.1}s loop {} times would make {:.0}s of audio, more than the {}s limit",
+            length, repeat_count, looped_length, MAX_LOOPED_SECS
+        )));
+    }
+
+    let graph = |filter: String, inputs: u32| LoopGraph {
+        filter,
+        inputs,
+        length: looped_length,
+    };
+    if repeat_count == 1 {
+        return Ok(graph("[0:a:0]anull[looped]".to_string(), 1));
+    }
+    if crossfade > 0.0 {
+        let mut filter = String::new();
+        let mut previous = "[0:a:0]".to_string();
+        for input in 1..repeat_count {
+            let output = if input + 1 == repeat_count {
+                "[looped]".to_string()
+            } else {
+                format!("[x{}]", input)
+            };
+            if !filter.is_empty() {
+                filter.push(';');
+            }
+            filter.push_str(&format!(
+                "{}[{}:a:0]acrossfade=d={}{}",
+                previous, input, crossfade, output
+            ));
+            previous = output;
+        }
+        return Ok(graph(filter, repeat_count));
+    }
+    if sample_rate == 0 || length > MAX_IN_MEMORY_LOOP_SECS {
+        let inputs: String = (0..repeat_count)
+            .map(|input| format!("[{}:a:0]", input))
+            .collect();
+        return Ok(graph(
+            format!("{}concat=n={}:v=0:a=1[looped]", inputs, repeat_count),
+            repeat_count,
+        ));
+    }
+    let size = (length * sample_rate as f64).ceil() as u64;
+    Ok(graph(
+        format!(
+            "[0:a:0]aloop=loop={}:size={}[looped]",
+            repeat_count - 1,
+            size
+        ),
+        1,
+    ))
+}
+
+/// Lists the playback rate of each of `repeat_count` repeats of a tempo
+/// ramp, stepping evenly from `start_rate` on the first to `end_rate` on the
+/// last. The rates may fall as well as rise.
+pub fn tempo_ramp_rates(
+    start_rate: f64,
+    end_rate: f64,
+    repeat_count: u32,
+) -> Result<Vec<f64>, AppError> {
+    for rate in [start_rate, end_rate] {
+        if !(MIN_TEMPO_RATE..=MAX_TEMPO_RATE).contains(&rate) {
+            return Err(AppError::InvalidInput(format!(
+                "Playback rate {} is out of range ({}..={})",
+                rate, MIN_TEMPO_RATE, MAX_TEMPO_RATE
+            )));
+        }
+    }
+    if !(2..=MAX_LOOP_REPEATS).contains(&repeat_count) {
+        return Err(AppError::InvalidInput(format!(
+            "A tempo ramp needs between 2 and {} repeats, got {}",
+            MAX_LOOP_REPEATS, repeat_count
+        )));
+    }
+
+    let steps = (repeat_count - 1) as f64;
+    Ok((0..repeat_count)
+        .map(|index| start_rate + (end_rate - start_rate) * index as f64 / steps)
+        .collect())
+}
+
+/// Builds a graph playing a segment `length` seconds long once at each of
+/// `rates` in turn, as listed by [`tempo_ramp_rates`].
+///
+/// `count_ins` holds the length of the count-in played before each repeat,
+/// or is empty for none. With count-ins the inputs alternate between a
+/// count-in and the segment, count-in first. Every repeat is its own input,
+/// so the segment is never held in memory while earlier repeats play.
+pub fn tempo_ramp_graph(
+    rates: &[f64],
+    length: f64,
+    count_ins: &[f64],
+) -> Result<LoopGraph, AppError> {
+    let counted = !count_ins.is_empty();
+    if counted && count_ins.len() != rates.len() {
+        return Err(AppError::InvalidInput(format!(
+            "Got {} count-ins for {} repeats",
+            count_ins.len(),
+            rates.len()
+        )));
+    }
+    let ramped_length: f64 =
+        rates.iter().map(|rate| length / rate).sum::<f64>() + count_ins.iter().sum::<f64>();
+    if ramped_length > MAX_LOOPED_SECS {
+        return Err(AppError::InvalidInput(format!(
+            "Ramping the {:.1}s loop over {} repeats would make {:.0}s of audio, more than the {}s limit",
+            length,
+            rates.len(),
+            ramped_length,
+            MAX_LOOPED_SECS
+        )));
+    }
+
+    let per_repeat = if counted { 2 } else { 1 };
+    let mut filter = String::new();
+    let mut parts = String::new();
+    for (repeat, rate) in rates.iter().enumerate() {
+        let input = repeat * per_repeat + per_repeat - 1;
+        if counted {
+            parts.push_str(&format!("[{}:a:0]", input - 1));
+        }
+        // Leave repeats at their own speed untouched rather than run them
+        // through atempo.
+        let tempo = if *rate == 1.0 {
+            "anull".to_string()
+        } else {
+            atempo_chain(*rate)
+        };
+        filter.push_str(&format!("[{}:a:0]{}[r{}];", input, tempo, repeat));
+        parts.push_str(&format!("[r{}]", repeat));
+    }
+    let segments = rates.len() * per_repeat;
+    filter.push_str(&format!("{}concat=n={}:v=0:a=1[looped]", parts, segments));
+    Ok(LoopGraph {
+        filter,
+        inputs: segments as u32,
+        length: ramped_length,
+    })
+}
+
+/// Runs the `[looped]` output of `graph`, as built by [`loop_graph`],
+/// [`tempo_ramp_graph`] or [`seamless_loop`], through `filter`, keeping the
+/// label.
+pub fn filter_looped(graph: &str, filter: &str) -> String {
+    format!(
+        "{};[unfiltered]{}[looped]",
+        graph.replace("[looped]", "[unfiltered]"),
+        filter
+    )
+}
+
+/// A graph joining a segment's end onto its start, fed the `(start, end)`
+/// `ranges` of the source in input order. Its output is labelled `[looped]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeamlessLoop {
+    pub ranges: Vec<(f64, f64)>,
+    pub filter: String,
+    /// Length of the loop file in seconds.
+    pub length: f64,
+}
+
+/// Builds a loop of `start..end` that repeats without a click: the last
+/// `crossfade` seconds fade into the first, and the rest of the segment
+/// follows. Played on repeat, the body runs into the tail, which is
+/// already blended into the head, which runs on into the body.
+///
+/// The loop is `crossfade` seconds shorter than the segment. Each part is
+/// decoded as its own input, so nothing has to be held in memory.
+pub fn seamless_loop(start: f64, end: f64, crossfade: f64) -> Result<SeamlessLoop, AppError> {
+    let length = end - start;
+    if !(crossfade > 0.0 && crossfade < length / 2.0 && crossfade <= MAX_CROSSFADE_SECS) {
+        return Err(AppError::InvalidInput(format!(
+            "The crossfade must be longer than 0s and shorter than half the {:.3}s loop (at most {}s), got {}s",
+            length, MAX_CROSSFADE_SECS, crossfade
+        )));
+    }
+    Ok(SeamlessLoop {
+        ranges: vec![
+            (end - crossfade, end),
+            (start, start + crossfade),
+            (start + crossfade, end - crossfade),
+        ],
+        // Equal power curves keep the level steady across the seam, where
+        // the two ends are unrelated audio.
+        filter: format!(
+            "[0:a:0][1:a:0]acrossfade=d={}:c1=qsin:c2=qsin[seam];[seam][2:a:0]concat=n=2:v=0:a=1[looped]",
+            crossfade
+        ),
+        length: length - crossfade,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chains_atempo_outside_a_single_filter_range() {
+        assert_eq!(atempo_chain(0.25), "atempo=0.5,atempo=0.5");
+        assert_eq!(atempo_chain(0.3), "atempo=0.5,atempo=0.6");
+        assert_eq!(atempo_chain(4.0), "atempo=2,atempo=2");
+    }
+
+    #[test]
+    fn keeps_one_atempo_inside_its_range() {
+        assert_eq!(atempo_chain(0.75), "atempo=0.75");
+    }
+
+    #[test]
+    fn no_fades_means_no_filter() {
+        assert_eq!(fade_filter(None, None, 10.0).unwrap(), None);
+        assert_eq!(fade_filter(Some(0.0), Some(0.0), 10.0).unwrap(), None);
+    }
+
+    #[test]
+    fn fades_are_placed_from_both_ends() {
+        assert_eq!(
+            fade_filter(Some(1.0), Some(2.5), 10.0).unwrap().as_deref(),
+            Some("afade=t=in:st=0:d=1,afade=t=out:st=7.5:d=2.5")
+        );
+        assert_eq!(
+            fade_filter(None, Some(2.0), 10.0).unwrap().as_deref(),
+            Some("afade=t=out:st=8:d=2")
+        );
+    }
+
+    #[test]
+    fn refuses_fades_longer_than_the_segment() {
+        assert!(matches!(
+            fade_filter(Some(11.0), None, 10.0),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert!(fade_filter(None, Some(-1.0), 10.0).is_err());
+    }
+
+    #[test]
+    fn folds_5_1_down_to_stereo() {
+        assert_eq!(
+            downmix_filter(6).as_deref(),
+            Some("pan=stereo|FL<c0+0.707*c2+0.707*c4|FR<c1+0.707*c2+0.707*c5")
+        );
+    }
+
+    #[test]
+    fn folds_7_1_down_to_stereo() {
+        assert_eq!(
+            downmix_filter(8).as_deref(),
+            Some("pan=stereo|FL<c0+0.707*c2+0.707*c4+0.707*c6|FR<c1+0.707*c2+0.707*c5+0.707*c7")
+        );
+    }
+
+    #[test]
+    fn leaves_other_layouts_to_ffmpeg() {
+        assert_eq!(downmix_filter(2), None);
+        assert_eq!(downmix_filter(4), None);
+    }
+
+    #[test]
+    fn karaoke_takes_the_center_out() {
+        assert_eq!(
+            karaoke_filter(1.0).unwrap(),
+            "pan=stereo|FL=0.5*c0-0.5*c1|FR=0.5*c1-0.5*c0"
+        );
+        assert_eq!(
+            karaoke_filter(0.5).unwrap(),
+            "pan=stereo|FL=0.75*c0-0.25*c1|FR=0.75*c1-0.25*c0"
+        );
+        assert_eq!(
+            karaoke_filter(0.0).unwrap(),
+            "pan=stereo|FL=1*c0-0*c1|FR=1*c1-0*c0"
+        );
+    }
+
+    #[test]
+    fn refuses_attenuation_out_of_range() {
+        for attenuation in [-0.1, 1.5, f64::NAN] {
+            assert!(matches!(
+                karaoke_filter(attenuation),
+                Err(AppError::InvalidInput(_))
+            ));
+        }
+    }
+
+    fn assert_rates(found: &[f64], expected: &[f64]) {
+        assert_eq!(found.len(), expected.len(), "{:?}", found);
+        for (found, expected) in found.iter().zip(expected) {
+            assert!((found - expected).abs() < 1e-12, "{:?}", found);
+        }
+    }
+
+    #[test]
+    fn ramps_rates_evenly_across_repeats() {
+        assert_rates(
+            &tempo_ramp_rates(0.5, 1.0, 6).unwrap(),
+            &[0.5, 0.6, 0.7, 0.8, 0.9, 1.0],
+        );
+        assert_rates(&tempo_ramp_rates(0.75, 1.0, 2).unwrap(), &[0.75, 1.0]);
+        assert_rates(&tempo_ramp_rates(1.2, 0.8, 3).unwrap(), &[1.2, 1.0, 0.8]);
+        assert_rates(&tempo_ramp_rates(0.9, 0.9, 3).unwrap(), &[0.9; 3]);
+    }
+
+    #[test]
+    fn refuses_ramps_out_of_range() {
+        for (start_rate, end_rate, repeat_count) in [
+            (0.2, 1.0, 4),
+            (0.5, 2.5, 4),
+            (f64::NAN, 1.0, 4),
+            (0.5, 1.0, 1),
+            (0.5, 1.0, MAX_LOOP_REPEATS + 1),
+        ] {
+            assert!(matches!(
+                tempo_ramp_rates(start_rate, end_rate, repeat_count),
+                Err(AppError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn a_ramp_plays_each_repeat_at_its_rate() {
+        let graph = tempo_ramp_graph(&[0.5, 1.0], 10.0, &[]).unwrap();
+        assert_eq!(graph.inputs, 2);
+        assert_eq!(graph.length, 30.0);
+        assert_eq!(
+            graph.filter,
+            format!(
+                "[0:a:0]{}[r0];[1:a:0]anull[r1];[r0][r1]concat=n=2:v=0:a=1[looped]",
+                atempo_chain(0.5)
+            )
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::config::Tool;
+use crate::error::AppError;
+use crate::ffmpeg;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Encoder {
+    pub name: String,
+    pub description: String,
+}
+
+/// Managed state caching the encoders of the installed ffmpeg, which can't
+/// change while it runs. `None` until they have been listed.
+#[derive(Default)]
+pub struct EncoderCache(pub Mutex<Option<Vec<Encoder>>>);
+
+impl EncoderCache {
+    fn get(&self) -> Option<Vec<Encoder>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub fn set(&self, encoders: Option<Vec<Encoder>>) {
+        *self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = encoders;
+    }
+}
+
+/// Parses `ffmpeg -encoders` output. Entries follow a `------` line and look
+/// like ` A....D libmp3lame           libmp3lame MP3 (MPEG audio layer 3)`.
+pub fn parse_encoder_list(stdout: &str) -> Vec<Encoder> {
+    stdout
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| {
+            let line = line.trim_start();
+            let (_flags, rest) = line.split_once(char::is_whitespace)?;
+            let rest = rest.trim_start();
+            let (name, description) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            Some(Encoder {
+                name: name.to_string(),
+                description: description.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Lists the encoders of the installed ffmpeg, asking it only if they
+/// aren't cached yet. Failures aren't cached, so a later call can still
+/// succeed once ffmpeg is found.
+pub async fn available_encoders(handle: &AppHandle) -> Result<Vec<Encoder>, AppError> {
+    let cache = handle.state::<EncoderCache>();
+    if let Some(encoders) = cache.get() {
+        return Ok(encoders);
+    }
+
+    let command = ffmpeg::command(handle, Tool::Ffmpeg).args(["-hide_banner", "-encoders"]);
+    let output = ffmpeg::run_to_completion(handle, Tool::Ffmpeg, command).await?;
+    if !output.success() {
+        return Err(AppError::FfmpegFailed(format!(
+            "FFmpeg failed to list encoders: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let encoders = parse_encoder_list(&String::from_utf8_lossy(&output.stdout));
+    cache.set(Some(encoders.clone()));
+    Ok(encoders)
+}
+
+/// Fails with `EncoderUnavailable` unless the installed ffmpeg has the
+/// encoder `name`.
+pub async fn require(handle: &AppHandle, name: &str) -> Result<(), AppError> {
+    if available_encoders(handle)
+        .await?
+        .iter()
+        .any(|encoder| encoder.name == name)
+    {
+        return Ok(());
+    }
+    Err(AppError::EncoderUnavailable(format!(
+        "The installed FFmpeg was built without the '{}' encoder. Choose another format or install a full FFmpeg build.",
+        name
+    )))
+}
@@ -0,0 +1,171 @@
+use serde::Deserialize;
+
+use crate::error::AppError;
+
+/// Lowest and highest band frequencies accepted, the range of human hearing.
+pub const MIN_EQ_FREQ: f64 = 20.0;
+pub const MAX_EQ_FREQ: f64 = 20000.0;
+
+/// Largest boost or cut of a single band, in dB.
+pub const MAX_EQ_GAIN_DB: f64 = 24.0;
+
+/// Most bands a custom EQ may have.
+pub const MAX_EQ_BANDS: usize = 16;
+
+/// One peaking band of a graphic EQ.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct EqBand {
+    /// Center frequency in Hz.
+    pub freq: f64,
+    pub gain_db: f64,
+    /// Width of the band; higher is narrower.
+    pub q: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EqPreset {
+    /// Lifts the mids where lead guitar and vocals sit, and takes out some
+    /// low-mid mud, to make a lead easier to transcribe.
+    LeadBoost,
+    /// Cuts the lows, e.g. to practice bass lines along with the track.
+    BassCut,
+    /// Brightens the upper mids, bringing out pick attack and definition.
+    Presence,
+    Custom(Vec<EqBand>),
+}
+
+impl EqPreset {
+    pub fn bands(&self) -> Vec<EqBand> {
+        let band = |freq, gain_db, q| EqBand { freq, gain_db, q };
+        match self {
+            EqPreset::LeadBoost => vec![
+                band(250.0, -2.0, 1.0),
+                band(1000.0, 4.0, 1.0),
+                band(2500.0, 3.0, 1.2),
+            ],
+            EqPreset::BassCut => vec![
+                band(60.0, -12.0, 0.7),
+                band(120.0, -9.0, 0.7),
+                band(250.0, -4.0, 1.0),
+            ],
+            EqPreset::Presence => vec![band(4000.0, 4.0, 1.0), band(6000.0, 2.0, 1.0)],
+            EqPreset::Custom(bands) => bands.clone(),
+        }
+    }
+}
+
+/// Checks that there are between one and [`MAX_EQ_BANDS`] bands, and that
+/// every band's frequency is audible and below the Nyquist frequency of
+/// `sample_rate`, its Q positive and its gain within [`MAX_EQ_GAIN_DB`].
+pub fn validate_bands(bands: &[EqBand], sample_rate: u32) -> Result<(), AppError> {
+    if bands.is_empty() || bands.len() > MAX_EQ_BANDS {
+        return Err(AppError::InvalidInput(format!(
+            "An EQ needs between 1 and {} bands, got {}",
+            MAX_EQ_BANDS,
+            bands.len()
+        )));
+    }
+    let nyquist = sample_rate as f64 / 2.0;
+    for band in bands {
+        if !(MIN_EQ_FREQ..=MAX_EQ_FREQ).contains(&band.freq) {
+            return Err(AppError::InvalidInput(format!(
+                "EQ band frequencies must be between {} and {} Hz, got {}",
+                MIN_EQ_FREQ, MAX_EQ_FREQ, band.freq
+            )));
+        }
+        if sample_rate > 0 && band.freq >= nyquist {
+            return Err(AppError::InvalidInput(format!(
+                "The {} Hz EQ band is above what {} Hz audio can hold ({} Hz)",
+                band.freq, sample_rate, nyquist
+            )));
+        }
+        if !(band.q.is_finite() && band.q > 0.0) {
+            return Err(AppError::InvalidInput(format!(
+                "The Q of the {} Hz EQ band must be positive, got {}",
+                band.freq, band.q
+            )));
+        }
+        if !(-MAX_EQ_GAIN_DB..=MAX_EQ_GAIN_DB).contains(&band.gain_db) {
+            return Err(AppError::InvalidInput(format!(
+                "The gain of the {} Hz EQ band must be between -{} and {} dB, got {}",
+                band.freq, MAX_EQ_GAIN_DB, MAX_EQ_GAIN_DB, band.gain_db
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Builds a chain of `equalizer` filters, one peaking filter per band.
+pub fn eq_filter(bands: &[EqBand]) -> String {
+    bands
+        .iter()
+        .map(|band| {
+            format!(
+                "equalizer=f={}:t=q:w={}:g={}",
+                band.freq, band.q, band.gain_db
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn band(freq: f64, gain_db: f64, q: f64) -> EqBand {
+        EqBand { freq, gain_db, q }
+    }
+
+    #[test]
+    fn one_equalizer_per_band() {
+        assert_eq!(
+            eq_filter(&[band(1000.0, 4.0, 1.0), band(2500.5, -3.5, 0.7)]),
+            "equalizer=f=1000:t=q:w=1:g=4,equalizer=f=2500.5:t=q:w=0.7:g=-3.5"
+        );
+        assert_eq!(
+            eq_filter(&EqPreset::Presence.bands()),
+            "equalizer=f=4000:t=q:w=1:g=4,equalizer=f=6000:t=q:w=1:g=2"
+        );
+    }
+
+    #[test]
+    fn presets_are_valid() {
+        for preset in [EqPreset::LeadBoost, EqPreset::BassCut, EqPreset::Presence] {
+            assert!(
+                validate_bands(&preset.bands(), 44100).is_ok(),
+                "{:?}",
+                preset
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_bands() {
+        let too_many = vec![band(1000.0, 1.0, 1.0); MAX_EQ_BANDS + 1];
+        for (bands, sample_rate) in [
+            (Vec::new(), 44100),
+            (too_many, 44100),
+            (vec![band(19.9, 1.0, 1.0)], 44100),
+            (vec![band(20000.1, 1.0, 1.0)], 48000),
+            (vec![band(12000.0, 1.0, 1.0)], 22050),
+            (vec![band(1000.0, 1.0, 0.0)], 44100),
+            (vec![band(1000.0, 1.0, f64::NAN)], 44100),
+            (vec![band(1000.0, 24.5, 1.0)], 44100),
+        ] {
+            assert!(
+                matches!(
+                    validate_bands(&bands, sample_rate),
+                    Err(AppError::InvalidInput(_))
+                ),
+                "accepted {:?} at {} Hz",
+                bands,
+                sample_rate
+            );
+        }
+        assert!(
+            validate_bands(&[band(20.0, -24.0, 0.1), band(20000.0, 24.0, 10.0)], 48000).is_ok()
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// Error returned to the frontend from every command.
+///
+/// Serializes as `{ "kind": "FfmpegMissing", "message": "..." }` so the UI can
+/// branch on `kind` and only show `message` to the user.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "message")]
+pub enum AppError {
+    /// The ffmpeg or ffprobe executable could not be started.
+    FfmpegMissing(String),
+    /// ffmpeg or ffprobe ran but exited with an error.
+    FfmpegFailed(String),
+    FileNotFound(String),
+    /// The media file has no audio stream to work with.
+    NoAudioStream(String),
+    /// Tool output or a user-supplied file could not be parsed.
+    ParseFailed(String),
+    InvalidRange(String),
+    /// An argument other than a time range was rejected.
+    InvalidInput(String),
+    /// Output collected in memory grew past the caller's size cap.
+    OutputTooLarge(String),
+    /// Reading or writing app files (cache, sidecars, exports) failed.
+    Io(String),
+    /// The operation was cancelled by the user before it finished.
+    Cancelled(String),
+    /// ffmpeg or ffprobe stopped producing output and was killed.
+    Timeout(String),
+    /// The installed ffmpeg was built without a component the feature needs.
+    FeatureUnavailable(String),
+    /// The installed ffmpeg has no encoder for the requested format.
+    EncoderUnavailable(String),
+}
+
+impl AppError {
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::FfmpegMissing(message)
+            | AppError::FfmpegFailed(message)
+            | AppError::FileNotFound(message)
+            | AppError::NoAudioStream(message)
+            | AppError::ParseFailed(message)
+            | AppError::InvalidRange(message)
+            | AppError::InvalidInput(message)
+            | AppError::OutputTooLarge(message)
+            | AppError::Io(message)
+            | AppError::Cancelled(message)
+            | AppError::Timeout(message)
+            | AppError::FeatureUnavailable(message)
+            | AppError::EncoderUnavailable(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
@@ -0,0 +1,413 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+
+/// Characters that can't appear in a file name on Windows, a superset of
+/// what other platforms reject.
+const RESERVED_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Device names Windows refuses as file names, with or without an extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Name used for chapters whose title has nothing usable left.
+const FALLBACK_NAME: &str = "Chapter";
+
+/// Turns a chapter title into a file stem that is valid on every platform.
+///
+/// Path separators, reserved and control characters become `_`; leading
+/// dots, which hide files, and trailing dots and spaces, which Windows
+/// silently drops, are removed; and reserved device names get a `_`
+/// prepended.
+pub fn sanitize_file_name(title: &str) -> String {
+    let replaced: String = title
+        .chars()
+        .map(|c| {
+            if RESERVED_CHARS.contains(&c) || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let trimmed = replaced
+        .trim()
+        .trim_start_matches('.')
+        .trim_end_matches(['.', ' ']);
+    if trimmed.is_empty() {
+        return FALLBACK_NAME.to_string();
+    }
+    // Windows reserves "CON.anything" as well as "CON".
+    let device = trimmed.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|name| device.eq_ignore_ascii_case(name))
+    {
+        return format!("_{}", trimmed);
+    }
+    trimmed.to_string()
+}
+
+/// File name template exports use unless the user configures another.
+pub const DEFAULT_FILE_TEMPLATE: &str = "{title}.{ext}";
+
+/// Fields a file name template can refer to as `{field}`.
+pub const TEMPLATE_FIELDS: [&str; 5] = ["title", "index", "start", "end", "ext"];
+
+/// What the fields of a file name template are filled in with.
+#[derive(Debug, Clone, Copy)]
+pub struct TemplateValues<'a> {
+    pub title: &'a str,
+    /// Zero-based position of the chapter in the export.
+    pub index: usize,
+    pub start: f64,
+    /// `None` for a chapter running to the end of the file.
+    pub end: Option<f64>,
+    pub ext: &'a str,
+}
+
+/// Checks that `template` only refers to [`TEMPLATE_FIELDS`] and has no
+/// stray braces.
+pub fn validate_template(template: &str) -> Result<(), AppError> {
+    if template.trim().is_empty() {
+        return Err(AppError::InvalidInput(
+            "The file name template is empty".to_string(),
+        ));
+    }
+    let mut rest = template;
+    while let Some(open) = rest.find(['{', '}']) {
+        let close = rest[open..].find('}').map(|close| open + close);
+        let field = match close {
+            Some(close) if rest[open..].starts_with('{') => &rest[open + 1..close],
+            _ => {
+                return Err(AppError::InvalidInput(format!(
+                    "Unmatched brace in file name template '{}'",
+                    template
+                )))
+            }
+        };
+        if !TEMPLATE_FIELDS.contains(&field) {
+            return Err(AppError::InvalidInput(format!(
+                "Unknown field '{{{}}}' in file name template, expected one of {}",
+                field,
+                TEMPLATE_FIELDS.join(", ")
+            )));
+        }
+        rest = &rest[open + field.len() + 2..];
+    }
+    Ok(())
+}
+
+/// Fills in a template that passed [`validate_template`] and sanitizes the
+/// result into a file name.
+///
+/// `{index}` counts from 1 and is padded to two digits, and times are
+/// written like `03m15s` or `1h02m07s`, since colons aren't allowed in file
+/// names. A title with nothing usable becomes `Chapter` and a missing end
+/// becomes `end`.
+pub fn render_template(template: &str, values: &TemplateValues) -> String {
+    let mut name = String::with_capacity(template.len() + values.title.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        name.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('}').map(|close| open + close) else {
+            break;
+        };
+        match &rest[open + 1..close] {
+            "title" => name.push_str(&sanitize_file_name(values.title)),
+            "index" => name.push_str(&format!("{:02}", values.index + 1)),
+            "start" => name.push_str(&file_name_time(values.start)),
+            "end" => match values.end {
+                Some(end) => name.push_str(&file_name_time(end)),
+                None => name.push_str("end"),
+            },
+            "ext" => name.push_str(values.ext),
+            other => name.push_str(other),
+        }
+        rest = &rest[close + 1..];
+    }
+    name.push_str(rest);
+    sanitize_file_name(&name)
+}
+
+/// Formats whole seconds as `MMmSSs`, or `HhMMmSSs` from an hour on.
+fn file_name_time(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}h{:02}m{:02}s", hours, minutes, seconds)
+    } else {
+        format!("{:02}m{:02}s", minutes, seconds)
+    }
+}
+
+/// Inserts ` (suffix)` between the stem and extension of `name`.
+fn numbered_file_name(name: &str, suffix: usize) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => {
+            format!("{} ({}).{}", stem, suffix, extension)
+        }
+        _ => format!("{} ({})", name, suffix),
+    }
+}
+
+/// Returns `name`, or the first of `name (2)`, `name (3)`, ... (numbered
+/// before the extension) for which `taken` is false.
+fn first_free_name(name: &str, mut taken: impl FnMut(&str) -> bool) -> String {
+    let mut candidate = name.to_string();
+    let mut suffix = 2;
+    while taken(&candidate) {
+        candidate = numbered_file_name(name, suffix);
+        suffix += 1;
+    }
+    candidate
+}
+
+/// Returns `dir` joined with `name`, numbered if a file of that name already
+/// exists.
+pub fn unused_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(first_free_name(name, |candidate| {
+        dir.join(candidate).exists()
+    }))
+}
+
+/// Returns `dir` joined with each of `names` in order, numbered like
+/// [`unused_path`] where a file of that name exists or an earlier name of
+/// the batch already took it. Names are compared case-insensitively because
+/// that's how Windows and macOS see them.
+pub fn unused_paths(dir: &Path, names: Vec<String>) -> Vec<PathBuf> {
+    let mut taken = HashSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            dir.join(first_free_name(&name, |candidate| {
+                dir.join(candidate).exists() || !taken.insert(candidate.to_lowercase())
+            }))
+        })
+        .collect()
+}
+
+/// Arguments for encoding `start..end` of `file_path`, or `start` to the end
+/// of the file, to `output_path` with `codec`, running it through the audio
+/// `filter` if there is one.
+pub fn chapter_args(
+    file_path: &str,
+    start: f64,
+    end: Option<f64>,
+    filter: Option<&str>,
+    codec: &str,
+    output_path: &str,
+) -> Vec<String> {
+    let mut args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        start.to_string(),
+    ];
+    if let Some(end) = end {
+        args.push("-to".to_string());
+        args.push(end.to_string());
+    }
+    args.extend([
+        "-i".to_string(),
+        file_path.to_string(),
+        "-map".to_string(),
+        "0:a:0".to_string(),
+        "-vn".to_string(),
+    ]);
+    if let Some(filter) = filter {
+        args.push("-af".to_string());
+        args.push(filter.to_string());
+    }
+    args.extend([
+        "-c:a".to_string(),
+        codec.to_string(),
+        output_path.to_string(),
+    ]);
+    args
+}
+
+/// Shortest segment length `segment_by_duration` accepts, in seconds.
+pub const MIN_SEGMENT_SECS: f64 = 1.0;
+
+/// Output pattern for the segment muxer: `<stem>-000.<extension>`,
+/// `<stem>-001.<extension>`, ... in `output_dir`. Any `%` in the path is
+/// doubled so the muxer doesn't read it as part of the pattern.
+pub fn segment_pattern(output_dir: &Path, stem: &str, extension: &str) -> String {
+    let path = output_dir.join(format!("{}-", sanitize_file_name(stem)));
+    format!(
+        "{}%03d.{}",
+        path.to_string_lossy().replace('%', "%%"),
+        extension.replace('%', "%%")
+    )
+}
+
+/// Name of the `index`th file, counting from 0, of a [`segment_pattern`].
+pub fn segment_file_name(stem: &str, extension: &str, index: usize) -> String {
+    format!("{}-{:03}.{}", sanitize_file_name(stem), index, extension)
+}
+
+/// Arguments for splitting the first audio stream of `file_path` into
+/// `segment_secs` long files named after `pattern`, listing them with their
+/// times in the CSV file `list_path`. With `stream_copy` the audio isn't
+/// re-encoded; every audio packet can start a segment, so cuts still land
+/// within a frame of the requested times.
+pub fn segment_args(
+    file_path: &str,
+    segment_secs: f64,
+    pattern: &str,
+    list_path: &str,
+    stream_copy: bool,
+) -> Vec<String> {
+    let mut args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        file_path.to_string(),
+        "-map".to_string(),
+        "0:a:0".to_string(),
+        "-vn".to_string(),
+    ];
+    if stream_copy {
+        args.push("-c:a".to_string());
+        args.push("copy".to_string());
+    }
+    args.extend([
+        "-f".to_string(),
+        "segment".to_string(),
+        "-segment_time".to_string(),
+        segment_secs.to_string(),
+        "-reset_timestamps".to_string(),
+        "1".to_string(),
+        "-segment_list".to_string(),
+        list_path.to_string(),
+        "-segment_list_type".to_string(),
+        "csv".to_string(),
+        pattern.to_string(),
+    ]);
+    args
+}
+
+/// One file written by the segment muxer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub file_name: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Parses the segment muxer's CSV list, one `name,start,end` line per
+/// segment. Names with commas or quotes come quoted, with quotes doubled.
+pub fn parse_segment_list(csv: &str) -> Vec<Segment> {
+    csv.lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, ',');
+            let end = fields.next()?.trim().parse().ok()?;
+            let start = fields.next()?.trim().parse().ok()?;
+            let name = fields.next()?;
+            let file_name = match name
+                .strip_prefix('"')
+                .and_then(|name| name.strip_suffix('"'))
+            {
+                Some(quoted) => quoted.replace("\"\"", "\""),
+                None => name.to_string(),
+            };
+            Some(Segment {
+                file_name,
+                start,
+                end,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values<'a>(title: &'a str, end: Option<f64>) -> TemplateValues<'a> {
+        TemplateValues {
+            title,
+            index: 2,
+            start: 195.4,
+            end,
+            ext: "mp3",
+        }
+    }
+
+    #[test]
+    fn fills_in_every_field() {
+        assert_eq!(
+            render_template(
+                "{index} {title}_{start}-{end}.{ext}",
+                &values("Solo", Some(3727.0))
+            ),
+            "03 Solo_03m15s-1h02m07s.mp3"
+        );
+    }
+
+    #[test]
+    fn missing_fields_fall_back() {
+        assert_eq!(
+            render_template("{title}_{start}-{end}.{ext}", &values("  ", None)),
+            "Chapter_03m15s-end.mp3"
+        );
+    }
+
+    #[test]
+    fn titles_are_sanitized() {
+        assert_eq!(
+            render_template(DEFAULT_FILE_TEMPLATE, &values("AC/DC: Live?", None)),
+            "AC_DC_ Live_.mp3"
+        );
+        assert_eq!(render_template("{title}", &values("con", None)), "_con");
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_stray_braces() {
+        assert!(validate_template("{title}_{start}-{end}.{ext}").is_ok());
+        for template in ["", "{artist}.{ext}", "{title.{ext}", "title}.{ext}"] {
+            assert!(
+                matches!(validate_template(template), Err(AppError::InvalidInput(_))),
+                "accepted '{}'",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn numbers_names_already_taken() {
+        let dir = std::env::temp_dir().join(format!("guitar-looper-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Outro.mp3"), b"").unwrap();
+        let names = ["Solo.mp3", "solo.mp3", "Solo.mp3", "Intro", "Outro.mp3"].map(String::from);
+        assert_eq!(
+            unused_paths(&dir, names.to_vec()),
+            [
+                "Solo.mp3",
+                "solo (2).mp3",
+                "Solo (3).mp3",
+                "Intro",
+                "Outro (2).mp3"
+            ]
+            .map(|name| dir.join(name))
+        );
+
+        std::fs::write(dir.join("Solo.mp3"), b"").unwrap();
+        assert_eq!(unused_path(&dir, "Solo.mp3"), dir.join("Solo (2).mp3"));
+        assert_eq!(unused_path(&dir, "Intro.mp3"), dir.join("Intro.mp3"));
+        // A batch numbering around an existing file doesn't land on a name
+        // a later chapter of the batch gets.
+        let names = ["Solo.mp3", "Solo.mp3"].map(String::from);
+        assert_eq!(
+            unused_paths(&dir, names.to_vec()),
+            ["Solo (2).mp3", "Solo (3).mp3"].map(|name| dir.join(name))
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::Chapter;
+
+/// Containers ffmpeg can write chapters into.
+const CHAPTER_CONTAINERS: [&str; 9] = [
+    "mp4", "m4a", "m4b", "mov", "mkv", "mka", "mp3", "ogg", "opus",
+];
+
+/// Checks that the container of `file_path`, judged by its extension, can
+/// hold chapters.
+pub fn check_chapter_support(file_path: &str) -> Result<(), AppError> {
+    let extension = Path::new(file_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    if CHAPTER_CONTAINERS.contains(&extension.as_str()) {
+        return Ok(());
+    }
+    Err(AppError::InvalidInput(format!(
+        "{} files can't store chapters. Export a CUE sheet or save markers instead.",
+        if extension.is_empty() {
+            "Extensionless".to_string()
+        } else {
+            extension.to_uppercase()
+        }
+    )))
+}
+
+/// Ticks per second of the chapter timestamps. Integer microseconds are
+/// exact enough for any edit and can't pick up float formatting noise.
+const TICKS_PER_SECOND: f64 = 1_000_000.0;
+
+/// Escapes the characters ffmetadata treats specially in values.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Builds an ffmetadata document holding `chapters` with microsecond
+/// timestamps.
+///
+/// Chapters without an end run to the start of the next chapter, or to
+/// `duration` for the last one. Chapters are written in start order.
+pub fn build(chapters: &[Chapter], duration: Option<f64>) -> Result<String, AppError> {
+    let mut ordered: Vec<&Chapter> = chapters.iter().collect();
+    ordered.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let mut doc = String::from(";FFMETADATA1\n");
+    for (index, chapter) in ordered.iter().enumerate() {
+        let end = chapter
+            .end
+            .or_else(|| ordered.get(index + 1).map(|next| next.start))
+            .or(duration)
+            .ok_or_else(|| {
+                AppError::InvalidRange(format!(
+                    "Chapter '{}' has no end and the file length is unknown",
+                    chapter.title
+                ))
+            })?;
+        let start_ticks = (chapter.start * TICKS_PER_SECOND).round() as u64;
+        let end_ticks = (end * TICKS_PER_SECOND).round() as u64;
+        if end_ticks <= start_ticks {
+            return Err(AppError::InvalidRange(format!(
+                "Chapter '{}' ends ({}) before it starts ({})",
+                chapter.title, end, chapter.start
+            )));
+        }
+
+        doc.push_str(&format!(
+            "\n[CHAPTER]\nTIMEBASE=1/{}\n",
+            TICKS_PER_SECOND as u64
+        ));
+        doc.push_str(&format!("START={}\nEND={}\n", start_ticks, end_ticks));
+        doc.push_str(&format!("title={}\n", escape(&chapter.title)));
+    }
+    Ok(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(title: &str, start: f64, end: Option<f64>) -> Chapter {
+        Chapter {
+            id: title.to_lowercase(),
+            title: title.to_string(),
+            start,
+            end,
+            duration: end.map(|end| end - start),
+            ..Default::default()
+        }
+    }
+
+    const FIXTURE: &str = r";FFMETADATA1
+
+[CHAPTER]
+TIMEBASE=1/1000000
+START=0
+END=12345679
+title=Intro
+
+[CHAPTER]
+TIMEBASE=1/1000000
+START=12345679
+END=61000000
+title=Verse \= Chorus\; take 2 \#1
+
+[CHAPTER]
+TIMEBASE=1/1000000
+START=61000000
+END=200100000
+title=Outro
+";
+
+    #[test]
+    fn matches_the_fixture() {
+        let chapters = [
+            chapter("Outro", 61.0, None),
+            chapter("Intro", 0.0, Some(12.3456789)),
+            chapter("Verse = Chorus; take 2 #1", 12.3456789, None),
+        ];
+        assert_eq!(build(&chapters, Some(200.1)).unwrap(), FIXTURE);
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(escape("a\\b\nc"), "a\\\\b\\\nc");
+    }
+
+    #[test]
+    fn rejects_chapters_without_a_length() {
+        assert!(matches!(
+            build(&[chapter("Intro", 0.0, None)], None),
+            Err(AppError::InvalidRange(_))
+        ));
+        assert!(matches!(
+            build(&[chapter("Intro", 5.0, Some(5.0000001))], None),
+            Err(AppError::InvalidRange(_))
+        ));
+    }
+
+    #[test]
+    fn only_some_containers_hold_chapters() {
+        assert!(check_chapter_support("/music/Song.M4A").is_ok());
+        assert!(check_chapter_support("song.mkv").is_ok());
+        assert!(matches!(
+            check_chapter_support("song.wav"),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert!(check_chapter_support("song").is_err());
+    }
+}
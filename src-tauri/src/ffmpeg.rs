@@ -0,0 +1,1198 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::async_runtime::Receiver;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_shell::process::{Command, CommandChild, CommandEvent};
+use tauri_plugin_shell::ShellExt;
+
+use crate::config::{self, Tool};
+use crate::error::AppError;
+use crate::operations::OperationManager;
+use crate::pcm::PcmFormat;
+use crate::retry;
+use crate::util;
+
+/// Input options that stop ffprobe from waiting long on a dead server: a
+/// 10 second (in microseconds) connect and read timeout.
+pub(crate) const NETWORK_TIMEOUT_ARGS: [&str; 2] = ["-timeout", "10000000"];
+
+/// Minimum time between two `extract-progress` events.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Creates a command for `tool`, honouring any custom path the user set.
+pub(crate) fn command(handle: &AppHandle, tool: Tool) -> Command {
+    handle
+        .shell()
+        .command(config::current(handle).program(tool))
+}
+
+/// Creates an ffmpeg command for processing media, starting with the
+/// configured `-threads`. Coming before any `-i`, it sets the decoder's
+/// thread count, and ffmpeg carries it over to the encoder.
+fn job_command(handle: &AppHandle) -> Command {
+    command(handle, Tool::Ffmpeg).args(thread_args(handle))
+}
+
+fn thread_args(handle: &AppHandle) -> Vec<String> {
+    match config::current(handle).threads {
+        Some(threads) if threads > 0 => vec!["-threads".to_string(), threads.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+/// Quotes `arg` for a POSIX shell, leaving words that need no quoting alone.
+pub(crate) fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,+@%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Returns the command line a job with `args` runs, quoted so it can be
+/// pasted into a shell. The progress reporting options the app adds are
+/// left out since they only matter to the app.
+pub(crate) fn job_command_line(handle: &AppHandle, args: &[String]) -> String {
+    std::iter::once(config::current(handle).program(Tool::Ffmpeg))
+        .chain(thread_args(handle))
+        .chain(args.iter().cloned())
+        .map(|arg| shell_quote(&arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Logs the command line of a run, media paths included, so failures in bug
+/// reports can be reproduced.
+fn log_command<S: AsRef<str>>(tool: Tool, args: &[S]) {
+    let args: Vec<String> = args.iter().map(|arg| shell_quote(arg.as_ref())).collect();
+    tracing::info!("Running {} {}", tool.default_program(), args.join(" "));
+}
+
+fn spawn_error(tool: Tool, e: impl std::fmt::Display) -> AppError {
+    AppError::FfmpegMissing(format!(
+        "Failed to set up {} command: {}. Make sure FFmpeg is installed.",
+        tool.default_program(),
+        e
+    ))
+}
+
+/// Waits for the next event of a spawned process, failing with `Timeout` if
+/// it produces no output for `timeout`. The caller must kill the process.
+async fn next_event(
+    rx: &mut Receiver<CommandEvent>,
+    tool: Tool,
+    timeout: Duration,
+) -> Result<Option<CommandEvent>, AppError> {
+    tokio::time::timeout(timeout, rx.recv()).await.map_err(|_| {
+        AppError::Timeout(format!(
+            "{} stopped responding for {} seconds and was stopped",
+            tool.default_program(),
+            timeout.as_secs()
+        ))
+    })
+}
+
+/// What a process run by [`run_to_completion`] left behind.
+pub(crate) struct ProcessOutput {
+    pub code: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl ProcessOutput {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// Runs `command` (built with [`command`] for `tool`) and collects its
+/// output, killing it if it goes silent for longer than the configured
+/// timeout. Unlike `Command::output` the output bytes are left untouched.
+pub(crate) async fn run_to_completion(
+    handle: &AppHandle,
+    tool: Tool,
+    command: Command,
+) -> Result<ProcessOutput, AppError> {
+    let timeout = config::current(handle).timeout();
+    let (rx, child) = command
+        .set_raw_out(true)
+        .spawn()
+        .map_err(|e| spawn_error(tool, e))?;
+    collect_output(rx, child, tool, timeout).await
+}
+
+/// Collects the output of a spawned process until it exits, killing it if
+/// it goes silent for longer than `timeout`.
+async fn collect_output(
+    mut rx: Receiver<CommandEvent>,
+    child: CommandChild,
+    tool: Tool,
+    timeout: Duration,
+) -> Result<ProcessOutput, AppError> {
+    let mut output = ProcessOutput {
+        code: None,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    };
+    loop {
+        let event = match next_event(&mut rx, tool, timeout).await {
+            Ok(Some(event)) => event,
+            Ok(None) => break,
+            Err(e) => {
+                let _ = child.kill();
+                return Err(e);
+            }
+        };
+        match event {
+            CommandEvent::Stdout(bytes) => output.stdout.extend(bytes),
+            CommandEvent::Stderr(bytes) => output.stderr.extend(bytes),
+            CommandEvent::Terminated(payload) => output.code = payload.code,
+            CommandEvent::Error(e) => {
+                let _ = child.kill();
+                return Err(AppError::FfmpegFailed(format!(
+                    "Failed while running {}: {}",
+                    tool.default_program(),
+                    e
+                )));
+            }
+            _ => {}
+        }
+    }
+    Ok(output)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressPayload {
+    /// Completion in the range 0-100, or -1 when the total duration is unknown.
+    pub percent: f64,
+    /// Estimated seconds remaining, or -1 when it can't be estimated yet.
+    pub eta_seconds: f64,
+}
+
+/// One line of ffmpeg's stderr, emitted as `ffmpeg-log` by verbose runs.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogPayload {
+    pub operation_id: Option<String>,
+    pub line: String,
+}
+
+/// Runs ffprobe with JSON output and returns the parsed document, retrying
+/// transient failures.
+///
+/// `args` should contain the `-show_*` selectors and end with the input path.
+pub(crate) async fn run_ffprobe(
+    handle: &AppHandle,
+    args: &[&str],
+) -> Result<serde_json::Value, AppError> {
+    let retries = config::current(handle).retries();
+    retry::with_retries(retries, || run_ffprobe_once(handle, args)).await
+}
+
+async fn run_ffprobe_once(
+    handle: &AppHandle,
+    args: &[&str],
+) -> Result<serde_json::Value, AppError> {
+    // Errors go to stderr and don't mix with the JSON on stdout; they are
+    // what tells transient failures apart.
+    log_command(Tool::Ffprobe, args);
+    let command = command(handle, Tool::Ffprobe)
+        .args(["-v", "error", "-print_format", "json"])
+        .args(args);
+    let output = run_to_completion(handle, Tool::Ffprobe, command).await?;
+
+    if !output.success() {
+        // Correctly format the error message using debug formatting for the exit code and converting stderr to a string
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::FfmpegFailed(format!(
+            "FFprobe failed with exit code {:?}: {}",
+            output.code, stderr
+        )));
+    }
+
+    parse_ffprobe_json(&output.stdout)
+}
+
+/// Parses ffprobe's JSON output.
+///
+/// ffprobe copies tag values such as chapter titles into the JSON byte for
+/// byte, so a file tagged in a legacy encoding produces invalid UTF-8.
+/// Those bytes become U+FFFD replacement characters instead of failing the
+/// whole probe over one title.
+fn parse_ffprobe_json(stdout: &[u8]) -> Result<serde_json::Value, AppError> {
+    serde_json::from_str(&String::from_utf8_lossy(stdout))
+        .map_err(|e| AppError::ParseFailed(format!("Failed to parse JSON from ffprobe: {}", e)))
+}
+
+/// Returns the duration of `file_path` in seconds, if ffprobe can tell.
+///
+/// Used to turn ffmpeg's progress output into a percentage, so failures are
+/// swallowed and simply result in indeterminate progress.
+pub(crate) async fn probe_duration(handle: &AppHandle, file_path: &str) -> Option<f64> {
+    let parsed = run_ffprobe(handle, &["-show_format", file_path])
+        .await
+        .ok()?;
+    crate::probe::format_duration(&parsed)
+}
+
+/// Returns the first line of `<tool> -version`, or `None` if the tool can't
+/// be run.
+pub(crate) async fn tool_version(handle: &AppHandle, tool: Tool) -> Option<String> {
+    let output = run_to_completion(handle, tool, command(handle, tool).args(["-version"]))
+        .await
+        .ok()?;
+    if !output.success() {
+        return None;
+    }
+    let version = String::from_utf8_lossy(&output.stdout);
+    version.lines().next().map(|line| line.trim().to_string())
+}
+
+/// Lists the names of the filters compiled into the installed ffmpeg.
+pub(crate) async fn list_filters(handle: &AppHandle) -> Result<Vec<String>, AppError> {
+    let command = command(handle, Tool::Ffmpeg).args(["-hide_banner", "-filters"]);
+    let output = run_to_completion(handle, Tool::Ffmpeg, command).await?;
+    if !output.success() {
+        return Err(AppError::FfmpegFailed(format!(
+            "FFmpeg failed to list filters: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(parse_filter_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses `ffmpeg -filters` output, whose entries look like
+/// ` TSC rubberband        A->A       Apply time-stretching...`.
+fn parse_filter_list(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let flags = fields.next()?;
+            let name = fields.next()?;
+            let io = fields.next()?;
+            // Skip the legend and header, which don't have an `X->Y` column.
+            (io.contains("->") && flags.len() <= 3).then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// Lists the names of the output formats (muxers) compiled into the
+/// installed ffmpeg.
+pub(crate) async fn list_muxers(handle: &AppHandle) -> Result<Vec<String>, AppError> {
+    let command = command(handle, Tool::Ffmpeg).args(["-hide_banner", "-muxers"]);
+    let output = run_to_completion(handle, Tool::Ffmpeg, command).await?;
+    if !output.success() {
+        return Err(AppError::FfmpegFailed(format!(
+            "FFmpeg failed to list muxers: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(parse_muxer_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses `ffmpeg -muxers` output, whose entries look like
+/// `  E chromaprint     Chromaprint`. Some entries name several formats
+/// separated by commas.
+fn parse_muxer_list(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let flags = fields.next()?;
+            let names = fields.next()?;
+            // The legend's flags contain dots and the header has none.
+            flags
+                .chars()
+                .all(|c| matches!(c, 'D' | 'E' | 'd'))
+                .then_some(names)
+        })
+        .flat_map(|names| names.split(',').map(str::to_string))
+        .collect()
+}
+
+/// Runs ffmpeg to completion and returns everything it wrote to stderr,
+/// retrying transient failures.
+///
+/// Analysis filters such as `silencedetect` report their results on stderr,
+/// so callers parse the returned text rather than an output file.
+pub(crate) async fn run_ffmpeg_stderr(
+    handle: &AppHandle,
+    args: &[String],
+) -> Result<String, AppError> {
+    let retries = config::current(handle).retries();
+    retry::with_retries(retries, || run_ffmpeg_stderr_once(handle, args)).await
+}
+
+async fn run_ffmpeg_stderr_once(handle: &AppHandle, args: &[String]) -> Result<String, AppError> {
+    log_command(Tool::Ffmpeg, args);
+    // Filters like `loudnorm` print nothing until the end, so progress
+    // reports on stdout keep a long analysis from looking hung.
+    let command = job_command(handle)
+        .args(["-hide_banner", "-nostats", "-progress", "pipe:1"])
+        .args(args);
+    let output = run_to_completion(handle, Tool::Ffmpeg, command).await?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if !output.success() {
+        return Err(AppError::FfmpegFailed(format!(
+            "FFmpeg failed with exit code {:?}: {}",
+            output.code,
+            stderr.trim()
+        )));
+    }
+    Ok(stderr)
+}
+
+/// Extracts the input duration from the `Duration: HH:MM:SS.xx` line ffmpeg
+/// logs when it opens a file.
+pub(crate) fn parse_input_duration(stderr: &str) -> Option<f64> {
+    let line = stderr
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Duration:"))?;
+    parse_clock(line.split(',').next()?.trim())
+}
+
+/// Parses an `HH:MM:SS.xx` timestamp as ffmpeg logs them.
+fn parse_clock(timestamp: &str) -> Option<f64> {
+    let (negative, timestamp) = match timestamp.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, timestamp),
+    };
+    let mut parts = timestamp.split(':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let total = hours * 3600.0 + minutes * 60.0 + seconds;
+    Some(if negative { -total } else { total })
+}
+
+/// Extracts how much was decoded from the last `time=` of the stats ffmpeg
+/// logs, which it always prints once a run ends. Earlier stats lines, split
+/// by carriage returns rather than newlines, are skipped over.
+pub(crate) fn parse_decoded_duration(stderr: &str) -> Option<f64> {
+    let (_, rest) = stderr.rsplit_once("time=")?;
+    let timestamp = rest.split_whitespace().next()?;
+    parse_clock(timestamp).filter(|time| *time >= 0.0)
+}
+
+/// Runs ffmpeg and returns the raw bytes it wrote to stdout, retrying
+/// transient failures.
+///
+/// If `max_bytes` is set and the output grows beyond it, ffmpeg is killed and
+/// `OutputTooLarge` is returned rather than a truncated buffer.
+pub(crate) async fn run_ffmpeg_raw(
+    handle: &AppHandle,
+    args: &[String],
+    max_bytes: Option<usize>,
+) -> Result<Vec<u8>, AppError> {
+    let retries = config::current(handle).retries();
+    retry::with_retries(retries, || run_ffmpeg_raw_once(handle, args, max_bytes)).await
+}
+
+async fn run_ffmpeg_raw_once(
+    handle: &AppHandle,
+    args: &[String],
+    max_bytes: Option<usize>,
+) -> Result<Vec<u8>, AppError> {
+    log_command(Tool::Ffmpeg, args);
+    // `output()` splits stdout on line breaks, which would corrupt binary data,
+    // so we spawn in raw mode and collect the bytes ourselves.
+    let timeout = config::current(handle).timeout();
+    let (mut rx, child) = job_command(handle)
+        .args(args)
+        .set_raw_out(true)
+        .spawn()
+        .map_err(|e| spawn_error(Tool::Ffmpeg, e))?;
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut exit_code = None;
+    loop {
+        let event = match next_event(&mut rx, Tool::Ffmpeg, timeout).await {
+            Ok(Some(event)) => event,
+            Ok(None) => break,
+            Err(e) => {
+                let _ = child.kill();
+                return Err(e);
+            }
+        };
+        match event {
+            CommandEvent::Stdout(bytes) => {
+                stdout.extend(bytes);
+                if let Some(max_bytes) = max_bytes.filter(|max| stdout.len() > *max) {
+                    let _ = child.kill();
+                    return Err(AppError::OutputTooLarge(format!(
+                        "FFmpeg output exceeded the {} MB limit",
+                        max_bytes / (1024 * 1024)
+                    )));
+                }
+            }
+            CommandEvent::Stderr(bytes) => stderr.extend(bytes),
+            CommandEvent::Terminated(payload) => exit_code = payload.code,
+            CommandEvent::Error(e) => {
+                let _ = child.kill();
+                return Err(AppError::FfmpegFailed(format!(
+                    "Failed while running ffmpeg: {}",
+                    e
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    if exit_code != Some(0) {
+        let stderr = String::from_utf8_lossy(&stderr);
+        return Err(AppError::FfmpegFailed(format!(
+            "FFmpeg failed (exit code {:?}): {}",
+            exit_code,
+            stderr.trim()
+        )));
+    }
+    Ok(stdout)
+}
+
+/// Runs ffmpeg and passes its stdout to `on_chunk` in pieces of
+/// `buffer_size` bytes (the last one may be shorter) instead of collecting it.
+///
+/// If `on_chunk` fails or the stream breaks, ffmpeg is killed before the
+/// error is returned; the shell plugin reaps the process once it exits.
+pub(crate) async fn stream_ffmpeg_raw<F>(
+    handle: &AppHandle,
+    args: &[String],
+    buffer_size: usize,
+    on_chunk: F,
+) -> Result<(), AppError>
+where
+    F: FnMut(&[u8]) -> Result<(), AppError>,
+{
+    stream_cancellable_ffmpeg_raw(handle, args, buffer_size, None, "", on_chunk).await
+}
+
+/// Like [`stream_ffmpeg_raw`], but tracks ffmpeg under `operation_id`, if
+/// given, as an operation of `kind`, so `cancel_operation` can stop it. A
+/// cancelled stream fails with `AppError::Cancelled`.
+pub(crate) async fn stream_cancellable_ffmpeg_raw<F>(
+    handle: &AppHandle,
+    args: &[String],
+    buffer_size: usize,
+    operation_id: Option<&str>,
+    kind: &str,
+    mut on_chunk: F,
+) -> Result<(), AppError>
+where
+    F: FnMut(&[u8]) -> Result<(), AppError>,
+{
+    log_command(Tool::Ffmpeg, args);
+    let timeout = config::current(handle).timeout();
+    let (mut rx, child) = job_command(handle)
+        .args(args)
+        .set_raw_out(true)
+        .spawn()
+        .map_err(|e| spawn_error(Tool::Ffmpeg, e))?;
+
+    let operations = handle.state::<OperationManager>();
+    let mut child = match operation_id {
+        Some(id) => {
+            operations.register(id, kind, child)?;
+            None
+        }
+        None => Some(child),
+    };
+    let mut stop = || {
+        if let Some(child) = child.take() {
+            let _ = child.kill();
+        } else if let Some(id) = operation_id {
+            operations.cancel(id);
+            operations.mark_done(id);
+        }
+    };
+
+    let mut buffer = Vec::with_capacity(buffer_size);
+    let mut stderr = Vec::new();
+    let mut exit_code = None;
+    loop {
+        let event = match next_event(&mut rx, Tool::Ffmpeg, timeout).await {
+            Ok(Some(event)) => event,
+            Ok(None) => break,
+            Err(e) => {
+                stop();
+                return Err(e);
+            }
+        };
+        match event {
+            CommandEvent::Stdout(bytes) => {
+                buffer.extend(bytes);
+                while buffer.len() >= buffer_size {
+                    if let Err(e) = on_chunk(&buffer[..buffer_size]) {
+                        stop();
+                        return Err(e);
+                    }
+                    buffer.drain(..buffer_size);
+                }
+            }
+            CommandEvent::Stderr(bytes) => stderr.extend(bytes),
+            CommandEvent::Terminated(payload) => exit_code = payload.code,
+            CommandEvent::Error(e) => {
+                stop();
+                return Err(AppError::FfmpegFailed(format!(
+                    "Failed while running ffmpeg: {}",
+                    e
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(id) = operation_id.filter(|id| operations.mark_done(id)) {
+        return Err(AppError::Cancelled(format!(
+            "Operation {} was cancelled",
+            id
+        )));
+    }
+    if exit_code != Some(0) {
+        let stderr = String::from_utf8_lossy(&stderr);
+        return Err(AppError::FfmpegFailed(format!(
+            "FFmpeg failed (exit code {:?}): {}",
+            exit_code,
+            stderr.trim()
+        )));
+    }
+    if !buffer.is_empty() {
+        on_chunk(&buffer)?;
+    }
+    Ok(())
+}
+
+/// Builds the arguments for decoding `file_path` to mono PCM of `format` on
+/// stdout. See [`decode_mono_pcm`] for the meaning of the options.
+pub(crate) fn mono_pcm_args(
+    file_path: &str,
+    channel: Option<u8>,
+    range: Option<(f64, f64)>,
+    sample_rate: Option<u32>,
+    format: PcmFormat,
+) -> Vec<String> {
+    mono_pcm_args_skipping(file_path, channel, range, sample_rate, format, 0)
+}
+
+/// Like [`mono_pcm_args`], but leaves out the first `skip_samples` decoded
+/// samples. They are counted by ffmpeg's `atrim` filter rather than sought
+/// to, so the output continues exactly where a decode that stopped after
+/// that many samples left off.
+pub(crate) fn mono_pcm_args_skipping(
+    file_path: &str,
+    channel: Option<u8>,
+    range: Option<(f64, f64)>,
+    sample_rate: Option<u32>,
+    format: PcmFormat,
+    skip_samples: u64,
+) -> Vec<String> {
+    let mut args = vec!["-v".to_string(), "error".to_string()];
+    if let Some((start, end)) = range {
+        args.push("-ss".to_string());
+        args.push(start.to_string());
+        args.push("-to".to_string());
+        args.push(end.to_string());
+    }
+    args.push("-i".to_string());
+    args.push(file_path.to_string());
+    args.push("-vn".to_string());
+    if let Some(rate) = sample_rate {
+        args.push("-ar".to_string());
+        args.push(rate.to_string());
+    }
+    let mut filters = Vec::new();
+    match channel {
+        Some(c) => filters.push(format!("pan=mono|c0=c{}", c)),
+        None => {
+            args.push("-ac".to_string());
+            args.push("1".to_string());
+        }
+    }
+    if skip_samples > 0 {
+        filters.push(format!("atrim=start_sample={}", skip_samples));
+    }
+    if !filters.is_empty() {
+        args.push("-af".to_string());
+        args.push(filters.join(","));
+    }
+    args.extend(format.output_args());
+    args
+}
+
+/// Decodes the audio of `file_path` to mono PCM of `format`, read back as
+/// `f32` samples.
+///
+/// When `channel` is `None` ffmpeg's downmix averages all channels together,
+/// otherwise only the requested (zero-based) channel is kept. `range` limits
+/// decoding to a `(start, end)` window in seconds and `sample_rate` resamples
+/// the output, keeping the source rate when `None`.
+pub(crate) async fn decode_mono_pcm(
+    handle: &AppHandle,
+    file_path: &str,
+    channel: Option<u8>,
+    range: Option<(f64, f64)>,
+    sample_rate: Option<u32>,
+    format: PcmFormat,
+) -> Result<Vec<f32>, AppError> {
+    let args = mono_pcm_args(file_path, channel, range, sample_rate, format);
+    let stdout = run_ffmpeg_raw(handle, &args, None).await?;
+    Ok(format.decode(&stdout))
+}
+
+/// Builds the arguments for decoding `range` of the first audio stream of
+/// `file_path`, or all of it, to 32-bit float PCM on stdout at its own rate
+/// with its channels interleaved, frame by frame. `channels` sets the
+/// channel count, the source's when `None`.
+pub(crate) fn interleaved_pcm_args(
+    file_path: &str,
+    range: Option<(f64, f64)>,
+    channels: Option<u8>,
+) -> Vec<String> {
+    let mut args = vec!["-v".to_string(), "error".to_string()];
+    if let Some((start, end)) = range {
+        args.push("-ss".to_string());
+        args.push(start.to_string());
+        args.push("-to".to_string());
+        args.push(end.to_string());
+    }
+    args.extend([
+        "-i".to_string(),
+        file_path.to_string(),
+        "-map".to_string(),
+        "0:a:0".to_string(),
+    ]);
+    if let Some(channels) = channels {
+        args.push("-ac".to_string());
+        args.push(channels.to_string());
+    }
+    args.extend(PcmFormat::F32le.output_args());
+    args
+}
+
+/// The error for a decode of `file_path` that came back without a single
+/// sample, which almost always means there was no audio to decode.
+pub(crate) fn no_audio_samples(file_path: &str) -> AppError {
+    AppError::NoAudioStream(format!(
+        "FFmpeg produced no audio samples from {}. The file may not contain an audio stream.",
+        file_path
+    ))
+}
+
+/// Decodes audio as [`interleaved_pcm_args`] describes, without downmixing
+/// unless `channels` asks for fewer.
+pub(crate) async fn decode_interleaved_pcm(
+    handle: &AppHandle,
+    file_path: &str,
+    range: Option<(f64, f64)>,
+    channels: Option<u8>,
+) -> Result<Vec<f32>, AppError> {
+    let args = interleaved_pcm_args(file_path, range, channels);
+    let stdout = run_ffmpeg_raw(handle, &args, None).await?;
+    Ok(PcmFormat::F32le.decode(&stdout))
+}
+
+/// Tracks ffmpeg's `-progress` key/value output for a single run.
+struct ProgressTracker {
+    duration: Option<f64>,
+    started: Instant,
+    last_emit: Option<Instant>,
+    out_time: f64,
+}
+
+impl ProgressTracker {
+    fn new(duration: Option<f64>) -> Self {
+        Self {
+            duration: duration.filter(|d| *d > 0.0),
+            started: Instant::now(),
+            last_emit: None,
+            out_time: 0.0,
+        }
+    }
+
+    /// Feeds one `key=value` line, returning a payload when one is due.
+    fn update(&mut self, line: &str) -> Option<ProgressPayload> {
+        let (key, value) = line.trim().split_once('=')?;
+        match key {
+            // Despite the name, ffmpeg reports `out_time_ms` in microseconds.
+            "out_time_ms" => {
+                if let Ok(micros) = value.parse::<f64>() {
+                    self.out_time = micros / 1_000_000.0;
+                }
+                None
+            }
+            // Every progress block ends with a `progress=continue|end` line,
+            // so emit here once all of the block's values have been read.
+            "progress" if value == "end" => Some(self.finished()),
+            "progress" => {
+                let now = Instant::now();
+                if self
+                    .last_emit
+                    .is_some_and(|t| now.duration_since(t) < PROGRESS_INTERVAL)
+                {
+                    return None;
+                }
+                self.last_emit = Some(now);
+                Some(self.payload())
+            }
+            _ => None,
+        }
+    }
+
+    fn payload(&self) -> ProgressPayload {
+        let Some(duration) = self.duration else {
+            return ProgressPayload {
+                percent: -1.0,
+                eta_seconds: -1.0,
+            };
+        };
+
+        let fraction = (self.out_time / duration).clamp(0.0, 1.0);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let eta_seconds = if fraction > 0.0 {
+            elapsed * (1.0 - fraction) / fraction
+        } else {
+            -1.0
+        };
+        ProgressPayload {
+            percent: fraction * 100.0,
+            eta_seconds,
+        }
+    }
+
+    fn finished(&self) -> ProgressPayload {
+        let percent = if self.duration.is_some() { 100.0 } else { -1.0 };
+        ProgressPayload {
+            percent,
+            eta_seconds: 0.0,
+        }
+    }
+}
+
+/// Settings for [`run_ffmpeg_with_progress`].
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct RunOptions<'a> {
+    /// Expected length of the output in seconds, used to turn ffmpeg's output
+    /// timestamp into a percentage.
+    pub duration: Option<f64>,
+    /// Id the frontend can pass to `cancel_operation` to stop the run.
+    pub operation_id: Option<&'a str>,
+    /// What `list_operations` reports the run as, usually the name of the
+    /// command that started it.
+    pub kind: &'a str,
+    /// File the run writes. When it is the last argument, ffmpeg writes
+    /// its part file instead, which is renamed to it on success. Either way
+    /// it is deleted if the run fails.
+    pub output_path: Option<&'a str>,
+    /// Overrides the configured timeout for this run.
+    pub timeout: Option<Duration>,
+    /// Emits every stderr line as an `ffmpeg-log` event as it is printed.
+    pub verbose: bool,
+}
+
+/// Runs ffmpeg with `args` to completion while emitting `extract-progress`
+/// events. Returns its stderr, which holds any warnings or decode errors
+/// logged along the way; a failed run returns it inside the error instead.
+pub(crate) async fn run_ffmpeg_with_progress(
+    handle: &AppHandle,
+    args: &[String],
+    options: RunOptions<'_>,
+) -> Result<String, AppError> {
+    let Some((args, output_path, part_path)) = options
+        .output_path
+        .and_then(|output_path| with_part_output(args, output_path))
+    else {
+        return run_job(handle, args, options).await;
+    };
+
+    let part = part_path.to_string_lossy();
+    let options = RunOptions {
+        output_path: Some(&part),
+        ..options
+    };
+    finish_part(run_job(handle, &args, options), &part_path, output_path).await
+}
+
+/// Awaits `job`, which writes `part_path`, and renames the part file to
+/// `output_path` if it succeeds. If either fails, the part file is removed.
+async fn finish_part<T>(
+    job: impl Future<Output = Result<T, AppError>>,
+    part_path: &Path,
+    output_path: &str,
+) -> Result<T, AppError> {
+    let output = match job.await {
+        Ok(output) => output,
+        Err(e) => {
+            let _ = std::fs::remove_file(part_path);
+            return Err(e);
+        }
+    };
+    std::fs::rename(part_path, output_path).map_err(|e| {
+        let _ = std::fs::remove_file(part_path);
+        AppError::Io(format!(
+            "Failed to move {} into place: {}",
+            part_path.display(),
+            e
+        ))
+    })?;
+    Ok(output)
+}
+
+/// Muxers for the output extensions ffmpeg would otherwise recognize, needed
+/// once the output is renamed to a part file.
+fn muxer_for_extension(extension: &str) -> Option<&'static str> {
+    Some(match extension.to_ascii_lowercase().as_str() {
+        "mp3" => "mp3",
+        "wav" => "wav",
+        "flac" => "flac",
+        "ogg" | "oga" => "ogg",
+        "opus" => "opus",
+        "m4a" | "m4b" | "m4r" => "ipod",
+        "mp4" => "mp4",
+        "mov" => "mov",
+        "mka" | "mkv" => "matroska",
+        "webm" => "webm",
+        "aac" => "adts",
+        "aif" | "aiff" => "aiff",
+        "png" | "jpg" | "jpeg" => "image2",
+        "srt" => "srt",
+        "vtt" => "webvtt",
+        "ass" => "ass",
+        _ => return None,
+    })
+}
+
+/// Encoder for a still image output, which the image muxer would otherwise
+/// pick from the extension the part file hides.
+fn image_encoder(extension: &str) -> Option<&'static str> {
+    match extension.to_ascii_lowercase().as_str() {
+        "png" => Some("png"),
+        "jpg" | "jpeg" => Some("mjpeg"),
+        _ => None,
+    }
+}
+
+/// Points `args`, whose last argument is `output_path`, at the part file of
+/// `output_path` instead, naming the muxer ffmpeg can no longer guess from
+/// the extension. Returns `None` for outputs of any other shape or with an
+/// extension it doesn't know, which are written in place.
+fn with_part_output<'a>(
+    args: &[String],
+    output_path: &'a str,
+) -> Option<(Vec<String>, &'a str, PathBuf)> {
+    let (last, rest) = args.split_last()?;
+    if last != output_path {
+        return None;
+    }
+    let path = Path::new(output_path);
+    let extension = path.extension()?.to_str()?;
+    let muxer = muxer_for_extension(extension)?;
+    if muxer == "image2" && output_path.contains('%') {
+        // A numbered sequence writes many files, none named `output_path`.
+        return None;
+    }
+    let part_path = util::part_path(path);
+    let mut args = rest.to_vec();
+    if let Some(encoder) = image_encoder(extension) {
+        // A single picture, not a numbered sequence of them.
+        args.extend([
+            "-c:v".to_string(),
+            encoder.to_string(),
+            "-update".to_string(),
+            "1".to_string(),
+        ]);
+    }
+    args.extend([
+        "-f".to_string(),
+        muxer.to_string(),
+        part_path.to_string_lossy().into_owned(),
+    ]);
+    Some((args, output_path, part_path))
+}
+
+/// Stops a [`run_job`] run that ends before ffmpeg exits: kills ffmpeg,
+/// which is either `child` or registered under the run's operation id, and
+/// removes whatever it wrote.
+fn abort_job(operations: &OperationManager, child: Option<CommandChild>, options: &RunOptions) {
+    if let Some(child) = child {
+        let _ = child.kill();
+    } else if let Some(id) = options.operation_id {
+        operations.cancel(id);
+        operations.mark_done(id);
+    }
+    if let Some(output_path) = options.output_path {
+        let _ = std::fs::remove_file(output_path);
+    }
+}
+
+async fn run_job(
+    handle: &AppHandle,
+    args: &[String],
+    options: RunOptions<'_>,
+) -> Result<String, AppError> {
+    log_command(Tool::Ffmpeg, args);
+    let (mut rx, child) = job_command(handle)
+        .args(["-progress", "pipe:1", "-nostats"])
+        .args(args)
+        .spawn()
+        .map_err(|e| spawn_error(Tool::Ffmpeg, e))?;
+
+    let timeout = options
+        .timeout
+        .unwrap_or_else(|| config::current(handle).timeout());
+    let operations = handle.state::<OperationManager>();
+    // Without an operation id the child is kept here so it lives as long as
+    // the run does and can be killed on timeout.
+    let mut child = match options.operation_id {
+        Some(id) => {
+            operations.register(id, options.kind, child)?;
+            None
+        }
+        None => Some(child),
+    };
+
+    let mut tracker = ProgressTracker::new(options.duration);
+    let mut stderr = Vec::new();
+    let mut exit_code = None;
+    loop {
+        let event = match next_event(&mut rx, Tool::Ffmpeg, timeout).await {
+            Ok(Some(event)) => event,
+            Ok(None) => break,
+            Err(e) => {
+                abort_job(&operations, child.take(), &options);
+                return Err(e);
+            }
+        };
+        match event {
+            CommandEvent::Stdout(line) => {
+                if let Some(payload) = tracker.update(&String::from_utf8_lossy(&line)) {
+                    if let Some(id) = options.operation_id {
+                        operations.set_progress(id, payload.percent);
+                    }
+                    let _ = handle.emit("extract-progress", payload);
+                }
+            }
+            CommandEvent::Stderr(line) => {
+                if options.verbose {
+                    let payload = LogPayload {
+                        operation_id: options.operation_id.map(str::to_string),
+                        line: String::from_utf8_lossy(&line).trim_end().to_string(),
+                    };
+                    let _ = handle.emit("ffmpeg-log", payload);
+                }
+                stderr.extend(line);
+                stderr.push(b'\n');
+            }
+            CommandEvent::Terminated(payload) => exit_code = payload.code,
+            CommandEvent::Error(e) => {
+                abort_job(&operations, child.take(), &options);
+                return Err(AppError::FfmpegFailed(format!(
+                    "Failed while running ffmpeg: {}",
+                    e
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(id) = options.operation_id.filter(|id| operations.mark_done(id)) {
+        if let Some(output_path) = options.output_path {
+            let _ = std::fs::remove_file(output_path);
+        }
+        return Err(AppError::Cancelled(format!(
+            "Operation {} was cancelled",
+            id
+        )));
+    }
+    let stderr = String::from_utf8_lossy(&stderr).into_owned();
+    if exit_code != Some(0) {
+        if let Some(output_path) = options.output_path {
+            let _ = std::fs::remove_file(output_path);
+        }
+        return Err(AppError::FfmpegFailed(format!(
+            "FFmpeg failed (exit code {:?}): {}",
+            exit_code,
+            stderr.trim()
+        )));
+    }
+    Ok(stderr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn a_silent_process_times_out_and_is_killed() {
+        let app = tauri::test::mock_builder()
+            .plugin(tauri_plugin_shell::init())
+            .build(tauri::test::mock_context(tauri::test::noop_assets()))
+            .unwrap();
+        let (rx, child) = app.shell().command("sleep").arg("30").spawn().unwrap();
+        let pid = child.pid();
+
+        let result = tauri::async_runtime::block_on(collect_output(
+            rx,
+            child,
+            Tool::Ffmpeg,
+            Duration::from_millis(200),
+        ));
+        assert!(matches!(result, Err(AppError::Timeout(_))));
+
+        // The shell plugin reaps the killed process in the background.
+        let proc_entry = std::path::PathBuf::from(format!("/proc/{}", pid));
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while proc_entry.exists() && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!proc_entry.exists(), "sleep {} is still running", pid);
+    }
+
+    #[test]
+    fn invalid_utf8_in_a_chapter_title_is_replaced() {
+        let stdout =
+            b"{\"chapters\": [{\"start_time\": \"0.0\", \"tags\": {\"title\": \"Intro \xff\"}}]}";
+        let parsed = parse_ffprobe_json(stdout).unwrap();
+        let (title, language) =
+            crate::language::chapter_title(&parsed["chapters"][0]["tags"], None).unwrap();
+        assert_eq!(title, "Intro \u{FFFD}");
+        assert_eq!(language, None);
+    }
+
+    #[test]
+    fn quotes_only_args_that_need_it() {
+        assert_eq!(shell_quote("-ss"), "-ss");
+        assert_eq!(shell_quote("/music/take_1.mp3"), "/music/take_1.mp3");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("My Song.mp3"), "'My Song.mp3'");
+        assert_eq!(shell_quote("Don't Stop.mp3"), "'Don'\\''t Stop.mp3'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quoted_args_survive_a_shell() {
+        let args = [
+            "afade=t=in:st=0:d=1,volume=2",
+            "Don't $(stop) `me` \\ now!.mp3",
+            "tab\there; echo injected *",
+            "",
+        ];
+        let line: Vec<String> = args.iter().map(|arg| shell_quote(arg)).collect();
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf '%s\\n' {}", line.join(" ")))
+            .output()
+            .unwrap();
+        let printed = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(printed.split_terminator('\n').collect::<Vec<_>>(), args);
+    }
+
+    /// An empty folder in the temp dir for one test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "guitar-looper-ffmpeg-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn outputs_go_to_a_part_file() {
+        let args: Vec<String> = ["-i", "in.flac", "/music/out.mp3"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let (args, output_path, part_path) = with_part_output(&args, "/music/out.mp3").unwrap();
+        assert_eq!(args, ["-i", "in.flac", "-f", "mp3", "/music/out.mp3.part"]);
+        assert_eq!(output_path, "/music/out.mp3");
+        assert_eq!(part_path, PathBuf::from("/music/out.mp3.part"));
+
+        // Numbered image sequences and unknown extensions are written in place.
+        let frames = vec!["/frames/%04d.png".to_string()];
+        assert!(with_part_output(&frames, "/frames/%04d.png").is_none());
+        let unknown = vec!["/music/out.xyz".to_string()];
+        assert!(with_part_output(&unknown, "/music/out.xyz").is_none());
+    }
+
+    #[test]
+    fn a_finished_part_file_is_renamed() {
+        let dir = scratch_dir("renamed");
+        let output_path = dir.join("take.mp3");
+        let part_path = util::part_path(&output_path);
+        let job = async {
+            std::fs::write(&part_path, b"audio").unwrap();
+            Ok("")
+        };
+        tauri::async_runtime::block_on(finish_part(job, &part_path, output_path.to_str().unwrap()))
+            .unwrap();
+        assert_eq!(std::fs::read(&output_path).unwrap(), b"audio");
+        assert!(!part_path.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_job_leaves_no_output() {
+        let dir = scratch_dir("failed");
+        let output_path = dir.join("take.mp3");
+        let part_path = util::part_path(&output_path);
+        let job = async {
+            std::fs::write(&part_path, b"half written").unwrap();
+            Err::<(), _>(AppError::FfmpegFailed("Conversion failed!".to_string()))
+        };
+        let result = tauri::async_runtime::block_on(finish_part(
+            job,
+            &part_path,
+            output_path.to_str().unwrap(),
+        ));
+        assert!(matches!(result, Err(AppError::FfmpegFailed(_))));
+        assert!(!part_path.exists());
+        assert!(!output_path.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// The end of what `ffmpeg -i song.mp3 -f null -` logs, with the stats
+    /// lines overwriting each other through carriage returns.
+    const NULL_MUXER_LOG: &str = "Input #0, mp3, from 'song.mp3':\n  \
+        Duration: 00:03:58.32, start: 0.025057, bitrate: 128 kb/s\n\
+        Output #0, null, to 'pipe:':\n\
+        size=N/A time=00:01:02.50 bitrate=N/A speed= 125x\r\
+        size=N/A time=00:03:01.00 bitrate=N/A speed= 121x\r\
+        [out#0/null @ 0x5581] video:0kB audio:41236kB muxing overhead: unknown\n\
+        size=N/A time=01:04:12.73 bitrate=N/A speed= 122x\n";
+
+    #[test]
+    fn reads_the_last_decoded_time() {
+        assert_eq!(parse_decoded_duration(NULL_MUXER_LOG), Some(3852.73));
+        assert_eq!(parse_input_duration(NULL_MUXER_LOG), Some(238.32));
+    }
+
+    #[test]
+    fn ignores_missing_or_negative_times() {
+        assert_eq!(
+            parse_decoded_duration("Output #0, null, to 'pipe:':\n"),
+            None
+        );
+        assert_eq!(
+            parse_decoded_duration("size=N/A time=N/A bitrate=N/A\n"),
+            None
+        );
+        assert_eq!(
+            parse_decoded_duration("size=N/A time=-00:00:00.02 bitrate=N/A\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::ffmpeg;
+
+/// Name of the ffmpeg muxer that computes Chromaprint fingerprints. It is
+/// only present when ffmpeg was built with `--enable-chromaprint`.
+const CHROMAPRINT_MUXER: &str = "chromaprint";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Fingerprint {
+    /// Compressed, base64 encoded Chromaprint fingerprint, as printed by
+    /// `fpcalc`.
+    pub fingerprint: String,
+    /// Length of the fingerprinted audio in seconds.
+    pub duration: f64,
+}
+
+/// Managed state caching whether the installed ffmpeg has the Chromaprint
+/// muxer. `None` until it has been checked.
+#[derive(Default)]
+pub struct ChromaprintSupport(pub Mutex<Option<bool>>);
+
+impl ChromaprintSupport {
+    fn get(&self) -> Option<bool> {
+        *self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn set(&self, available: Option<bool>) {
+        *self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = available;
+    }
+}
+
+/// Checks whether the Chromaprint muxer is available, asking ffmpeg only if
+/// the answer isn't cached yet. A failure to run ffmpeg isn't cached, so a
+/// later call can still succeed once ffmpeg is found.
+pub async fn chromaprint_available(handle: &AppHandle) -> Result<bool, AppError> {
+    let support = handle.state::<ChromaprintSupport>();
+    if let Some(available) = support.get() {
+        return Ok(available);
+    }
+    let available = ffmpeg::list_muxers(handle)
+        .await?
+        .iter()
+        .any(|muxer| muxer == CHROMAPRINT_MUXER);
+    support.set(Some(available));
+    Ok(available)
+}
+
+/// Arguments for fingerprinting the first audio stream of `file_path`, or
+/// `range` of it, printing the fingerprint on stdout.
+pub fn fingerprint_args(file_path: &str, range: Option<(f64, f64)>) -> Vec<String> {
+    let mut args = vec!["-v".to_string(), "error".to_string()];
+    if let Some((start, end)) = range {
+        args.push("-ss".to_string());
+        args.push(start.to_string());
+        args.push("-to".to_string());
+        args.push(end.to_string());
+    }
+    args.extend(
+        [
+            "-i",
+            file_path,
+            "-map",
+            "0:a:0",
+            "-vn",
+            "-f",
+            CHROMAPRINT_MUXER,
+            "-fp_format",
+            "base64",
+            "-",
+        ]
+        .iter()
+        .map(|s| s.to_string()),
+    );
+    args
+}
@@ -0,0 +1,291 @@
+use serde::Serialize;
+
+use crate::probe::MediaInfo;
+
+/// Offered in place of an encoder when the source audio can go into a
+/// container as it is, without re-encoding.
+pub const STREAM_COPY: &str = "copy";
+
+/// Highest sample rate MP3 and Opus can hold, in Hz.
+const MAX_LOSSY_SAMPLE_RATE: u32 = 48000;
+
+/// An output container worth offering for a source.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportFormat {
+    /// Container, by its usual file extension.
+    pub container: &'static str,
+    /// Ways to write the audio, best first: [`STREAM_COPY`] if the source
+    /// audio fits the container as it is, then the ffmpeg encoders the
+    /// installed build has for it.
+    pub codecs: Vec<&'static str>,
+    /// What to expect from the format for this source, for the UI to show.
+    pub note: String,
+}
+
+struct Target {
+    container: &'static str,
+    /// Whether the container can also keep the source's video.
+    video: bool,
+    /// Encoders for the container, best first.
+    encoders: &'static [&'static str],
+    /// Source codecs, as ffprobe names them, the container can hold.
+    copyable: &'static [&'static str],
+    note: &'static str,
+}
+
+const TARGETS: [Target; 9] = [
+    Target {
+        container: "wav",
+        video: false,
+        encoders: &["pcm_s16le", "pcm_s24le"],
+        copyable: &["pcm_s16le", "pcm_s24le", "pcm_f32le"],
+        note: "Uncompressed, exact and large",
+    },
+    Target {
+        container: "flac",
+        video: false,
+        encoders: &["flac"],
+        copyable: &["flac"],
+        note: "Lossless at about half the size of WAV",
+    },
+    Target {
+        container: "mp3",
+        video: false,
+        encoders: &["libmp3lame"],
+        copyable: &["mp3"],
+        note: "Plays everywhere",
+    },
+    Target {
+        container: "ogg",
+        video: false,
+        encoders: &["libvorbis", "libopus", "flac"],
+        copyable: &["vorbis", "opus", "flac"],
+        note: "Open format, smaller than MP3 at the same quality",
+    },
+    Target {
+        container: "opus",
+        video: false,
+        encoders: &["libopus"],
+        copyable: &["opus"],
+        note: "Smallest files for the quality",
+    },
+    Target {
+        container: "m4a",
+        video: false,
+        encoders: &["aac", "alac"],
+        copyable: &["aac", "alac"],
+        note: "Suits Apple devices",
+    },
+    Target {
+        container: "mp4",
+        video: true,
+        encoders: &["aac"],
+        copyable: &["aac", "mp3", "alac"],
+        note: "Keeps the video",
+    },
+    Target {
+        container: "mkv",
+        video: true,
+        encoders: &[
+            "flac",
+            "aac",
+            "libopus",
+            "libvorbis",
+            "libmp3lame",
+            "pcm_s16le",
+        ],
+        copyable: &[
+            "aac",
+            "mp3",
+            "flac",
+            "alac",
+            "vorbis",
+            "opus",
+            "ac3",
+            "eac3",
+            "dts",
+            "pcm_s16le",
+            "pcm_s24le",
+        ],
+        note: "Keeps the video and takes any audio",
+    },
+    Target {
+        container: "webm",
+        video: true,
+        encoders: &["libopus", "libvorbis"],
+        copyable: &["opus", "vorbis"],
+        note: "Keeps the video, for the web",
+    },
+];
+
+/// Lists the containers `source` can sensibly be exported to with the
+/// encoders in `available`, as `ffmpeg -encoders` names them.
+///
+/// Containers that keep video are only offered for sources with video,
+/// and containers none of whose encoders are available are left out
+/// unless the source audio can be copied into them as it is.
+pub fn compatible_formats(source: &MediaInfo, available: &[&str]) -> Vec<ExportFormat> {
+    TARGETS
+        .iter()
+        .filter(|target| source.has_video || !target.video)
+        .filter_map(|target| {
+            let copyable = target.copyable.contains(&source.codec_name.as_str());
+            let codecs: Vec<&'static str> = copyable
+                .then_some(STREAM_COPY)
+                .into_iter()
+                .chain(
+                    target
+                        .encoders
+                        .iter()
+                        .copied()
+                        .filter(|encoder| available.contains(encoder)),
+                )
+                .collect();
+            if codecs.is_empty() {
+                return None;
+            }
+            Some(ExportFormat {
+                container: target.container,
+                note: note(target, source, copyable),
+                codecs,
+            })
+        })
+        .collect()
+}
+
+/// Adds what happens to this particular source to the note of `target`.
+fn note(target: &Target, source: &MediaInfo, copyable: bool) -> String {
+    let mut notes = vec![target.note.to_string()];
+    if copyable {
+        notes.push("the audio can be copied without re-encoding".to_string());
+    }
+    if source.has_video && !target.video {
+        notes.push("the video is left out".to_string());
+    }
+    let lossy_only = target
+        .encoders
+        .iter()
+        .all(|encoder| matches!(*encoder, "libmp3lame" | "libopus" | "libvorbis" | "aac"));
+    if lossy_only && !copyable {
+        if source.sample_rate > MAX_LOSSY_SAMPLE_RATE && matches!(target.container, "mp3" | "opus")
+        {
+            notes.push(format!("resampled to {} kHz", MAX_LOSSY_SAMPLE_RATE / 1000));
+        }
+        if source.bit_depth.is_some() {
+            notes.push("lossy, unlike the source".to_string());
+        }
+    }
+    if source.surround && target.container == "mp3" {
+        notes.push("downmixed to stereo".to_string());
+    }
+    notes.join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(codec_name: &str, has_video: bool) -> MediaInfo {
+        MediaInfo {
+            duration_seconds: 180.0,
+            codec_name: codec_name.to_string(),
+            sample_rate: 44100,
+            channels: 2,
+            bit_rate: None,
+            surround: false,
+            bit_depth: None,
+            high_resolution: false,
+            has_video,
+        }
+    }
+
+    const EVERY_ENCODER: [&str; 9] = [
+        "pcm_s16le",
+        "pcm_s24le",
+        "flac",
+        "libmp3lame",
+        "libvorbis",
+        "libopus",
+        "aac",
+        "alac",
+        "ac3",
+    ];
+
+    fn containers(formats: &[ExportFormat]) -> Vec<&str> {
+        formats.iter().map(|f| f.container).collect()
+    }
+
+    fn codecs<'a>(formats: &'a [ExportFormat], container: &str) -> &'a [&'static str] {
+        &formats
+            .iter()
+            .find(|f| f.container == container)
+            .unwrap()
+            .codecs
+    }
+
+    #[test]
+    fn audio_sources_get_audio_containers_only() {
+        let formats = compatible_formats(&source("mp3", false), &EVERY_ENCODER);
+        assert_eq!(
+            containers(&formats),
+            ["wav", "flac", "mp3", "ogg", "opus", "m4a"]
+        );
+        assert_eq!(codecs(&formats, "mp3"), [STREAM_COPY, "libmp3lame"]);
+        assert_eq!(codecs(&formats, "m4a"), ["aac", "alac"]);
+    }
+
+    #[test]
+    fn video_sources_also_get_video_containers() {
+        let formats = compatible_formats(&source("aac", true), &EVERY_ENCODER);
+        assert_eq!(
+            containers(&formats),
+            ["wav", "flac", "mp3", "ogg", "opus", "m4a", "mp4", "mkv", "webm"]
+        );
+        assert_eq!(codecs(&formats, "mp4"), [STREAM_COPY, "aac"]);
+        assert!(formats[0].note.ends_with("the video is left out"));
+        assert_eq!(
+            formats[6].note,
+            "Keeps the video; the audio can be copied without re-encoding"
+        );
+    }
+
+    #[test]
+    fn missing_encoders_drop_containers_unless_copyable() {
+        // A minimal build without the external encoders.
+        let formats = compatible_formats(&source("opus", false), &["pcm_s16le", "flac", "aac"]);
+        assert_eq!(containers(&formats), ["wav", "flac", "ogg", "opus", "m4a"]);
+        assert_eq!(codecs(&formats, "ogg"), [STREAM_COPY, "flac"]);
+        assert_eq!(codecs(&formats, "opus"), [STREAM_COPY]);
+        assert!(compatible_formats(&source("ac3", false), &[]).is_empty());
+    }
+
+    #[test]
+    fn notes_what_happens_to_the_source() {
+        let master = MediaInfo {
+            sample_rate: 96000,
+            bit_depth: Some(24),
+            surround: true,
+            ..source("flac", false)
+        };
+        let formats = compatible_formats(&master, &EVERY_ENCODER);
+        let note = |container| {
+            &formats
+                .iter()
+                .find(|f| f.container == container)
+                .unwrap()
+                .note
+        };
+        assert_eq!(
+            note("mp3"),
+            "Plays everywhere; resampled to 48 kHz; lossy, unlike the source; downmixed to stereo"
+        );
+        assert_eq!(
+            note("flac"),
+            "Lossless at about half the size of WAV; the audio can be copied without re-encoding"
+        );
+        assert_eq!(
+            note("opus"),
+            "Smallest files for the quality; resampled to 48 kHz; lossy, unlike the source"
+        );
+    }
+}
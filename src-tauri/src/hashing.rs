@@ -0,0 +1,157 @@
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::AppError;
+
+/// Bytes read from each end of the file by [`fast_hash`].
+pub const SAMPLE_BYTES: u64 = 64 * 1024;
+
+/// Read buffer for [`full_hash`].
+const CHUNK_BYTES: usize = 1024 * 1024;
+
+fn open(path: &str) -> Result<File, AppError> {
+    File::open(path).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => AppError::FileNotFound(format!("File not found: {}", path)),
+        _ => AppError::Io(format!("Cannot read {}: {}", path, e)),
+    })
+}
+
+fn read_failed(path: &str, e: std::io::Error) -> AppError {
+    AppError::Io(format!("Failed to read {}: {}", path, e))
+}
+
+/// Hashes the file size and the first and last [`SAMPLE_BYTES`] of `path`,
+/// so multi-GB files are hashed as quickly as small ones.
+///
+/// Media editors almost always touch the header or the tail, and any change
+/// in length is caught, but an edit confined to the middle of a large file
+/// is missed; use [`full_hash`] when that matters.
+pub fn fast_hash(path: &str) -> Result<String, AppError> {
+    let mut file = open(path)?;
+    let size = file.metadata().map_err(|e| read_failed(path, e))?.len();
+
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_le_bytes());
+    let mut sample = Vec::with_capacity(SAMPLE_BYTES as usize);
+    (&mut file)
+        .take(SAMPLE_BYTES)
+        .read_to_end(&mut sample)
+        .map_err(|e| read_failed(path, e))?;
+    hasher.update(&sample);
+
+    // Small files are covered by the first sample alone.
+    if size > SAMPLE_BYTES {
+        let tail_start = size.saturating_sub(SAMPLE_BYTES).max(SAMPLE_BYTES);
+        sample.clear();
+        file.seek(SeekFrom::Start(tail_start))
+            .and_then(|_| file.take(SAMPLE_BYTES).read_to_end(&mut sample))
+            .map_err(|e| read_failed(path, e))?;
+        hasher.update(&sample);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// SHA-256 of the whole contents of `path`.
+pub fn full_hash(path: &str) -> Result<String, AppError> {
+    let mut file = open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; CHUNK_BYTES];
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(read_failed(path, e)),
+        };
+        hasher.update(&buffer[..read]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Writes `contents` to a file in the temp dir named after the test.
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "guitar-looper-hashing-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn hashes(name: &str, contents: &[u8]) -> (String, String) {
+        let path = temp_file(name, contents);
+        let hashes = (
+            fast_hash(path.to_str().unwrap()).unwrap(),
+            full_hash(path.to_str().unwrap()).unwrap(),
+        );
+        std::fs::remove_file(path).unwrap();
+        hashes
+    }
+
+    #[test]
+    fn full_hash_is_sha256() {
+        assert_eq!(
+            hashes("abc", b"abc").1,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn different_content_hashes_differently() {
+        let large = vec![7u8; SAMPLE_BYTES as usize * 3];
+        let mut head = large.clone();
+        head[10] = 8;
+        let mut tail = large.clone();
+        *tail.last_mut().unwrap() = 8;
+
+        let original = hashes("original", &large);
+        assert_eq!(hashes("copy", &large), original);
+        for (name, changed) in [
+            ("head", head),
+            ("tail", tail),
+            ("longer", [large.as_slice(), &[7]].concat()),
+        ] {
+            let changed = hashes(name, &changed);
+            assert_ne!(
+                changed.0, original.0,
+                "fast hash missed the {} change",
+                name
+            );
+            assert_ne!(
+                changed.1, original.1,
+                "full hash missed the {} change",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn only_the_full_hash_sees_the_middle() {
+        let large = vec![7u8; SAMPLE_BYTES as usize * 3];
+        let mut middle = large.clone();
+        middle[SAMPLE_BYTES as usize + 100] = 8;
+        let (original, changed) = (hashes("before", &large), hashes("middle", &middle));
+        assert_eq!(changed.0, original.0);
+        assert_ne!(changed.1, original.1);
+    }
+
+    #[test]
+    fn a_missing_file_is_not_found() {
+        assert!(matches!(
+            fast_hash("/nonexistent/guitar-looper.mp3"),
+            Err(AppError::FileNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::probe;
+
+/// Segment length `prepare_hls` aims for unless told otherwise, in seconds.
+pub const DEFAULT_SEGMENT_SECS: f64 = 6.0;
+
+/// Shortest and longest accepted segment lengths, in seconds.
+pub const MIN_SEGMENT_SECS: f64 = 1.0;
+pub const MAX_SEGMENT_SECS: f64 = 60.0;
+
+/// Folder of the temp dir every prepared stream lives under, one
+/// subfolder each. [`remove`] deletes nothing outside it.
+const ROOT_DIR: &str = "guitar-looper-hls";
+
+const PLAYLIST_NAME: &str = "playlist.m3u8";
+
+/// Video codecs fMP4 HLS carries that browsers play, which are copied
+/// rather than re-encoded.
+const COPYABLE_VIDEO: [&str; 2] = ["h264", "hevc"];
+
+/// Bit rate audio that can't be copied is encoded to AAC at.
+const AUDIO_BITRATE: &str = "192k";
+
+/// The folder all prepared streams live under.
+pub fn root() -> PathBuf {
+    std::env::temp_dir().join(ROOT_DIR)
+}
+
+/// Where the stream of a file with cache key `key`, cut into segments of
+/// `segment_secs`, is written. The key changes with the file, so a stream
+/// of an edited file is never reused.
+pub fn playlist_path(key: &str, segment_secs: f64) -> PathBuf {
+    root()
+        .join(format!(
+            "{}-{}ms",
+            key,
+            (segment_secs * 1000.0).round() as u64
+        ))
+        .join(PLAYLIST_NAME)
+}
+
+pub fn validate_segment_secs(segment_secs: f64) -> Result<(), AppError> {
+    if !(MIN_SEGMENT_SECS..=MAX_SEGMENT_SECS).contains(&segment_secs) {
+        return Err(AppError::InvalidInput(format!(
+            "Segments must be between {} and {} seconds long, got {}",
+            MIN_SEGMENT_SECS, MAX_SEGMENT_SECS, segment_secs
+        )));
+    }
+    Ok(())
+}
+
+/// Whether ffmpeg finished writing the playlist at `path`. Only a finished
+/// one ends with `#EXT-X-ENDLIST`.
+pub fn is_complete(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|contents| contents.contains("#EXT-X-ENDLIST"))
+}
+
+/// Builds the ffmpeg arguments writing `file_path`, probed as `parsed` by
+/// `ffprobe -show_streams`, as an HLS stream of fMP4 segments about
+/// `segment_secs` long to `playlist`.
+///
+/// Its moving video, if any, and first audio stream are copied when
+/// browsers can play them as they are, which takes little more than the
+/// time to read the file; copied video can only be cut at keyframes, so
+/// segments may run longer than asked. Anything else is re-encoded, to
+/// H.264 with a keyframe at every segment boundary and to AAC. Cover art,
+/// subtitles and data streams are left out.
+pub fn args(file_path: &str, parsed: &Value, segment_secs: f64, playlist: &Path) -> Vec<String> {
+    let dir = playlist.parent().unwrap_or(Path::new("."));
+    let mut args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        file_path.to_string(),
+    ];
+
+    let video = probe::video_stream_index(parsed).and_then(|index| {
+        parsed["streams"]
+            .as_array()?
+            .iter()
+            .find(|stream| stream["index"].as_u64() == Some(index))
+            .map(|stream| (index, stream["codec_name"].as_str().unwrap_or_default()))
+    });
+    if let Some((index, codec)) = video {
+        args.extend(["-map".to_string(), format!("0:{}", index)]);
+        if COPYABLE_VIDEO.contains(&codec) {
+            args.extend(["-c:v".to_string(), "copy".to_string()]);
+        } else {
+            args.extend([
+                "-c:v".to_string(),
+                "libx264".to_string(),
+                "-preset".to_string(),
+                "veryfast".to_string(),
+                "-pix_fmt".to_string(),
+                "yuv420p".to_string(),
+                "-force_key_frames".to_string(),
+                format!("expr:gte(t,n_forced*{})", segment_secs),
+            ]);
+        }
+    }
+
+    let audio_codec = probe::first_audio_stream(parsed)
+        .and_then(|stream| stream["codec_name"].as_str())
+        .unwrap_or_default();
+    args.extend(["-map".to_string(), "0:a:0?".to_string()]);
+    if audio_codec == "aac" {
+        args.extend(["-c:a".to_string(), "copy".to_string()]);
+    } else {
+        args.extend([
+            "-c:a".to_string(),
+            "aac".to_string(),
+            "-b:a".to_string(),
+            AUDIO_BITRATE.to_string(),
+        ]);
+    }
+
+    args.extend([
+        "-f".to_string(),
+        "hls".to_string(),
+        "-hls_time".to_string(),
+        segment_secs.to_string(),
+        "-hls_playlist_type".to_string(),
+        "vod".to_string(),
+        "-hls_segment_type".to_string(),
+        "fmp4".to_string(),
+        "-hls_fmp4_init_filename".to_string(),
+        "init.mp4".to_string(),
+        "-hls_segment_filename".to_string(),
+        dir.join("segment-%05d.m4s").to_string_lossy().into_owned(),
+        playlist.to_string_lossy().into_owned(),
+    ]);
+    args
+}
+
+/// Deletes the stream whose playlist is at `playlist`, returning whether
+/// there was one. Only folders directly under [`root`] are touched, so a
+/// stray path from the frontend can't delete anything else.
+pub fn remove(playlist: &Path) -> Result<bool, AppError> {
+    let Some(dir) = playlist.parent() else {
+        return Ok(false);
+    };
+    if !dir.exists() {
+        return Ok(false);
+    }
+    let canonical = |path: &Path| {
+        path.canonicalize()
+            .map_err(|e| AppError::Io(format!("Failed to resolve {}: {}", path.display(), e)))
+    };
+    if canonical(dir)?.parent() != Some(canonical(&root())?.as_path()) {
+        return Err(AppError::InvalidInput(format!(
+            "{} is not a stream prepared by prepare_hls",
+            playlist.display()
+        )));
+    }
+    fs::remove_dir_all(dir)
+        .map_err(|e| AppError::Io(format!("Failed to remove {}: {}", dir.display(), e)))?;
+    Ok(true)
+}
@@ -0,0 +1,225 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+/// Sample rate the audio is decoded at before key analysis. The highest
+/// pitch analysed sits well below its Nyquist frequency.
+pub const ANALYSIS_SAMPLE_RATE: u32 = 11025;
+
+/// Samples per analysis frame, long enough to resolve semitones in the low
+/// guitar register.
+const FRAME_SIZE: usize = 4096;
+
+/// MIDI notes whose energy is gathered into the chromagram, from E2 (the
+/// low guitar string) to E6.
+const LOWEST_NOTE: u8 = 40;
+const HIGHEST_NOTE: u8 = 88;
+
+/// RMS level below which a frame is treated as silence and skipped.
+const SILENCE_RMS: f64 = 1e-4;
+
+const PITCH_CLASSES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Krumhansl-Kessler key profiles, starting at the tonic.
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyEstimate {
+    /// Tonic and mode, e.g. "A minor".
+    pub key: String,
+    /// Either "major" or "minor".
+    pub mode: String,
+    /// Correlation between the chromagram and the key profile, from 0 to 1.
+    pub confidence: f64,
+}
+
+/// Estimates the key of mono `samples` recorded at `sample_rate`.
+///
+/// The energy at every semitone in the guitar range is folded into a
+/// 12-bin chromagram, which is compared against all 24 rotations of the
+/// major and minor profiles. Silent input yields an empty key and mode with
+/// zero confidence.
+pub fn estimate_key(samples: &[f32], sample_rate: u32) -> KeyEstimate {
+    let chroma = chromagram(samples, sample_rate);
+    if chroma.iter().all(|bin| *bin <= 0.0) {
+        return KeyEstimate {
+            key: String::new(),
+            mode: String::new(),
+            confidence: 0.0,
+        };
+    }
+
+    let mut best = (0, "major", f64::MIN);
+    for tonic in 0..12 {
+        for (mode, profile) in [("major", &MAJOR_PROFILE), ("minor", &MINOR_PROFILE)] {
+            let rotated: Vec<f64> = (0..12).map(|i| profile[(i + 12 - tonic) % 12]).collect();
+            let score = correlation(&chroma, &rotated);
+            if score > best.2 {
+                best = (tonic, mode, score);
+            }
+        }
+    }
+
+    let (tonic, mode, score) = best;
+    KeyEstimate {
+        key: format!("{} {}", PITCH_CLASSES[tonic], mode),
+        mode: mode.to_string(),
+        confidence: score.clamp(0.0, 1.0),
+    }
+}
+
+/// Taps on either side of the centre of the [`halve_rate`] low-pass filter.
+const HALF_BAND_TAPS: i64 = 16;
+
+/// Converts mono `samples` to half their sample rate, such as audio
+/// decoded at `tempo::ANALYSIS_SAMPLE_RATE` to [`ANALYSIS_SAMPLE_RATE`], so
+/// one decode serves both analyses. A windowed-sinc low-pass at the new
+/// Nyquist frequency keeps high harmonics from folding into the guitar
+/// range.
+pub fn halve_rate(samples: &[f32]) -> Vec<f32> {
+    let width = (HALF_BAND_TAPS + 1) as f64;
+    let taps: Vec<f64> = (-HALF_BAND_TAPS..=HALF_BAND_TAPS)
+        .map(|n| {
+            let x = n as f64;
+            let sinc = if n == 0 {
+                0.5
+            } else {
+                (PI * x / 2.0).sin() / (PI * x)
+            };
+            let blackman =
+                0.42 + 0.5 * (PI * x / width).cos() + 0.08 * (2.0 * PI * x / width).cos();
+            sinc * blackman
+        })
+        .collect();
+
+    let len = samples.len() as i64;
+    (0..len / 2)
+        .map(|i| {
+            let first = 2 * i - HALF_BAND_TAPS;
+            taps.iter()
+                .enumerate()
+                .filter_map(|(k, tap)| {
+                    let index = first + k as i64;
+                    (0..len)
+                        .contains(&index)
+                        .then(|| samples[index as usize] as f64 * tap)
+                })
+                .sum::<f64>() as f32
+        })
+        .collect()
+}
+
+/// Sums the per-frame pitch class distribution over all non-silent frames.
+///
+/// Each frame is normalized first so loud passages don't outweigh quiet ones.
+fn chromagram(samples: &[f32], sample_rate: u32) -> [f64; 12] {
+    let window: Vec<f64> = (0..FRAME_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / (FRAME_SIZE - 1) as f64).cos())
+        .collect();
+    let coefficients: Vec<(usize, f64)> = (LOWEST_NOTE..=HIGHEST_NOTE)
+        .map(|note| {
+            let frequency = 440.0 * 2f64.powf((note as f64 - 69.0) / 12.0);
+            let coefficient = 2.0 * (2.0 * PI * frequency / sample_rate as f64).cos();
+            (note as usize % 12, coefficient)
+        })
+        .collect();
+
+    let mut chroma = [0.0; 12];
+    let mut frame = vec![0.0; FRAME_SIZE];
+    for chunk in samples.chunks_exact(FRAME_SIZE) {
+        let rms =
+            (chunk.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / FRAME_SIZE as f64).sqrt();
+        if rms < SILENCE_RMS {
+            continue;
+        }
+        for ((value, sample), weight) in frame.iter_mut().zip(chunk).zip(&window) {
+            *value = *sample as f64 * weight;
+        }
+
+        let mut frame_chroma = [0.0; 12];
+        for (pitch_class, coefficient) in &coefficients {
+            frame_chroma[*pitch_class] += goertzel_power(&frame, *coefficient).sqrt();
+        }
+        let total: f64 = frame_chroma.iter().sum();
+        if total > 0.0 {
+            for (bin, value) in chroma.iter_mut().zip(frame_chroma) {
+                *bin += value / total;
+            }
+        }
+    }
+    chroma
+}
+
+/// Power of `frame` at the frequency encoded by `coefficient`
+/// (`2 * cos(2 * pi * f / sample_rate)`).
+fn goertzel_power(frame: &[f64], coefficient: f64) -> f64 {
+    let (mut prev, mut prev2) = (0.0, 0.0);
+    for sample in frame {
+        let current = sample + coefficient * prev - prev2;
+        prev2 = prev;
+        prev = current;
+    }
+    prev * prev + prev2 * prev2 - coefficient * prev * prev2
+}
+
+/// Pearson correlation of two equally long series.
+fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let mean_a = a.iter().sum::<f64>() / a.len() as f64;
+    let mean_b = b.iter().sum::<f64>() / b.len() as f64;
+    let (mut covariance, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        covariance += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    if var_a <= f64::EPSILON || var_b <= f64::EPSILON {
+        return 0.0;
+    }
+    covariance / (var_a * var_b).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Four seconds of sine tones at the given MIDI notes.
+    fn chord(notes: &[u8]) -> Vec<f32> {
+        let rate = ANALYSIS_SAMPLE_RATE as f64;
+        (0..ANALYSIS_SAMPLE_RATE as usize * 4)
+            .map(|i| {
+                let t = i as f64 / rate;
+                let sum: f64 = notes
+                    .iter()
+                    .map(|note| {
+                        let frequency = 440.0 * 2f64.powf((*note as f64 - 69.0) / 12.0);
+                        (2.0 * PI * frequency * t).sin()
+                    })
+                    .sum();
+                (0.2 * sum) as f32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_a_c_major_triad() {
+        // C4, E4, G4
+        let estimate = estimate_key(&chord(&[60, 64, 67]), ANALYSIS_SAMPLE_RATE);
+        assert_eq!(estimate.key, "C major");
+        assert_eq!(estimate.mode, "major");
+    }
+
+    #[test]
+    fn detects_an_a_minor_triad() {
+        // A3, C4, E4
+        let estimate = estimate_key(&chord(&[57, 60, 64]), ANALYSIS_SAMPLE_RATE);
+        assert_eq!(estimate.key, "A minor");
+        assert_eq!(estimate.mode, "minor");
+    }
+}
@@ -0,0 +1,156 @@
+use crate::error::AppError;
+use crate::{chapters, Chapter, ChapterSource};
+
+/// Splits the first field off `line`, returning it and the rest. Fields are
+/// separated by a tab when the line has any, as Audacity writes them, and
+/// by runs of spaces otherwise, as in hand-edited files.
+fn next_field(line: &str, tabbed: bool) -> (&str, &str) {
+    if tabbed {
+        line.split_once('\t').unwrap_or((line, ""))
+    } else {
+        let line = line.trim_start();
+        match line.split_once(char::is_whitespace) {
+            Some((field, rest)) => (field, rest.trim_start()),
+            None => (line, ""),
+        }
+    }
+}
+
+fn parse_time(field: &str) -> Option<f64> {
+    field
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|time| time.is_finite() && *time >= 0.0)
+}
+
+/// Parses a `start[ end] label` line into its start, end and label.
+fn parse_line(line: &str) -> Option<(f64, Option<f64>, &str)> {
+    let tabbed = line.contains('\t');
+    let (start, rest) = next_field(line, tabbed);
+    let start = parse_time(start)?;
+    let (end, after_end) = next_field(rest, tabbed);
+    match parse_time(end) {
+        Some(end) => Some((start, Some(end), after_end.trim())),
+        None => Some((start, None, rest.trim())),
+    }
+}
+
+/// Parses an Audacity label track export (Reaper reads and writes the same
+/// format) with one `start<tab>end<tab>label` line per label, times in
+/// seconds.
+///
+/// Region labels become chapters from start to end. Point labels, written
+/// with a single time or with the end equal to the start, become chapters
+/// without an end. Blank lines and the `\` lines Audacity adds for spectral
+/// selections are skipped; any other line that doesn't start with a time is
+/// an error. Chapters are returned in start order.
+pub fn parse(contents: &str) -> Result<Vec<Chapter>, AppError> {
+    let mut labels = Vec::new();
+    // Notepad saves UTF-8 with a byte order mark.
+    let contents = contents.trim_start_matches('\u{feff}');
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('\\') {
+            continue;
+        }
+        let (start, end, label) = parse_line(line).ok_or_else(|| {
+            AppError::ParseFailed(format!(
+                "Line {} is not a label, expected 'start<tab>end<tab>label': {}",
+                number + 1,
+                line
+            ))
+        })?;
+        let end = end.filter(|&end| end != start);
+        if end.is_some_and(|end| end < start) {
+            return Err(AppError::InvalidRange(format!(
+                "Label '{}' on line {} ends before it starts",
+                label,
+                number + 1
+            )));
+        }
+        labels.push((start, end, label));
+    }
+    if labels.is_empty() {
+        return Err(AppError::ParseFailed(
+            "The file contains no labels".to_string(),
+        ));
+    }
+    labels.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    Ok(labels
+        .into_iter()
+        .enumerate()
+        .map(|(index, (start, end, label))| {
+            let title = if label.is_empty() {
+                format!("Label {}", index + 1)
+            } else {
+                label.to_string()
+            };
+            chapters::imported(index, title, start, end, ChapterSource::LabelTrack)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(contents: &str) -> Vec<(String, f64, Option<f64>)> {
+        parse(contents)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.title, c.start, c.end))
+            .collect()
+    }
+
+    #[test]
+    fn parses_an_audacity_export() {
+        // As written by Audacity 3, including a spectral selection line.
+        let export = "0.000000\t0.000000\tCount in\n\
+                      4.127347\t31.904762\tVerse 1\n\
+                      \\\t220.500000\t4410.000000\n\
+                      31.904762\t58.560000\tChorus\n";
+        assert_eq!(
+            parsed(export),
+            [
+                ("Count in".to_string(), 0.0, None),
+                ("Verse 1".to_string(), 4.127347, Some(31.904762)),
+                ("Chorus".to_string(), 31.904762, Some(58.56)),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_spaces_blank_lines_and_point_labels() {
+        let edited = "\u{feff}12.5   Solo take 2\r\n\r\n  3.0 8.25   Intro riff\r\n";
+        assert_eq!(
+            parsed(edited),
+            [
+                ("Intro riff".to_string(), 3.0, Some(8.25)),
+                ("Solo take 2".to_string(), 12.5, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn unnamed_labels_are_numbered() {
+        assert_eq!(
+            parsed("1.5\t2.5\t\n"),
+            [("Label 1".to_string(), 1.5, Some(2.5))]
+        );
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(matches!(
+            parse("Verse\t1.0\t2.0"),
+            Err(AppError::ParseFailed(_))
+        ));
+        assert!(matches!(
+            parse("5.0\t2.0\tBackwards"),
+            Err(AppError::InvalidRange(_))
+        ));
+        assert!(matches!(parse("\n\n"), Err(AppError::ParseFailed(_))));
+    }
+}
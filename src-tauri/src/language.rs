@@ -0,0 +1,107 @@
+use serde_json::Value;
+
+/// Two letter ISO 639-1 codes, as BCP-47 tags use them, with the three
+/// letter ISO 639-2 codes containers store. Languages with a separate
+/// bibliographic code list it second.
+const LANGUAGE_CODES: [(&str, &[&str]); 28] = [
+    ("ar", &["ara"]),
+    ("cs", &["ces", "cze"]),
+    ("da", &["dan"]),
+    ("de", &["deu", "ger"]),
+    ("el", &["ell", "gre"]),
+    ("en", &["eng"]),
+    ("es", &["spa"]),
+    ("fa", &["fas", "per"]),
+    ("fi", &["fin"]),
+    ("fr", &["fra", "fre"]),
+    ("he", &["heb"]),
+    ("hi", &["hin"]),
+    ("hu", &["hun"]),
+    ("id", &["ind"]),
+    ("it", &["ita"]),
+    ("ja", &["jpn"]),
+    ("ko", &["kor"]),
+    ("nl", &["nld", "dut"]),
+    ("no", &["nor"]),
+    ("pl", &["pol"]),
+    ("pt", &["por"]),
+    ("ro", &["ron", "rum"]),
+    ("ru", &["rus"]),
+    ("sv", &["swe"]),
+    ("th", &["tha"]),
+    ("tr", &["tur"]),
+    ("uk", &["ukr"]),
+    ("zh", &["zho", "chi"]),
+];
+
+/// Reduces a BCP-47 tag or ISO 639 code to a lowercase primary language
+/// subtag, using the two letter form where there is one, so `en-US`, `EN`
+/// and `eng` all become `en`.
+fn primary_language(tag: &str) -> String {
+    let primary = tag
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    LANGUAGE_CODES
+        .iter()
+        .find(|(_, codes)| codes.contains(&primary.as_str()))
+        .map_or(primary, |(code, _)| code.to_string())
+}
+
+/// Whether two language tags name the same language, ignoring region and
+/// script subtags.
+pub fn same_language(a: &str, b: &str) -> bool {
+    let a = primary_language(a);
+    !a.is_empty() && a != "und" && a == primary_language(b)
+}
+
+/// Picks the option in `preferred` language if there is one, then the
+/// default one, then the first.
+pub fn pick<'a, T>(
+    options: &'a [T],
+    preferred: Option<&str>,
+    language: impl Fn(&T) -> Option<&str>,
+    is_default: impl Fn(&T) -> bool,
+) -> Option<&'a T> {
+    preferred
+        .and_then(|preferred| {
+            options.iter().find(|option| {
+                language(option).is_some_and(|language| same_language(language, preferred))
+            })
+        })
+        .or_else(|| options.iter().find(|option| is_default(option)))
+        .or_else(|| options.first())
+}
+
+/// Chooses a chapter title from ffprobe chapter `tags`, which hold the
+/// default title as `title` and translations as `title-<language>`.
+///
+/// Returns the title and its language, which is unknown for the default
+/// title.
+pub fn chapter_title(tags: &Value, preferred: Option<&str>) -> Option<(String, Option<String>)> {
+    let titles: Vec<(String, Option<String>)> = tags
+        .as_object()?
+        .iter()
+        .filter_map(|(key, value)| {
+            let title = value.as_str()?.trim();
+            if title.is_empty() {
+                return None;
+            }
+            let language = match key.strip_prefix("title") {
+                Some("") => None,
+                Some(suffix) => Some(suffix.strip_prefix('-')?.to_string()),
+                None => return None,
+            };
+            Some((title.to_string(), language))
+        })
+        .collect();
+    pick(
+        &titles,
+        preferred,
+        |(_, language)| language.as_deref(),
+        |(_, language)| language.is_none(),
+    )
+    .cloned()
+}
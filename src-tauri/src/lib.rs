@@ -1,53 +1,184 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod align;
+mod artwork;
+mod batch;
+mod beatgrid;
+mod cache;
+mod chapter_json;
+mod chapter_txt;
+mod chapters;
+mod clipping;
+mod concat;
+mod config;
+mod cue;
+mod dc;
+mod effects;
+mod encoders;
+mod eq;
+mod error;
+mod export;
+mod ffmetadata;
+mod ffmpeg;
+mod fingerprint;
+mod formats;
+mod hashing;
+mod hls;
+mod key;
+mod labels;
+mod language;
+mod logging;
+mod loudness;
+mod markers;
+mod metronome;
+mod mp4;
+mod mux;
+mod novelty;
+mod operations;
+mod pcm;
+mod playlist;
+mod probe;
+mod project;
+mod recent;
+mod reconcile;
+mod replaygain;
+mod retry;
+mod seam;
+mod search;
+mod sections;
+mod session;
+mod settings;
+mod sidecar;
+mod silence;
+mod spectrogram;
+mod subtitles;
+mod tempo;
+mod timestamps;
+mod transcode;
+mod util;
+mod verify;
+mod vorbis;
+mod waveform;
+mod webvtt;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use base64::Engine;
 use serde::{Deserialize, Serialize};
-use tauri::AppHandle;
-use tauri_plugin_shell::ShellExt;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Semaphore;
+use tracing::{info, instrument, warn};
+
+use config::{FfmpegConfig, Tool, ToolSource};
+use error::AppError;
+use ffmpeg::RunOptions;
+use operations::OperationManager;
+use pcm::PcmFormat;
+use recent::{RecentFile, RecentFilesState};
+use settings::{Settings, SettingsState};
+
+/// Where a chapter was read from, kept to help track down misparsed files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterSource {
+    /// `ffprobe -show_chapters`.
+    Ffprobe,
+    /// A Nero `chpl` atom in an MP4/M4B file.
+    Chpl,
+    /// A QuickTime text track in an MP4/M4B file.
+    TextTrack,
+    /// An imported CUE sheet.
+    Cue,
+    /// Timestamps pasted from a video description.
+    Description,
+    /// `CHAPTERnnn` Vorbis comments in an Ogg Vorbis or Opus file.
+    VorbisComment,
+    /// An imported Audacity or Reaper label track.
+    LabelTrack,
+    /// An imported JSON chapter file, see `export_chapters_json`.
+    ChapterJson,
+    /// An imported plain `HH:MM:SS Title` chapter file.
+    ChapterTxt,
+    /// Suggested by `auto_segment` from changes in loudness.
+    AutoSegment,
+}
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Chapter {
     pub id: String,
     pub title: String,
     pub start: f64,
     pub end: Option<f64>,
+    /// Unset for chapters the user created by hand.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ChapterSource>,
+    /// `end - start`, or `None` while the end is unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    /// How the user likes to practice this loop, saved in the sidecar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loop_settings: Option<markers::LoopSettings>,
+    /// Language of the title, when the file has titles in several.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Color the user marked the chapter with, as a CSS color.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Labels the user gave the chapter, such as a difficulty or song part.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 #[tauri::command]
+#[instrument]
 fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+/// Reads the chapters of `file_path`. Where titles exist in several
+/// languages, those in `preferred_language` (a BCP-47 tag such as `de` or
+/// `en-US`) are used, falling back to the default and then the first.
 #[tauri::command]
-async fn extract_chapters(handle: AppHandle, file_path: String) -> Result<Vec<Chapter>, String> {
-    println!("Extracting chapters from: {}", file_path);
+#[instrument(skip(handle), err)]
+async fn extract_chapters(
+    handle: AppHandle,
+    file_path: String,
+    preferred_language: Option<String>,
+) -> Result<Vec<Chapter>, AppError> {
+    read_chapters(&handle, &file_path, preferred_language.as_deref()).await
+}
 
-    let output = handle.shell()
-        .command("ffprobe")
-        .args([
-            "-v",
-            "quiet",
-            "-print_format",
-            "json",
-            "-show_chapters",
-            &file_path,
-        ])
-        .output()
-        .await
-        .map_err(|e| format!("Failed to set up ffprobe command: {}. Make sure FFmpeg is installed.", e))?;
+/// Reads the chapters of `file_path`, serving them from the cache when the
+/// file hasn't changed since it was last probed.
+async fn read_chapters(
+    handle: &AppHandle,
+    file_path: &str,
+    preferred_language: Option<&str>,
+) -> Result<Vec<Chapter>, AppError> {
+    info!(
+        "Extracting chapters from: {} (language {:?})",
+        file_path, preferred_language
+    );
+    let file_path = &util::validate_media_source(file_path)?;
 
-    if !output.status.success() {
-        // Correctly format the error message using debug formatting for status and converting stderr to a string
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("FFprobe failed with status {:?}: {}", output.status, stderr));
+    // Failing to fingerprint the file only means we skip the cache.
+    let fingerprint = cache::FileFingerprint::of(file_path).ok();
+    if let Some(chapters) = fingerprint
+        .as_ref()
+        .and_then(|f| cache::load_chapters(handle, f, preferred_language))
+    {
+        info!("Loaded {} chapters from cache", chapters.len());
+        return Ok(chapters);
     }
 
-    // `output.stdout` is Vec<u8>, so we convert it to a String
-    let json_output = String::from_utf8(output.stdout)
-        .map_err(|e| format!("Invalid UTF-8 output from ffprobe: {}", e))?;
-
-    let parsed: serde_json::Value = serde_json::from_str(&json_output)
-        .map_err(|e| format!("Failed to parse JSON from ffprobe: {}", e))?;
+    let mut args = Vec::new();
+    if util::is_remote(file_path) {
+        args.extend(ffmpeg::NETWORK_TIMEOUT_ARGS);
+    }
+    args.extend(["-show_chapters", "-show_format", "-show_streams", file_path]);
+    let parsed = ffmpeg::run_ffprobe(handle, &args).await?;
 
     let mut chapters = Vec::new();
     if let Some(chapters_array) = parsed["chapters"].as_array() {
@@ -61,34 +192,239 @@ async fn extract_chapters(handle: AppHandle, file_path: String) -> Result<Vec<Ch
                 .as_str()
                 .and_then(|s| s.parse::<f64>().ok());
 
-            let title = chapter["tags"]["title"]
-                .as_str()
-                .unwrap_or(&format!("Chapter {}", index + 1))
-                .to_string();
+            let (title, language) = language::chapter_title(&chapter["tags"], preferred_language)
+                .unwrap_or_else(|| (format!("Chapter {}", index + 1), None));
 
             chapters.push(Chapter {
                 id: format!("chapter-{}", index),
                 title,
                 start: start_time,
                 end: end_time,
+                source: Some(ChapterSource::Ffprobe),
+                language,
+                ..Default::default()
             });
         }
     }
+    if chapters.is_empty() {
+        chapters = read_vorbis_chapters(&parsed);
+    }
+    // The fallbacks read the whole file, which isn't worth it over the network.
+    if chapters.is_empty() && !util::is_remote(file_path) {
+        chapters = read_mp4_chapters(handle, file_path, preferred_language).await;
+    }
+    chapters::fill_durations(&mut chapters, probe::format_duration(&parsed));
 
-    println!("Found {} chapters", chapters.len());
+    info!("Found {} chapters", chapters.len());
+    if let Some(fingerprint) = fingerprint {
+        if let Err(e) = cache::store_chapters(handle, &fingerprint, preferred_language, &chapters) {
+            warn!("Failed to cache chapters: {}", e);
+        }
+    }
     Ok(chapters)
 }
 
+/// Reads `CHAPTERnnn` Vorbis comments from the container tags, or else from
+/// the first audio stream's, where Ogg files keep their comments.
+fn read_vorbis_chapters(parsed: &serde_json::Value) -> Vec<Chapter> {
+    let mut chapters = vorbis::parse_chapter_tags(&parsed["format"]["tags"]);
+    if chapters.is_empty() {
+        if let Some(stream) = probe::first_audio_stream(parsed) {
+            chapters = vorbis::parse_chapter_tags(&stream["tags"]);
+        }
+    }
+    if !chapters.is_empty() {
+        info!("Read {} chapters from Vorbis comments", chapters.len());
+    }
+    chapters
+}
+
+/// Looks for chapters `ffprobe -show_chapters` misses: a Nero `chpl` atom
+/// first, then a QuickTime text track, in `preferred_language` if there are
+/// several. Failures only mean no chapters.
+async fn read_mp4_chapters(
+    handle: &AppHandle,
+    file_path: &str,
+    preferred_language: Option<&str>,
+) -> Vec<Chapter> {
+    match mp4::read_chpl(file_path) {
+        Ok(chapters) if !chapters.is_empty() => {
+            info!("Read {} chapters from the chpl atom", chapters.len());
+            return chapters;
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Failed to read chpl atom: {}", e),
+    }
+
+    let streams = match ffmpeg::run_ffprobe(
+        handle,
+        &["-show_streams", "-select_streams", "d", file_path],
+    )
+    .await
+    {
+        Ok(streams) => streams,
+        Err(e) => {
+            warn!("Failed to list data streams: {}", e);
+            return Vec::new();
+        }
+    };
+    let Some((index, language)) = mp4::find_text_track(&streams, preferred_language) else {
+        return Vec::new();
+    };
+    let index = index.to_string();
+    match ffmpeg::run_ffprobe(
+        handle,
+        &[
+            "-show_packets",
+            "-show_data",
+            "-select_streams",
+            &index,
+            file_path,
+        ],
+    )
+    .await
+    {
+        Ok(packets) => {
+            let mut chapters = mp4::parse_text_track(&packets);
+            for chapter in &mut chapters {
+                chapter.language = language.clone();
+            }
+            info!(
+                "Read {} chapters from text track {} ({:?})",
+                chapters.len(),
+                index,
+                language
+            );
+            chapters
+        }
+        Err(e) => {
+            warn!("Failed to read text track {}: {}", index, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Maximum number of ffprobe processes a batch runs at once.
+const BATCH_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone, Serialize)]
+struct BatchProgress {
+    file_path: String,
+    completed: usize,
+    total: usize,
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::type_complexity)]
+async fn batch_extract_chapters(
+    handle: AppHandle,
+    file_paths: Vec<String>,
+    preferred_language: Option<String>,
+) -> Result<Vec<(String, Result<Vec<Chapter>, AppError>)>, AppError> {
+    info!("Batch extracting chapters from {} files", file_paths.len());
+
+    let total = file_paths.len();
+    let semaphore = Arc::new(Semaphore::new(BATCH_CONCURRENCY));
+    let completed = Arc::new(AtomicUsize::new(0));
+
+    let tasks: Vec<_> = file_paths
+        .into_iter()
+        .map(|file_path| {
+            let handle = handle.clone();
+            let semaphore = semaphore.clone();
+            let completed = completed.clone();
+            let preferred_language = preferred_language.clone();
+            tauri::async_runtime::spawn(async move {
+                let result = match semaphore.acquire().await {
+                    Ok(_permit) => {
+                        handle
+                            .state::<batch::BatchController>()
+                            .wait_if_paused()
+                            .await;
+                        read_chapters(&handle, &file_path, preferred_language.as_deref()).await
+                    }
+                    Err(e) => Err(AppError::Io(format!("Batch was aborted: {}", e))),
+                };
+                let progress = BatchProgress {
+                    file_path: file_path.clone(),
+                    completed: completed.fetch_add(1, Ordering::SeqCst) + 1,
+                    total,
+                };
+                let _ = handle.emit("batch-progress", progress);
+                (file_path, result)
+            })
+        })
+        .collect();
+
+    // Await in submission order so results line up with the input paths.
+    let mut results = Vec::with_capacity(total);
+    for task in tasks {
+        let result = task
+            .await
+            .map_err(|e| AppError::Io(format!("Batch task failed: {}", e)))?;
+        results.push(result);
+    }
+
+    let failed = results.iter().filter(|(_, r)| r.is_err()).count();
+    info!(
+        "Batch finished: {} succeeded, {} failed",
+        total - failed,
+        failed
+    );
+    Ok(results)
+}
+
+/// Holds back the next file of every running batch, and of batches started
+/// later, until `resume_batch`. Files already being processed finish.
+/// Emits `batch-state`.
+#[tauri::command]
+#[instrument(skip(handle, controller))]
+fn pause_batch(
+    handle: AppHandle,
+    controller: State<'_, batch::BatchController>,
+) -> batch::BatchState {
+    if controller.pause() {
+        info!("Paused batches");
+        let _ = handle.emit("batch-state", controller.state());
+    }
+    controller.state()
+}
+
+/// Lets paused batches carry on. Emits `batch-state`.
+#[tauri::command]
+#[instrument(skip(handle, controller))]
+fn resume_batch(
+    handle: AppHandle,
+    controller: State<'_, batch::BatchController>,
+) -> batch::BatchState {
+    if controller.resume() {
+        info!("Resumed batches");
+        let _ = handle.emit("batch-state", controller.state());
+    }
+    controller.state()
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn clear_chapter_cache(handle: AppHandle) -> Result<(), AppError> {
+    cache::clear_chapters(&handle)
+}
+
 #[tauri::command]
-async fn check_ffmpeg(handle: AppHandle) -> Result<String, String> {
-    let output = handle.shell()
-        .command("ffprobe")
-        .args(["-version"])
-        .output()
+#[instrument(skip(handle), err)]
+async fn check_ffmpeg(handle: AppHandle) -> Result<String, AppError> {
+    let command = ffmpeg::command(&handle, Tool::Ffprobe).args(["-version"]);
+    let output = ffmpeg::run_to_completion(&handle, Tool::Ffprobe, command)
         .await
-        .map_err(|_| "ffprobe command not found. Make sure FFmpeg is installed and in your system's PATH.".to_string())?;
+        .map_err(|e| match e {
+            AppError::FfmpegMissing(_) => AppError::FfmpegMissing(
+                "ffprobe command not found. Make sure FFmpeg is installed and in your system's PATH, or set its location.".to_string(),
+            ),
+            other => other,
+        })?;
 
-    if output.status.success() {
+    if output.success() {
         // Convert stdout to a string to get the first line
         let version = String::from_utf8_lossy(&output.stdout);
         let version_line = version.lines().next().unwrap_or("Unknown version");
@@ -96,17 +432,5894 @@ async fn check_ffmpeg(handle: AppHandle) -> Result<String, String> {
     } else {
         // Correctly format the error message by converting stderr to a string
         let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("FFprobe execution failed: {}", stderr))
+        Err(AppError::FfmpegFailed(format!(
+            "FFprobe execution failed: {}",
+            stderr
+        )))
     }
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_shell::init())
-        .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_dialog::init())
-        .invoke_handler(tauri::generate_handler![greet, extract_chapters, check_ffmpeg])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
-}
\ No newline at end of file
+#[derive(Debug, Clone, Serialize)]
+struct ResolvedTool {
+    /// Program launched for the tool, a path or a name looked up on PATH.
+    program: String,
+    source: ToolSource,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ResolvedFfmpeg {
+    ffmpeg: ResolvedTool,
+    ffprobe: ResolvedTool,
+}
+
+/// Looks again for ffmpeg and ffprobe sidecar binaries shipped with the app
+/// and reports which program each tool runs. Bundled binaries are used
+/// unless a path was set with `set_ffmpeg_path`; without either, the tools
+/// are looked up on PATH.
+#[tauri::command]
+#[instrument(skip(handle))]
+fn resolve_bundled_ffmpeg(handle: AppHandle) -> ResolvedFfmpeg {
+    if handle
+        .state::<sidecar::BundledToolsState>()
+        .set(sidecar::find_all(&handle))
+    {
+        forget_ffmpeg_features(&handle);
+    }
+    let config = config::current(&handle);
+    let resolve = |tool| ResolvedTool {
+        program: config.program(tool),
+        source: config.source(tool),
+    };
+    ResolvedFfmpeg {
+        ffmpeg: resolve(Tool::Ffmpeg),
+        ffprobe: resolve(Tool::Ffprobe),
+    }
+}
+
+/// Points the app at custom ffmpeg/ffprobe executables. Passing `None` for
+/// a tool goes back to the bundled binary, or to looking it up on PATH.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn set_ffmpeg_path(
+    handle: AppHandle,
+    ffmpeg_path: Option<String>,
+    ffprobe_path: Option<String>,
+) -> Result<FfmpegConfig, AppError> {
+    let settings = settings::update(&handle, |settings| {
+        settings.ffmpeg.ffmpeg_path = ffmpeg_path;
+        settings.ffmpeg.ffprobe_path = ffprobe_path;
+        Ok(())
+    })?;
+    forget_ffmpeg_features(&handle);
+    info!("Updated ffmpeg config: {:?}", settings.ffmpeg);
+    Ok(settings.ffmpeg)
+}
+
+/// Puts every ffmpeg setting back to its default, in memory and on disk:
+/// no custom binaries, so the bundled ones or those on PATH are used, the
+/// default timeout and retries, and as many threads as ffmpeg likes. Then
+/// checks the tools again and returns what was found.
+///
+/// Refused while operations are running, since they were started with the
+/// old settings.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn reset_ffmpeg_config(handle: AppHandle) -> Result<DependencyStatus, AppError> {
+    let running = handle.state::<OperationManager>().count();
+    if running > 0 {
+        return Err(AppError::InvalidInput(format!(
+            "Can't reset the ffmpeg settings while {} operation(s) are running. Cancel or wait for them first.",
+            running
+        )));
+    }
+    settings::update(&handle, |settings| {
+        settings.ffmpeg = FfmpegConfig::default();
+        Ok(())
+    })?;
+    forget_ffmpeg_features(&handle);
+    info!("Reset ffmpeg config to defaults");
+    check_dependencies(handle).await
+}
+
+/// Drops what was learned about the installed ffmpeg, since a different
+/// build may have been built with different features.
+fn forget_ffmpeg_features(handle: &AppHandle) {
+    handle.state::<fingerprint::ChromaprintSupport>().set(None);
+    handle.state::<encoders::EncoderCache>().set(None);
+}
+
+/// Sets how many seconds ffmpeg/ffprobe may go without output before they
+/// are killed. `None` restores the default.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn set_ffmpeg_timeout(
+    handle: AppHandle,
+    timeout_secs: Option<u64>,
+) -> Result<FfmpegConfig, AppError> {
+    let settings = settings::update(&handle, |settings| {
+        settings.ffmpeg.timeout_secs = timeout_secs;
+        Ok(())
+    })?;
+    info!("Updated ffmpeg config: {:?}", settings.ffmpeg);
+    Ok(settings.ffmpeg)
+}
+
+/// Sets how many threads each ffmpeg run may use. `None` or 0 lets ffmpeg
+/// decide.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn set_ffmpeg_threads(handle: AppHandle, threads: Option<u32>) -> Result<FfmpegConfig, AppError> {
+    let settings = settings::update(&handle, |settings| {
+        settings.ffmpeg.threads = threads.filter(|&threads| threads > 0);
+        Ok(())
+    })?;
+    info!("Updated ffmpeg config: {:?}", settings.ffmpeg);
+    Ok(settings.ffmpeg)
+}
+
+/// Sets how many times ffprobe and ffmpeg reads are retried after transient
+/// I/O failures. `None` restores the default.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn set_ffmpeg_retries(handle: AppHandle, retries: Option<u32>) -> Result<FfmpegConfig, AppError> {
+    let settings = settings::update(&handle, |settings| {
+        settings.ffmpeg.retries = retries;
+        Ok(())
+    })?;
+    info!("Updated ffmpeg config: {:?}", settings.ffmpeg);
+    Ok(settings.ffmpeg)
+}
+
+#[tauri::command]
+#[instrument(skip(handle))]
+fn get_settings(handle: AppHandle) -> Settings {
+    settings::current(&handle)
+}
+
+/// Applies a partial settings update: only the fields present in `patch`
+/// change, nested objects such as `ffmpeg` are merged, and `null` resets a
+/// field to its default. Returns the settings as saved.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn update_settings(handle: AppHandle, patch: serde_json::Value) -> Result<Settings, AppError> {
+    let previous = settings::current(&handle);
+    let settings = settings::update(&handle, |settings| settings::apply_patch(settings, &patch))?;
+    if settings.ffmpeg.ffmpeg_path != previous.ffmpeg.ffmpeg_path {
+        forget_ffmpeg_features(&handle);
+    }
+    if settings.waveform_cache_limit_bytes < previous.waveform_cache_limit_bytes {
+        cache::evict_waveforms(&handle, settings.waveform_cache_limit_bytes)?;
+    }
+    info!("Updated settings: {:?}", settings);
+    Ok(settings)
+}
+
+/// Length in seconds of the tone `benchmark_transcode` encodes.
+const BENCHMARK_SECS: u32 = 30;
+
+#[derive(Debug, Clone, Serialize)]
+struct TranscodeBenchmark {
+    /// Seconds of audio encoded per second of wall time; above 1 is faster
+    /// than realtime.
+    realtime_factor: f64,
+    elapsed_ms: u64,
+}
+
+/// Times encoding a synthetic stereo tone to MP3 with the configured thread
+/// count, so the settings screen can tell whether this machine transcodes
+/// faster than realtime. Nothing is written to disk.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn benchmark_transcode(handle: AppHandle) -> Result<TranscodeBenchmark, AppError> {
+    encoders::require(&handle, "libmp3lame").await?;
+    let args = vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-f".to_string(),
+        "lavfi".to_string(),
+        "-i".to_string(),
+        format!(
+            "sine=frequency=440:sample_rate=44100:duration={}",
+            BENCHMARK_SECS
+        ),
+        "-ac".to_string(),
+        "2".to_string(),
+        "-c:a".to_string(),
+        "libmp3lame".to_string(),
+        "-b:a".to_string(),
+        "192k".to_string(),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ];
+    let started = std::time::Instant::now();
+    ffmpeg::run_ffmpeg_stderr(&handle, &args).await?;
+    let elapsed = started.elapsed();
+
+    let benchmark = TranscodeBenchmark {
+        realtime_factor: BENCHMARK_SECS as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        elapsed_ms: elapsed.as_millis() as u64,
+    };
+    info!("Transcode benchmark: {:?}", benchmark);
+    Ok(benchmark)
+}
+
+/// Lists the encoders the installed ffmpeg was built with, so the UI only
+/// offers formats it can write.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn list_available_encoders(handle: AppHandle) -> Result<Vec<encoders::Encoder>, AppError> {
+    let encoders = encoders::available_encoders(&handle).await?;
+    info!("FFmpeg has {} encoders", encoders.len());
+    Ok(encoders)
+}
+
+/// Lists the containers `media_info`, as `get_media_info` returned it, can
+/// sensibly be exported to, with the ways the installed ffmpeg can write
+/// its audio into each. Video containers are only offered for sources with
+/// video.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn compatible_export_formats(
+    handle: AppHandle,
+    media_info: probe::MediaInfo,
+) -> Result<Vec<formats::ExportFormat>, AppError> {
+    let encoders = encoders::available_encoders(&handle).await?;
+    let names: Vec<&str> = encoders
+        .iter()
+        .map(|encoder| encoder.name.as_str())
+        .collect();
+    let compatible = formats::compatible_formats(&media_info, &names);
+    info!(
+        "{} ({}) can be exported as: {:?}",
+        media_info.codec_name,
+        if media_info.has_video {
+            "with video"
+        } else {
+            "audio only"
+        },
+        compatible
+            .iter()
+            .map(|format| format.container)
+            .collect::<Vec<_>>()
+    );
+    Ok(compatible)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DependencyStatus {
+    /// First line of `ffmpeg -version`, or `None` when ffmpeg can't be run.
+    ffmpeg: Option<String>,
+    ffprobe: Option<String>,
+    /// Whether ffmpeg was built with the higher quality `rubberband` filter.
+    rubberband_available: bool,
+    /// Whether ffmpeg can compute Chromaprint fingerprints.
+    chromaprint_available: bool,
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn check_dependencies(handle: AppHandle) -> Result<DependencyStatus, AppError> {
+    let (ffmpeg_version, ffprobe_version) = tokio::join!(
+        ffmpeg::tool_version(&handle, Tool::Ffmpeg),
+        ffmpeg::tool_version(&handle, Tool::Ffprobe)
+    );
+
+    let rubberband_available = if ffmpeg_version.is_some() {
+        ffmpeg::list_filters(&handle)
+            .await
+            .map(|filters| filters.iter().any(|f| f == "rubberband"))
+            .unwrap_or(false)
+    } else {
+        false
+    };
+    let chromaprint_available = ffmpeg_version.is_some()
+        && fingerprint::chromaprint_available(&handle)
+            .await
+            .unwrap_or(false);
+
+    let status = DependencyStatus {
+        ffmpeg: ffmpeg_version,
+        ffprobe: ffprobe_version,
+        rubberband_available,
+        chromaprint_available,
+    };
+    info!("Dependency status: {:?}", status);
+    Ok(status)
+}
+
+/// Computes normalized `[min, max]` peaks of `file_path`, one pair per
+/// `samples_per_pixel` samples. `pcm_format` is the sample format decoded
+/// to, `f32le` unless given; `s16le` moves half the data and is enough for
+/// drawing.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn generate_waveform(
+    handle: AppHandle,
+    file_path: String,
+    samples_per_pixel: u32,
+    channel: Option<u8>,
+    pcm_format: Option<PcmFormat>,
+) -> Result<Vec<f32>, AppError> {
+    let pcm_format = pcm_format.unwrap_or_default();
+    info!(
+        "Generating waveform for: {} ({} samples per pixel)",
+        file_path, samples_per_pixel
+    );
+    util::validate_media_path(&file_path)?;
+
+    waveform::validate_samples_per_pixel(samples_per_pixel)?;
+
+    let cache_key = cache::FileFingerprint::of(&file_path)
+        .ok()
+        .map(|fingerprint| {
+            cache::waveform_key(&fingerprint, samples_per_pixel, channel, pcm_format)
+        });
+    if let Some(peaks) = cache_key
+        .as_deref()
+        .and_then(|key| cache::load_waveform(&handle, key))
+    {
+        info!("Loaded {} waveform peaks from cache", peaks.len() / 2);
+        return Ok(peaks);
+    }
+
+    let samples =
+        ffmpeg::decode_mono_pcm(&handle, &file_path, channel, None, None, pcm_format).await?;
+    if samples.is_empty() {
+        return Err(ffmpeg::no_audio_samples(&file_path));
+    }
+
+    let peaks = waveform::compute_peaks(&samples, samples_per_pixel as usize);
+    info!("Generated {} waveform peaks", peaks.len() / 2);
+    if let Some(key) = cache_key {
+        let limit = settings::current(&handle).waveform_cache_limit_bytes;
+        if let Err(e) = cache::store_waveform(&handle, &key, &peaks, limit) {
+            warn!("Failed to cache waveform: {}", e);
+        }
+    }
+    Ok(peaks)
+}
+
+/// Default amount of decoded PCM behind each `waveform-chunk` event, about
+/// six seconds of mono 44.1 kHz audio.
+const DEFAULT_WAVEFORM_BUFFER_BYTES: usize = 1024 * 1024;
+/// Smallest accepted buffer, to keep the UI from being flooded with events.
+const MIN_WAVEFORM_BUFFER_BYTES: usize = 4096;
+/// How often `stream_waveform` checkpoints the peaks computed so far.
+const WAVEFORM_CHECKPOINT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Clone, Serialize)]
+struct WaveformChunk {
+    file_path: String,
+    /// Index of the first `[min, max]` pair in `peaks`.
+    offset: usize,
+    peaks: Vec<f32>,
+}
+
+/// Like `generate_waveform`, but decodes the file as a stream and emits the
+/// peaks of every `buffer_bytes` of PCM as a `waveform-chunk` event so the UI
+/// can draw long recordings progressively. Chunk peaks are not normalized;
+/// the command resolves to the complete normalized peaks. Cache hits resolve
+/// straight away without emitting chunks.
+///
+/// Progress is checkpointed to the cache every few seconds. When a run was
+/// interrupted, the next one emits the checkpointed peaks as its first
+/// chunk and decodes only the rest, giving the same peaks as a run that
+/// was never interrupted.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn stream_waveform(
+    handle: AppHandle,
+    file_path: String,
+    samples_per_pixel: u32,
+    channel: Option<u8>,
+    buffer_bytes: Option<usize>,
+    pcm_format: Option<PcmFormat>,
+) -> Result<Vec<f32>, AppError> {
+    let pcm_format = pcm_format.unwrap_or_default();
+    let buffer_bytes = buffer_bytes.unwrap_or(DEFAULT_WAVEFORM_BUFFER_BYTES);
+    info!(
+        "Streaming waveform for: {} ({} samples per pixel, {} byte buffer)",
+        file_path, samples_per_pixel, buffer_bytes
+    );
+    util::validate_media_path(&file_path)?;
+
+    waveform::validate_samples_per_pixel(samples_per_pixel)?;
+    if buffer_bytes < MIN_WAVEFORM_BUFFER_BYTES {
+        return Err(AppError::InvalidInput(format!(
+            "buffer_bytes must be at least {}",
+            MIN_WAVEFORM_BUFFER_BYTES
+        )));
+    }
+
+    let cache_key = cache::FileFingerprint::of(&file_path)
+        .ok()
+        .map(|fingerprint| {
+            cache::waveform_key(&fingerprint, samples_per_pixel, channel, pcm_format)
+        });
+    if let Some(peaks) = cache_key
+        .as_deref()
+        .and_then(|key| cache::load_waveform(&handle, key))
+    {
+        info!("Loaded {} waveform peaks from cache", peaks.len() / 2);
+        return Ok(peaks);
+    }
+
+    let mut peaks = cache_key
+        .as_deref()
+        .and_then(|key| cache::load_waveform_checkpoint(&handle, key))
+        .unwrap_or_default();
+    if !peaks.is_empty() {
+        info!(
+            "Resuming waveform from a checkpoint of {} peaks",
+            peaks.len() / 2
+        );
+        let _ = handle.emit(
+            "waveform-chunk",
+            WaveformChunk {
+                file_path: file_path.clone(),
+                offset: 0,
+                peaks: peaks.clone(),
+            },
+        );
+    }
+
+    let skip_samples = (peaks.len() / 2) as u64 * samples_per_pixel as u64;
+    let args =
+        ffmpeg::mono_pcm_args_skipping(&file_path, channel, None, None, pcm_format, skip_samples);
+    let mut accumulator = waveform::PeakAccumulator::new(samples_per_pixel as usize, pcm_format);
+    let mut checkpointed_at = std::time::Instant::now();
+    let mut checkpointed_len = peaks.len();
+    ffmpeg::stream_ffmpeg_raw(&handle, &args, buffer_bytes, |bytes| {
+        let chunk = accumulator.push(bytes);
+        if !chunk.is_empty() {
+            let _ = handle.emit(
+                "waveform-chunk",
+                WaveformChunk {
+                    file_path: file_path.clone(),
+                    offset: peaks.len() / 2,
+                    peaks: chunk.clone(),
+                },
+            );
+            peaks.extend(chunk);
+        }
+        if let Some(key) = cache_key.as_deref() {
+            if peaks.len() > checkpointed_len
+                && checkpointed_at.elapsed() >= WAVEFORM_CHECKPOINT_INTERVAL
+            {
+                if let Err(e) = cache::store_waveform_checkpoint(&handle, key, &peaks) {
+                    warn!("Failed to checkpoint waveform: {}", e);
+                }
+                checkpointed_at = std::time::Instant::now();
+                checkpointed_len = peaks.len();
+            }
+        }
+        Ok(())
+    })
+    .await?;
+
+    let tail = accumulator.finish();
+    if !tail.is_empty() {
+        let _ = handle.emit(
+            "waveform-chunk",
+            WaveformChunk {
+                file_path: file_path.clone(),
+                offset: peaks.len() / 2,
+                peaks: tail.clone(),
+            },
+        );
+        peaks.extend(tail);
+    }
+    if peaks.is_empty() {
+        return Err(ffmpeg::no_audio_samples(&file_path));
+    }
+
+    waveform::normalize_peaks(&mut peaks);
+    info!("Streamed {} waveform peaks", peaks.len() / 2);
+    if let Some(key) = cache_key {
+        let limit = settings::current(&handle).waveform_cache_limit_bytes;
+        if let Err(e) = cache::store_waveform(&handle, &key, &peaks, limit) {
+            warn!("Failed to cache waveform: {}", e);
+        }
+        if let Err(e) = cache::remove_waveform_checkpoint(&handle, &key) {
+            warn!("Failed to remove waveform checkpoint: {}", e);
+        }
+    }
+    Ok(peaks)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct WaveformRange {
+    /// Index of the first `[min, max]` pair in the whole file's waveform at
+    /// the same `samples_per_pixel`.
+    first_bucket: u64,
+    /// Time the first pair starts at, `start` rounded down to a whole bucket.
+    start: f64,
+    peaks: Vec<f32>,
+}
+
+/// Computes waveform peaks for `start..end` of `file_path` only, decoding
+/// just that part, for drawing a zoomed-in view.
+///
+/// The range is widened to whole buckets counted from the start of the
+/// file, so pair `i` covers the same samples as pair `first_bucket + i` of
+/// `generate_waveform`. The peaks are not normalized: they are sample
+/// values, which a view normalized to the whole file has to scale itself.
+/// `pcm_format` is as for `generate_waveform`.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn generate_waveform_range(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    samples_per_pixel: u32,
+    channel: Option<u8>,
+    pcm_format: Option<PcmFormat>,
+) -> Result<WaveformRange, AppError> {
+    let pcm_format = pcm_format.unwrap_or_default();
+    info!(
+        "Generating waveform for {} - {} of {} ({} samples per pixel)",
+        start, end, file_path, samples_per_pixel
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+    waveform::validate_samples_per_pixel(samples_per_pixel)?;
+
+    // Buckets are counted in source samples, as in the full waveform.
+    let info = read_media_info(&handle, &file_path).await?;
+    if info.sample_rate == 0 {
+        return Err(AppError::ParseFailed(format!(
+            "Could not read the sample rate of {}",
+            file_path
+        )));
+    }
+    let range = waveform::bucket_range(start, end, info.sample_rate, samples_per_pixel);
+    let samples = ffmpeg::decode_mono_pcm(
+        &handle,
+        &file_path,
+        channel,
+        Some((range.start, range.end)),
+        None,
+        pcm_format,
+    )
+    .await?;
+    if samples.is_empty() {
+        return Err(AppError::InvalidRange(format!(
+            "There is no audio between {} and {} in {}",
+            start, end, file_path
+        )));
+    }
+
+    let peaks = waveform::compute_raw_peaks(&samples, samples_per_pixel as usize);
+    info!(
+        "Generated {} waveform peaks from bucket {}",
+        peaks.len() / 2,
+        range.first_bucket
+    );
+    Ok(WaveformRange {
+        first_bucket: range.first_bucket,
+        start: range.start,
+        peaks,
+    })
+}
+
+/// Rate `chapter_waveforms` decodes at. Previews are a few hundred pixels
+/// wide, so this keeps an hour of audio to about 115 MB of samples.
+const CHAPTER_WAVEFORM_SAMPLE_RATE: u32 = 8000;
+
+/// Most peaks `chapter_waveforms` computes per chapter.
+const MAX_CHAPTER_WAVEFORM_PEAKS: u32 = 2000;
+
+#[derive(Debug, Clone, Serialize)]
+struct ChapterWaveform {
+    chapter_id: String,
+    /// `peaks_per_chapter` `[min, max]` pairs, or none when the chapter lies
+    /// past the end of the audio.
+    peaks: Vec<f32>,
+}
+
+/// Computes a small waveform preview of every chapter, `peaks_per_chapter`
+/// `[min, max]` pairs each, decoding `file_path` only once.
+///
+/// All previews are scaled by the loudest peak among them, so a quiet
+/// chapter looks quiet next to a loud one. Emits `analysis-progress` as
+/// each chapter's audio finishes decoding.
+#[tauri::command]
+#[instrument(skip(handle, chapters), fields(chapters = chapters.len()), err)]
+async fn chapter_waveforms(
+    handle: AppHandle,
+    file_path: String,
+    chapters: Vec<Chapter>,
+    peaks_per_chapter: u32,
+) -> Result<Vec<ChapterWaveform>, AppError> {
+    info!(
+        "Generating {} peak waveforms for {} chapters of {}",
+        peaks_per_chapter,
+        chapters.len(),
+        file_path
+    );
+    util::validate_media_path(&file_path)?;
+    for chapter in &chapters {
+        util::validate_range(chapter.start, chapter.end)?;
+    }
+    if !(1..=MAX_CHAPTER_WAVEFORM_PEAKS).contains(&peaks_per_chapter) {
+        return Err(AppError::InvalidInput(format!(
+            "peaks_per_chapter must be between 1 and {}, got {}",
+            MAX_CHAPTER_WAVEFORM_PEAKS, peaks_per_chapter
+        )));
+    }
+
+    let rate = CHAPTER_WAVEFORM_SAMPLE_RATE as f64;
+    let sample_range = |chapter: &Chapter, len: usize| {
+        let start = ((chapter.start * rate).floor() as usize).min(len);
+        let end = chapter
+            .end
+            .map_or(len, |end| ((end * rate).ceil() as usize).min(len));
+        start..end.max(start)
+    };
+    // Chapters in the order their audio becomes available; open-ended ones
+    // have to wait for the end of the stream.
+    let mut pending: Vec<usize> = (0..chapters.len()).collect();
+    pending.sort_by(|&a, &b| {
+        let end = |index: usize| chapters[index].end.unwrap_or(f64::INFINITY);
+        end(a).total_cmp(&end(b))
+    });
+    let mut pending = pending.into_iter().peekable();
+    let mut peaks = vec![Vec::new(); chapters.len()];
+    let mut completed = 0;
+    let mut finish_chapter = |index: usize, samples: &[f32]| {
+        let chapter = &chapters[index];
+        peaks[index] = waveform::fixed_count_peaks(
+            &samples[sample_range(chapter, samples.len())],
+            peaks_per_chapter as usize,
+        );
+        completed += 1;
+        let progress = AnalysisProgress {
+            chapter_id: chapter.id.clone(),
+            completed,
+            total: chapters.len(),
+        };
+        let _ = handle.emit("analysis-progress", progress);
+    };
+
+    let args = ffmpeg::mono_pcm_args(
+        &file_path,
+        None,
+        None,
+        Some(CHAPTER_WAVEFORM_SAMPLE_RATE),
+        PcmFormat::F32le,
+    );
+    let mut decoder = pcm::PcmDecoder::new(PcmFormat::F32le);
+    let mut samples = Vec::new();
+    ffmpeg::stream_ffmpeg_raw(&handle, &args, DEFAULT_WAVEFORM_BUFFER_BYTES, |bytes| {
+        decoder.push(bytes, |sample| samples.push(sample));
+        let decoded = samples.len() as f64 / rate;
+        while let Some(index) =
+            pending.next_if(|&index| chapters[index].end.is_some_and(|end| end <= decoded))
+        {
+            finish_chapter(index, &samples);
+        }
+        Ok(())
+    })
+    .await?;
+    if samples.is_empty() {
+        return Err(ffmpeg::no_audio_samples(&file_path));
+    }
+    for index in pending {
+        finish_chapter(index, &samples);
+    }
+
+    waveform::normalize_peak_sets(&mut peaks);
+    info!(
+        "Generated waveforms for {} chapters from {} samples",
+        chapters.len(),
+        samples.len()
+    );
+    Ok(chapters
+        .iter()
+        .zip(peaks)
+        .map(|(chapter, peaks)| ChapterWaveform {
+            chapter_id: chapter.id.clone(),
+            peaks,
+        })
+        .collect())
+}
+
+/// Computes a waveform for every channel of `file_path`, up to eight,
+/// decoded without downmixing so the UI can overlay them. Each is a list of
+/// `[min, max]` pairs as from `generate_waveform`, all scaled by the loudest
+/// peak of any channel so their levels can be compared.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn channel_peaks(
+    handle: AppHandle,
+    file_path: String,
+    samples_per_pixel: u32,
+) -> Result<Vec<Vec<f32>>, AppError> {
+    info!(
+        "Metering the channels of {} ({} samples per pixel)",
+        file_path, samples_per_pixel
+    );
+    util::validate_media_path(&file_path)?;
+    waveform::validate_samples_per_pixel(samples_per_pixel)?;
+
+    let info = read_media_info(&handle, &file_path).await?;
+    if info.channels == 0 {
+        return Err(AppError::ParseFailed(format!(
+            "Could not read the channel count of {}",
+            file_path
+        )));
+    }
+    if info.channels > waveform::MAX_METER_CHANNELS {
+        return Err(AppError::InvalidInput(format!(
+            "{} has {} channels, but at most {} can be metered",
+            file_path,
+            info.channels,
+            waveform::MAX_METER_CHANNELS
+        )));
+    }
+
+    let samples =
+        ffmpeg::decode_interleaved_pcm(&handle, &file_path, None, Some(info.channels)).await?;
+    if samples.is_empty() {
+        return Err(ffmpeg::no_audio_samples(&file_path));
+    }
+    let mut peaks: Vec<Vec<f32>> = waveform::deinterleave(&samples, info.channels as usize)
+        .iter()
+        .map(|channel| waveform::compute_raw_peaks(channel, samples_per_pixel as usize))
+        .collect();
+    waveform::normalize_peak_sets(&mut peaks);
+    info!(
+        "Generated {} waveform peaks for each of {} channels",
+        peaks[0].len() / 2,
+        peaks.len()
+    );
+    Ok(peaks)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct WaveformComparison {
+    /// Rate both takes were decoded at before computing their peaks.
+    sample_rate: u32,
+    /// `[min, max]` pairs of the first take, as from `generate_waveform`.
+    first_peaks: Vec<f32>,
+    /// `[min, max]` pairs of the second take. Pair `i` of either take covers
+    /// the same time from the start of its range.
+    second_peaks: Vec<f32>,
+    #[serde(flatten)]
+    alignment: align::Alignment,
+}
+
+/// Computes overlayable waveforms of two takes of the same music, such as a
+/// student's recording and the original, and the offset that lines them up.
+///
+/// Both takes, or `first_range` and `second_range` of them, are decoded to
+/// mono at a common rate and reduced to peaks with the same
+/// `samples_per_pixel`, scaled by the loudest peak of either so their levels
+/// can be compared. The offset is searched up to `max_offset` seconds either
+/// way, 10 by default; shifting the second take's waveform back by it syncs
+/// the overlay.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn compare_waveforms(
+    handle: AppHandle,
+    first_path: String,
+    second_path: String,
+    first_range: Option<(f64, f64)>,
+    second_range: Option<(f64, f64)>,
+    samples_per_pixel: u32,
+    max_offset: Option<f64>,
+) -> Result<WaveformComparison, AppError> {
+    let max_offset = max_offset.unwrap_or(align::DEFAULT_MAX_OFFSET_SECS);
+    info!(
+        "Comparing the waveforms of {} ({:?}) and {} ({:?})",
+        first_path, first_range, second_path, second_range
+    );
+    for (path, range) in [(&first_path, first_range), (&second_path, second_range)] {
+        util::validate_media_path(path)?;
+        if let Some((start, end)) = range {
+            util::validate_range(start, Some(end))?;
+        }
+    }
+    waveform::validate_samples_per_pixel(samples_per_pixel)?;
+    if !(max_offset.is_finite() && max_offset >= 0.0) {
+        return Err(AppError::InvalidInput(format!(
+            "max_offset must be zero or more seconds, got {}",
+            max_offset
+        )));
+    }
+
+    let rate = Some(align::COMPARE_SAMPLE_RATE);
+    let (first, second) = tokio::join!(
+        ffmpeg::decode_mono_pcm(
+            &handle,
+            &first_path,
+            None,
+            first_range,
+            rate,
+            PcmFormat::F32le
+        ),
+        ffmpeg::decode_mono_pcm(
+            &handle,
+            &second_path,
+            None,
+            second_range,
+            rate,
+            PcmFormat::F32le
+        ),
+    );
+    let (first, second) = (first?, second?);
+    for (path, samples) in [(&first_path, &first), (&second_path, &second)] {
+        if samples.is_empty() {
+            return Err(ffmpeg::no_audio_samples(path));
+        }
+    }
+
+    let alignment = align::estimate_offset(&first, &second, align::COMPARE_SAMPLE_RATE, max_offset);
+    let mut peaks = [
+        waveform::compute_raw_peaks(&first, samples_per_pixel as usize),
+        waveform::compute_raw_peaks(&second, samples_per_pixel as usize),
+    ];
+    waveform::normalize_peak_sets(&mut peaks);
+    let [first_peaks, second_peaks] = peaks;
+    info!(
+        "The second take lags the first by {:.3}s (confidence {:.2})",
+        alignment.offset, alignment.confidence
+    );
+    Ok(WaveformComparison {
+        sample_rate: align::COMPARE_SAMPLE_RATE,
+        first_peaks,
+        second_peaks,
+        alignment,
+    })
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn waveform_cache_size(handle: AppHandle) -> Result<u64, AppError> {
+    cache::waveform_cache_size(&handle)
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn clear_waveform_cache(handle: AppHandle) -> Result<(), AppError> {
+    cache::clear_waveforms(&handle)
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn set_waveform_cache_limit(handle: AppHandle, limit_bytes: u64) -> Result<(), AppError> {
+    settings::update(&handle, |settings| {
+        settings.waveform_cache_limit_bytes = limit_bytes;
+        Ok(())
+    })?;
+    cache::evict_waveforms(&handle, limit_bytes)
+}
+
+/// Builds the argument list for cutting `start..end` out of `file_path`,
+/// taking the audio stream `stream_index` or else the first audio stream and
+/// running it through `filter` if given. A filter rules out `stream_copy`.
+///
+/// By default `-ss`/`-to` go before `-i`, letting ffmpeg jump straight to
+/// the start using the container's index. An `accurate` cut puts them after
+/// `-i` instead, so ffmpeg decodes from the beginning of the file and drops
+/// everything before `start`: exact to the sample, but slower the later the
+/// cut starts.
+#[allow(clippy::too_many_arguments)]
+fn extraction_args(
+    file_path: &str,
+    start: f64,
+    end: Option<f64>,
+    stream_index: Option<u32>,
+    filter: Option<&str>,
+    output_path: &str,
+    stream_copy: bool,
+    accurate: bool,
+) -> Vec<String> {
+    let mut seek = vec!["-ss".to_string(), start.to_string()];
+    if let Some(end) = end {
+        seek.push("-to".to_string());
+        seek.push(end.to_string());
+    }
+
+    let mut args = vec!["-y".to_string(), "-v".to_string(), "error".to_string()];
+    if !accurate {
+        args.extend(seek.iter().cloned());
+    }
+    args.push("-i".to_string());
+    args.push(file_path.to_string());
+    if accurate {
+        args.extend(seek);
+    }
+    args.push("-map".to_string());
+    args.push(match stream_index {
+        Some(index) => format!("0:{}", index),
+        None => "0:a:0".to_string(),
+    });
+    args.push("-vn".to_string());
+    if let Some(filter) = filter {
+        args.push("-af".to_string());
+        args.push(filter.to_string());
+    } else if stream_copy {
+        args.push("-c".to_string());
+        args.push("copy".to_string());
+    }
+    args.push(output_path.to_string());
+    args
+}
+
+/// How long a cut came out compared to what was asked for.
+#[derive(Debug, Clone, Serialize)]
+struct CutTiming {
+    requested_duration: f64,
+    actual_duration: f64,
+    /// Where the audio really begins relative to the requested start,
+    /// negative when a fast seek snapped back to an earlier keyframe. The
+    /// end is cut where asked, so the whole difference in length is put
+    /// down to the start.
+    start_offset: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ExtractedAudio {
+    output_path: String,
+    /// The ffmpeg command line that would have run, for a dry run.
+    command_line: Option<String>,
+    /// `None` for a dry run or when the output couldn't be measured.
+    timing: Option<CutTiming>,
+}
+
+impl ExtractedAudio {
+    fn dry_run(output_path: String, command_line: String) -> Self {
+        ExtractedAudio {
+            output_path,
+            command_line: Some(command_line),
+            timing: None,
+        }
+    }
+}
+
+/// Measures how the cut of `start..end` of `file_path` written to
+/// `output_path` differs from what was requested. An open-ended cut is
+/// compared against the rest of the source. Failures are only logged, as
+/// the export itself already succeeded.
+async fn measure_cut(
+    handle: &AppHandle,
+    file_path: &str,
+    output_path: &str,
+    start: f64,
+    end: Option<f64>,
+) -> Option<CutTiming> {
+    let end = match end {
+        Some(end) => end,
+        None => ffmpeg::probe_duration(handle, file_path).await?,
+    };
+    let actual_duration = match read_media_info(handle, output_path).await {
+        Ok(info) => info.duration_seconds,
+        Err(e) => {
+            warn!("Could not measure {}: {}", output_path, e);
+            return None;
+        }
+    };
+    let requested_duration = end - start;
+    let timing = CutTiming {
+        requested_duration,
+        actual_duration,
+        start_offset: requested_duration - actual_duration,
+    };
+    info!(
+        "Cut is {:.3}s long, {:.3}s requested",
+        timing.actual_duration, timing.requested_duration
+    );
+    Some(timing)
+}
+
+/// Cuts `start..end` of `file_path` into `output_path`, stream copying
+/// when possible. Without `output_path`, the file goes to the export
+/// directory in the default export format, named with the file name
+/// template from `title`.
+///
+/// Fast seeking, the default, can land the cut up to a keyframe or MP3
+/// frame away from `start` in files with a sparse seek index. `accurate`
+/// cuts exactly at `start` but decodes everything before it and always
+/// re-encodes, so cuts late in a long file take noticeably longer.
+///
+/// `remove_dc` takes out any DC offset, which also means re-encoding.
+///
+/// Returns the output path along with how long the cut really is, from
+/// probing the output, so the UI can warn about keyframe drift. With
+/// `dry_run`, returns the ffmpeg command line instead of running it. When
+/// stream copy would be tried first, that is the command returned.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn extract_chapter_audio(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: Option<f64>,
+    output_path: Option<String>,
+    title: Option<String>,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+    stream_index: Option<u32>,
+    fade_in: Option<f64>,
+    fade_out: Option<f64>,
+    accurate: Option<bool>,
+    dry_run: Option<bool>,
+    remove_dc: Option<bool>,
+) -> Result<ExtractedAudio, AppError> {
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, end)?;
+
+    let accurate = accurate.unwrap_or(false);
+    let output_path = match output_path {
+        Some(output_path) => output_path,
+        None => {
+            let format = settings::current(&handle).default_export_format;
+            let values = export::TemplateValues {
+                title: title.as_deref().unwrap_or_default(),
+                index: 0,
+                start,
+                end,
+                ext: &format,
+            };
+            let output_path = template_export_path(&handle, &values, None)?;
+            if let Some(dir) = std::path::Path::new(&output_path)
+                .parent()
+                .filter(|_| !dry_run.unwrap_or(false))
+            {
+                std::fs::create_dir_all(dir).map_err(|e| {
+                    AppError::Io(format!("Failed to create {}: {}", dir.display(), e))
+                })?;
+            }
+            output_path
+        }
+    };
+    info!(
+        "Extracting audio from {} ({} - {:?}) to {}",
+        file_path, start, end, output_path
+    );
+    if let Some(index) = stream_index {
+        let streams = read_audio_streams(&handle, &file_path).await?;
+        if !streams.iter().any(|stream| stream.index == index) {
+            return Err(AppError::InvalidInput(format!(
+                "Stream {} is not an audio stream of {}",
+                index, file_path
+            )));
+        }
+    }
+
+    let fade = if fade_in.is_some() || fade_out.is_some() {
+        // A fade out is placed from the end, so an open-ended segment needs
+        // the file length.
+        let length = match end {
+            Some(end) => end - start,
+            None => {
+                let duration = ffmpeg::probe_duration(&handle, &file_path)
+                    .await
+                    .ok_or_else(|| {
+                        AppError::ParseFailed(format!("Could not read the length of {}", file_path))
+                    })?;
+                duration - start
+            }
+        };
+        effects::fade_filter(fade_in, fade_out, length)?
+    } else {
+        None
+    };
+    let filter = dc::with_removal(remove_dc, fade);
+
+    let options = RunOptions {
+        duration: end.map(|end| end - start),
+        operation_id: Some(&operation_id),
+        kind: "extract_chapter_audio",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    if filter.is_some() || accurate {
+        // Filters have to be rendered, and stream copy can only cut on whole
+        // packets, so both need re-encoding.
+        let encode_args = extraction_args(
+            &file_path,
+            start,
+            end,
+            stream_index,
+            filter.as_deref(),
+            &output_path,
+            false,
+            accurate,
+        );
+        if dry_run.unwrap_or(false) {
+            return Ok(ExtractedAudio::dry_run(
+                output_path,
+                ffmpeg::job_command_line(&handle, &encode_args),
+            ));
+        }
+        ffmpeg::run_ffmpeg_with_progress(&handle, &encode_args, options).await?;
+        info!("Wrote chapter audio to {}", output_path);
+        let timing = measure_cut(&handle, &file_path, &output_path, start, end).await;
+        return Ok(ExtractedAudio {
+            output_path,
+            command_line: None,
+            timing,
+        });
+    }
+
+    // Stream copy is fast and lossless, but fails when the source codec
+    // can't live in the output container (e.g. AAC into WAV), so fall back
+    // to letting ffmpeg pick an encoder for the output format.
+    let copy_args = extraction_args(
+        &file_path,
+        start,
+        end,
+        stream_index,
+        None,
+        &output_path,
+        true,
+        false,
+    );
+    if dry_run.unwrap_or(false) {
+        return Ok(ExtractedAudio::dry_run(
+            output_path,
+            ffmpeg::job_command_line(&handle, &copy_args),
+        ));
+    }
+    match ffmpeg::run_ffmpeg_with_progress(&handle, &copy_args, options).await {
+        Ok(_) => {}
+        Err(AppError::Cancelled(message)) => return Err(AppError::Cancelled(message)),
+        Err(copy_err) => {
+            warn!("Stream copy failed, re-encoding instead: {}", copy_err);
+            let encode_args = extraction_args(
+                &file_path,
+                start,
+                end,
+                stream_index,
+                None,
+                &output_path,
+                false,
+                false,
+            );
+            ffmpeg::run_ffmpeg_with_progress(&handle, &encode_args, options).await?;
+        }
+    }
+
+    info!("Wrote chapter audio to {}", output_path);
+    let timing = measure_cut(&handle, &file_path, &output_path, start, end).await;
+    Ok(ExtractedAudio {
+        output_path,
+        command_line: None,
+        timing,
+    })
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn detect_tempo(
+    handle: AppHandle,
+    file_path: String,
+    range: Option<(f64, f64)>,
+) -> Result<tempo::TempoEstimate, AppError> {
+    info!("Detecting tempo of {} (range {:?})", file_path, range);
+    util::validate_media_path(&file_path)?;
+
+    let samples = decode_for_rhythm(&handle, &file_path, range).await?;
+    let estimate = tempo::estimate_tempo(&samples, tempo::ANALYSIS_SAMPLE_RATE);
+    info!(
+        "Estimated {:.1} BPM (confidence {:.2})",
+        estimate.bpm, estimate.confidence
+    );
+    Ok(estimate)
+}
+
+/// Decodes `range` of `file_path`, or all of it, for tempo analysis.
+async fn decode_for_rhythm(
+    handle: &AppHandle,
+    file_path: &str,
+    range: Option<(f64, f64)>,
+) -> Result<Vec<f32>, AppError> {
+    if let Some((start, end)) = range {
+        util::validate_range(start, Some(end))?;
+    }
+    ffmpeg::decode_mono_pcm(
+        handle,
+        file_path,
+        None,
+        range,
+        Some(tempo::ANALYSIS_SAMPLE_RATE),
+        PcmFormat::F32le,
+    )
+    .await
+}
+
+/// Finds the first downbeat of `file_path`, or of `range` of it, as a time
+/// in the file. The tempo is detected first unless `bpm` is given.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn detect_downbeat(
+    handle: AppHandle,
+    file_path: String,
+    range: Option<(f64, f64)>,
+    bpm: Option<f64>,
+) -> Result<tempo::Downbeat, AppError> {
+    info!(
+        "Detecting the downbeat of {} (range {:?})",
+        file_path, range
+    );
+    util::validate_media_path(&file_path)?;
+    if let Some(bpm) = bpm.filter(|bpm| !bpm.is_finite() || *bpm <= 0.0) {
+        return Err(AppError::InvalidInput(format!(
+            "BPM must be positive, got {}",
+            bpm
+        )));
+    }
+
+    let samples = decode_for_rhythm(&handle, &file_path, range).await?;
+    let bpm =
+        bpm.unwrap_or_else(|| tempo::estimate_tempo(&samples, tempo::ANALYSIS_SAMPLE_RATE).bpm);
+    let mut downbeat = tempo::detect_downbeat(&samples, tempo::ANALYSIS_SAMPLE_RATE, bpm);
+    downbeat.offset += range.map_or(0.0, |(start, _)| start);
+    info!(
+        "First downbeat at {:.3}s (confidence {:.2})",
+        downbeat.offset, downbeat.confidence
+    );
+    Ok(downbeat)
+}
+
+/// Finds note onsets in `file_path`, or in `range` of it, and returns their
+/// times in the file. `sensitivity` from 0 to 1 trades missed soft notes
+/// for spurious onsets in ringing ones.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn detect_onsets(
+    handle: AppHandle,
+    file_path: String,
+    range: Option<(f64, f64)>,
+    sensitivity: Option<f64>,
+) -> Result<Vec<f64>, AppError> {
+    info!("Detecting onsets in {} (range {:?})", file_path, range);
+    util::validate_media_path(&file_path)?;
+    let sensitivity = sensitivity.unwrap_or(tempo::DEFAULT_ONSET_SENSITIVITY);
+    if !(0.0..=1.0).contains(&sensitivity) {
+        return Err(AppError::InvalidInput(format!(
+            "Sensitivity must be between 0 and 1, got {}",
+            sensitivity
+        )));
+    }
+
+    let samples = decode_for_rhythm(&handle, &file_path, range).await?;
+    let offset = range.map_or(0.0, |(start, _)| start);
+    let onsets: Vec<f64> = tempo::detect_onsets(&samples, tempo::ANALYSIS_SAMPLE_RATE, sensitivity)
+        .into_iter()
+        .map(|time| time + offset)
+        .collect();
+    info!("Found {} onsets", onsets.len());
+    Ok(onsets)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RhythmAnalysis {
+    bpm: f64,
+    /// Time of the first downbeat in the file, the phase anchor of the grid.
+    offset: f64,
+    /// The lower of the tempo and downbeat confidences, from 0 to 1.
+    confidence: f64,
+}
+
+/// Detects the tempo and first downbeat of `file_path`, or of `range` of
+/// it, in one pass over the audio, ready for `generate_beat_grid`.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn analyze_rhythm(
+    handle: AppHandle,
+    file_path: String,
+    range: Option<(f64, f64)>,
+) -> Result<RhythmAnalysis, AppError> {
+    info!("Analyzing the rhythm of {} (range {:?})", file_path, range);
+    util::validate_media_path(&file_path)?;
+
+    let samples = decode_for_rhythm(&handle, &file_path, range).await?;
+    let tempo = tempo::estimate_tempo(&samples, tempo::ANALYSIS_SAMPLE_RATE);
+    let downbeat = tempo::detect_downbeat(&samples, tempo::ANALYSIS_SAMPLE_RATE, tempo.bpm);
+    let analysis = RhythmAnalysis {
+        bpm: tempo.bpm,
+        offset: downbeat.offset + range.map_or(0.0, |(start, _)| start),
+        confidence: tempo.confidence.min(downbeat.confidence),
+    };
+    info!("Rhythm: {:?}", analysis);
+    Ok(analysis)
+}
+
+#[tauri::command]
+#[instrument(err)]
+fn quantize_to_beat(
+    start: f64,
+    end: f64,
+    bpm: f64,
+    offset: f64,
+    subdivision: u32,
+) -> Result<(f64, f64), AppError> {
+    util::validate_range(start, Some(end))?;
+    let snapped = beatgrid::quantize(start, end, bpm, offset, subdivision)?;
+    info!(
+        "Quantized loop {} - {} to {} - {} ({} BPM, 1/{} beat)",
+        start, end, snapped.0, snapped.1, bpm, subdivision
+    );
+    Ok(snapped)
+}
+
+/// Lists the grid lines between `start` and `end` for drawing, with
+/// `subdivision` lines per beat (4 for sixteenths) and a downbeat every
+/// `beats_per_bar` beats counted from `offset`.
+#[tauri::command]
+#[instrument(err)]
+fn generate_beat_grid(
+    bpm: f64,
+    offset: f64,
+    start: f64,
+    end: f64,
+    subdivision: Option<u32>,
+    beats_per_bar: Option<u32>,
+) -> Result<Vec<beatgrid::GridLine>, AppError> {
+    util::validate_range(start, Some(end))?;
+    let lines = beatgrid::grid_lines(
+        bpm,
+        offset,
+        start,
+        end,
+        subdivision.unwrap_or(1),
+        beats_per_bar.unwrap_or(4),
+    )?;
+    info!(
+        "Generated {} grid lines for {} - {} at {} BPM",
+        lines.len(),
+        start,
+        end,
+        bpm
+    );
+    Ok(lines)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ClickTrack {
+    output_path: String,
+    /// Clicks in the track, accented ones included.
+    beats: usize,
+}
+
+/// Writes a metronome click at `bpm` to `output_path`, `duration` seconds
+/// long, with every `beats_per_bar`th beat (four by default) from the
+/// downbeat at `offset` accented. With `mix_with`, the clicks are mixed
+/// over the audio of that file instead, e.g. an extracted chapter, and the
+/// output is as long as it is.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn generate_click_track(
+    handle: AppHandle,
+    bpm: f64,
+    offset: f64,
+    duration: f64,
+    beats_per_bar: Option<u32>,
+    output_path: String,
+    mix_with: Option<String>,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<ClickTrack, AppError> {
+    let beats_per_bar = beats_per_bar.unwrap_or(metronome::DEFAULT_BEATS_PER_BAR);
+    info!(
+        "Generating a {}s click track at {} BPM ({} beats per bar, downbeat at {}) to {}",
+        duration, bpm, beats_per_bar, offset, output_path
+    );
+    let clicks = metronome::clicks(bpm, offset, duration, beats_per_bar)?;
+    let source = metronome::click_source(bpm, offset, duration, beats_per_bar);
+
+    let mut args = vec!["-y".to_string(), "-v".to_string(), "error".to_string()];
+    let output_duration = match &mix_with {
+        Some(mix_with) => {
+            util::validate_media_path(mix_with)?;
+            args.extend(["-i".to_string(), mix_with.clone()]);
+            ffmpeg::probe_duration(&handle, mix_with).await
+        }
+        None => Some(duration),
+    };
+    args.extend([
+        "-f".to_string(),
+        "lavfi".to_string(),
+        "-i".to_string(),
+        source,
+    ]);
+    if mix_with.is_some() {
+        args.extend([
+            "-filter_complex".to_string(),
+            metronome::MIX_FILTER.to_string(),
+            "-map".to_string(),
+            "[out]".to_string(),
+        ]);
+    }
+    args.push(output_path.clone());
+
+    let options = RunOptions {
+        duration: output_duration,
+        operation_id: Some(&operation_id),
+        kind: "generate_click_track",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+    info!("Wrote {} clicks to {}", clicks.len(), output_path);
+    Ok(ClickTrack {
+        output_path,
+        beats: clicks.len(),
+    })
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn detect_key(
+    handle: AppHandle,
+    file_path: String,
+    range: Option<(f64, f64)>,
+) -> Result<key::KeyEstimate, AppError> {
+    info!("Detecting key of {} (range {:?})", file_path, range);
+    util::validate_media_path(&file_path)?;
+
+    if let Some((start, end)) = range {
+        util::validate_range(start, Some(end))?;
+    }
+
+    let samples = ffmpeg::decode_mono_pcm(
+        &handle,
+        &file_path,
+        None,
+        range,
+        Some(key::ANALYSIS_SAMPLE_RATE),
+        PcmFormat::F32le,
+    )
+    .await?;
+
+    let estimate = key::estimate_key(&samples, key::ANALYSIS_SAMPLE_RATE);
+    info!(
+        "Estimated key {} (confidence {:.2})",
+        estimate.key, estimate.confidence
+    );
+    Ok(estimate)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ChapterAnalysis {
+    chapter_id: String,
+    tempo: tempo::TempoEstimate,
+    key: key::KeyEstimate,
+    /// Whether the result was cached from an earlier run.
+    cached: bool,
+}
+
+// One decode at the tempo analysis rate serves key detection too, at half
+// the rate.
+const _: () = assert!(tempo::ANALYSIS_SAMPLE_RATE == 2 * key::ANALYSIS_SAMPLE_RATE);
+
+/// Detects the tempo and key of every chapter of `file_path`, such as each
+/// song of an album, decoding the file once.
+///
+/// Results are cached per chapter range for the current version of the
+/// file, so chapters analyzed before are answered straight away and only
+/// new or moved ones are decoded. Emits `analysis-progress` as each chapter
+/// is done. With an `operation_id` the run can be stopped with
+/// `cancel_operation`, failing with a `Cancelled` error.
+#[tauri::command]
+#[instrument(skip(handle, chapters), fields(chapters = chapters.len()), err)]
+async fn analyze_all_chapters(
+    handle: AppHandle,
+    file_path: String,
+    chapters: Vec<Chapter>,
+    operation_id: Option<String>,
+) -> Result<Vec<ChapterAnalysis>, AppError> {
+    info!(
+        "Analyzing tempo and key of {} chapters in {}",
+        chapters.len(),
+        file_path
+    );
+    util::validate_media_path(&file_path)?;
+    for chapter in &chapters {
+        util::validate_range(chapter.start, chapter.end)?;
+    }
+
+    let fingerprint = cache::FileFingerprint::of(&file_path).ok();
+    let mut stored = fingerprint.as_ref().map_or_else(Vec::new, |fingerprint| {
+        cache::load_analysis(&handle, fingerprint)
+    });
+    let mut results: Vec<Option<ChapterAnalysis>> = chapters
+        .iter()
+        .map(|chapter| {
+            stored
+                .iter()
+                .find(|range| range.start == chapter.start && range.end == chapter.end)
+                .map(|range| ChapterAnalysis {
+                    chapter_id: chapter.id.clone(),
+                    tempo: range.tempo.clone(),
+                    key: range.key.clone(),
+                    cached: true,
+                })
+        })
+        .collect();
+    let mut completed = 0;
+    for (chapter, _) in chapters
+        .iter()
+        .zip(&results)
+        .filter(|(_, result)| result.is_some())
+    {
+        completed += 1;
+        let progress = AnalysisProgress {
+            chapter_id: chapter.id.clone(),
+            completed,
+            total: chapters.len(),
+        };
+        let _ = handle.emit("analysis-progress", progress);
+    }
+
+    // Chapters to analyze in the order their audio becomes available, as in
+    // `chapter_waveforms`. Only the stretch of the file they cover is
+    // decoded.
+    let mut pending: Vec<usize> = (0..chapters.len())
+        .filter(|&index| results[index].is_none())
+        .collect();
+    if !pending.is_empty() {
+        pending.sort_by(|&a, &b| {
+            let end = |index: usize| chapters[index].end.unwrap_or(f64::INFINITY);
+            end(a).total_cmp(&end(b))
+        });
+        let first_start = pending
+            .iter()
+            .map(|&index| chapters[index].start)
+            .fold(f64::INFINITY, f64::min);
+        let last_end = pending.iter().try_fold(0.0, |last: f64, &index| {
+            chapters[index].end.map(|end| last.max(end))
+        });
+        let range = last_end.map(|end| (first_start, end));
+        let base = range.map_or(0.0, |(start, _)| start);
+        let rate = tempo::ANALYSIS_SAMPLE_RATE as f64;
+        let sample_at = |time: f64| ((time - base) * rate).max(0.0).round() as usize;
+
+        // Decoded samples from `first_sample` on; those no chapter still
+        // waiting needs are dropped as chapters finish.
+        let mut samples: Vec<f32> = Vec::new();
+        let mut first_sample = 0;
+        let mut finish = |index: usize, samples: &mut Vec<f32>, first_sample: &mut usize| {
+            let chapter = &chapters[index];
+            let from = sample_at(chapter.start)
+                .saturating_sub(*first_sample)
+                .min(samples.len());
+            let to = chapter.end.map_or(samples.len(), |end| {
+                sample_at(end)
+                    .saturating_sub(*first_sample)
+                    .min(samples.len())
+            });
+            let audio = &samples[from..to.max(from)];
+            results[index] = Some(ChapterAnalysis {
+                chapter_id: chapter.id.clone(),
+                tempo: tempo::estimate_tempo(audio, tempo::ANALYSIS_SAMPLE_RATE),
+                key: key::estimate_key(&key::halve_rate(audio), key::ANALYSIS_SAMPLE_RATE),
+                cached: false,
+            });
+            completed += 1;
+            let progress = AnalysisProgress {
+                chapter_id: chapter.id.clone(),
+                completed,
+                total: chapters.len(),
+            };
+            let _ = handle.emit("analysis-progress", progress);
+
+            let keep_from = chapters
+                .iter()
+                .zip(&results)
+                .filter(|(_, result)| result.is_none())
+                .map(|(chapter, _)| sample_at(chapter.start))
+                .min()
+                .unwrap_or(usize::MAX);
+            let drop = keep_from.saturating_sub(*first_sample).min(samples.len());
+            samples.drain(..drop);
+            *first_sample += drop;
+        };
+
+        let args = ffmpeg::mono_pcm_args(
+            &file_path,
+            None,
+            range,
+            Some(tempo::ANALYSIS_SAMPLE_RATE),
+            PcmFormat::F32le,
+        );
+        let mut decoder = pcm::PcmDecoder::new(PcmFormat::F32le);
+        let mut pending = pending.into_iter().peekable();
+        ffmpeg::stream_cancellable_ffmpeg_raw(
+            &handle,
+            &args,
+            DEFAULT_WAVEFORM_BUFFER_BYTES,
+            operation_id.as_deref(),
+            "analyze_all_chapters",
+            |bytes| {
+                decoder.push(bytes, |sample| samples.push(sample));
+                let decoded = base + (first_sample + samples.len()) as f64 / rate;
+                while let Some(index) =
+                    pending.next_if(|&index| chapters[index].end.is_some_and(|end| end <= decoded))
+                {
+                    finish(index, &mut samples, &mut first_sample);
+                }
+                Ok(())
+            },
+        )
+        .await?;
+        // Open-ended chapters, and any that end after the audio does.
+        for index in pending {
+            finish(index, &mut samples, &mut first_sample);
+        }
+    }
+
+    let results: Vec<ChapterAnalysis> = results
+        .into_iter()
+        .map(|result| result.expect("every chapter is analyzed"))
+        .collect();
+    if let Some(fingerprint) = &fingerprint {
+        for (chapter, result) in chapters
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| !result.cached)
+        {
+            stored.retain(|range| range.start != chapter.start || range.end != chapter.end);
+            stored.push(cache::RangeAnalysis {
+                start: chapter.start,
+                end: chapter.end,
+                tempo: result.tempo.clone(),
+                key: result.key.clone(),
+            });
+        }
+        if let Err(e) = cache::store_analysis(&handle, fingerprint, &stored) {
+            warn!("Failed to cache chapter analysis: {}", e);
+        }
+    }
+    info!("Analyzed tempo and key of {} chapters", results.len());
+    Ok(results)
+}
+
+/// Reads the format and first audio stream of `file_path`. With
+/// `accurate_duration` the duration comes from decoding the whole file, as
+/// `get_accurate_duration` does, instead of from the headers.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn get_media_info(
+    handle: AppHandle,
+    file_path: String,
+    accurate_duration: Option<bool>,
+    operation_id: Option<String>,
+) -> Result<probe::MediaInfo, AppError> {
+    let mut info = read_media_info(&handle, &file_path).await?;
+    if accurate_duration.unwrap_or(false) {
+        let source = util::validate_media_source(&file_path)?;
+        info.duration_seconds = decode_duration(
+            &handle,
+            &source,
+            Some(info.duration_seconds),
+            operation_id.as_deref(),
+        )
+        .await?;
+    }
+    Ok(info)
+}
+
+/// Measures the true length of `file_path` by decoding all of its audio,
+/// for files whose headers get it wrong, such as VBR MP3s without a Xing
+/// header, where ffprobe estimates from the bit rate. This takes as long as
+/// decoding the file, so it emits `extract-progress` events and can be
+/// stopped with `cancel_operation` when given an `operation_id`.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn get_accurate_duration(
+    handle: AppHandle,
+    file_path: String,
+    operation_id: Option<String>,
+) -> Result<f64, AppError> {
+    util::validate_media_path(&file_path)?;
+    let estimate = ffmpeg::probe_duration(&handle, &file_path).await;
+    decode_duration(&handle, &file_path, estimate, operation_id.as_deref()).await
+}
+
+/// Decodes the first audio stream of `file_path` into the null muxer and
+/// returns how long it was. `estimate`, usually what ffprobe reported, only
+/// drives the progress percentage.
+async fn decode_duration(
+    handle: &AppHandle,
+    file_path: &str,
+    estimate: Option<f64>,
+    operation_id: Option<&str>,
+) -> Result<f64, AppError> {
+    info!("Decoding {} to measure its duration", file_path);
+    let mut args: Vec<String> = Vec::new();
+    if util::is_remote(file_path) {
+        args.extend(ffmpeg::NETWORK_TIMEOUT_ARGS.map(str::to_string));
+    }
+    args.extend([
+        "-i".to_string(),
+        file_path.to_string(),
+        "-map".to_string(),
+        "0:a:0".to_string(),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ]);
+    let options = RunOptions {
+        duration: estimate,
+        operation_id,
+        kind: "get_accurate_duration",
+        ..Default::default()
+    };
+    let stderr = ffmpeg::run_ffmpeg_with_progress(handle, &args, options).await?;
+    let duration = ffmpeg::parse_decoded_duration(&stderr).ok_or_else(|| {
+        AppError::ParseFailed(format!(
+            "FFmpeg did not report how much of {} it decoded",
+            file_path
+        ))
+    })?;
+    match estimate {
+        Some(estimate) if (estimate - duration).abs() > 0.5 => {
+            warn!(
+                "{} decodes to {:.3}s, but its headers say {:.3}s",
+                file_path, duration, estimate
+            )
+        }
+        _ => info!("{} decodes to {:.3}s", file_path, duration),
+    }
+    Ok(duration)
+}
+
+async fn read_media_info(
+    handle: &AppHandle,
+    file_path: &str,
+) -> Result<probe::MediaInfo, AppError> {
+    info!("Reading media info for: {}", file_path);
+    let file_path = &util::validate_media_source(file_path)?;
+
+    let mut args = Vec::new();
+    if util::is_remote(file_path) {
+        args.extend(ffmpeg::NETWORK_TIMEOUT_ARGS);
+    }
+    args.extend(["-show_format", "-show_streams", file_path]);
+    let parsed = ffmpeg::run_ffprobe(handle, &args).await?;
+    let info = probe::parse_media_info(&parsed)?;
+    info!(
+        "{}: {:.2}s, {} Hz, {} channel(s)",
+        info.codec_name, info.duration_seconds, info.sample_rate, info.channels
+    );
+    Ok(info)
+}
+
+async fn read_audio_streams(
+    handle: &AppHandle,
+    file_path: &str,
+) -> Result<Vec<probe::AudioStream>, AppError> {
+    let parsed = ffmpeg::run_ffprobe(
+        handle,
+        &["-show_streams", "-select_streams", "a", file_path],
+    )
+    .await?;
+    Ok(probe::parse_audio_streams(&parsed))
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AudioStreamList {
+    streams: Vec<probe::AudioStream>,
+    /// Whether the streams don't all share one sample rate, so they can't
+    /// be joined or switched between without resampling.
+    mixed_sample_rates: bool,
+}
+
+/// Lists the audio streams of `file_path` so the user can pick which one to
+/// loop, e.g. in files with separate stems.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn list_audio_streams(
+    handle: AppHandle,
+    file_path: String,
+) -> Result<AudioStreamList, AppError> {
+    info!("Listing audio streams of {}", file_path);
+    util::validate_media_path(&file_path)?;
+
+    let streams = read_audio_streams(&handle, &file_path).await?;
+    let rates: Vec<u32> = streams.iter().map(|stream| stream.sample_rate).collect();
+    let mixed_sample_rates = concat::mixed_sample_rates(&rates);
+    if mixed_sample_rates {
+        warn!(
+            "Audio streams of {} have different sample rates: {:?}",
+            file_path, rates
+        );
+    }
+    info!("Found {} audio streams", streams.len());
+    Ok(AudioStreamList {
+        streams,
+        mixed_sample_rates,
+    })
+}
+
+/// Lists the subtitle streams of `file_path`, such as lyrics embedded in a
+/// concert video, with their languages. Files without any give an empty
+/// list.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn list_subtitle_tracks(
+    handle: AppHandle,
+    file_path: String,
+) -> Result<Vec<probe::SubtitleStream>, AppError> {
+    info!("Listing subtitle streams of {}", file_path);
+    util::validate_media_path(&file_path)?;
+
+    let parsed = ffmpeg::run_ffprobe(
+        &handle,
+        &["-show_streams", "-select_streams", "s", &file_path],
+    )
+    .await?;
+    let streams = probe::parse_subtitle_streams(&parsed);
+    info!("Found {} subtitle streams", streams.len());
+    Ok(streams)
+}
+
+/// Writes subtitle stream `stream_index` of `file_path` to `output_path` as
+/// SRT or WebVTT, keeping only the cues that start within `range` when one
+/// is given. Cue times stay as in the whole file so they follow playback.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn extract_subtitles(
+    handle: AppHandle,
+    file_path: String,
+    stream_index: u32,
+    format: subtitles::SubtitleFormat,
+    range: Option<(f64, f64)>,
+    output_path: String,
+) -> Result<String, AppError> {
+    info!(
+        "Extracting subtitle stream {} of {} (range {:?}) to {}",
+        stream_index, file_path, range, output_path
+    );
+    util::validate_media_path(&file_path)?;
+    if let Some((start, end)) = range {
+        util::validate_range(start, Some(end))?;
+    }
+
+    let parsed = ffmpeg::run_ffprobe(
+        &handle,
+        &["-show_streams", "-select_streams", "s", &file_path],
+    )
+    .await?;
+    let Some(stream) = probe::parse_subtitle_streams(&parsed)
+        .into_iter()
+        .find(|stream| stream.index == stream_index)
+    else {
+        return Err(AppError::InvalidInput(format!(
+            "Stream {} is not a subtitle stream of {}",
+            stream_index, file_path
+        )));
+    };
+    if !stream.text_based {
+        return Err(AppError::InvalidInput(format!(
+            "Stream {} holds {} picture subtitles, which can't be converted to text",
+            stream_index, stream.codec
+        )));
+    }
+
+    let args = subtitles::extract_args(&file_path, stream_index, range, format);
+    let text = ffmpeg::run_ffmpeg_raw(&handle, &args, None).await?;
+    util::write_atomic(std::path::Path::new(&output_path), &text)?;
+    info!("Wrote {} bytes of subtitles to {}", text.len(), output_path);
+    Ok(output_path)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FileAnalysis {
+    info: probe::MediaInfo,
+    chapters: Vec<Chapter>,
+}
+
+/// Reads the media info and chapters of `file_path` in one round trip, running
+/// both ffprobe passes concurrently.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn load_file(
+    handle: AppHandle,
+    file_path: String,
+    preferred_language: Option<String>,
+) -> Result<FileAnalysis, AppError> {
+    let (info, chapters) = tokio::join!(
+        read_media_info(&handle, &file_path),
+        read_chapters(&handle, &file_path, preferred_language.as_deref())
+    );
+    Ok(FileAnalysis {
+        info: info?,
+        chapters: chapters?,
+    })
+}
+
+#[tauri::command]
+#[instrument(err)]
+fn load_markers(file_path: String) -> Result<markers::Sidecar, AppError> {
+    let sidecar = markers::load(&file_path)?;
+    info!(
+        "Loaded {} markers and {} loop regions for {}",
+        sidecar.chapters.len(),
+        sidecar.loop_regions.len(),
+        file_path
+    );
+    Ok(sidecar)
+}
+
+/// Saves `markers` and `loop_regions` next to `file_path`. Without
+/// `loop_regions`, the regions already saved are kept.
+#[tauri::command]
+#[instrument(skip(markers, loop_regions), fields(markers = markers.len()), err)]
+fn save_markers(
+    file_path: String,
+    markers: Vec<Chapter>,
+    loop_regions: Option<Vec<markers::LoopRegion>>,
+) -> Result<(), AppError> {
+    let loop_regions = match loop_regions {
+        Some(loop_regions) => loop_regions,
+        None => markers::load(&file_path)?.loop_regions,
+    };
+    let sidecar = markers::Sidecar {
+        chapters: markers,
+        loop_regions,
+    };
+    let path = markers::save(&file_path, &sidecar)?;
+    info!(
+        "Saved {} markers and {} loop regions to {}",
+        sidecar.chapters.len(),
+        sidecar.loop_regions.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Hashes `file_path` with `hashing::fast_hash` off the async runtime.
+async fn fast_hash_blocking(file_path: &str) -> Result<String, AppError> {
+    let path = file_path.to_string();
+    tauri::async_runtime::spawn_blocking(move || hashing::fast_hash(&path))
+        .await
+        .map_err(|e| AppError::Io(format!("Hashing {} failed: {}", file_path, e)))?
+}
+
+/// Writes the whole practice setup for `file_path`, its chapters, loop
+/// regions and `analysis` results, to a `.glp` project bundle at
+/// `output_path`, adding the extension if it is missing. Returns the path
+/// written.
+#[tauri::command]
+#[instrument(skip(chapters, loop_regions), fields(chapters = chapters.len()), err)]
+async fn export_project(
+    file_path: String,
+    chapters: Vec<Chapter>,
+    loop_regions: Option<Vec<markers::LoopRegion>>,
+    analysis: Option<project::ProjectAnalysis>,
+    output_path: String,
+) -> Result<String, AppError> {
+    info!("Exporting the project for {} to {}", file_path, output_path);
+    util::validate_media_path(&file_path)?;
+    let hash = match fast_hash_blocking(&file_path).await {
+        Ok(hash) => Some(hash),
+        Err(e) => {
+            warn!(
+                "Exporting the project without a hash of {}: {}",
+                file_path, e
+            );
+            None
+        }
+    };
+
+    let bundle = project::Project {
+        version: project::FORMAT_VERSION,
+        source: project::ProjectSource {
+            path: file_path,
+            hash,
+        },
+        chapters,
+        loop_regions: loop_regions.unwrap_or_default(),
+        analysis: analysis.unwrap_or_default(),
+    };
+    let json = project::write(&bundle)?;
+    let mut output = std::path::PathBuf::from(&output_path);
+    let has_extension = output
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(project::EXTENSION));
+    if !has_extension {
+        let mut path = output.into_os_string();
+        path.push(format!(".{}", project::EXTENSION));
+        output = path.into();
+    }
+    util::write_atomic(&output, json.as_bytes())?;
+    info!(
+        "Saved {} chapters and {} loop regions to {}",
+        bundle.chapters.len(),
+        bundle.loop_regions.len(),
+        output.display()
+    );
+    Ok(output.to_string_lossy().into_owned())
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ImportedProject {
+    project: project::Project,
+    /// The media file the project names doesn't exist any more, so the UI
+    /// should ask where it went.
+    source_missing: bool,
+    /// The media file exists but its hash differs from the one saved, so
+    /// it was replaced or re-encoded and the markers may be off.
+    source_changed: bool,
+}
+
+/// Reads a project bundle written by `export_project`. A bundle whose media
+/// file is gone is still returned, flagged so the UI can have the user
+/// locate it.
+#[tauri::command]
+#[instrument(err)]
+async fn import_project(project_path: String) -> Result<ImportedProject, AppError> {
+    info!("Importing project: {}", project_path);
+    let contents = util::read_to_string(&project_path)?;
+    let project = project::parse(&contents)?;
+
+    let source = &project.source;
+    let source_missing = !std::path::Path::new(&source.path).is_file();
+    let source_changed = match (&source.hash, source_missing) {
+        (Some(saved), false) => match fast_hash_blocking(&source.path).await {
+            Ok(hash) => hash != *saved,
+            Err(e) => {
+                warn!("Could not check {} against the project: {}", source.path, e);
+                false
+            }
+        },
+        _ => false,
+    };
+    if source_missing {
+        warn!("The project's media file {} is missing", source.path);
+    } else if source_changed {
+        warn!("{} changed since the project was exported", source.path);
+    }
+    info!(
+        "Found {} chapters and {} loop regions",
+        project.chapters.len(),
+        project.loop_regions.len()
+    );
+    Ok(ImportedProject {
+        project,
+        source_missing,
+        source_changed,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Reconciliation {
+    /// `hashing::fast_hash` of the file now, to save along with the
+    /// reconciled markers.
+    hash: String,
+    /// The file differs from the one the markers were saved for, or there
+    /// was no saved hash to tell.
+    changed: bool,
+    /// `None` when the file is unchanged.
+    diff: Option<reconcile::MarkerDiff>,
+}
+
+/// Checks whether `file_path` changed since its markers were saved, e.g.
+/// because it was downloaded or exported again, and if so how the markers
+/// fit the new file. The markers are those of the project bundle at
+/// `project_path`, checked against the hash saved in it, or else those of
+/// the sidecar next to the file, checked against `saved_hash`. Without a
+/// saved hash there is no telling, so the file is taken to have changed.
+///
+/// A changed file has its chapters read again, in `preferred_language`, to
+/// list the ones added and removed, and its length probed to list the
+/// markers that now run past the end. Nothing is saved; the UI lets the
+/// user decide.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn reconcile_file(
+    handle: AppHandle,
+    file_path: String,
+    project_path: Option<String>,
+    saved_hash: Option<String>,
+    preferred_language: Option<String>,
+) -> Result<Reconciliation, AppError> {
+    info!(
+        "Reconciling the markers of {} (project {:?})",
+        file_path, project_path
+    );
+    util::validate_media_path(&file_path)?;
+    let (saved_hash, chapters, loop_regions) = match &project_path {
+        Some(project_path) => {
+            let contents = util::read_to_string(project_path)?;
+            let project = project::parse(&contents)?;
+            (project.source.hash, project.chapters, project.loop_regions)
+        }
+        None => {
+            let sidecar = markers::load(&file_path)?;
+            (saved_hash, sidecar.chapters, sidecar.loop_regions)
+        }
+    };
+
+    let hash = fast_hash_blocking(&file_path).await?;
+    let changed = saved_hash.as_ref() != Some(&hash);
+    if !changed {
+        info!("{} is unchanged", file_path);
+        return Ok(Reconciliation {
+            hash,
+            changed,
+            diff: None,
+        });
+    }
+
+    let (current, duration) = tokio::join!(
+        read_chapters(&handle, &file_path, preferred_language.as_deref()),
+        ffmpeg::probe_duration(&handle, &file_path)
+    );
+    let diff = reconcile::diff(&chapters, &loop_regions, &current?, duration);
+    if diff.is_empty() {
+        info!("{} changed, but its markers still fit", file_path);
+    } else {
+        warn!(
+            "{} changed: {} chapters added, {} removed, {} chapters and {} loop regions past the end",
+            file_path,
+            diff.added.len(),
+            diff.removed.len(),
+            diff.out_of_range_chapters.len(),
+            diff.out_of_range_regions.len()
+        );
+    }
+    Ok(Reconciliation {
+        hash,
+        changed,
+        diff: Some(diff),
+    })
+}
+
+#[tauri::command]
+#[instrument(err)]
+fn parse_cue_sheet(cue_path: String) -> Result<Vec<Chapter>, AppError> {
+    info!("Parsing CUE sheet: {}", cue_path);
+
+    // CUE sheets are frequently written in a legacy code page, so decode
+    // lossily instead of rejecting the whole file over one accented title.
+    let bytes = util::read_file(&cue_path)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let chapters = cue::parse(&text)?;
+    // Times restart in every FILE, so the tracks of a sheet naming several
+    // files can't be put in one order.
+    let chapters = if cue::file_count(&text) > 1 {
+        chapters
+    } else {
+        chapters::normalize(&chapters, None)
+    };
+
+    info!("Found {} tracks", chapters.len());
+    Ok(chapters)
+}
+
+/// Imports an Audacity or Reaper label track export as chapters.
+#[tauri::command]
+#[instrument(err)]
+fn parse_label_track(label_path: String) -> Result<Vec<Chapter>, AppError> {
+    info!("Parsing label track: {}", label_path);
+    let bytes = util::read_file(&label_path)?;
+    let chapters = chapters::normalize(&labels::parse(&String::from_utf8_lossy(&bytes))?, None);
+
+    info!("Found {} labels", chapters.len());
+    Ok(chapters)
+}
+
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()), err)]
+fn export_cue_sheet(
+    chapters: Vec<Chapter>,
+    file_path: String,
+    output_path: String,
+) -> Result<String, AppError> {
+    info!(
+        "Exporting {} chapters to CUE sheet: {}",
+        chapters.len(),
+        output_path
+    );
+
+    // Reference the source by name when the sheet sits next to it, which is
+    // what players expect, and fall back to the full path otherwise.
+    let source = std::path::Path::new(&file_path);
+    let same_dir = source.parent() == std::path::Path::new(&output_path).parent();
+    let file_name = match source.file_name().and_then(|n| n.to_str()) {
+        Some(name) if same_dir => name.to_string(),
+        _ => file_path.clone(),
+    };
+
+    let sheet = cue::write(&chapters, &file_name)?;
+    util::write_atomic(std::path::Path::new(&output_path), sheet.as_bytes())?;
+    Ok(output_path)
+}
+
+/// Writes `chapters` as a versioned JSON chapter file with millisecond
+/// times, for exchanging chapters with other players and tools. The file
+/// name of `file_path`, if given, is recorded as the chapters' source.
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()), err)]
+fn export_chapters_json(
+    chapters: Vec<Chapter>,
+    file_path: Option<String>,
+    output_path: String,
+) -> Result<String, AppError> {
+    info!(
+        "Exporting {} chapters to JSON: {}",
+        chapters.len(),
+        output_path
+    );
+    let source = file_path
+        .as_deref()
+        .and_then(|path| std::path::Path::new(path).file_name())
+        .and_then(|name| name.to_str());
+    let json = chapter_json::write(&chapters, source)?;
+    util::write_atomic(std::path::Path::new(&output_path), json.as_bytes())?;
+    Ok(output_path)
+}
+
+/// Reads a JSON chapter file written by `export_chapters_json`. Files from
+/// a newer, unsupported format version are rejected.
+#[tauri::command]
+#[instrument(err)]
+fn import_chapters_json(json_path: String) -> Result<Vec<Chapter>, AppError> {
+    info!("Importing JSON chapters: {}", json_path);
+    let contents = util::read_to_string(&json_path)?;
+    let chapters = chapters::normalize(&chapter_json::parse(&contents)?, None);
+
+    info!("Found {} chapters", chapters.len());
+    Ok(chapters)
+}
+
+/// Reads a plain `.chapters.txt` file with one `HH:MM:SS Title` line per
+/// chapter, as written by some podcast tools.
+#[tauri::command]
+#[instrument(err)]
+fn parse_simple_chapters(chapters_path: String) -> Result<Vec<Chapter>, AppError> {
+    info!("Parsing simple chapter file: {}", chapters_path);
+    let contents = util::read_to_string(&chapters_path)?;
+    let chapters = chapters::normalize(&chapter_txt::parse(&contents)?, None);
+
+    info!("Found {} chapters", chapters.len());
+    Ok(chapters)
+}
+
+/// Writes `chapters` as a plain `HH:MM:SS Title` chapter file. Only the
+/// whole-second starts are kept; each chapter ends where the next begins.
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()), err)]
+fn export_simple_chapters(chapters: Vec<Chapter>, output_path: String) -> Result<String, AppError> {
+    info!(
+        "Exporting {} chapters to simple chapter file: {}",
+        chapters.len(),
+        output_path
+    );
+    let file = chapter_txt::write(&chapters)?;
+    util::write_atomic(std::path::Path::new(&output_path), file.as_bytes())?;
+    Ok(output_path)
+}
+
+/// Turns pasted video description text, one `[H:]M:SS title` per line,
+/// into chapters.
+#[tauri::command]
+#[instrument(skip(text), err)]
+fn parse_timestamp_text(text: String) -> Result<Vec<Chapter>, AppError> {
+    let chapters = chapters::normalize(&timestamps::parse(&text)?, None);
+    info!(
+        "Found {} timestamps in {} lines",
+        chapters.len(),
+        text.lines().count()
+    );
+    Ok(chapters)
+}
+
+/// Writes `chapters` as a WebVTT chapter track for web players. The last
+/// chapter runs to `file_duration` unless it has an end of its own.
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()), err)]
+fn export_webvtt(
+    chapters: Vec<Chapter>,
+    output_path: String,
+    file_duration: Option<f64>,
+) -> Result<String, AppError> {
+    info!(
+        "Exporting {} chapters to WebVTT: {}",
+        chapters.len(),
+        output_path
+    );
+    let track = webvtt::write(&chapters, file_duration)?;
+    util::write_atomic(std::path::Path::new(&output_path), track.as_bytes())?;
+    Ok(output_path)
+}
+
+/// Writes `chapters` as a standalone ffmetadata file, the format ffmpeg
+/// reads with `-f ffmetadata`, for use in other ffmpeg pipelines. The last
+/// chapter runs to `file_duration` unless it has an end of its own.
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()), err)]
+fn export_ffmetadata(
+    chapters: Vec<Chapter>,
+    output_path: String,
+    file_duration: Option<f64>,
+) -> Result<String, AppError> {
+    info!(
+        "Exporting {} chapters to ffmetadata: {}",
+        chapters.len(),
+        output_path
+    );
+    markers::validate(&chapters)?;
+    let metadata = ffmetadata::build(&chapters, file_duration)?;
+    util::write_atomic(std::path::Path::new(&output_path), metadata.as_bytes())?;
+    Ok(output_path)
+}
+
+/// Embeds `chapters` in the metadata of `file_path`, replacing any chapters
+/// it already has. The file is rewritten next to the original and only
+/// swapped in once ffmpeg succeeds.
+#[tauri::command]
+#[instrument(skip(handle, chapters), fields(chapters = chapters.len()), err)]
+async fn write_chapters_to_file(
+    handle: AppHandle,
+    file_path: String,
+    chapters: Vec<Chapter>,
+) -> Result<(), AppError> {
+    info!("Writing {} chapters into {}", chapters.len(), file_path);
+    util::validate_media_path(&file_path)?;
+    ffmetadata::check_chapter_support(&file_path)?;
+    markers::validate(&chapters)?;
+
+    let duration = ffmpeg::probe_duration(&handle, &file_path).await;
+    let metadata = ffmetadata::build(&chapters, duration)?;
+
+    let metadata_path = std::env::temp_dir().join(format!(
+        "guitar-looper-{}.ffmeta",
+        cache::FileFingerprint::of(&file_path)?.key()
+    ));
+    std::fs::write(&metadata_path, metadata).map_err(|e| {
+        AppError::Io(format!(
+            "Failed to write {}: {}",
+            metadata_path.display(),
+            e
+        ))
+    })?;
+
+    let args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        file_path.clone(),
+        "-f".to_string(),
+        "ffmetadata".to_string(),
+        "-i".to_string(),
+        metadata_path.to_string_lossy().to_string(),
+        // Data streams (timecodes, old chapter tracks) often can't be copied
+        // into the new file, so drop them.
+        "-map".to_string(),
+        "0".to_string(),
+        "-map".to_string(),
+        "-0:d?".to_string(),
+        // Take only the chapters from the metadata file so the original
+        // title/artist tags survive.
+        "-map_metadata".to_string(),
+        "0".to_string(),
+        "-map_chapters".to_string(),
+        "1".to_string(),
+        "-codec".to_string(),
+        "copy".to_string(),
+    ];
+    let result = rewrite_in_place(&handle, &file_path, "chapters", args, duration).await;
+    let _ = std::fs::remove_file(&metadata_path);
+    result?;
+    info!("Wrote chapters into {}", file_path);
+    Ok(())
+}
+
+/// Runs ffmpeg with `args` writing to a temp file next to `file_path`, then
+/// swaps it in for the original, which is left untouched if ffmpeg fails.
+/// `purpose` goes into the temp file's name.
+async fn rewrite_in_place(
+    handle: &AppHandle,
+    file_path: &str,
+    purpose: &str,
+    mut args: Vec<String>,
+    duration: Option<f64>,
+) -> Result<(), AppError> {
+    let source = std::path::Path::new(file_path);
+    let stem = source
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+    let extension = source
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    // Keep the extension so ffmpeg picks the same muxer for the temp file.
+    let temp_output = source.with_file_name(format!("{}.{}-tmp.{}", stem, purpose, extension));
+    args.push(temp_output.to_string_lossy().to_string());
+    let options = RunOptions {
+        duration,
+        ..Default::default()
+    };
+    if let Err(e) = ffmpeg::run_ffmpeg_with_progress(handle, &args, options).await {
+        let _ = std::fs::remove_file(&temp_output);
+        return Err(e);
+    }
+
+    std::fs::rename(&temp_output, source).map_err(|e| {
+        let _ = std::fs::remove_file(&temp_output);
+        AppError::Io(format!("Failed to replace {}: {}", file_path, e))
+    })
+}
+
+/// Tags `file_path` with the gain that brings it from `integrated_lufs` to
+/// the reference loudness of `convention`, as measured by
+/// `analyze_loudness`, so players can level it at playback time. The audio
+/// is stream copied, not re-encoded. `peak_db` is the true peak and only
+/// written for ReplayGain.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn write_replaygain_tags(
+    handle: AppHandle,
+    file_path: String,
+    integrated_lufs: f64,
+    peak_db: Option<f64>,
+    convention: replaygain::GainTagConvention,
+) -> Result<(), AppError> {
+    info!(
+        "Writing {:?} gain for {} LUFS into {}",
+        convention, integrated_lufs, file_path
+    );
+    util::validate_media_path(&file_path)?;
+    let extension = replaygain::check_tag_support(&file_path)?;
+    if !integrated_lufs.is_finite() || integrated_lufs <= loudness::SILENCE_FLOOR_DB {
+        return Err(AppError::InvalidInput(format!(
+            "Can't compute a gain for a loudness of {} LUFS",
+            integrated_lufs
+        )));
+    }
+
+    let mut args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        file_path.clone(),
+        "-map".to_string(),
+        "0".to_string(),
+        "-map".to_string(),
+        "-0:d?".to_string(),
+        "-map_metadata".to_string(),
+        "0".to_string(),
+        "-codec".to_string(),
+        "copy".to_string(),
+    ];
+    // Ogg keeps its comments on the stream, other containers on the file.
+    let target = if matches!(extension.as_str(), "ogg" | "opus") {
+        "-metadata:s:a:0"
+    } else {
+        "-metadata"
+    };
+    for (key, value) in replaygain::tags(convention, integrated_lufs, peak_db) {
+        args.push(target.to_string());
+        args.push(format!("{}={}", key, value));
+    }
+    // The MP4 muxer drops tags it has no atom for unless told otherwise.
+    if matches!(extension.as_str(), "mp4" | "m4a" | "m4b") {
+        args.push("-movflags".to_string());
+        args.push("use_metadata_tags".to_string());
+    }
+    let duration = ffmpeg::probe_duration(&handle, &file_path).await;
+    rewrite_in_place(&handle, &file_path, "replaygain", args, duration).await?;
+
+    info!("Wrote gain tags into {}", file_path);
+    Ok(())
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn detect_silence(
+    handle: AppHandle,
+    file_path: String,
+    threshold_db: Option<f64>,
+    min_duration: Option<f64>,
+) -> Result<Vec<(f64, f64)>, AppError> {
+    let threshold_db = threshold_db.unwrap_or(silence::DEFAULT_THRESHOLD_DB);
+    let min_duration = min_duration.unwrap_or(silence::DEFAULT_MIN_DURATION);
+    info!(
+        "Detecting silence in {} (threshold {} dB, min {}s)",
+        file_path, threshold_db, min_duration
+    );
+    util::validate_media_path(&file_path)?;
+
+    if min_duration <= 0.0 {
+        return Err(AppError::InvalidInput(
+            "min_duration must be greater than zero".to_string(),
+        ));
+    }
+
+    let args = vec![
+        "-i".to_string(),
+        file_path.clone(),
+        "-vn".to_string(),
+        "-af".to_string(),
+        silence::filter(threshold_db, min_duration),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ];
+    let stderr = ffmpeg::run_ffmpeg_stderr(&handle, &args).await?;
+    let silences = silence::parse(&stderr, ffmpeg::parse_input_duration(&stderr));
+
+    info!("Found {} silent ranges", silences.len());
+    Ok(silences)
+}
+
+/// Suggests a loop within `start..end`: the longest passage there without a
+/// silence as `detect_silence` finds them. With `bpm` the suggestion is
+/// shrunk to the beat grid, `subdivision` lines per beat (1 by default)
+/// counted from `beat_offset`, or from where the passage starts if that
+/// isn't known, so it never reaches into the silence.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(handle), err)]
+async fn suggest_loop_region(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    bpm: Option<f64>,
+    beat_offset: Option<f64>,
+    subdivision: Option<u32>,
+    threshold_db: Option<f64>,
+    min_duration: Option<f64>,
+) -> Result<(f64, f64), AppError> {
+    let threshold_db = threshold_db.unwrap_or(silence::DEFAULT_THRESHOLD_DB);
+    let min_duration = min_duration.unwrap_or(silence::DEFAULT_MIN_DURATION);
+    info!(
+        "Suggesting a loop in {} from {} - {}",
+        file_path, start, end
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+    if min_duration <= 0.0 {
+        return Err(AppError::InvalidInput(
+            "min_duration must be greater than zero".to_string(),
+        ));
+    }
+
+    let args = vec![
+        "-ss".to_string(),
+        start.to_string(),
+        "-to".to_string(),
+        end.to_string(),
+        "-i".to_string(),
+        file_path.clone(),
+        "-vn".to_string(),
+        "-af".to_string(),
+        silence::filter(threshold_db, min_duration),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ];
+    let stderr = ffmpeg::run_ffmpeg_stderr(&handle, &args).await?;
+    // Seeking restarts the timestamps at zero, and the logged duration is the
+    // whole file's, so a silence running to the end is closed at the range's.
+    let silences: Vec<(f64, f64)> = silence::parse(&stderr, Some(end - start))
+        .into_iter()
+        .map(|(s, e)| (start + s, start + e))
+        .collect();
+    let sound = silence::longest_sound(&silences, start, end).ok_or_else(|| {
+        AppError::InvalidRange(format!("{} - {} is silent throughout", start, end))
+    })?;
+
+    let suggestion = match bpm {
+        Some(bpm) => beatgrid::quantize_within(
+            sound.0,
+            sound.1,
+            bpm,
+            beat_offset.unwrap_or(sound.0),
+            subdivision.unwrap_or(1),
+        )?,
+        None => sound,
+    };
+    info!(
+        "Suggested loop {} - {} ({} silent ranges in the range)",
+        suggestion.0,
+        suggestion.1,
+        silences.len()
+    );
+    Ok(suggestion)
+}
+
+/// Suggests chapters for a long recording without markers, such as a jam,
+/// by splitting it where it turns from loud to quiet or back for at least
+/// `min_section` seconds. Unlike `detect_silence` this finds quieter
+/// passages too, not just gaps.
+///
+/// `sensitivity` from 0 to 1 (0.5 by default) lowers how much louder the
+/// loud parts must be than the quiet ones for the file to be split at all.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn auto_segment(
+    handle: AppHandle,
+    file_path: String,
+    min_section: Option<f64>,
+    sensitivity: Option<f64>,
+) -> Result<Vec<Chapter>, AppError> {
+    let min_section = min_section.unwrap_or(sections::DEFAULT_MIN_SECTION);
+    let sensitivity = sensitivity.unwrap_or(sections::DEFAULT_SENSITIVITY);
+    info!(
+        "Segmenting {} (min section {}s, sensitivity {})",
+        file_path, min_section, sensitivity
+    );
+    util::validate_media_path(&file_path)?;
+    if !min_section.is_finite() || min_section <= 0.0 {
+        return Err(AppError::InvalidInput(format!(
+            "min_section must be greater than zero, got {}",
+            min_section
+        )));
+    }
+    if !(0.0..=1.0).contains(&sensitivity) {
+        return Err(AppError::InvalidInput(format!(
+            "Sensitivity must be between 0 and 1, got {}",
+            sensitivity
+        )));
+    }
+
+    let args = ffmpeg::mono_pcm_args(
+        &file_path,
+        None,
+        None,
+        Some(sections::ANALYSIS_SAMPLE_RATE),
+        PcmFormat::F32le,
+    );
+    let mut decoder = pcm::PcmDecoder::new(PcmFormat::F32le);
+    let mut meter = sections::LevelMeter::new(sections::ANALYSIS_SAMPLE_RATE);
+    ffmpeg::stream_ffmpeg_raw(&handle, &args, DEFAULT_WAVEFORM_BUFFER_BYTES, |bytes| {
+        decoder.push(bytes, |sample| meter.add(sample));
+        Ok(())
+    })
+    .await?;
+    let levels = meter.finish();
+    if levels.is_empty() {
+        return Err(ffmpeg::no_audio_samples(&file_path));
+    }
+
+    let found = sections::find_sections(&levels, min_section, sensitivity);
+    let split = found.len() > 1;
+    let chapters: Vec<Chapter> = found
+        .iter()
+        .enumerate()
+        .map(|(index, section)| Chapter {
+            id: format!("chapter-{}", index),
+            title: match (split, section.loud) {
+                (false, _) => format!("Section {}", index + 1),
+                (true, true) => format!("Section {} (loud)", index + 1),
+                (true, false) => format!("Section {} (quiet)", index + 1),
+            },
+            start: section.start,
+            end: Some(section.end),
+            source: Some(ChapterSource::AutoSegment),
+            duration: Some(section.end - section.start),
+            ..Default::default()
+        })
+        .collect();
+    info!("Found {} sections", chapters.len());
+    Ok(chapters)
+}
+
+/// Suggests where to split `start`..`end` of `file_path` into subsections,
+/// as times in the file where the timbre changes for good: where a medley
+/// moves to the next song, or a take from rhythm to lead. Unlike
+/// `auto_segment`, which only follows loudness, this compares how the audio
+/// sounds; see [`novelty::find_boundaries`].
+///
+/// `kernel_secs` (8 by default) is how much audio on each side of a point
+/// is compared, so changes that don't last about that long are ignored and
+/// no two splits are closer than twice it. `threshold` from 0 to 1 (0.25 by
+/// default) is the novelty a split needs.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn detect_subsections(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    kernel_secs: Option<f64>,
+    threshold: Option<f64>,
+) -> Result<Vec<novelty::Boundary>, AppError> {
+    let kernel_secs = kernel_secs.unwrap_or(novelty::DEFAULT_KERNEL_SECS);
+    let threshold = threshold.unwrap_or(novelty::DEFAULT_THRESHOLD);
+    info!(
+        "Detecting subsections of {} from {}s to {}s (kernel {}s, threshold {})",
+        file_path, start, end, kernel_secs, threshold
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+    if !(novelty::MIN_KERNEL_SECS..=novelty::MAX_KERNEL_SECS).contains(&kernel_secs) {
+        return Err(AppError::InvalidInput(format!(
+            "kernel_secs must be between {} and {}, got {}",
+            novelty::MIN_KERNEL_SECS,
+            novelty::MAX_KERNEL_SECS,
+            kernel_secs
+        )));
+    }
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(AppError::InvalidInput(format!(
+            "Threshold must be between 0 and 1, got {}",
+            threshold
+        )));
+    }
+
+    let args = ffmpeg::mono_pcm_args(
+        &file_path,
+        None,
+        Some((start, end)),
+        Some(novelty::ANALYSIS_SAMPLE_RATE),
+        PcmFormat::F32le,
+    );
+    let mut decoder = pcm::PcmDecoder::new(PcmFormat::F32le);
+    let mut meter = novelty::TimbreMeter::new();
+    ffmpeg::stream_ffmpeg_raw(&handle, &args, DEFAULT_WAVEFORM_BUFFER_BYTES, |bytes| {
+        decoder.push(bytes, |sample| meter.add(sample));
+        Ok(())
+    })
+    .await?;
+    let blocks = meter.finish();
+    if blocks.is_empty() {
+        return Err(ffmpeg::no_audio_samples(&file_path));
+    }
+
+    let boundaries: Vec<novelty::Boundary> =
+        novelty::find_boundaries(&blocks, kernel_secs, threshold)
+            .into_iter()
+            .map(|boundary| novelty::Boundary {
+                time: start + boundary.time,
+                ..boundary
+            })
+            .collect();
+    info!("Found {} subsection boundaries", boundaries.len());
+    Ok(boundaries)
+}
+
+/// Payload of the `resolution-warning` event `transcode_audio` emits before
+/// an export that would lower the source's sample rate or bit depth.
+#[derive(Debug, Clone, Serialize)]
+struct ResolutionWarning {
+    operation_id: String,
+    input_path: String,
+    #[serde(flatten)]
+    loss: transcode::ResolutionLoss,
+}
+
+/// Transcodes `input_path` to `output_path`. With `dry_run`, returns the
+/// ffmpeg command line instead of running it. Emits a `resolution-warning`
+/// event first when the output would be of lower resolution than the
+/// source; the transcode goes ahead regardless.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn transcode_audio(
+    handle: AppHandle,
+    input_path: String,
+    output_path: String,
+    options: transcode::TranscodeOptions,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<String, AppError> {
+    info!(
+        "Transcoding {} to {} ({:?})",
+        input_path, output_path, options
+    );
+    util::validate_media_path(&input_path)?;
+    let args = transcode::transcode_args(&input_path, &output_path, &options)?;
+    if dry_run.unwrap_or(false) {
+        return Ok(ffmpeg::job_command_line(&handle, &args));
+    }
+    encoders::require(&handle, transcode::codec_for_format(&options.format)?).await?;
+
+    // Only informational, so a source that can't be probed is left to fail
+    // in ffmpeg with its own error.
+    if let Ok(info) = read_media_info(&handle, &input_path).await {
+        if let Some(loss) = transcode::resolution_loss(info.sample_rate, info.bit_depth, &options)?
+        {
+            warn!("{}: {}", input_path, loss.message);
+            let warning = ResolutionWarning {
+                operation_id: operation_id.clone(),
+                input_path: input_path.clone(),
+                loss,
+            };
+            let _ = handle.emit("resolution-warning", warning);
+        }
+    }
+
+    let run_options = RunOptions {
+        duration: ffmpeg::probe_duration(&handle, &input_path).await,
+        operation_id: Some(&operation_id),
+        kind: "transcode_audio",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, run_options).await?;
+
+    info!("Wrote transcoded audio to {}", output_path);
+    Ok(output_path)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ExportSizeEstimate {
+    estimated_bytes: u64,
+    method: transcode::SizeEstimateMethod,
+}
+
+/// Estimates how big exporting `start..end` of `file_path`, or `start` to
+/// the end of the file, as `format` at `bitrate` kbit/s would be, so users
+/// short on disk space can check first. `format` is `mp3`, `ogg`, `wav` or
+/// `copy` for a stream copy, which is estimated from the source's average
+/// bit rate.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn estimate_export_size(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: Option<f64>,
+    format: String,
+    bitrate: Option<u32>,
+) -> Result<ExportSizeEstimate, AppError> {
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, end)?;
+    let info = read_media_info(&handle, &file_path).await?;
+    let duration = end
+        .unwrap_or(info.duration_seconds)
+        .min(info.duration_seconds)
+        - start;
+    if duration <= 0.0 {
+        return Err(AppError::InvalidRange(format!(
+            "{} starts at or after the end of {} ({}s)",
+            start, file_path, info.duration_seconds
+        )));
+    }
+
+    let (estimated_bytes, method) = if format.eq_ignore_ascii_case("copy") {
+        let bit_rate = match info.bit_rate {
+            Some(bit_rate) => bit_rate,
+            None => {
+                let size = std::fs::metadata(&file_path)
+                    .map_err(|e| AppError::Io(format!("Failed to read {}: {}", file_path, e)))?
+                    .len();
+                (size as f64 * 8.0 / info.duration_seconds.max(f64::EPSILON)) as u64
+            }
+        };
+        (
+            (duration * bit_rate as f64 / 8.0).round() as u64,
+            transcode::SizeEstimateMethod::SourceBitrate,
+        )
+    } else {
+        transcode::estimate_size(&format, bitrate, duration, info.sample_rate, info.channels)?
+    };
+    info!(
+        "Estimated {} bytes for {}s of {} as {} ({:?})",
+        estimated_bytes, duration, file_path, format, method
+    );
+    Ok(ExportSizeEstimate {
+        estimated_bytes,
+        method,
+    })
+}
+
+/// Shifts the pitch of `start..end` by `semitones` while keeping its tempo.
+///
+/// Uses the `asetrate`/`aresample`/`atempo` chain rather than `rubberband`
+/// so it works on any ffmpeg build; formants shift along with the pitch, so
+/// expect some coloration on large intervals. `remove_dc` takes out any DC
+/// offset.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn pitch_shift_chapter(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    semitones: i32,
+    output_path: String,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+    remove_dc: Option<bool>,
+) -> Result<String, AppError> {
+    info!(
+        "Pitch shifting {} ({} - {}) by {} semitones to {}",
+        file_path, start, end, semitones, output_path
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+
+    let info = read_media_info(&handle, &file_path).await?;
+    let mut filter = effects::pitch_shift_filter(semitones, info.sample_rate)?;
+    if remove_dc.unwrap_or(false) {
+        filter = format!("{},{}", dc::REMOVE_FILTER, filter);
+    }
+
+    let args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        start.to_string(),
+        "-to".to_string(),
+        end.to_string(),
+        "-i".to_string(),
+        file_path,
+        "-vn".to_string(),
+        "-af".to_string(),
+        filter,
+        output_path.clone(),
+    ];
+    let options = RunOptions {
+        duration: Some(end - start),
+        operation_id: Some(&operation_id),
+        kind: "pitch_shift_chapter",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote pitch shifted audio to {}", output_path);
+    Ok(output_path)
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn change_tempo(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    rate: f64,
+    output_path: String,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+    remove_dc: Option<bool>,
+) -> Result<String, AppError> {
+    info!(
+        "Changing tempo of {} ({} - {}) by {}x to {}",
+        file_path, start, end, rate, output_path
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+    if !(effects::MIN_TEMPO_RATE..=effects::MAX_TEMPO_RATE).contains(&rate) {
+        return Err(AppError::InvalidInput(format!(
+            "Playback rate {} is out of range ({}..={})",
+            rate,
+            effects::MIN_TEMPO_RATE,
+            effects::MAX_TEMPO_RATE
+        )));
+    }
+
+    let mut filter = effects::atempo_chain(rate);
+    if remove_dc.unwrap_or(false) {
+        filter = format!("{},{}", dc::REMOVE_FILTER, filter);
+    }
+    let args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        start.to_string(),
+        "-to".to_string(),
+        end.to_string(),
+        "-i".to_string(),
+        file_path,
+        "-vn".to_string(),
+        "-af".to_string(),
+        filter,
+        output_path.clone(),
+    ];
+    let options = RunOptions {
+        // The output is longer or shorter than the source range by the rate.
+        duration: Some((end - start) / rate),
+        operation_id: Some(&operation_id),
+        kind: "change_tempo",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote tempo adjusted audio to {}", output_path);
+    Ok(output_path)
+}
+
+/// One piece of audio to be joined with others.
+#[derive(Debug, Clone, Deserialize)]
+struct ConcatSource {
+    file_path: String,
+    range: Option<(f64, f64)>,
+    /// Audio stream to use, by file index; the first when unset.
+    stream_index: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ConcatCompatibility {
+    /// Whether every source shares one sample rate and can be joined as is.
+    concatenable: bool,
+    /// Sample rate of each source, in order.
+    sample_rates: Vec<u32>,
+    /// Rate to resample all sources to before joining them, the highest of
+    /// theirs, or `None` if they can already be joined.
+    target_sample_rate: Option<u32>,
+}
+
+/// Checks whether `sources` can be joined directly or need resampling to a
+/// common sample rate first, which otherwise comes out as pitch and speed
+/// jumps at the joins.
+#[tauri::command]
+#[instrument(skip(handle, sources), fields(sources = sources.len()), err)]
+async fn can_concat(
+    handle: AppHandle,
+    sources: Vec<ConcatSource>,
+) -> Result<ConcatCompatibility, AppError> {
+    info!("Checking whether {} sources can be joined", sources.len());
+    if sources.is_empty() {
+        return Err(AppError::InvalidInput("No sources to check".to_string()));
+    }
+
+    let mut sample_rates = Vec::with_capacity(sources.len());
+    for source in &sources {
+        util::validate_media_path(&source.file_path)?;
+        if let Some((start, end)) = source.range {
+            util::validate_range(start, Some(end))?;
+        }
+        let streams = read_audio_streams(&handle, &source.file_path).await?;
+        let stream = match source.stream_index {
+            Some(index) => streams.iter().find(|stream| stream.index == index),
+            None => streams.first(),
+        }
+        .ok_or_else(|| match source.stream_index {
+            Some(index) => AppError::InvalidInput(format!(
+                "Stream {} is not an audio stream of {}",
+                index, source.file_path
+            )),
+            None => AppError::InvalidInput(format!("{} has no audio stream", source.file_path)),
+        })?;
+        if stream.sample_rate == 0 {
+            return Err(AppError::ParseFailed(format!(
+                "Could not read the sample rate of {}",
+                source.file_path
+            )));
+        }
+        sample_rates.push(stream.sample_rate);
+    }
+
+    let concatenable = !concat::mixed_sample_rates(&sample_rates);
+    let compatibility = ConcatCompatibility {
+        concatenable,
+        target_sample_rate: if concatenable {
+            None
+        } else {
+            concat::target_sample_rate(&sample_rates)
+        },
+        sample_rates,
+    };
+    info!("Concat compatibility: {:?}", compatibility);
+    Ok(compatibility)
+}
+
+/// Joins the `ranges` of `file_path`, in order, into one file at
+/// `output_path`, e.g. to loop several solos back to back.
+///
+/// Each range is first decoded to a PCM segment and the joined result is
+/// encoded once, because stream copying cuts that don't fall on codec frames
+/// produces clicks and gaps. `remove_dc` takes out any DC offset.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn concat_chapters(
+    handle: AppHandle,
+    file_path: String,
+    ranges: Vec<(f64, f64)>,
+    output_path: String,
+    remove_dc: Option<bool>,
+) -> Result<String, AppError> {
+    info!(
+        "Concatenating {} ranges of {} into {}",
+        ranges.len(),
+        file_path,
+        output_path
+    );
+    util::validate_media_path(&file_path)?;
+    if ranges.is_empty() {
+        return Err(AppError::InvalidInput(
+            "No ranges to concatenate".to_string(),
+        ));
+    }
+    for (start, end) in &ranges {
+        util::validate_range(*start, Some(*end))?;
+    }
+
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let temp_dir = std::env::temp_dir().join(format!(
+        "guitar-looper-concat-{}-{}",
+        std::process::id(),
+        nanos
+    ));
+    std::fs::create_dir_all(&temp_dir)
+        .map_err(|e| AppError::Io(format!("Failed to create {}: {}", temp_dir.display(), e)))?;
+
+    let result = async {
+        let mut segments = Vec::with_capacity(ranges.len());
+        for (index, (start, end)) in ranges.iter().enumerate() {
+            let segment = temp_dir.join(format!("segment-{:03}.wav", index));
+            let args = concat::segment_args(&file_path, *start, *end, &segment.to_string_lossy());
+            ffmpeg::run_ffmpeg_stderr(&handle, &args).await?;
+            segments.push(segment);
+        }
+
+        let list_path = temp_dir.join("segments.txt");
+        std::fs::write(&list_path, concat::concat_list(&segments))
+            .map_err(|e| AppError::Io(format!("Failed to write {}: {}", list_path.display(), e)))?;
+
+        let mut args = vec![
+            "-y".to_string(),
+            "-v".to_string(),
+            "error".to_string(),
+            "-f".to_string(),
+            "concat".to_string(),
+            "-safe".to_string(),
+            "0".to_string(),
+            "-i".to_string(),
+            list_path.to_string_lossy().to_string(),
+        ];
+        if remove_dc.unwrap_or(false) {
+            args.push("-af".to_string());
+            args.push(dc::REMOVE_FILTER.to_string());
+        }
+        args.push(output_path.clone());
+        let options = RunOptions {
+            duration: Some(ranges.iter().map(|(start, end)| end - start).sum()),
+            output_path: Some(&output_path),
+            ..Default::default()
+        };
+        ffmpeg::run_ffmpeg_with_progress(&handle, &args, options)
+            .await
+            .map(|_| ())
+    }
+    .await;
+    // Clean up the segments whether or not the concatenation worked.
+    if let Err(e) = std::fs::remove_dir_all(&temp_dir) {
+        warn!("Failed to remove {}: {}", temp_dir.display(), e);
+    }
+    result?;
+
+    info!("Wrote concatenated audio to {}", output_path);
+    Ok(output_path)
+}
+
+/// Renders a spectrogram of `start..end` to a PNG at `output_path`.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn generate_spectrogram(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    output_path: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    color_scheme: Option<String>,
+) -> Result<String, AppError> {
+    info!(
+        "Rendering spectrogram of {} ({} - {}) to {}",
+        file_path, start, end, output_path
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+    let is_png = std::path::Path::new(&output_path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+    if !is_png {
+        return Err(AppError::InvalidInput(format!(
+            "Spectrograms are saved as PNG, but {} has a different extension",
+            output_path
+        )));
+    }
+
+    let filter = spectrogram::filter(
+        width.unwrap_or(spectrogram::DEFAULT_WIDTH),
+        height.unwrap_or(spectrogram::DEFAULT_HEIGHT),
+        color_scheme
+            .as_deref()
+            .unwrap_or(spectrogram::DEFAULT_COLOR_SCHEME),
+    )?;
+    let args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        start.to_string(),
+        "-to".to_string(),
+        end.to_string(),
+        "-i".to_string(),
+        file_path,
+        "-lavfi".to_string(),
+        filter,
+        "-frames:v".to_string(),
+        "1".to_string(),
+        output_path.clone(),
+    ];
+    let options = RunOptions {
+        duration: Some(end - start),
+        output_path: Some(&output_path),
+        ..Default::default()
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote spectrogram to {}", output_path);
+    Ok(output_path)
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn get_cover_art(handle: AppHandle, file_path: String) -> Result<Option<String>, AppError> {
+    info!("Reading cover art from: {}", file_path);
+    util::validate_media_path(&file_path)?;
+
+    let parsed = ffmpeg::run_ffprobe(&handle, &["-show_streams", &file_path]).await?;
+    let Some(stream_index) = probe::attached_picture_index(&parsed) else {
+        info!("No embedded cover art found");
+        return Ok(None);
+    };
+
+    let args = vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        file_path,
+        "-map".to_string(),
+        format!("0:{}", stream_index),
+        "-c".to_string(),
+        "copy".to_string(),
+        "-frames:v".to_string(),
+        "1".to_string(),
+        "-f".to_string(),
+        "image2pipe".to_string(),
+        "-".to_string(),
+    ];
+    let bytes = ffmpeg::run_ffmpeg_raw(&handle, &args, Some(artwork::MAX_COVER_ART_BYTES)).await?;
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+
+    info!("Found {} bytes of cover art", bytes.len());
+    artwork::to_data_url(&bytes).map(Some)
+}
+
+/// Tells whether `file_path` is audio, video or both, so the UI can offer
+/// video-only features such as frame extraction.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn classify_media(
+    handle: AppHandle,
+    file_path: String,
+) -> Result<probe::MediaKind, AppError> {
+    util::validate_media_path(&file_path)?;
+    let parsed = ffmpeg::run_ffprobe(&handle, &["-show_streams", &file_path]).await?;
+    let kind = probe::classify(&parsed);
+    info!("{} is {:?}", file_path, kind);
+    Ok(kind)
+}
+
+/// Returns the video frame of `file_path` at `timestamp` as a JPEG `data:`
+/// URL, scaled down to at most `max_width` pixels wide (640 by default).
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn extract_frame(
+    handle: AppHandle,
+    file_path: String,
+    timestamp: f64,
+    max_width: Option<u32>,
+) -> Result<String, AppError> {
+    info!("Extracting frame at {}s from: {}", timestamp, file_path);
+    util::validate_media_path(&file_path)?;
+    util::validate_range(timestamp, None)?;
+    let max_width = max_width.unwrap_or(artwork::DEFAULT_FRAME_WIDTH);
+    if !(1..=artwork::MAX_FRAME_WIDTH).contains(&max_width) {
+        return Err(AppError::InvalidInput(format!(
+            "Frame width must be between 1 and {} pixels, got {}",
+            artwork::MAX_FRAME_WIDTH,
+            max_width
+        )));
+    }
+
+    let parsed = ffmpeg::run_ffprobe(&handle, &["-show_streams", &file_path]).await?;
+    let Some(stream_index) = probe::video_stream_index(&parsed) else {
+        return Err(AppError::InvalidInput(format!(
+            "{} has no video to take a frame from",
+            file_path
+        )));
+    };
+
+    let data_url = grab_frame(&handle, &file_path, stream_index, timestamp, max_width).await?;
+    info!("Extracted a {} byte frame", data_url.len());
+    Ok(data_url)
+}
+
+/// Takes the frame of video stream `stream_index` at `timestamp` as a JPEG
+/// `data:` URL at most `max_width` pixels wide.
+async fn grab_frame(
+    handle: &AppHandle,
+    file_path: &str,
+    stream_index: u64,
+    timestamp: f64,
+    max_width: u32,
+) -> Result<String, AppError> {
+    let args = vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        timestamp.to_string(),
+        "-i".to_string(),
+        file_path.to_string(),
+        "-map".to_string(),
+        format!("0:{}", stream_index),
+        "-frames:v".to_string(),
+        "1".to_string(),
+        // Never upscale, and keep the height even as the encoder needs.
+        "-vf".to_string(),
+        format!("scale='min(iw,{})':-2", max_width),
+        "-c:v".to_string(),
+        "mjpeg".to_string(),
+        "-q:v".to_string(),
+        "3".to_string(),
+        "-f".to_string(),
+        "image2pipe".to_string(),
+        "-".to_string(),
+    ];
+    let bytes = ffmpeg::run_ffmpeg_raw(handle, &args, Some(artwork::MAX_COVER_ART_BYTES)).await?;
+    if bytes.is_empty() {
+        return Err(AppError::InvalidRange(format!(
+            "There is no video frame at {}s",
+            timestamp
+        )));
+    }
+    artwork::to_data_url(&bytes)
+}
+
+/// How many frames `generate_timeline_thumbnails` grabs at once.
+const THUMBNAIL_CONCURRENCY: usize = 4;
+
+/// Takes `count` evenly spaced frames of `file_path`, `thumb_width` pixels
+/// wide, for a scrubber along the timeline.
+///
+/// With `sprite`, the frames come from a single decode through the `fps`
+/// and `tile` filters into one PNG in the app cache, which is reused while
+/// the file is unchanged. Otherwise every frame is a separate seek and the
+/// thumbnails come back as JPEG `data:` URLs. Files with fewer frames than
+/// `count` get one thumbnail per frame.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn generate_timeline_thumbnails(
+    handle: AppHandle,
+    file_path: String,
+    count: u32,
+    thumb_width: u32,
+    sprite: bool,
+) -> Result<artwork::TimelineThumbnails, AppError> {
+    info!(
+        "Generating {} timeline thumbnails for: {}",
+        count, file_path
+    );
+    util::validate_media_path(&file_path)?;
+    if !(1..=artwork::MAX_TIMELINE_THUMBNAILS).contains(&count) {
+        return Err(AppError::InvalidInput(format!(
+            "Thumbnail count must be between 1 and {}, got {}",
+            artwork::MAX_TIMELINE_THUMBNAILS,
+            count
+        )));
+    }
+    if !(1..=artwork::MAX_THUMBNAIL_WIDTH).contains(&thumb_width) {
+        return Err(AppError::InvalidInput(format!(
+            "Thumbnail width must be between 1 and {} pixels, got {}",
+            artwork::MAX_THUMBNAIL_WIDTH,
+            thumb_width
+        )));
+    }
+
+    let parsed =
+        ffmpeg::run_ffprobe(&handle, &["-show_format", "-show_streams", &file_path]).await?;
+    let Some(stream_index) = probe::video_stream_index(&parsed) else {
+        return Err(AppError::InvalidInput(format!(
+            "{} has no video to take thumbnails from",
+            file_path
+        )));
+    };
+    let duration = probe::format_duration(&parsed)
+        .filter(|&duration| duration > 0.0)
+        .ok_or_else(|| {
+            AppError::ParseFailed(format!("Could not determine the duration of {}", file_path))
+        })?;
+    let count = match probe::video_frame_count(&parsed, stream_index) {
+        Some(frames) => count.min(u32::try_from(frames).unwrap_or(u32::MAX)),
+        None => count,
+    };
+
+    if sprite {
+        let (columns, rows) = artwork::sprite_grid(count);
+        let key = cache::FileFingerprint::of(&file_path)?.key();
+        let output_path = cache::cache_dir(&handle, "thumbnails")?
+            .join(format!("{}-{}x{}.png", key, count, thumb_width))
+            .to_string_lossy()
+            .into_owned();
+        if !std::path::Path::new(&output_path).is_file() {
+            let args = vec![
+                "-y".to_string(),
+                "-v".to_string(),
+                "error".to_string(),
+                "-i".to_string(),
+                file_path.clone(),
+                "-map".to_string(),
+                format!("0:{}", stream_index),
+                "-an".to_string(),
+                "-vf".to_string(),
+                format!(
+                    "fps={}/{},scale={}:-2,tile={}x{}",
+                    count, duration, thumb_width, columns, rows
+                ),
+                "-frames:v".to_string(),
+                "1".to_string(),
+                "-update".to_string(),
+                "1".to_string(),
+                output_path.clone(),
+            ];
+            let options = RunOptions {
+                duration: Some(duration),
+                output_path: Some(&output_path),
+                ..Default::default()
+            };
+            ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+        }
+        info!(
+            "Wrote a {}x{} thumbnail sprite to {}",
+            columns, rows, output_path
+        );
+        return Ok(artwork::TimelineThumbnails::Sprite {
+            path: output_path,
+            count,
+            columns,
+            rows,
+        });
+    }
+
+    let semaphore = Arc::new(Semaphore::new(THUMBNAIL_CONCURRENCY));
+    let tasks: Vec<_> = artwork::thumbnail_times(duration, count)
+        .into_iter()
+        .map(|timestamp| {
+            let handle = handle.clone();
+            let file_path = file_path.clone();
+            let semaphore = semaphore.clone();
+            tauri::async_runtime::spawn(async move {
+                let _permit = semaphore.acquire().await.map_err(|e| {
+                    AppError::Io(format!("Thumbnail generation was aborted: {}", e))
+                })?;
+                grab_frame(&handle, &file_path, stream_index, timestamp, thumb_width).await
+            })
+        })
+        .collect();
+    let mut thumbnails = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        let thumbnail = task
+            .await
+            .map_err(|e| AppError::Io(format!("Thumbnail task failed: {}", e)))??;
+        thumbnails.push(thumbnail);
+    }
+    info!("Generated {} timeline thumbnails", thumbnails.len());
+    Ok(artwork::TimelineThumbnails::Frames { thumbnails })
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AnalysisProgress {
+    chapter_id: String,
+    completed: usize,
+    total: usize,
+}
+
+#[tauri::command]
+#[instrument(skip(handle, chapters), fields(chapters = chapters.len()), err)]
+async fn analyze_loudness(
+    handle: AppHandle,
+    file_path: String,
+    chapters: Vec<Chapter>,
+) -> Result<Vec<loudness::ChapterLoudness>, AppError> {
+    info!(
+        "Analyzing loudness of {} chapters in {}",
+        chapters.len(),
+        file_path
+    );
+    util::validate_media_path(&file_path)?;
+    for chapter in &chapters {
+        util::validate_range(chapter.start, chapter.end)?;
+    }
+
+    let mut results = Vec::with_capacity(chapters.len());
+    for (index, chapter) in chapters.iter().enumerate() {
+        let measurement = measure_loudness(&handle, &file_path, chapter.start, chapter.end)
+            .await?
+            .ok_or_else(|| {
+                AppError::ParseFailed(format!(
+                    "Could not read loudness summary for chapter '{}'",
+                    chapter.title
+                ))
+            })?;
+        results.push(loudness::ChapterLoudness {
+            chapter_id: chapter.id.clone(),
+            integrated_lufs: measurement.integrated_lufs,
+            peak_db: measurement.peak_db,
+        });
+
+        let progress = AnalysisProgress {
+            chapter_id: chapter.id.clone(),
+            completed: index + 1,
+            total: chapters.len(),
+        };
+        let _ = handle.emit("analysis-progress", progress);
+    }
+
+    info!("Analyzed loudness of {} chapters", results.len());
+    Ok(results)
+}
+
+/// Measures `start..end` of `file_path` with ebur128. `None` means ffmpeg
+/// ran but printed no summary.
+async fn measure_loudness(
+    handle: &AppHandle,
+    file_path: &str,
+    start: f64,
+    end: Option<f64>,
+) -> Result<Option<loudness::Measurement>, AppError> {
+    let mut args = vec!["-ss".to_string(), start.to_string()];
+    if let Some(end) = end {
+        args.push("-to".to_string());
+        args.push(end.to_string());
+    }
+    args.extend([
+        "-i".to_string(),
+        file_path.to_string(),
+        "-vn".to_string(),
+        "-af".to_string(),
+        loudness::EBUR128_FILTER.to_string(),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ]);
+    let stderr = ffmpeg::run_ffmpeg_stderr(handle, &args).await?;
+    Ok(loudness::parse_ebur128_summary(&stderr))
+}
+
+/// Default silence between the two halves of an A/B comparison, in seconds.
+const DEFAULT_AB_GAP_SECS: f64 = 1.0;
+
+/// Longest silence allowed between the two halves of an A/B comparison.
+const MAX_AB_GAP_SECS: f64 = 10.0;
+
+#[derive(Debug, Clone, Serialize)]
+struct AbComparison {
+    output_path: String,
+    reference_lufs: f64,
+    take_lufs: f64,
+    /// Gain applied to the take in dB.
+    gain_db: f64,
+    /// Whether the take was resampled to the reference's sample rate.
+    resampled: bool,
+}
+
+/// Writes `reference_range` of `reference_path`, a `gap_secs` pause (one
+/// second by default), then `take_range` of `take_path` to `output_path`, so
+/// the original and the user's take can be looped back to back.
+///
+/// The take is gained to the reference's integrated loudness so the louder
+/// one doesn't sound better just for being louder, unless that would push
+/// it over the true-peak ceiling. A take at a different sample rate is
+/// resampled to the reference's. Emits `analysis-progress` while measuring
+/// and `extract-progress` while rendering.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn create_ab_comparison(
+    handle: AppHandle,
+    reference_path: String,
+    reference_range: (f64, f64),
+    take_path: String,
+    take_range: (f64, f64),
+    output_path: String,
+    gap_secs: Option<f64>,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<AbComparison, AppError> {
+    info!(
+        "Comparing {} {:?} with {} {:?} into {}",
+        reference_path, reference_range, take_path, take_range, output_path
+    );
+    let gap = gap_secs.unwrap_or(DEFAULT_AB_GAP_SECS);
+    if !(0.0..=MAX_AB_GAP_SECS).contains(&gap) {
+        return Err(AppError::InvalidInput(format!(
+            "The gap must be between 0 and {} seconds, got {}",
+            MAX_AB_GAP_SECS, gap
+        )));
+    }
+    let inputs = [
+        ("reference", &reference_path, reference_range),
+        ("take", &take_path, take_range),
+    ];
+    let mut rates = Vec::with_capacity(inputs.len());
+    let mut measurements = Vec::with_capacity(inputs.len());
+    for (index, (name, path, (start, end))) in inputs.iter().enumerate() {
+        util::validate_media_path(path)?;
+        util::validate_range(*start, Some(*end))?;
+        let info = read_media_info(&handle, path).await?;
+        if info.sample_rate == 0 {
+            return Err(AppError::ParseFailed(format!(
+                "Could not read the sample rate of the {} ({}), so it can't be joined",
+                name, path
+            )));
+        }
+        rates.push(info.sample_rate);
+        let measurement = measure_loudness(&handle, path, *start, Some(*end))
+            .await?
+            .ok_or_else(|| {
+                AppError::ParseFailed(format!("Could not measure the loudness of the {}", name))
+            })?;
+        measurements.push(measurement);
+        let progress = AnalysisProgress {
+            chapter_id: name.to_string(),
+            completed: index + 1,
+            total: inputs.len(),
+        };
+        let _ = handle.emit("analysis-progress", progress);
+    }
+    let (reference, take) = (measurements[0], measurements[1]);
+    let resampled = rates[0] != rates[1];
+    if resampled {
+        info!(
+            "Resampling the take from {} Hz to {} Hz",
+            rates[1], rates[0]
+        );
+    }
+    let gain_db = loudness::matching_gain(&reference, &take);
+    info!(
+        "Reference is {} LUFS, take is {} LUFS, applying {} dB",
+        reference.integrated_lufs, take.integrated_lufs, gain_db
+    );
+
+    let mut args = vec!["-y".to_string(), "-v".to_string(), "error".to_string()];
+    for (_, path, (start, end)) in &inputs {
+        args.extend([
+            "-ss".to_string(),
+            start.to_string(),
+            "-to".to_string(),
+            end.to_string(),
+            "-i".to_string(),
+            path.to_string(),
+        ]);
+    }
+    args.extend([
+        "-filter_complex".to_string(),
+        concat::ab_comparison_graph(rates[0], gain_db, gap),
+        "-map".to_string(),
+        "[ab]".to_string(),
+        output_path.clone(),
+    ]);
+    let options = RunOptions {
+        duration: Some(
+            (reference_range.1 - reference_range.0) + gap + (take_range.1 - take_range.0),
+        ),
+        operation_id: Some(&operation_id),
+        kind: "create_ab_comparison",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote A/B comparison to {}", output_path);
+    Ok(AbComparison {
+        output_path,
+        reference_lufs: reference.integrated_lufs,
+        take_lufs: take.integrated_lufs,
+        gain_db,
+        resampled,
+    })
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn normalize_audio(
+    handle: AppHandle,
+    input_path: String,
+    output_path: String,
+    target_lufs: Option<f64>,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<String, AppError> {
+    let target_lufs = target_lufs.unwrap_or(loudness::DEFAULT_TARGET_LUFS);
+    info!(
+        "Normalizing {} to {} LUFS into {}",
+        input_path, target_lufs, output_path
+    );
+    util::validate_media_path(&input_path)?;
+    if !(-70.0..=-5.0).contains(&target_lufs) {
+        return Err(AppError::InvalidInput(format!(
+            "Target loudness {} LUFS is out of range (-70..=-5)",
+            target_lufs
+        )));
+    }
+
+    let info = read_media_info(&handle, &input_path).await?;
+
+    let measure_args = vec![
+        "-i".to_string(),
+        input_path.clone(),
+        "-vn".to_string(),
+        "-af".to_string(),
+        loudness::first_pass_filter(target_lufs),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ];
+    let stderr = ffmpeg::run_ffmpeg_stderr(&handle, &measure_args).await?;
+    let measured = loudness::parse_loudnorm_json(&stderr)?;
+    info!(
+        "Measured {} LUFS, {} dBTP",
+        measured.input_i, measured.input_tp
+    );
+
+    let mut args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        input_path,
+        "-vn".to_string(),
+        "-af".to_string(),
+        loudness::second_pass_filter(target_lufs, &measured),
+    ];
+    // loudnorm works at 192 kHz internally, so resample back to the source rate.
+    if info.sample_rate > 0 {
+        args.push("-ar".to_string());
+        args.push(info.sample_rate.to_string());
+    }
+    args.push(output_path.clone());
+    let options = RunOptions {
+        duration: Some(info.duration_seconds),
+        operation_id: Some(&operation_id),
+        kind: "normalize_audio",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote normalized audio to {}", output_path);
+    Ok(output_path)
+}
+
+/// Deletes part files in `output_dir` left behind by exports that were
+/// interrupted, for example by a crash, and returns their paths. Part files
+/// written to in the last few minutes may belong to a running export and
+/// are kept.
+#[tauri::command]
+#[instrument(err)]
+fn cleanup_temp_files(output_dir: String) -> Result<Vec<String>, AppError> {
+    let removed: Vec<String> = util::remove_orphaned_parts(std::path::Path::new(&output_dir))?
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    info!(
+        "Removed {} orphaned part files from {}",
+        removed.len(),
+        output_dir
+    );
+    Ok(removed)
+}
+
+/// Stops the ffmpeg process started for `operation_id`. The command that
+/// started it fails with a `Cancelled` error and removes its partial output.
+#[tauri::command]
+#[instrument(skip(operations), err)]
+fn cancel_operation(
+    operations: State<OperationManager>,
+    operation_id: String,
+) -> Result<(), AppError> {
+    info!("Cancelling operation {}", operation_id);
+    if !operations.cancel(&operation_id) {
+        return Err(AppError::InvalidInput(format!(
+            "No running operation with id {}",
+            operation_id
+        )));
+    }
+    Ok(())
+}
+
+/// Lists the operations started with an operation id that are still
+/// running, oldest first, with how far along each one is.
+#[tauri::command]
+#[instrument(skip(operations))]
+fn list_operations(operations: State<OperationManager>) -> Vec<operations::OperationInfo> {
+    operations.list_active()
+}
+
+/// Checks an edited chapter list for overlaps, empty chapters, chapters
+/// past the end of the file and entries out of order, for the UI to offer
+/// fixes.
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()))]
+fn validate_chapters(chapters: Vec<Chapter>, file_duration: f64) -> Vec<chapters::ChapterWarning> {
+    let warnings = chapters::validate(&chapters, file_duration);
+    info!(
+        "Found {} problems in {} chapters",
+        warnings.len(),
+        chapters.len()
+    );
+    warnings
+}
+
+/// Cleans up a chapter list the way every importer does: sorted, without
+/// negative times or repeats, with every end filled in, the last from
+/// `file_duration` if given, and renumbered. See [`chapters::normalize`].
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()))]
+fn normalize_chapters(chapters: Vec<Chapter>, file_duration: Option<f64>) -> Vec<Chapter> {
+    let normalized = chapters::normalize(&chapters, file_duration);
+    info!(
+        "Normalized {} chapters into {}",
+        chapters.len(),
+        normalized.len()
+    );
+    normalized
+}
+
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()), err)]
+fn merge_chapters(
+    chapters: Vec<Chapter>,
+    ids: Vec<String>,
+    title: String,
+) -> Result<Vec<Chapter>, AppError> {
+    info!("Merging chapters {:?} into '{}'", ids, title);
+    chapters::merge(&chapters, &ids, &title)
+}
+
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()), err)]
+fn split_chapter(
+    chapters: Vec<Chapter>,
+    chapter_id: String,
+    split_at: f64,
+) -> Result<Vec<Chapter>, AppError> {
+    info!("Splitting chapter {} at {}", chapter_id, split_at);
+    chapters::split(&chapters, &chapter_id, split_at)
+}
+
+/// Saves the loop `start..end` as a new chapter, titled `title` or after the
+/// chapters around it, and returns the renumbered chapter list.
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()), err)]
+fn create_chapter_from_range(
+    chapters: Vec<Chapter>,
+    start: f64,
+    end: f64,
+    title: Option<String>,
+) -> Result<Vec<Chapter>, AppError> {
+    info!("Creating a chapter from {} - {}", start, end);
+    chapters::insert_range(&chapters, start, end, title.as_deref())
+}
+
+/// Moves all `chapters` by `offset` seconds to correct sync, dropping those
+/// pushed entirely outside `0..=file_duration`.
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()), err)]
+fn shift_chapters(
+    chapters: Vec<Chapter>,
+    offset: f64,
+    file_duration: f64,
+) -> Result<chapters::ShiftedChapters, AppError> {
+    let shifted = chapters::shift(&chapters, offset, file_duration)?;
+    info!(
+        "Shifted {} chapters by {}s, dropped {}",
+        shifted.chapters.len(),
+        offset,
+        shifted.dropped
+    );
+    Ok(shifted)
+}
+
+/// Stretches `chapters` over the gaps between them, and to the ends of the
+/// file, so looping through them in order plays no dead air.
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()))]
+fn fill_chapter_gaps(
+    chapters: Vec<Chapter>,
+    mode: chapters::GapFillMode,
+    file_duration: Option<f64>,
+) -> Vec<Chapter> {
+    info!(
+        "Filling gaps between {} chapters ({:?})",
+        chapters.len(),
+        mode
+    );
+    chapters::fill_gaps(&chapters, mode, file_duration)
+}
+
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()), err)]
+fn chapters_total_duration(
+    chapters: Vec<Chapter>,
+    file_duration: f64,
+    dedupe_overlaps: bool,
+) -> Result<f64, AppError> {
+    let total = chapters::total_duration(&chapters, file_duration, dedupe_overlaps)?;
+    info!("{} chapters add up to {:.2}s", chapters.len(), total);
+    Ok(total)
+}
+
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()), err)]
+fn group_chapters(
+    chapters: Vec<Chapter>,
+    separator: String,
+) -> Result<Vec<chapters::ChapterGroup>, AppError> {
+    let groups = chapters::group(&chapters, &separator)?;
+    info!(
+        "Grouped {} chapters into {} groups by '{}'",
+        chapters.len(),
+        groups.len(),
+        separator
+    );
+    Ok(groups)
+}
+
+/// Tidies imported chapter titles such as "01 - intro  " or "02_SOLO",
+/// stripping track numbers and underscores and collapsing whitespace by
+/// default. Cleaning already cleaned titles changes nothing.
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()))]
+fn clean_chapter_titles(
+    chapters: Vec<Chapter>,
+    options: Option<chapters::TitleCleanup>,
+) -> Vec<Chapter> {
+    let cleaned = chapters::clean_titles(&chapters, &options.unwrap_or_default());
+    let changed = chapters
+        .iter()
+        .zip(&cleaned)
+        .filter(|(before, after)| before.title != after.title)
+        .count();
+    info!("Cleaned {} of {} chapter titles", changed, chapters.len());
+    cleaned
+}
+
+/// Returns the chapters tagged with any or, with `mode` `all`, every one of
+/// `tags`.
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()))]
+fn filter_chapters_by_tag(
+    chapters: Vec<Chapter>,
+    tags: Vec<String>,
+    mode: chapters::TagMatch,
+) -> Vec<Chapter> {
+    let matching = chapters::filter_by_tags(&chapters, &tags, mode);
+    info!(
+        "Found {} of {} chapters tagged {:?} ({:?})",
+        matching.len(),
+        chapters.len(),
+        tags,
+        mode
+    );
+    matching
+}
+
+#[tauri::command]
+#[instrument(skip(chapters), fields(chapters = chapters.len()))]
+fn search_chapters(chapters: Vec<Chapter>, query: String) -> Vec<Chapter> {
+    let results = search::search(&chapters, &query);
+    info!(
+        "Found {} of {} chapters matching '{}'",
+        results.len(),
+        chapters.len(),
+        query
+    );
+    results
+}
+
+/// Trims leading and trailing silence from an extracted loop at
+/// `input_path`, keeping `padding` seconds of it at each end, and returns
+/// the length of the trimmed file at `output_path`.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn trim_silence(
+    handle: AppHandle,
+    input_path: String,
+    output_path: String,
+    threshold_db: Option<f64>,
+    padding: Option<f64>,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<f64, AppError> {
+    let threshold_db = threshold_db.unwrap_or(silence::DEFAULT_THRESHOLD_DB);
+    let padding = padding.unwrap_or(silence::DEFAULT_TRIM_PADDING);
+    info!(
+        "Trimming silence from {} (threshold {} dB, padding {}s) into {}",
+        input_path, threshold_db, padding, output_path
+    );
+    util::validate_media_path(&input_path)?;
+
+    let args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        input_path.clone(),
+        "-vn".to_string(),
+        "-af".to_string(),
+        silence::trim_filter(threshold_db, padding)?,
+        output_path.clone(),
+    ];
+    let options = RunOptions {
+        duration: ffmpeg::probe_duration(&handle, &input_path).await,
+        operation_id: Some(&operation_id),
+        kind: "trim_silence",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    let duration = ffmpeg::probe_duration(&handle, &output_path)
+        .await
+        .ok_or_else(|| {
+            AppError::ParseFailed(format!("Could not read the length of {}", output_path))
+        })?;
+    info!("Trimmed loop to {}s", duration);
+    Ok(duration)
+}
+
+/// Maximum number of chapters `export_all_chapters` encodes at once.
+const EXPORT_CONCURRENCY: usize = 2;
+
+#[derive(Debug, Clone, Serialize)]
+struct ExportProgress {
+    chapter_id: String,
+    /// File written for the chapter, or `None` if it was skipped.
+    output_path: Option<String>,
+    /// Why the chapter was skipped.
+    error: Option<String>,
+    completed: usize,
+    total: usize,
+}
+
+/// Directory an export without an explicit destination goes to:
+/// `output_dir` if given, otherwise the one in the settings.
+fn export_dir(handle: &AppHandle, output_dir: Option<String>) -> Result<String, AppError> {
+    output_dir
+        .or_else(|| settings::current(handle).export_dir)
+        .ok_or_else(|| {
+            AppError::InvalidInput(
+                "No output directory was given and no export directory is set".to_string(),
+            )
+        })
+}
+
+/// Names a file for `values` with the configured template, in `output_dir`
+/// or the export directory, numbered if the name is already taken.
+fn template_export_path(
+    handle: &AppHandle,
+    values: &export::TemplateValues,
+    output_dir: Option<String>,
+) -> Result<String, AppError> {
+    let dir = export_dir(handle, output_dir)?;
+    let template = settings::current(handle).export_file_template;
+    let name = export::render_template(&template, values);
+    Ok(export::unused_path(std::path::Path::new(&dir), &name)
+        .to_string_lossy()
+        .into_owned())
+}
+
+/// Returns where an export of `chapter` as `format` (the default export
+/// format if unset) would go, from the configured export directory, or
+/// `output_dir`, and file name template. `index` is the chapter's position
+/// for `{index}`, the first if unset. Names of existing files are numbered
+/// rather than reused.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn resolve_export_path(
+    handle: AppHandle,
+    chapter: Chapter,
+    format: Option<String>,
+    index: Option<usize>,
+    output_dir: Option<String>,
+) -> Result<String, AppError> {
+    let format = format.unwrap_or_else(|| settings::current(&handle).default_export_format);
+    if format.is_empty() || !format.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(AppError::InvalidInput(format!(
+            "'{}' is not a file extension",
+            format
+        )));
+    }
+    util::validate_range(chapter.start, chapter.end)?;
+    let ext = format.to_ascii_lowercase();
+    let values = export::TemplateValues {
+        title: &chapter.title,
+        index: index.unwrap_or(0),
+        start: chapter.start,
+        end: chapter.end,
+        ext: &ext,
+    };
+    let path = template_export_path(&handle, &values, output_dir)?;
+    info!("Resolved export path for '{}': {}", chapter.title, path);
+    Ok(path)
+}
+
+/// Writes every chapter of `file_path` to its own `format` file in
+/// `output_dir`, or the configured export directory, named with the
+/// configured file name template, and returns the written paths in start
+/// order. Names of existing files are numbered rather than overwritten.
+///
+/// Chapters without an end run to the start of the next one, or to the end
+/// of the file. Chapters with an invalid range are skipped and reported in
+/// their `export-progress` event rather than failing the export; any other
+/// failure is returned once the remaining chapters have finished. The
+/// export can be paused between chapters with `pause_batch`. `remove_dc`
+/// takes out any DC offset.
+#[tauri::command]
+#[instrument(skip(handle, chapters), fields(chapters = chapters.len()), err)]
+async fn export_all_chapters(
+    handle: AppHandle,
+    file_path: String,
+    chapters: Vec<Chapter>,
+    output_dir: Option<String>,
+    format: Option<String>,
+    remove_dc: Option<bool>,
+) -> Result<Vec<String>, AppError> {
+    let format = format.unwrap_or_else(|| settings::current(&handle).default_export_format);
+    let output_dir = export_dir(&handle, output_dir)?;
+    info!(
+        "Exporting {} chapters of {} as {} into {}",
+        chapters.len(),
+        file_path,
+        format,
+        output_dir
+    );
+    util::validate_media_path(&file_path)?;
+    let codec = transcode::codec_for_format(&format)?;
+    std::fs::create_dir_all(&output_dir)
+        .map_err(|e| AppError::Io(format!("Failed to create {}: {}", output_dir, e)))?;
+
+    let mut ordered = chapters;
+    ordered.sort_by(|a, b| a.start.total_cmp(&b.start));
+    let ext = format.to_ascii_lowercase();
+    let filter = remove_dc.unwrap_or(false).then_some(dc::REMOVE_FILTER);
+    let template = settings::current(&handle).export_file_template;
+    let paths = export::unused_paths(
+        std::path::Path::new(&output_dir),
+        ordered
+            .iter()
+            .enumerate()
+            .map(|(index, chapter)| {
+                let values = export::TemplateValues {
+                    title: &chapter.title,
+                    index,
+                    start: chapter.start,
+                    end: chapter
+                        .end
+                        .or_else(|| ordered.get(index + 1).map(|next| next.start)),
+                    ext: &ext,
+                };
+                export::render_template(&template, &values)
+            })
+            .collect(),
+    );
+
+    let total = ordered.len();
+    let semaphore = Arc::new(Semaphore::new(EXPORT_CONCURRENCY));
+    let completed = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<_> = ordered
+        .iter()
+        .zip(paths)
+        .enumerate()
+        .map(|(index, (chapter, output_path))| {
+            let start = chapter.start;
+            let end = chapter
+                .end
+                .or_else(|| ordered.get(index + 1).map(|next| next.start));
+            let output_path = output_path.to_string_lossy().into_owned();
+            let args = export::chapter_args(&file_path, start, end, filter, codec, &output_path);
+            let chapter_id = chapter.id.clone();
+            let handle = handle.clone();
+            let semaphore = semaphore.clone();
+            let completed = completed.clone();
+            tauri::async_runtime::spawn(async move {
+                let result = match util::validate_range(start, end) {
+                    Ok(()) => match semaphore.acquire().await {
+                        Ok(_permit) => {
+                            handle
+                                .state::<batch::BatchController>()
+                                .wait_if_paused()
+                                .await;
+                            let options = RunOptions {
+                                duration: end.map(|end| end - start),
+                                output_path: Some(&output_path),
+                                ..Default::default()
+                            };
+                            ffmpeg::run_ffmpeg_with_progress(&handle, &args, options)
+                                .await
+                                .map(|_| output_path)
+                        }
+                        Err(e) => Err(AppError::Io(format!("Export was aborted: {}", e))),
+                    },
+                    Err(e) => Err(e),
+                };
+                let progress = ExportProgress {
+                    chapter_id,
+                    output_path: result.as_ref().ok().cloned(),
+                    error: result.as_ref().err().map(|e| e.message().to_string()),
+                    completed: completed.fetch_add(1, Ordering::SeqCst) + 1,
+                    total,
+                };
+                let _ = handle.emit("export-progress", progress);
+                result
+            })
+        })
+        .collect();
+
+    let mut written = Vec::with_capacity(total);
+    let mut failure = None;
+    for task in tasks {
+        let result = task
+            .await
+            .map_err(|e| AppError::Io(format!("Export task failed: {}", e)))?;
+        match result {
+            Ok(output_path) => written.push(output_path),
+            Err(AppError::InvalidRange(message)) => warn!("Skipping chapter: {}", message),
+            Err(e) => {
+                failure.get_or_insert(e);
+            }
+        }
+    }
+    if let Some(e) = failure {
+        return Err(e);
+    }
+
+    info!("Exported {} of {} chapters", written.len(), total);
+    Ok(written)
+}
+
+/// Name of the segment list the segment muxer writes, removed once read.
+const SEGMENT_LIST_FILE: &str = ".segments.csv";
+
+/// Removes what a failed `segment_by_duration` run wrote: the segments in
+/// its list, the one it was writing when it stopped, and the list.
+fn remove_partial_segments(
+    output_dir: &std::path::Path,
+    list_path: &str,
+    stem: &str,
+    extension: &str,
+) {
+    let listed = std::fs::read_to_string(list_path)
+        .map(|list| export::parse_segment_list(&list))
+        .unwrap_or_default();
+    let unlisted = export::segment_file_name(stem, extension, listed.len());
+    for name in listed
+        .into_iter()
+        .map(|segment| segment.file_name)
+        .chain([unlisted])
+    {
+        let _ = std::fs::remove_file(output_dir.join(name));
+    }
+    let _ = std::fs::remove_file(list_path);
+}
+
+/// Splits `file_path` into `segment_seconds` long files in `output_dir`,
+/// named `<file name>-000.<extension>` and so on, for practicing a long
+/// recording without chapters. Returns the written paths in order.
+///
+/// The audio is stream copied into the source's container where possible,
+/// otherwise re-encoded. The last segment holds whatever is left over;
+/// segments shorter than `min_segment_seconds` are deleted, so a few
+/// seconds of trailing silence don't end up as a file of their own.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn segment_by_duration(
+    handle: AppHandle,
+    file_path: String,
+    segment_seconds: f64,
+    output_dir: String,
+    min_segment_seconds: Option<f64>,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<Vec<String>, AppError> {
+    info!(
+        "Splitting {} into {}s segments in {}",
+        file_path, segment_seconds, output_dir
+    );
+    util::validate_media_path(&file_path)?;
+    if !segment_seconds.is_finite() || segment_seconds < export::MIN_SEGMENT_SECS {
+        return Err(AppError::InvalidInput(format!(
+            "Segments must be at least {} second long, got {}",
+            export::MIN_SEGMENT_SECS,
+            segment_seconds
+        )));
+    }
+    let min_segment_seconds = min_segment_seconds.unwrap_or(0.0);
+    if !(0.0..=segment_seconds).contains(&min_segment_seconds) {
+        return Err(AppError::InvalidInput(format!(
+            "The shortest kept segment must be between 0 and {} seconds, got {}",
+            segment_seconds, min_segment_seconds
+        )));
+    }
+    let output_dir = std::path::Path::new(&output_dir);
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| AppError::Io(format!("Failed to create {}: {}", output_dir.display(), e)))?;
+
+    let source = std::path::Path::new(&file_path);
+    let stem = source
+        .file_stem()
+        .map_or("Segment".into(), |stem| stem.to_string_lossy());
+    let extension = source.extension().map_or("mka".to_string(), |extension| {
+        extension.to_string_lossy().to_ascii_lowercase()
+    });
+    let pattern = export::segment_pattern(output_dir, &stem, &extension);
+    let list_path = output_dir
+        .join(SEGMENT_LIST_FILE)
+        .to_string_lossy()
+        .into_owned();
+
+    let options = RunOptions {
+        duration: ffmpeg::probe_duration(&handle, &file_path).await,
+        operation_id: Some(&operation_id),
+        kind: "segment_by_duration",
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+        ..Default::default()
+    };
+    let copy_args = export::segment_args(&file_path, segment_seconds, &pattern, &list_path, true);
+    match ffmpeg::run_ffmpeg_with_progress(&handle, &copy_args, options).await {
+        Ok(_) => {}
+        Err(AppError::Cancelled(message)) => {
+            remove_partial_segments(output_dir, &list_path, &stem, &extension);
+            return Err(AppError::Cancelled(message));
+        }
+        Err(copy_err) => {
+            warn!("Stream copy failed, re-encoding instead: {}", copy_err);
+            // Start over rather than mix copied and re-encoded segments.
+            remove_partial_segments(output_dir, &list_path, &stem, &extension);
+            let encode_args =
+                export::segment_args(&file_path, segment_seconds, &pattern, &list_path, false);
+            if let Err(e) = ffmpeg::run_ffmpeg_with_progress(&handle, &encode_args, options).await {
+                remove_partial_segments(output_dir, &list_path, &stem, &extension);
+                return Err(e);
+            }
+        }
+    }
+
+    let list = std::fs::read_to_string(&list_path).map_err(|e| {
+        AppError::Io(format!(
+            "Failed to read the segment list {}: {}",
+            list_path, e
+        ))
+    })?;
+    if let Err(e) = std::fs::remove_file(&list_path) {
+        warn!("Failed to remove {}: {}", list_path, e);
+    }
+    let mut written = Vec::new();
+    for segment in export::parse_segment_list(&list) {
+        let path = output_dir.join(&segment.file_name);
+        if segment.end - segment.start < min_segment_seconds {
+            info!(
+                "Discarding {} ({}s long)",
+                path.display(),
+                segment.end - segment.start
+            );
+            std::fs::remove_file(&path)
+                .map_err(|e| AppError::Io(format!("Failed to remove {}: {}", path.display(), e)))?;
+            continue;
+        }
+        written.push(path.to_string_lossy().into_owned());
+    }
+
+    info!("Wrote {} segments", written.len());
+    Ok(written)
+}
+
+/// Reports runs of at least `min_run` consecutive full-scale samples in
+/// `file_path`, or in `range` of it, in any channel.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn detect_clipping(
+    handle: AppHandle,
+    file_path: String,
+    range: Option<(f64, f64)>,
+    min_run: Option<usize>,
+) -> Result<clipping::ClippingReport, AppError> {
+    let min_run = min_run.unwrap_or(clipping::DEFAULT_MIN_RUN);
+    info!(
+        "Detecting clipping in {} (range {:?}, runs of {}+ samples)",
+        file_path, range, min_run
+    );
+    util::validate_media_path(&file_path)?;
+    if let Some((start, end)) = range {
+        util::validate_range(start, Some(end))?;
+    }
+    if min_run == 0 {
+        return Err(AppError::InvalidInput(
+            "min_run must be at least 1".to_string(),
+        ));
+    }
+
+    // Decode every channel at the source rate; downmixing or resampling
+    // would smear the flat tops that give clipping away.
+    let info = read_media_info(&handle, &file_path).await?;
+    if info.sample_rate == 0 || info.channels == 0 {
+        return Err(AppError::NoAudioStream(format!(
+            "{} has no decodable audio stream",
+            file_path
+        )));
+    }
+    let samples = ffmpeg::decode_interleaved_pcm(&handle, &file_path, range, None).await?;
+
+    let report = clipping::detect(
+        &samples,
+        info.channels as usize,
+        info.sample_rate,
+        min_run,
+        range.map_or(0.0, |(start, _)| start),
+    );
+    info!(
+        "Found {} clipped samples in {} regions",
+        report.clipped_sample_count,
+        report.clipped_regions.len()
+    );
+    Ok(report)
+}
+
+/// Measures the DC offset of each channel of `file_path`, or of `range` of
+/// it, as some cheap interfaces record one. It wastes headroom and clicks
+/// where the audio starts or stops; exports take it out with `remove_dc`.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn detect_dc_offset(
+    handle: AppHandle,
+    file_path: String,
+    range: Option<(f64, f64)>,
+) -> Result<dc::DcOffsetReport, AppError> {
+    info!("Detecting DC offset in {} (range {:?})", file_path, range);
+    util::validate_media_path(&file_path)?;
+    if let Some((start, end)) = range {
+        util::validate_range(start, Some(end))?;
+    }
+
+    let info = read_media_info(&handle, &file_path).await?;
+    if info.channels == 0 {
+        return Err(AppError::NoAudioStream(format!(
+            "{} has no decodable audio stream",
+            file_path
+        )));
+    }
+    let samples = ffmpeg::decode_interleaved_pcm(&handle, &file_path, range, None).await?;
+    let report = dc::measure(&samples, info.channels as usize);
+    if report.significant {
+        warn!(
+            "{} has a DC offset of {:.1} dBFS: {:?}",
+            file_path, report.max_offset_db, report.offsets
+        );
+    } else {
+        info!("DC offsets: {:?}", report.offsets);
+    }
+    Ok(report)
+}
+
+/// Records `file_path` as just opened and returns the updated recent files.
+#[tauri::command]
+#[instrument(skip(handle, state), err)]
+fn add_recent_file(
+    handle: AppHandle,
+    state: State<'_, RecentFilesState>,
+    file_path: String,
+    title: String,
+) -> Result<Vec<RecentFile>, AppError> {
+    info!("Adding recent file {} ('{}')", file_path, title);
+    util::validate_media_path(&file_path)?;
+
+    let mut files = state
+        .0
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    recent::add(&mut files, &file_path, &title);
+    recent::save(&handle, &files)?;
+    Ok(files.clone())
+}
+
+/// Returns the recently opened files, most recent first, forgetting any
+/// that have since been moved or deleted.
+#[tauri::command]
+#[instrument(skip(handle, state), err)]
+fn get_recent_files(
+    handle: AppHandle,
+    state: State<'_, RecentFilesState>,
+) -> Result<Vec<RecentFile>, AppError> {
+    let mut files = state
+        .0
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if recent::prune_missing(&mut files) {
+        recent::save(&handle, &files)?;
+    }
+    info!("Returning {} recent files", files.len());
+    Ok(files.clone())
+}
+
+/// Decodes all of `file_path` and reports any decode errors, to catch
+/// truncated or damaged files before a practice session depends on them.
+///
+/// A file ffmpeg can't open at all is reported as not ok rather than as an
+/// error, so the result always says whether the file is usable.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn verify_media(
+    handle: AppHandle,
+    file_path: String,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<verify::MediaVerification, AppError> {
+    info!("Verifying {}", file_path);
+    util::validate_media_path(&file_path)?;
+
+    let args = vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        file_path.clone(),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ];
+    let options = RunOptions {
+        duration: ffmpeg::probe_duration(&handle, &file_path).await,
+        operation_id: Some(&operation_id),
+        kind: "verify_media",
+        output_path: None,
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    let verification = match ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await {
+        Ok(stderr) => verify::parse_errors(&stderr),
+        Err(AppError::FfmpegFailed(message)) => verify::MediaVerification {
+            ok: false,
+            errors: vec![message],
+        },
+        Err(e) => return Err(e),
+    };
+
+    info!(
+        "{} is {}",
+        file_path,
+        if verification.ok { "intact" } else { "damaged" }
+    );
+    Ok(verification)
+}
+
+/// Computes the Chromaprint fingerprint of `file_path`, or of `range` of
+/// it, to find recordings of the same audio.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn fingerprint_audio(
+    handle: AppHandle,
+    file_path: String,
+    range: Option<(f64, f64)>,
+) -> Result<fingerprint::Fingerprint, AppError> {
+    info!("Fingerprinting {} (range {:?})", file_path, range);
+    util::validate_media_path(&file_path)?;
+    if let Some((start, end)) = range {
+        util::validate_range(start, Some(end))?;
+    }
+    if !fingerprint::chromaprint_available(&handle).await? {
+        return Err(AppError::FeatureUnavailable(
+            "This FFmpeg build has no Chromaprint support. Install a build configured with --enable-chromaprint."
+                .to_string(),
+        ));
+    }
+
+    let duration = match range {
+        Some((start, end)) => end - start,
+        None => ffmpeg::probe_duration(&handle, &file_path)
+            .await
+            .ok_or_else(|| {
+                AppError::ParseFailed(format!("Could not read the length of {}", file_path))
+            })?,
+    };
+    let args = fingerprint::fingerprint_args(&file_path, range);
+    let stdout = ffmpeg::run_ffmpeg_raw(&handle, &args, None).await?;
+    let fingerprint = String::from_utf8_lossy(&stdout).trim().to_string();
+    if fingerprint.is_empty() {
+        return Err(AppError::NoAudioStream(format!(
+            "FFmpeg produced no fingerprint for {}",
+            file_path
+        )));
+    }
+
+    info!("Fingerprinted {:.2}s of audio", duration);
+    Ok(fingerprint::Fingerprint {
+        fingerprint,
+        duration,
+    })
+}
+
+/// Reads an M3U/M3U8 playlist and returns the media it lists, to load a
+/// lesson's files in one go.
+#[tauri::command]
+#[instrument(err)]
+fn parse_playlist(playlist_path: String) -> Result<playlist::Playlist, AppError> {
+    info!("Parsing playlist: {}", playlist_path);
+
+    // Plain .m3u files are often in a legacy code page, like CUE sheets.
+    let bytes = util::read_file(&playlist_path)?;
+    let playlist_dir = std::path::absolute(&playlist_path)
+        .map_err(|e| AppError::Io(format!("Failed to resolve {}: {}", playlist_path, e)))?
+        .parent()
+        .map(std::path::Path::to_path_buf)
+        .unwrap_or_default();
+    let playlist = playlist::resolve(&String::from_utf8_lossy(&bytes), &playlist_dir);
+
+    info!(
+        "Found {} entries, skipped {}",
+        playlist.paths.len(),
+        playlist.warnings.len()
+    );
+    Ok(playlist)
+}
+
+/// Writes `start..end` of `file_path` played backwards to `output_path`,
+/// for ear training.
+///
+/// `areverse` buffers the whole segment, so ranges longer than
+/// `max_seconds` (five minutes by default) are rejected.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn reverse_segment(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    output_path: String,
+    max_seconds: Option<f64>,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<String, AppError> {
+    let max_seconds = max_seconds.unwrap_or(effects::DEFAULT_MAX_REVERSE_SECS);
+    info!(
+        "Reversing {} ({} - {}) to {}",
+        file_path, start, end, output_path
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+    if !max_seconds.is_finite() || max_seconds <= 0.0 {
+        return Err(AppError::InvalidInput(format!(
+            "max_seconds must be a positive number, got {}",
+            max_seconds
+        )));
+    }
+    if end - start > max_seconds {
+        return Err(AppError::InvalidRange(format!(
+            "Can't reverse {:.1}s at once: reversing holds the whole segment in memory, so it is limited to {}s. Pick a shorter range.",
+            end - start,
+            max_seconds
+        )));
+    }
+
+    let args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        start.to_string(),
+        "-to".to_string(),
+        end.to_string(),
+        "-i".to_string(),
+        file_path,
+        "-vn".to_string(),
+        "-af".to_string(),
+        "areverse".to_string(),
+        output_path.clone(),
+    ];
+    let options = RunOptions {
+        duration: Some(end - start),
+        operation_id: Some(&operation_id),
+        kind: "reverse_segment",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote reversed audio to {}", output_path);
+    Ok(output_path)
+}
+
+/// Runs `range` of `file_path`, or all of it, through the user's own `-af`
+/// filtergraph into `output_path`.
+///
+/// Security: the filtergraph is passed to ffmpeg as one argument of its own,
+/// never through a shell or spliced into other arguments, so it can't add
+/// options, inputs or outputs. `effects::validate_custom_filter` also limits
+/// it to filtergraph characters and rejects filters that can reach files,
+/// plugins or the network.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn apply_filter(
+    handle: AppHandle,
+    file_path: String,
+    range: Option<(f64, f64)>,
+    filter: String,
+    output_path: String,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<String, AppError> {
+    info!(
+        "Applying filter '{}' to {} (range {:?}) into {}",
+        filter, file_path, range, output_path
+    );
+    util::validate_media_path(&file_path)?;
+    if let Some((start, end)) = range {
+        util::validate_range(start, Some(end))?;
+    }
+    effects::validate_custom_filter(&filter)?;
+
+    let mut args = vec!["-y".to_string(), "-v".to_string(), "error".to_string()];
+    if let Some((start, end)) = range {
+        args.push("-ss".to_string());
+        args.push(start.to_string());
+        args.push("-to".to_string());
+        args.push(end.to_string());
+    }
+    args.push("-i".to_string());
+    args.push(file_path.clone());
+    args.push("-vn".to_string());
+    args.push("-af".to_string());
+    args.push(filter.trim().to_string());
+    args.push(output_path.clone());
+
+    let duration = match range {
+        Some((start, end)) => Some(end - start),
+        None => ffmpeg::probe_duration(&handle, &file_path).await,
+    };
+    let options = RunOptions {
+        duration,
+        operation_id: Some(&operation_id),
+        kind: "apply_filter",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote filtered audio to {}", output_path);
+    Ok(output_path)
+}
+
+/// Converts `input_path` into a file `preset`'s device can play. With
+/// `dry_run`, returns the ffmpeg command line instead of running it.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn convert_for_device(
+    handle: AppHandle,
+    input_path: String,
+    output_path: String,
+    preset: transcode::DevicePreset,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<String, AppError> {
+    info!(
+        "Converting {} to {} for {:?}",
+        input_path, output_path, preset
+    );
+    util::validate_media_path(&input_path)?;
+
+    let args = transcode::device_args(&input_path, &output_path, &preset)?;
+    if dry_run.unwrap_or(false) {
+        return Ok(ffmpeg::job_command_line(&handle, &args));
+    }
+    let run_options = RunOptions {
+        duration: ffmpeg::probe_duration(&handle, &input_path).await,
+        operation_id: Some(&operation_id),
+        kind: "convert_for_device",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, run_options).await?;
+
+    info!("Wrote converted audio to {}", output_path);
+    Ok(output_path)
+}
+
+/// Cuts `start..end` of `file_path` into a ringtone for `platform`, ending
+/// in a `fade_out` of [`transcode::DEFAULT_RINGTONE_FADE_OUT`] seconds
+/// unless told otherwise (0 for none), and returns where it was written.
+/// Without `output_path`, the file goes to the export directory, named
+/// with the file name template from `title`. Selections longer than the
+/// platform plays are rejected rather than cut short. `remove_dc` takes
+/// out any DC offset.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn export_ringtone(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    platform: transcode::RingtonePlatform,
+    output_path: Option<String>,
+    title: Option<String>,
+    fade_out: Option<f64>,
+    remove_dc: Option<bool>,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<String, AppError> {
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+    let templated = output_path.is_none();
+    let output_path = match output_path {
+        Some(output_path) => output_path,
+        None => {
+            let values = export::TemplateValues {
+                title: title.as_deref().unwrap_or_default(),
+                index: 0,
+                start,
+                end: Some(end),
+                ext: platform.extension(),
+            };
+            template_export_path(&handle, &values, None)?
+        }
+    };
+    info!(
+        "Exporting {} ({} - {}) as a {:?} ringtone to {}",
+        file_path, start, end, platform, output_path
+    );
+
+    let length = end - start;
+    let fade = effects::fade_filter(
+        None,
+        Some(
+            fade_out
+                .unwrap_or(transcode::DEFAULT_RINGTONE_FADE_OUT)
+                .min(length),
+        ),
+        length,
+    )?;
+    let filter = dc::with_removal(remove_dc, fade);
+    let args = transcode::ringtone_args(
+        &file_path,
+        start,
+        end,
+        filter.as_deref(),
+        &output_path,
+        platform,
+    )?;
+    // Only create the export directory once the selection is known to fit.
+    if let Some(dir) = std::path::Path::new(&output_path)
+        .parent()
+        .filter(|_| templated)
+    {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| AppError::Io(format!("Failed to create {}: {}", dir.display(), e)))?;
+    }
+    let options = RunOptions {
+        duration: Some(length),
+        operation_id: Some(&operation_id),
+        kind: "export_ringtone",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote ringtone to {}", output_path);
+    Ok(output_path)
+}
+
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn save_session(handle: AppHandle, session: session::Session) -> Result<(), AppError> {
+    info!("Saving session: {:?}", session);
+    session::save(&handle, &session)
+}
+
+#[tauri::command]
+#[instrument(skip(handle))]
+fn load_session(handle: AppHandle) -> session::Session {
+    let session = session::load(&handle);
+    info!("Loaded session: {:?}", session);
+    session
+}
+
+/// Folds surround audio in `input_path` down to stereo in `output_path`.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn downmix_to_stereo(
+    handle: AppHandle,
+    input_path: String,
+    output_path: String,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<String, AppError> {
+    info!("Downmixing {} to stereo into {}", input_path, output_path);
+    util::validate_media_path(&input_path)?;
+    let info = read_media_info(&handle, &input_path).await?;
+    if !info.surround {
+        return Err(AppError::InvalidInput(format!(
+            "{} has {} channel(s) and doesn't need a downmix",
+            input_path, info.channels
+        )));
+    }
+
+    let mut args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        input_path,
+        "-vn".to_string(),
+    ];
+    match effects::downmix_filter(info.channels) {
+        Some(filter) => {
+            args.push("-af".to_string());
+            args.push(filter);
+        }
+        None => {
+            args.push("-ac".to_string());
+            args.push("2".to_string());
+        }
+    }
+    args.push(output_path.clone());
+    let options = RunOptions {
+        duration: Some(info.duration_seconds),
+        operation_id: Some(&operation_id),
+        kind: "downmix_to_stereo",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote stereo downmix to {}", output_path);
+    Ok(output_path)
+}
+
+/// Combines the video of `video_path` with the audio of `audio_path` in
+/// `output_path`, e.g. a good audio rip with a low quality concert video, as
+/// a new practice source. `audio_offset` seconds delay the audio to bring
+/// it in sync, or move it earlier when negative.
+///
+/// Both streams are copied when the output container takes them, and only
+/// what it doesn't is re-encoded. The output is as long as the video.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn mux_audio_video(
+    handle: AppHandle,
+    video_path: String,
+    audio_path: String,
+    audio_offset: Option<f64>,
+    output_path: String,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<String, AppError> {
+    info!(
+        "Muxing the video of {} with the audio of {} into {}",
+        video_path, audio_path, output_path
+    );
+    util::validate_media_path(&video_path)?;
+    util::validate_media_path(&audio_path)?;
+    let audio_offset = audio_offset.unwrap_or(0.0);
+    if !audio_offset.is_finite() {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid audio offset {}",
+            audio_offset
+        )));
+    }
+
+    let video =
+        ffmpeg::run_ffprobe(&handle, &["-show_format", "-show_streams", &video_path]).await?;
+    let Some(video_stream) = probe::video_stream_index(&video) else {
+        return Err(AppError::InvalidInput(format!(
+            "{} has no video stream",
+            video_path
+        )));
+    };
+    let audio = ffmpeg::run_ffprobe(&handle, &["-show_streams", &audio_path]).await?;
+    if probe::first_audio_stream(&audio).is_none() {
+        return Err(AppError::NoAudioStream(format!(
+            "{} has no audio stream",
+            audio_path
+        )));
+    }
+
+    let duration = probe::format_duration(&video);
+    let options = RunOptions {
+        duration,
+        operation_id: Some(&operation_id),
+        kind: "mux_audio_video",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    let attempt = |copy| {
+        let args = mux::mux_args(
+            &video_path,
+            video_stream,
+            &audio_path,
+            audio_offset,
+            duration,
+            &output_path,
+            copy,
+        );
+        let handle = &handle;
+        async move { ffmpeg::run_ffmpeg_with_progress(handle, &args, options).await }
+    };
+    let [earlier @ .., last] = mux::COPY_ATTEMPTS;
+    for copy in earlier {
+        match attempt(copy).await {
+            Ok(_) => {
+                info!("Wrote {} ({:?})", output_path, copy);
+                return Ok(output_path);
+            }
+            Err(AppError::Cancelled(message)) => return Err(AppError::Cancelled(message)),
+            Err(e) => warn!("Muxing with {:?} failed, re-encoding more: {}", copy, e),
+        }
+    }
+    // The last attempt re-encodes the most, so its error is the one to report.
+    attempt(last).await?;
+    info!("Wrote {} ({:?})", output_path, last);
+    Ok(output_path)
+}
+
+/// Writes `start..end` of `file_path` to `output_path` through a graphic EQ,
+/// either a named preset or custom bands, e.g. to bring out a lead for
+/// transcription. Boosts can push loud material into clipping. `remove_dc`
+/// takes out any DC offset.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn apply_eq_preset(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    preset: eq::EqPreset,
+    output_path: String,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+    remove_dc: Option<bool>,
+) -> Result<String, AppError> {
+    info!(
+        "Applying EQ {:?} to {} ({} - {}) into {}",
+        preset, file_path, start, end, output_path
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+    let info = read_media_info(&handle, &file_path).await?;
+    let bands = preset.bands();
+    eq::validate_bands(&bands, info.sample_rate)?;
+    let mut filter = eq::eq_filter(&bands);
+    if remove_dc.unwrap_or(false) {
+        filter = format!("{},{}", dc::REMOVE_FILTER, filter);
+    }
+
+    let args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        start.to_string(),
+        "-to".to_string(),
+        end.to_string(),
+        "-i".to_string(),
+        file_path,
+        "-vn".to_string(),
+        "-af".to_string(),
+        filter,
+        output_path.clone(),
+    ];
+    let options = RunOptions {
+        duration: Some(end - start),
+        operation_id: Some(&operation_id),
+        kind: "apply_eq_preset",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote equalized audio to {}", output_path);
+    Ok(output_path)
+}
+
+/// Removes the center of the stereo mix in `input_path`, where vocals and
+/// lead parts usually sit, so the user can play along to what's left.
+/// `center_attenuation` goes from 0 (unchanged) to 1 (center removed, the
+/// default).
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn extract_karaoke(
+    handle: AppHandle,
+    input_path: String,
+    output_path: String,
+    center_attenuation: Option<f64>,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<String, AppError> {
+    info!("Removing the center of {} into {}", input_path, output_path);
+    util::validate_media_path(&input_path)?;
+    let filter = effects::karaoke_filter(center_attenuation.unwrap_or(1.0))?;
+    let info = read_media_info(&handle, &input_path).await?;
+    if info.channels != 2 {
+        return Err(AppError::InvalidInput(if info.surround {
+            format!(
+                "{} has {} channels. Downmix it to stereo first to remove the center.",
+                input_path, info.channels
+            )
+        } else {
+            format!(
+                "{} is mono, so there is no center to remove. It needs to be stereo.",
+                input_path
+            )
+        }));
+    }
+
+    let args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        input_path,
+        "-vn".to_string(),
+        "-af".to_string(),
+        filter,
+        output_path.clone(),
+    ];
+    let options = RunOptions {
+        duration: Some(info.duration_seconds),
+        operation_id: Some(&operation_id),
+        kind: "extract_karaoke",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote karaoke mix to {}", output_path);
+    Ok(output_path)
+}
+
+/// Largest clip `extract_chapter_blob` returns inline.
+const MAX_BLOB_BYTES: usize = 25 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+struct AudioBlob {
+    mime: String,
+    data_base64: String,
+}
+
+/// Like `extract_chapter_audio`, but encodes `start..end` into memory and
+/// returns it base64 encoded, so short loops can be played without a temp
+/// file. Clips over 25 MB are refused.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn extract_chapter_blob(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    options: transcode::TranscodeOptions,
+) -> Result<AudioBlob, AppError> {
+    info!(
+        "Extracting {} ({} - {}) into memory as {:?}",
+        file_path, start, end, options
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+    let (muxer, mime) = transcode::pipe_format(&options.format)?;
+
+    let mut args = vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        start.to_string(),
+        "-to".to_string(),
+        end.to_string(),
+        "-i".to_string(),
+        file_path,
+        "-map".to_string(),
+        "0:a:0".to_string(),
+        "-vn".to_string(),
+    ];
+    args.extend(transcode::encoder_args(&options)?);
+    args.extend(["-f".to_string(), muxer.to_string(), "pipe:1".to_string()]);
+    let bytes = match ffmpeg::run_ffmpeg_raw(&handle, &args, Some(MAX_BLOB_BYTES)).await {
+        Ok(bytes) => bytes,
+        Err(AppError::OutputTooLarge(_)) => {
+            return Err(AppError::InvalidInput(format!(
+                "This range encodes to more than {} MB. Extract it to a file instead.",
+                MAX_BLOB_BYTES / (1024 * 1024)
+            )))
+        }
+        Err(e) => return Err(e),
+    };
+
+    info!("Extracted {} bytes of {}", bytes.len(), mime);
+    Ok(AudioBlob {
+        mime: mime.to_string(),
+        data_base64: base64::engine::general_purpose::STANDARD.encode(bytes),
+    })
+}
+
+/// Converts `file_path` into an HLS stream in the temp dir and returns the
+/// path of its playlist, so the player can seek around a long file without
+/// loading it whole. Segments are about `segment_secs` long, 6 unless
+/// given. A finished stream of the same file with the same segment length
+/// is reused; `cleanup_hls` deletes it once it isn't needed.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn prepare_hls(
+    handle: AppHandle,
+    file_path: String,
+    segment_secs: Option<f64>,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<String, AppError> {
+    let segment_secs = segment_secs.unwrap_or(hls::DEFAULT_SEGMENT_SECS);
+    info!(
+        "Preparing an HLS stream of {} ({}s segments)",
+        file_path, segment_secs
+    );
+    util::validate_media_path(&file_path)?;
+    hls::validate_segment_secs(segment_secs)?;
+
+    let playlist = hls::playlist_path(&cache::FileFingerprint::of(&file_path)?.key(), segment_secs);
+    let playlist_path = playlist.to_string_lossy().into_owned();
+    if hls::is_complete(&playlist) {
+        info!("Reusing the HLS stream at {}", playlist_path);
+        return Ok(playlist_path);
+    }
+    // Whatever is there is left over from an interrupted run.
+    hls::remove(&playlist)?;
+    if let Some(dir) = playlist.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| AppError::Io(format!("Failed to create {}: {}", dir.display(), e)))?;
+    }
+
+    let parsed =
+        ffmpeg::run_ffprobe(&handle, &["-show_format", "-show_streams", &file_path]).await?;
+    let args = hls::args(&file_path, &parsed, segment_secs, &playlist);
+    let options = RunOptions {
+        duration: probe::format_duration(&parsed),
+        operation_id: Some(&operation_id),
+        kind: "prepare_hls",
+        output_path: None,
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    if let Err(e) = ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await {
+        if let Err(cleanup) = hls::remove(&playlist) {
+            warn!(
+                "Failed to clean up the HLS stream at {}: {}",
+                playlist_path, cleanup
+            );
+        }
+        return Err(e);
+    }
+
+    info!("Wrote an HLS stream of {} to {}", file_path, playlist_path);
+    Ok(playlist_path)
+}
+
+/// Deletes the HLS stream `prepare_hls` wrote to `playlist_path`, returning
+/// whether there was one to delete.
+#[tauri::command]
+#[instrument(err)]
+fn cleanup_hls(playlist_path: String) -> Result<bool, AppError> {
+    let removed = hls::remove(std::path::Path::new(&playlist_path))?;
+    info!(
+        "{} the HLS stream at {}",
+        if removed { "Removed" } else { "Found no" },
+        playlist_path
+    );
+    Ok(removed)
+}
+
+/// Writes `start..end` of `file_path` repeated `repeat_count` times to
+/// `output_path`, so a loop can be practised hands-free from any player.
+///
+/// A `crossfade` overlaps each repeat with the next by that many seconds to
+/// hide the seam; without one the repeats are joined sample accurately.
+/// `remove_dc` takes out any DC offset. `prepend_count_in` clicks a whole
+/// number of bars at the loop's tempo before every repeat, which rules out
+/// a crossfade.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn export_looped(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    repeat_count: u32,
+    crossfade: Option<f64>,
+    remove_dc: Option<bool>,
+    prepend_count_in: Option<metronome::CountIn>,
+    output_path: String,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<String, AppError> {
+    info!(
+        "Exporting {} ({} - {}) looped {} times to {}",
+        file_path, start, end, repeat_count, output_path
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+
+    let info = read_media_info(&handle, &file_path).await?;
+    let mut graph = effects::loop_graph(repeat_count, crossfade, end - start, info.sample_rate)?;
+    let mut count_ins = Vec::new();
+    if let Some(count_in) = prepend_count_in {
+        count_in.validate()?;
+        if crossfade.is_some_and(|crossfade| crossfade > 0.0) {
+            return Err(AppError::InvalidInput(
+                "A count-in goes between the repeats, so they can't also crossfade".to_string(),
+            ));
+        }
+        let rates = vec![1.0; repeat_count as usize];
+        let lengths = vec![count_in.length(1.0); rates.len()];
+        graph = effects::tempo_ramp_graph(&rates, end - start, &lengths)?;
+        count_ins = vec![count_in.source(1.0, info.sample_rate, info.channels); rates.len()];
+    }
+    if remove_dc.unwrap_or(false) {
+        graph.filter = effects::filter_looped(&graph.filter, dc::REMOVE_FILTER);
+    }
+
+    let mut args = vec!["-y".to_string(), "-v".to_string(), "error".to_string()];
+    args.extend(loop_input_args(&file_path, start, end, &graph, &count_ins));
+    args.extend([
+        "-filter_complex".to_string(),
+        graph.filter,
+        "-map".to_string(),
+        "[looped]".to_string(),
+        "-vn".to_string(),
+        output_path.clone(),
+    ]);
+    let options = RunOptions {
+        duration: Some(graph.length),
+        operation_id: Some(&operation_id),
+        kind: "export_looped",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!(
+        "Wrote {:.1}s of looped audio to {}",
+        graph.length, output_path
+    );
+    Ok(output_path)
+}
+
+/// Writes `start..end` of `file_path` to `output_path` repeated
+/// `repeat_count` times, each repeat a little faster than the last: the
+/// first plays at `start_rate` and the last at `end_rate`, with the pitch
+/// unchanged. Practising along builds up to full speed gradually.
+/// `remove_dc` takes out any DC offset. `prepend_count_in` clicks a whole
+/// number of bars before every repeat, at the tempo that repeat plays at.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn export_tempo_ramp(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    start_rate: f64,
+    end_rate: f64,
+    repeat_count: u32,
+    remove_dc: Option<bool>,
+    prepend_count_in: Option<metronome::CountIn>,
+    output_path: String,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<String, AppError> {
+    info!(
+        "Exporting {} ({} - {}) ramped from {}x to {}x over {} repeats to {}",
+        file_path, start, end, start_rate, end_rate, repeat_count, output_path
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+    let rates = effects::tempo_ramp_rates(start_rate, end_rate, repeat_count)?;
+    let (count_in_lengths, count_ins) = match prepend_count_in {
+        Some(count_in) => {
+            count_in.validate()?;
+            let info = read_media_info(&handle, &file_path).await?;
+            rates
+                .iter()
+                .map(|rate| {
+                    (
+                        count_in.length(*rate),
+                        count_in.source(*rate, info.sample_rate, info.channels),
+                    )
+                })
+                .unzip()
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+    let mut graph = effects::tempo_ramp_graph(&rates, end - start, &count_in_lengths)?;
+    if remove_dc.unwrap_or(false) {
+        graph.filter = effects::filter_looped(&graph.filter, dc::REMOVE_FILTER);
+    }
+
+    let mut args = vec!["-y".to_string(), "-v".to_string(), "error".to_string()];
+    args.extend(loop_input_args(&file_path, start, end, &graph, &count_ins));
+    args.extend([
+        "-filter_complex".to_string(),
+        graph.filter,
+        "-map".to_string(),
+        "[looped]".to_string(),
+        "-vn".to_string(),
+        output_path.clone(),
+    ]);
+    let options = RunOptions {
+        duration: Some(graph.length),
+        operation_id: Some(&operation_id),
+        kind: "export_tempo_ramp",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!("Wrote {:.1}s tempo ramp to {}", graph.length, output_path);
+    Ok(output_path)
+}
+
+/// Input arguments for `graph`: `start..end` of `file_path` for each of its
+/// inputs, or, with `count_ins`, each count-in as a `lavfi` source followed
+/// by the range.
+fn loop_input_args(
+    file_path: &str,
+    start: f64,
+    end: f64,
+    graph: &effects::LoopGraph,
+    count_ins: &[String],
+) -> Vec<String> {
+    let segment = [
+        "-ss".to_string(),
+        start.to_string(),
+        "-to".to_string(),
+        end.to_string(),
+        "-i".to_string(),
+        file_path.to_string(),
+    ];
+    if count_ins.is_empty() {
+        return (0..graph.inputs).flat_map(|_| segment.clone()).collect();
+    }
+    count_ins
+        .iter()
+        .flat_map(|source| {
+            [
+                "-f".to_string(),
+                "lavfi".to_string(),
+                "-i".to_string(),
+                source.clone(),
+            ]
+            .into_iter()
+            .chain(segment.clone())
+        })
+        .collect()
+}
+
+/// Hashes `file_path` so the frontend can tell whether it changed between
+/// sessions. By default only the size and both ends of the file are read;
+/// `full` hashes every byte with SHA-256, which takes a while on large files.
+#[tauri::command]
+#[instrument(err)]
+async fn hash_file(file_path: String, full: Option<bool>) -> Result<String, AppError> {
+    let full = full.unwrap_or(false);
+    info!(
+        "Hashing {} ({})",
+        file_path,
+        if full { "full" } else { "fast" }
+    );
+    util::validate_media_path(&file_path)?;
+
+    let path = file_path.clone();
+    let hash = tauri::async_runtime::spawn_blocking(move || {
+        if full {
+            hashing::full_hash(&path)
+        } else {
+            hashing::fast_hash(&path)
+        }
+    })
+    .await
+    .map_err(|e| AppError::Io(format!("Hashing {} failed: {}", file_path, e)))??;
+
+    info!("{} hashes to {}", file_path, hash);
+    Ok(hash)
+}
+
+/// Returns the log file being written, for attaching to bug reports, or the
+/// log directory if nothing has been logged yet.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+fn get_log_path(handle: AppHandle) -> Result<String, AppError> {
+    let dir = logging::log_dir(&handle)?;
+    let path = logging::current_log_file(&dir).unwrap_or(dir);
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Writes `start..end` of `file_path` to `output_path` as one loop-ready
+/// file whose end crossfades into its start over `crossfade` seconds, so a
+/// player on repeat plays it without a click. Unlike `export_looped` this
+/// produces a single pass of the loop. `remove_dc` takes out any DC offset.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+#[allow(clippy::too_many_arguments)]
+async fn export_seamless_loop(
+    handle: AppHandle,
+    file_path: String,
+    start: f64,
+    end: f64,
+    crossfade: f64,
+    remove_dc: Option<bool>,
+    output_path: String,
+    operation_id: String,
+    timeout_secs: Option<u64>,
+    verbose: Option<bool>,
+) -> Result<String, AppError> {
+    info!(
+        "Exporting seamless loop of {} ({} - {}) with a {}s crossfade to {}",
+        file_path, start, end, crossfade, output_path
+    );
+    util::validate_media_path(&file_path)?;
+    util::validate_range(start, Some(end))?;
+    let mut seamless = effects::seamless_loop(start, end, crossfade)?;
+    if remove_dc.unwrap_or(false) {
+        seamless.filter = effects::filter_looped(&seamless.filter, dc::REMOVE_FILTER);
+    }
+
+    let mut args = vec!["-y".to_string(), "-v".to_string(), "error".to_string()];
+    for (from, to) in &seamless.ranges {
+        args.extend([
+            "-ss".to_string(),
+            from.to_string(),
+            "-to".to_string(),
+            to.to_string(),
+            "-i".to_string(),
+            file_path.clone(),
+        ]);
+    }
+    args.extend([
+        "-filter_complex".to_string(),
+        seamless.filter,
+        "-map".to_string(),
+        "[looped]".to_string(),
+        "-vn".to_string(),
+        output_path.clone(),
+    ]);
+    let options = RunOptions {
+        duration: Some(seamless.length),
+        operation_id: Some(&operation_id),
+        kind: "export_seamless_loop",
+        output_path: Some(&output_path),
+        timeout: timeout_secs.map(std::time::Duration::from_secs),
+        verbose: verbose.unwrap_or(false),
+    };
+    ffmpeg::run_ffmpeg_with_progress(&handle, &args, options).await?;
+
+    info!(
+        "Wrote {:.1}s seamless loop to {}",
+        seamless.length, output_path
+    );
+    Ok(output_path)
+}
+
+/// Checks how cleanly the exported loop `file_path` runs from its end back
+/// into its start, comparing the last and first `window_ms` milliseconds of
+/// every channel, for tuning the crossfade of `export_seamless_loop`.
+#[tauri::command]
+#[instrument(skip(handle), err)]
+async fn check_loop_seam(
+    handle: AppHandle,
+    file_path: String,
+    window_ms: Option<f64>,
+) -> Result<seam::SeamReport, AppError> {
+    let window_ms = window_ms.unwrap_or(seam::DEFAULT_WINDOW_MS);
+    info!(
+        "Checking the loop seam of {} ({} ms window)",
+        file_path, window_ms
+    );
+    util::validate_media_path(&file_path)?;
+    if !(window_ms > 0.0 && window_ms <= seam::MAX_WINDOW_MS) {
+        return Err(AppError::InvalidInput(format!(
+            "window_ms must be greater than 0 and at most {}",
+            seam::MAX_WINDOW_MS
+        )));
+    }
+
+    // Decode every channel at the source rate, as a click in one channel
+    // can cancel out in a downmix.
+    let info = read_media_info(&handle, &file_path).await?;
+    if info.sample_rate == 0 || info.channels == 0 {
+        return Err(AppError::NoAudioStream(format!(
+            "{} has no decodable audio stream",
+            file_path
+        )));
+    }
+    let samples = ffmpeg::decode_interleaved_pcm(&handle, &file_path, None, None).await?;
+
+    let window = (info.sample_rate as f64 * window_ms / 1000.0).round() as usize;
+    let report = seam::measure(&samples, info.channels as usize, window).ok_or_else(|| {
+        AppError::InvalidInput(format!("{} is too short to check as a loop", file_path))
+    })?;
+    info!(
+        "Loop seam of {}: {:.1} dB discontinuity, {:.1} dB typical",
+        file_path, report.discontinuity_db, report.typical_db
+    );
+    Ok(report)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .setup(|app| {
+            match logging::init(app.handle()) {
+                Ok(guard) => {
+                    app.manage(guard);
+                }
+                Err(e) => eprintln!("Logging to a file is disabled: {}", e),
+            }
+            let settings = settings::load(app.handle());
+            app.manage(SettingsState(Mutex::new(settings)));
+            app.manage(OperationManager::default());
+            app.manage(batch::BatchController::default());
+            app.manage(RecentFilesState(Mutex::new(recent::load(app.handle()))));
+            app.manage(fingerprint::ChromaprintSupport::default());
+            app.manage(encoders::EncoderCache::default());
+            app.manage(sidecar::BundledToolsState(Mutex::new(sidecar::find_all(
+                app.handle(),
+            ))));
+            // Probe optional ffmpeg features up front so the UI can ask
+            // without waiting on ffmpeg.
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                match fingerprint::chromaprint_available(&handle).await {
+                    Ok(available) => info!("Chromaprint available: {}", available),
+                    Err(e) => warn!("Failed to check for Chromaprint: {}", e),
+                }
+            });
+            Ok(())
+        })
+        .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
+        .invoke_handler(tauri::generate_handler![
+            greet,
+            extract_chapters,
+            batch_extract_chapters,
+            pause_batch,
+            resume_batch,
+            check_ffmpeg,
+            resolve_bundled_ffmpeg,
+            check_dependencies,
+            list_available_encoders,
+            compatible_export_formats,
+            benchmark_transcode,
+            set_ffmpeg_path,
+            set_ffmpeg_timeout,
+            set_ffmpeg_threads,
+            set_ffmpeg_retries,
+            reset_ffmpeg_config,
+            get_settings,
+            update_settings,
+            generate_waveform,
+            stream_waveform,
+            generate_waveform_range,
+            chapter_waveforms,
+            channel_peaks,
+            compare_waveforms,
+            waveform_cache_size,
+            clear_waveform_cache,
+            set_waveform_cache_limit,
+            extract_chapter_audio,
+            detect_tempo,
+            detect_downbeat,
+            detect_onsets,
+            analyze_rhythm,
+            detect_key,
+            analyze_all_chapters,
+            quantize_to_beat,
+            generate_beat_grid,
+            generate_click_track,
+            clear_chapter_cache,
+            get_media_info,
+            get_accurate_duration,
+            list_audio_streams,
+            list_subtitle_tracks,
+            extract_subtitles,
+            load_file,
+            load_markers,
+            save_markers,
+            export_project,
+            import_project,
+            reconcile_file,
+            parse_cue_sheet,
+            parse_label_track,
+            export_cue_sheet,
+            export_webvtt,
+            export_ffmetadata,
+            export_chapters_json,
+            import_chapters_json,
+            parse_simple_chapters,
+            export_simple_chapters,
+            parse_timestamp_text,
+            write_chapters_to_file,
+            write_replaygain_tags,
+            detect_silence,
+            suggest_loop_region,
+            auto_segment,
+            detect_subsections,
+            transcode_audio,
+            estimate_export_size,
+            pitch_shift_chapter,
+            change_tempo,
+            can_concat,
+            concat_chapters,
+            generate_spectrogram,
+            get_cover_art,
+            extract_frame,
+            generate_timeline_thumbnails,
+            classify_media,
+            analyze_loudness,
+            normalize_audio,
+            cancel_operation,
+            list_operations,
+            cleanup_temp_files,
+            merge_chapters,
+            validate_chapters,
+            normalize_chapters,
+            split_chapter,
+            create_chapter_from_range,
+            fill_chapter_gaps,
+            shift_chapters,
+            chapters_total_duration,
+            group_chapters,
+            clean_chapter_titles,
+            filter_chapters_by_tag,
+            search_chapters,
+            trim_silence,
+            resolve_export_path,
+            export_all_chapters,
+            segment_by_duration,
+            detect_clipping,
+            detect_dc_offset,
+            add_recent_file,
+            get_recent_files,
+            verify_media,
+            fingerprint_audio,
+            parse_playlist,
+            reverse_segment,
+            apply_filter,
+            convert_for_device,
+            export_ringtone,
+            save_session,
+            load_session,
+            downmix_to_stereo,
+            extract_karaoke,
+            mux_audio_video,
+            apply_eq_preset,
+            extract_chapter_blob,
+            prepare_hls,
+            cleanup_hls,
+            export_looped,
+            export_tempo_ramp,
+            export_seamless_loop,
+            check_loop_seam,
+            create_ab_comparison,
+            hash_file,
+            get_log_path
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_arg(args: &[String], arg: &str) -> bool {
+        args.iter().any(|a| a == arg)
+    }
+
+    #[test]
+    fn extraction_filters_only_when_fading() {
+        let plain = extraction_args("in.mp3", 5.0, Some(9.0), None, None, "out.mp3", true, false);
+        assert!(!has_arg(&plain, "-af"));
+        assert!(has_arg(&plain, "copy"));
+
+        let fade = effects::fade_filter(Some(1.0), None, 4.0).unwrap();
+        let faded = extraction_args(
+            "in.mp3",
+            5.0,
+            Some(9.0),
+            None,
+            fade.as_deref(),
+            "out.mp3",
+            true,
+            false,
+        );
+        let af = faded.iter().position(|a| a == "-af").unwrap();
+        assert_eq!(faded[af + 1], "afade=t=in:st=0:d=1");
+        // Filtered audio has to be re-encoded.
+        assert!(!has_arg(&faded, "copy"));
+    }
+
+    #[test]
+    fn accurate_extraction_seeks_after_the_input() {
+        let position = |args: &[String], arg: &str| args.iter().position(|a| a == arg).unwrap();
+
+        let fast = extraction_args("in.mp3", 5.0, Some(9.0), None, None, "out.mp3", true, false);
+        assert!(position(&fast, "-ss") < position(&fast, "-i"));
+        assert!(position(&fast, "-to") < position(&fast, "-i"));
+
+        let accurate = extraction_args("in.mp3", 5.0, Some(9.0), None, None, "out.mp3", true, true);
+        assert!(position(&accurate, "-ss") > position(&accurate, "-i"));
+        assert!(position(&accurate, "-to") > position(&accurate, "-i"));
+    }
+}
@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use tauri::{AppHandle, Manager};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{Builder, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::error::AppError;
+
+const LOG_FILE_PREFIX: &str = "guitar-looper";
+const LOG_FILE_SUFFIX: &str = "log";
+
+/// Daily log files kept before the oldest is deleted.
+const MAX_LOG_FILES: usize = 7;
+
+/// Managed state keeping the background log writer alive. Whatever is still
+/// buffered is flushed when it is dropped on exit.
+pub struct LogGuard {
+    _writer: WorkerGuard,
+}
+
+/// Returns (creating if needed) the app log directory.
+pub fn log_dir(handle: &AppHandle) -> Result<PathBuf, AppError> {
+    let dir = handle
+        .path()
+        .app_log_dir()
+        .map_err(|e| AppError::Io(format!("Failed to resolve app log directory: {}", e)))?;
+    fs::create_dir_all(&dir).map_err(|e| {
+        AppError::Io(format!(
+            "Failed to create log directory {}: {}",
+            dir.display(),
+            e
+        ))
+    })?;
+    Ok(dir)
+}
+
+/// Sends all `tracing` output to a log file in the app log directory that
+/// rolls over daily, and in debug builds to stdout as well. Command spans
+/// are logged when they open and close, with how long they took.
+pub fn init(handle: &AppHandle) -> Result<LogGuard, AppError> {
+    let dir = log_dir(handle)?;
+    let appender = Builder::new()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(&dir)
+        .map_err(|e| {
+            AppError::Io(format!(
+                "Failed to open log file in {}: {}",
+                dir.display(),
+                e
+            ))
+        })?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let file_layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE);
+    let stdout_layer = cfg!(debug_assertions)
+        .then(|| tracing_subscriber::fmt::layer().with_span_events(FmtSpan::NEW | FmtSpan::CLOSE));
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(file_layer)
+        .with(stdout_layer)
+        .try_init()
+        .map_err(|e| AppError::Io(format!("Failed to set up logging: {}", e)))?;
+    Ok(LogGuard { _writer: guard })
+}
+
+/// Returns the log file currently written to in `dir`, the most recently
+/// modified one.
+pub fn current_log_file(dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with(LOG_FILE_PREFIX) && name.ends_with(LOG_FILE_SUFFIX)
+        })
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().unwrap_or(UNIX_EPOCH);
+            Some((modified, entry.path()))
+        })
+        .max()
+        .map(|(_, path)| path)
+}
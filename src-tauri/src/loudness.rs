@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+
+/// ebur128 reports silence as `-inf`, which JSON can't represent, so levels
+/// are floored here instead.
+pub const SILENCE_FLOOR_DB: f64 = -120.0;
+
+/// `ebur128` filter with true-peak measurement. Per-frame logging is pushed
+/// down to the verbose level so stderr only carries the summary.
+pub const EBUR128_FILTER: &str = "ebur128=peak=true:framelog=verbose";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterLoudness {
+    pub chapter_id: String,
+    pub integrated_lufs: f64,
+    /// True peak in dBFS.
+    pub peak_db: f64,
+}
+
+/// Integrated loudness and true peak read from an ebur128 summary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub integrated_lufs: f64,
+    pub peak_db: f64,
+}
+
+fn parse_level(line: &str, label: &str) -> Option<f64> {
+    let value = line.trim().strip_prefix(label)?;
+    let level: f64 = value.split_whitespace().next()?.parse().ok()?;
+    Some(if level.is_finite() {
+        level
+    } else {
+        SILENCE_FLOOR_DB
+    })
+}
+
+/// Parses the `Summary:` block ebur128 prints to stderr when it finishes.
+pub fn parse_ebur128_summary(stderr: &str) -> Option<Measurement> {
+    // The summary comes last; earlier lines may contain the same labels.
+    let summary = &stderr[stderr.rfind("Summary:")?..];
+    let integrated_lufs = summary.lines().find_map(|l| parse_level(l, "I:"))?;
+    let peak_db = summary
+        .lines()
+        .find_map(|l| parse_level(l, "Peak:"))
+        .unwrap_or(SILENCE_FLOOR_DB);
+    Some(Measurement {
+        integrated_lufs,
+        peak_db,
+    })
+}
+
+/// Gain in dB that brings `take` to the integrated loudness of `reference`,
+/// held back where needed so the take's true peak stays below
+/// [`TARGET_TRUE_PEAK`]. Silence in either leaves the take as it is, since
+/// there is nothing to match.
+pub fn matching_gain(reference: &Measurement, take: &Measurement) -> f64 {
+    if reference.integrated_lufs <= SILENCE_FLOOR_DB || take.integrated_lufs <= SILENCE_FLOOR_DB {
+        return 0.0;
+    }
+    let gain = reference.integrated_lufs - take.integrated_lufs;
+    gain.min(TARGET_TRUE_PEAK - take.peak_db)
+}
+
+/// Default integrated loudness target for normalized exports.
+pub const DEFAULT_TARGET_LUFS: f64 = -16.0;
+/// True-peak ceiling in dBTP for normalized exports.
+const TARGET_TRUE_PEAK: f64 = -1.5;
+/// Loudness range target in LU for normalized exports.
+const TARGET_LRA: f64 = 11.0;
+
+/// Values measured by the first `loudnorm` pass.
+///
+/// loudnorm prints every number as a JSON string, hence the `String` fields.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoudnormMeasurement {
+    pub input_i: String,
+    pub input_tp: String,
+    pub input_lra: String,
+    pub input_thresh: String,
+    pub target_offset: String,
+}
+
+fn loudnorm_targets(target_lufs: f64) -> String {
+    format!(
+        "loudnorm=I={}:TP={}:LRA={}",
+        target_lufs, TARGET_TRUE_PEAK, TARGET_LRA
+    )
+}
+
+/// Filter for the measuring pass, which reports its findings as JSON.
+pub fn first_pass_filter(target_lufs: f64) -> String {
+    format!("{}:print_format=json", loudnorm_targets(target_lufs))
+}
+
+/// Extracts the JSON block loudnorm prints at the end of the first pass.
+pub fn parse_loudnorm_json(stderr: &str) -> Result<LoudnormMeasurement, AppError> {
+    let start = stderr.rfind('{');
+    let end = stderr.rfind('}');
+    let json = match (start, end) {
+        (Some(start), Some(end)) if start < end => &stderr[start..=end],
+        _ => {
+            return Err(AppError::ParseFailed(
+                "loudnorm did not report its measurements".to_string(),
+            ))
+        }
+    };
+    serde_json::from_str(json)
+        .map_err(|e| AppError::ParseFailed(format!("Failed to parse loudnorm measurements: {}", e)))
+}
+
+/// Filter for the normalizing pass, fed with the first pass's measurements
+/// so loudnorm can apply a single linear gain instead of dynamic correction.
+pub fn second_pass_filter(target_lufs: f64, measured: &LoudnormMeasurement) -> String {
+    format!(
+        "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true:print_format=summary",
+        loudnorm_targets(target_lufs),
+        measured.input_i,
+        measured.input_tp,
+        measured.input_lra,
+        measured.input_thresh,
+        measured.target_offset
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIRST_PASS_STDERR: &str = r#"[Parsed_loudnorm_0 @ 0x5581c0] 
+{
+	"input_i" : "-23.54",
+	"input_tp" : "-4.12",
+	"input_lra" : "7.30",
+	"input_thresh" : "-34.01",
+	"output_i" : "-16.02",
+	"output_tp" : "-1.50",
+	"output_lra" : "6.10",
+	"output_thresh" : "-26.40",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.02"
+}"#;
+
+    #[test]
+    fn second_pass_uses_first_pass_measurements() {
+        let measured = parse_loudnorm_json(FIRST_PASS_STDERR).unwrap();
+        assert_eq!(
+            second_pass_filter(-16.0, &measured),
+            "loudnorm=I=-16:TP=-1.5:LRA=11:measured_I=-23.54:measured_TP=-4.12:measured_LRA=7.30:measured_thresh=-34.01:offset=0.02:linear=true:print_format=summary"
+        );
+    }
+
+    #[test]
+    fn missing_measurements_fail_to_parse() {
+        assert!(matches!(
+            parse_loudnorm_json("Conversion failed!"),
+            Err(AppError::ParseFailed(_))
+        ));
+    }
+}
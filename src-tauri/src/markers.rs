@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::effects;
+use crate::error::AppError;
+use crate::util::write_atomic;
+use crate::Chapter;
+
+/// Practice settings remembered for a loop.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoopSettings {
+    /// Playback rate, 1.0 being the original speed.
+    pub speed: f64,
+    pub pitch_semitones: i32,
+    /// Repetitions before playback stops, or `None` to loop until stopped.
+    pub loop_count: Option<u32>,
+}
+
+impl LoopSettings {
+    fn validate(&self, title: &str) -> Result<(), AppError> {
+        if !(effects::MIN_TEMPO_RATE..=effects::MAX_TEMPO_RATE).contains(&self.speed) {
+            return Err(AppError::InvalidInput(format!(
+                "Marker '{}' has a speed of {}, outside {}..={}",
+                title,
+                self.speed,
+                effects::MIN_TEMPO_RATE,
+                effects::MAX_TEMPO_RATE
+            )));
+        }
+        if !(-effects::MAX_SEMITONES..=effects::MAX_SEMITONES).contains(&self.pitch_semitones) {
+            return Err(AppError::InvalidInput(format!(
+                "Marker '{}' is pitch shifted by {} semitones, outside {}..={}",
+                title,
+                self.pitch_semitones,
+                -effects::MAX_SEMITONES,
+                effects::MAX_SEMITONES
+            )));
+        }
+        if self.loop_count == Some(0) {
+            return Err(AppError::InvalidInput(format!(
+                "Marker '{}' must loop at least once",
+                title
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the sidecar path for `file_path`, e.g. `song.mp3.loops.json`.
+pub fn sidecar_path(file_path: &str) -> PathBuf {
+    let mut path = PathBuf::from(file_path).into_os_string();
+    path.push(".loops.json");
+    PathBuf::from(path)
+}
+
+/// Checks that every marker has a non-negative start and, if it has an end,
+/// that the end comes after the start, and that its loop settings are in
+/// range.
+pub fn validate(markers: &[Chapter]) -> Result<(), AppError> {
+    for marker in markers {
+        if !marker.start.is_finite() || marker.start < 0.0 {
+            return Err(AppError::InvalidRange(format!(
+                "Marker '{}' has a negative start ({})",
+                marker.title, marker.start
+            )));
+        }
+        if let Some(end) = marker.end {
+            if !end.is_finite() || end <= marker.start {
+                return Err(AppError::InvalidRange(format!(
+                    "Marker '{}' ends ({}) before it starts ({})",
+                    marker.title, end, marker.start
+                )));
+            }
+        }
+        if let Some(settings) = &marker.loop_settings {
+            settings.validate(&marker.title)?;
+        }
+    }
+    Ok(())
+}
+
+/// A named range the user loops independently of the chapters, e.g. a
+/// tricky lick inside a solo. Regions may overlap each other and chapters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoopRegion {
+    pub id: String,
+    pub name: String,
+    pub start: f64,
+    pub end: f64,
+    /// CSS hex color, `#rgb` or `#rrggbb`, or `None` for the default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Disabled regions are kept but not played.
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loop_settings: Option<LoopSettings>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+fn is_hex_color(color: &str) -> bool {
+    color.strip_prefix('#').is_some_and(|digits| {
+        matches!(digits.len(), 3 | 6) && digits.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+/// Everything saved in a sidecar: the chapter markers and, in order, the
+/// loop regions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Sidecar {
+    pub chapters: Vec<Chapter>,
+    #[serde(default)]
+    pub loop_regions: Vec<LoopRegion>,
+}
+
+/// Checks that every region has a name, a unique id, an end after its
+/// non-negative start, a valid color and loop settings in range.
+pub fn validate_regions(regions: &[LoopRegion]) -> Result<(), AppError> {
+    let mut ids = HashSet::new();
+    for region in regions {
+        if region.name.trim().is_empty() {
+            return Err(AppError::InvalidInput(format!(
+                "Loop region {} has no name",
+                region.id
+            )));
+        }
+        if !ids.insert(region.id.as_str()) {
+            return Err(AppError::InvalidInput(format!(
+                "More than one loop region has the id {}",
+                region.id
+            )));
+        }
+        if !region.start.is_finite() || region.start < 0.0 {
+            return Err(AppError::InvalidRange(format!(
+                "Loop region '{}' has a negative start ({})",
+                region.name, region.start
+            )));
+        }
+        if !region.end.is_finite() || region.end <= region.start {
+            return Err(AppError::InvalidRange(format!(
+                "Loop region '{}' ends ({}) before it starts ({})",
+                region.name, region.end, region.start
+            )));
+        }
+        if let Some(color) = region.color.as_deref().filter(|color| !is_hex_color(color)) {
+            return Err(AppError::InvalidInput(format!(
+                "Loop region '{}' has the color '{}', expected #rgb or #rrggbb",
+                region.name, color
+            )));
+        }
+        if let Some(settings) = &region.loop_settings {
+            settings.validate(&region.name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the markers and loop regions saved next to `file_path`, or an
+/// empty sidecar if none has been saved yet. Sidecars from before loop
+/// regions existed read as having none.
+pub fn load(file_path: &str) -> Result<Sidecar, AppError> {
+    let path = sidecar_path(file_path);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Sidecar::default()),
+        Err(e) => {
+            return Err(AppError::Io(format!(
+                "Failed to read {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+    let parse_error = |e: serde_json::Error| {
+        AppError::ParseFailed(format!("Failed to parse {}: {}", path.display(), e))
+    };
+    let document: serde_json::Value = serde_json::from_str(&contents).map_err(parse_error)?;
+    // Before loop regions, sidecars were a bare list of chapters.
+    if document.is_array() {
+        return Ok(Sidecar {
+            chapters: serde_json::from_value(document).map_err(parse_error)?,
+            loop_regions: Vec::new(),
+        });
+    }
+    serde_json::from_value(document).map_err(parse_error)
+}
+
+pub fn save(file_path: &str, sidecar: &Sidecar) -> Result<PathBuf, AppError> {
+    validate(&sidecar.chapters)?;
+    validate_regions(&sidecar.loop_regions)?;
+    let path = sidecar_path(file_path);
+    let json = serde_json::to_string_pretty(sidecar)
+        .map_err(|e| AppError::ParseFailed(format!("Failed to serialize markers: {}", e)))?;
+    write_atomic(&path, json.as_bytes())?;
+    Ok(path)
+}
@@ -0,0 +1,255 @@
+use serde::Deserialize;
+
+use crate::beatgrid;
+use crate::error::AppError;
+
+/// Beats per bar a click track accents unless told otherwise.
+pub const DEFAULT_BEATS_PER_BAR: u32 = 4;
+
+/// Longest click track `generate_click_track` synthesizes, in seconds.
+pub const MAX_CLICK_TRACK_SECS: f64 = 3.0 * 60.0 * 60.0;
+
+/// Sample rate clicks are synthesized at.
+pub const CLICK_SAMPLE_RATE: u32 = 44100;
+
+/// Pitch of an ordinary click and of the accented first beat of a bar.
+const CLICK_HZ: f64 = 1000.0;
+const ACCENT_HZ: f64 = 1500.0;
+
+/// Peak level of an ordinary click, and how much louder an accented one is,
+/// kept well below full scale so the clicks don't clip when mixed over a
+/// loud track.
+const CLICK_LEVEL: f64 = 0.4;
+const ACCENT_BOOST: f64 = 0.3;
+
+/// How long each click sounds, and how fast it dies away within that time.
+const CLICK_SECS: f64 = 0.03;
+const CLICK_DECAY_PER_SEC: f64 = 150.0;
+
+/// Times of the clicks of a track `duration` seconds long at `bpm`, with a
+/// downbeat at `offset`, paired with whether each is the accented first beat
+/// of a bar. Like the beat grid, the clicks extend back from `offset` to the
+/// start of the track.
+pub fn clicks(
+    bpm: f64,
+    offset: f64,
+    duration: f64,
+    beats_per_bar: u32,
+) -> Result<Vec<beatgrid::GridLine>, AppError> {
+    if !duration.is_finite() || duration <= 0.0 || duration > MAX_CLICK_TRACK_SECS {
+        return Err(AppError::InvalidInput(format!(
+            "A click track must be between 0 and {} seconds long, got {}",
+            MAX_CLICK_TRACK_SECS, duration
+        )));
+    }
+    let mut lines = beatgrid::grid_lines(bpm, offset, 0.0, duration, 1, beats_per_bar)?;
+    // A click right at the end would be cut off before it's heard.
+    lines.retain(|line| line.time < duration);
+    Ok(lines)
+}
+
+/// Describes an `aevalsrc` source playing the clicks listed by [`clicks`],
+/// for use as a `lavfi` input. Arguments are assumed to have been checked
+/// by [`clicks`].
+pub fn click_source(bpm: f64, offset: f64, duration: f64, beats_per_bar: u32) -> String {
+    format!(
+        "aevalsrc='{}':s={}:d={}",
+        click_expression(bpm, offset, beats_per_bar),
+        CLICK_SAMPLE_RATE,
+        duration
+    )
+}
+
+/// The `aevalsrc` expression for one channel of clicks at `bpm` with a
+/// downbeat at `offset`.
+///
+/// Every sample works out which beat it falls in from its own time, rather
+/// than counting elapsed beats, so the clicks don't drift over long tracks.
+fn click_expression(bpm: f64, offset: f64, beats_per_bar: u32) -> String {
+    let period = 60.0 / bpm;
+    // 0: time since the downbeat at `offset`, 1: beat index, 2: time into
+    // the beat, 3: whether the beat starts a bar.
+    format!(
+        "st(0,t-{offset});st(1,floor(ld(0)/{period}));st(2,ld(0)-ld(1)*{period});\
+         st(3,eq(mod(ld(1),{bar}),0));\
+         lt(ld(2),{length})*({level}+ld(3)*{accent_boost})*exp(-ld(2)*{decay})\
+         *sin(2*PI*({pitch}+ld(3)*{accent_shift})*ld(2))",
+        offset = offset,
+        period = period,
+        bar = beats_per_bar,
+        length = CLICK_SECS,
+        level = CLICK_LEVEL,
+        accent_boost = ACCENT_BOOST,
+        decay = CLICK_DECAY_PER_SEC,
+        pitch = CLICK_HZ,
+        accent_shift = ACCENT_HZ - CLICK_HZ,
+    )
+}
+
+/// Most bars a count-in may last.
+pub const MAX_COUNT_IN_BARS: u32 = 4;
+
+/// Clicks played before each repeat of an exported loop, counting the
+/// player in at the loop's tempo.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CountIn {
+    /// Tempo of the loop as it is in the source, e.g. from `detect_tempo`.
+    pub bpm: f64,
+    /// Whole bars to count, 1 unless given.
+    pub bars: Option<u32>,
+    /// Clicks per bar, the first accented, [`DEFAULT_BEATS_PER_BAR`] unless
+    /// given.
+    pub beats_per_bar: Option<u32>,
+}
+
+impl CountIn {
+    fn bars(&self) -> u32 {
+        self.bars.unwrap_or(1)
+    }
+
+    fn beats_per_bar(&self) -> u32 {
+        self.beats_per_bar.unwrap_or(DEFAULT_BEATS_PER_BAR)
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        beatgrid::grid_step(self.bpm, 1)?;
+        if !(1..=MAX_COUNT_IN_BARS).contains(&self.bars()) {
+            return Err(AppError::InvalidInput(format!(
+                "A count-in must last between 1 and {} bars, got {}",
+                MAX_COUNT_IN_BARS,
+                self.bars()
+            )));
+        }
+        if self.beats_per_bar() == 0 {
+            return Err(AppError::InvalidInput(
+                "A bar must have at least 1 beat".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Length in seconds of the count-in before a repeat played at `rate`
+    /// times the original speed, so at `rate` times the tempo.
+    pub fn length(&self, rate: f64) -> f64 {
+        (self.bars() * self.beats_per_bar()) as f64 * 60.0 / (self.bpm * rate)
+    }
+
+    /// Describes an `aevalsrc` source for the count-in before a repeat at
+    /// `rate`, for use as a `lavfi` input. It has the sample rate and as
+    /// many channels as the loop, so the two join without conversion.
+    pub fn source(&self, rate: f64, sample_rate: u32, channels: u8) -> String {
+        let expression = click_expression(self.bpm * rate, 0.0, self.beats_per_bar());
+        let sample_rate = if sample_rate == 0 {
+            CLICK_SAMPLE_RATE
+        } else {
+            sample_rate
+        };
+        format!(
+            "aevalsrc='{}':s={}:d={}",
+            vec![expression; channels.max(1) as usize].join("|"),
+            sample_rate,
+            self.length(rate)
+        )
+    }
+}
+
+/// Mixes the first audio stream of input 0 with the clicks of input 1 into
+/// `[out]`, as long as input 0. Levels are left alone rather than halved as
+/// `amix` does by default, so the track sounds as loud as it did.
+pub const MIX_FILTER: &str = "[0:a:0][1:a]amix=inputs=2:duration=first:normalize=0[out]";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_click_per_beat() {
+        for (bpm, offset, duration) in [(120.0, 0.0, 10.0), (90.0, 0.5, 60.0), (174.0, 0.0, 30.0)] {
+            let lines = clicks(bpm, offset, duration, DEFAULT_BEATS_PER_BAR).unwrap();
+            assert_eq!(lines.len() as f64, duration * bpm / 60.0, "at {} bpm", bpm);
+        }
+    }
+
+    #[test]
+    fn accents_the_first_beat_of_each_bar() {
+        let lines = clicks(120.0, 0.0, 6.0, 3).unwrap();
+        let accents: Vec<f64> = lines
+            .iter()
+            .filter(|l| l.downbeat)
+            .map(|l| l.time)
+            .collect();
+        assert_eq!(accents, [0.0, 1.5, 3.0, 4.5]);
+    }
+
+    #[test]
+    fn rejects_bad_durations() {
+        for duration in [0.0, -1.0, f64::NAN, MAX_CLICK_TRACK_SECS + 1.0] {
+            assert!(matches!(
+                clicks(120.0, 0.0, duration, DEFAULT_BEATS_PER_BAR),
+                Err(AppError::InvalidInput(_))
+            ));
+        }
+    }
+
+    fn count_in(bpm: f64, bars: Option<u32>, beats_per_bar: Option<u32>) -> CountIn {
+        CountIn {
+            bpm,
+            bars,
+            beats_per_bar,
+        }
+    }
+
+    #[test]
+    fn a_count_in_lasts_whole_bars_at_the_loop_tempo() {
+        assert_eq!(count_in(120.0, None, None).length(1.0), 2.0);
+        assert_eq!(count_in(90.0, Some(2), Some(3)).length(1.0), 4.0);
+        // A repeat slowed to half speed is counted in at half the tempo.
+        assert_eq!(count_in(120.0, None, None).length(0.5), 4.0);
+    }
+
+    #[test]
+    fn count_ins_add_to_the_output_length() {
+        let count_in = count_in(120.0, Some(2), None);
+        let rates = [1.0; 3];
+        let lengths = vec![count_in.length(1.0); rates.len()];
+        let graph = crate::effects::tempo_ramp_graph(&rates, 10.0, &lengths).unwrap();
+        assert_eq!(graph.length, 3.0 * (10.0 + 4.0));
+        assert_eq!(graph.inputs, 6);
+
+        let rates = [0.5, 1.0];
+        let lengths: Vec<f64> = rates.iter().map(|&rate| count_in.length(rate)).collect();
+        let graph = crate::effects::tempo_ramp_graph(&rates, 10.0, &lengths).unwrap();
+        assert_eq!(graph.length, (20.0 + 8.0) + (10.0 + 4.0));
+    }
+
+    #[test]
+    fn a_count_in_source_matches_the_loop() {
+        let source = count_in(120.0, None, Some(3)).source(1.0, 48000, 2);
+        assert!(source.starts_with("aevalsrc='"), "{}", source);
+        assert!(source.ends_with("':s=48000:d=1.5"), "{}", source);
+        let expression = click_expression(120.0, 0.0, 3);
+        assert_eq!(
+            source,
+            format!("aevalsrc='{0}|{0}':s=48000:d=1.5", expression)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_count_ins() {
+        for bad in [
+            count_in(0.0, None, None),
+            count_in(120.0, Some(0), None),
+            count_in(120.0, Some(MAX_COUNT_IN_BARS + 1), None),
+            count_in(120.0, None, Some(0)),
+        ] {
+            assert!(
+                matches!(bad.validate(), Err(AppError::InvalidInput(_))),
+                "accepted {:?}",
+                bad
+            );
+        }
+        assert!(count_in(120.0, Some(MAX_COUNT_IN_BARS), Some(7))
+            .validate()
+            .is_ok());
+    }
+}
@@ -0,0 +1,204 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use serde_json::Value;
+
+use crate::chapters;
+use crate::error::AppError;
+use crate::language;
+use crate::{Chapter, ChapterSource};
+
+/// `chpl` timestamps count 100 ns units.
+const CHPL_TIMESCALE: f64 = 10_000_000.0;
+
+/// Largest `chpl` atom we are willing to read into memory.
+const MAX_CHPL_BYTES: u64 = 1024 * 1024;
+
+/// Finds the first box of type `kind` between `start` and `end` and returns
+/// the byte range of its payload.
+fn find_box(
+    file: &mut File,
+    start: u64,
+    end: u64,
+    kind: &[u8; 4],
+) -> std::io::Result<Option<(u64, u64)>> {
+    let mut offset = start;
+    while offset + 8 <= end {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; 8];
+        file.read_exact(&mut header)?;
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let (payload_start, box_end) = match size {
+            0 => (offset + 8, end),
+            1 => {
+                let mut large = [0u8; 8];
+                file.read_exact(&mut large)?;
+                (
+                    offset + 16,
+                    offset.saturating_add(u64::from_be_bytes(large)),
+                )
+            }
+            _ => (offset + 8, offset + size),
+        };
+        if box_end < payload_start || box_end > end {
+            return Ok(None);
+        }
+        if &header[4..8] == kind {
+            return Ok(Some((payload_start, box_end)));
+        }
+        offset = box_end;
+    }
+    Ok(None)
+}
+
+/// Reads chapters from the Nero `chpl` atom (`moov/udta/chpl`) of an
+/// MP4/M4B file. Files without one, including non-MP4 files, yield no
+/// chapters.
+pub fn read_chpl(path: &str) -> Result<Vec<Chapter>, AppError> {
+    let io_error = |e: std::io::Error| AppError::Io(format!("Failed to read {}: {}", path, e));
+    let mut file = File::open(path).map_err(io_error)?;
+    let len = file.metadata().map_err(io_error)?.len();
+
+    // Anything that doesn't start with `ftyp` isn't an MP4 box structure.
+    let mut header = [0u8; 8];
+    if file.read_exact(&mut header).is_err() || &header[4..8] != b"ftyp" {
+        return Ok(Vec::new());
+    }
+
+    let mut range = (0, len);
+    for kind in [b"moov", b"udta", b"chpl"] {
+        match find_box(&mut file, range.0, range.1, kind).map_err(io_error)? {
+            Some(found) => range = found,
+            None => return Ok(Vec::new()),
+        }
+    }
+    if range.1 - range.0 > MAX_CHPL_BYTES {
+        return Err(AppError::ParseFailed(format!(
+            "The chpl atom in {} is unreasonably large",
+            path
+        )));
+    }
+
+    let mut payload = vec![0u8; (range.1 - range.0) as usize];
+    file.seek(SeekFrom::Start(range.0)).map_err(io_error)?;
+    file.read_exact(&mut payload).map_err(io_error)?;
+    Ok(parse_chpl(&payload))
+}
+
+/// Parses a `chpl` payload: version, flags, a reserved word in version 1,
+/// then a count followed by `(start: u64, title_len: u8, title)` entries.
+/// A truncated payload yields the entries read so far.
+fn parse_chpl(payload: &[u8]) -> Vec<Chapter> {
+    let Some(version) = payload.first() else {
+        return Vec::new();
+    };
+    let mut pos = if *version == 0 { 4 } else { 8 };
+    let Some(count) = payload.get(pos) else {
+        return Vec::new();
+    };
+    pos += 1;
+
+    let mut marks = Vec::new();
+    for _ in 0..*count {
+        let Some(start) = payload.get(pos..pos + 8) else {
+            break;
+        };
+        let start = u64::from_be_bytes(start.try_into().expect("slice of 8 bytes"));
+        let Some(title_len) = payload.get(pos + 8) else {
+            break;
+        };
+        let Some(title) = payload.get(pos + 9..pos + 9 + *title_len as usize) else {
+            break;
+        };
+        marks.push((
+            start as f64 / CHPL_TIMESCALE,
+            String::from_utf8_lossy(title).trim().to_string(),
+            None,
+        ));
+        pos += 9 + *title_len as usize;
+    }
+    chapters::to_chapters(marks, ChapterSource::Chpl)
+}
+
+/// Returns the index and language of the QuickTime text track to read
+/// chapters from in the output of `ffprobe -show_streams -select_streams d`.
+///
+/// Files with chapters in several languages have one text track per
+/// language; the one in `preferred` language wins, then the track marked
+/// as the default, then the first.
+pub fn find_text_track(parsed: &Value, preferred: Option<&str>) -> Option<(u64, Option<String>)> {
+    let tracks: Vec<&Value> = parsed["streams"]
+        .as_array()?
+        .iter()
+        .filter(|stream| matches!(stream["codec_tag_string"].as_str(), Some("text" | "tx3g")))
+        .collect();
+    let track = language::pick(
+        &tracks,
+        preferred,
+        |stream| track_language(stream),
+        |stream| stream["disposition"]["default"].as_i64() == Some(1),
+    )?;
+    Some((
+        track["index"].as_u64()?,
+        track_language(track).map(str::to_string),
+    ))
+}
+
+fn track_language(stream: &Value) -> Option<&str> {
+    stream["tags"]["language"]
+        .as_str()
+        .map(str::trim)
+        .filter(|language| !language.is_empty() && *language != "und")
+}
+
+/// Turns the packets of a text track (`ffprobe -show_packets -show_data`)
+/// into chapters, one per text sample.
+pub fn parse_text_track(parsed: &Value) -> Vec<Chapter> {
+    let Some(packets) = parsed["packets"].as_array() else {
+        return Vec::new();
+    };
+    let number = |value: &Value| value.as_str().and_then(|s| s.parse::<f64>().ok());
+
+    let marks = packets
+        .iter()
+        .filter_map(|packet| {
+            let start = number(&packet["pts_time"])?;
+            let end = number(&packet["duration_time"])
+                .filter(|d| *d > 0.0)
+                .map(|d| start + d);
+            let sample = decode_hexdump(packet["data"].as_str()?);
+            // Text samples are a big-endian length followed by the text,
+            // optionally followed by style boxes.
+            let len = u16::from_be_bytes([*sample.first()?, *sample.get(1)?]) as usize;
+            let text = sample.get(2..2 + len)?;
+            Some((start, String::from_utf8_lossy(text).trim().to_string(), end))
+        })
+        .collect();
+    chapters::to_chapters(marks, ChapterSource::TextTrack)
+}
+
+/// Decodes ffprobe's `-show_data` hexdump, whose lines look like
+/// `00000000: 0005 496e 7472 6f    ..Intro` with the hex in the first 40
+/// columns after the offset.
+fn decode_hexdump(dump: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for line in dump.lines() {
+        let Some((_, rest)) = line.split_once(": ") else {
+            continue;
+        };
+        let hex: String = rest
+            .chars()
+            .take(40)
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        for pair in hex.as_bytes().chunks_exact(2) {
+            if let Some(byte) = std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            {
+                bytes.push(byte);
+            }
+        }
+    }
+    bytes
+}
@@ -0,0 +1,75 @@
+/// Which streams [`mux_args`] copies instead of re-encoding, tried in this
+/// order: copying both is instant and lossless, but the output container
+/// may not accept the audio codec, and less often the video codec.
+pub const COPY_ATTEMPTS: [StreamCopy; 3] = [
+    StreamCopy {
+        video: true,
+        audio: true,
+    },
+    StreamCopy {
+        video: true,
+        audio: false,
+    },
+    StreamCopy {
+        video: false,
+        audio: false,
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamCopy {
+    pub video: bool,
+    pub audio: bool,
+}
+
+/// Builds the ffmpeg arguments that write video stream `video_stream` of
+/// `video_path` with the first audio stream of `audio_path` to
+/// `output_path`.
+///
+/// The audio is delayed by `audio_offset` seconds, or moved earlier when
+/// negative, and the output is cut to `duration`, the length of the video,
+/// so a longer rip doesn't leave a frozen last frame. Streams not copied as
+/// `copy` says are encoded with ffmpeg's default for the container.
+pub fn mux_args(
+    video_path: &str,
+    video_stream: u64,
+    audio_path: &str,
+    audio_offset: f64,
+    duration: Option<f64>,
+    output_path: &str,
+    copy: StreamCopy,
+) -> Vec<String> {
+    let mut args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        video_path.to_string(),
+    ];
+    if audio_offset != 0.0 {
+        args.push("-itsoffset".to_string());
+        args.push(audio_offset.to_string());
+    }
+    args.extend([
+        "-i".to_string(),
+        audio_path.to_string(),
+        "-map".to_string(),
+        format!("0:{}", video_stream),
+        "-map".to_string(),
+        "1:a:0".to_string(),
+    ]);
+    if copy.video {
+        args.push("-c:v".to_string());
+        args.push("copy".to_string());
+    }
+    if copy.audio {
+        args.push("-c:a".to_string());
+        args.push("copy".to_string());
+    }
+    if let Some(duration) = duration {
+        args.push("-t".to_string());
+        args.push(duration.to_string());
+    }
+    args.push(output_path.to_string());
+    args
+}
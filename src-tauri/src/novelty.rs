@@ -0,0 +1,390 @@
+use std::f64::consts::PI;
+
+use serde::Serialize;
+
+use crate::tempo;
+
+/// Sample rate audio is decoded at for finding timbre changes. Up to about
+/// 5 kHz holds most of what tells instruments and mixes apart.
+pub const ANALYSIS_SAMPLE_RATE: u32 = 11025;
+
+/// Half the width of the comparison kernel `detect_subsections` uses unless
+/// told otherwise, in seconds: how much audio on each side of a moment is
+/// compared. Longer kernels ignore changes that don't last.
+pub const DEFAULT_KERNEL_SECS: f64 = 8.0;
+
+/// Shortest and longest accepted kernel half widths, in seconds.
+pub const MIN_KERNEL_SECS: f64 = 1.0;
+pub const MAX_KERNEL_SECS: f64 = 60.0;
+
+/// Novelty a boundary needs unless told otherwise, from 0 (any change) to 1
+/// (the two sides have nothing in common).
+pub const DEFAULT_THRESHOLD: f64 = 0.25;
+
+/// Samples per analysis frame and between frames.
+const FRAME_SIZE: usize = 1024;
+const HOP_SIZE: usize = 512;
+
+/// Frames whose features are averaged into each block, and the length of a
+/// block in seconds, about half a second. It is the resolution boundaries
+/// are found at.
+const BLOCK_FRAMES: usize = 11;
+const BLOCK_SECS: f64 = (BLOCK_FRAMES * HOP_SIZE) as f64 / ANALYSIS_SAMPLE_RATE as f64;
+
+/// Mel bands the spectrum is summarised in, and coefficients kept of their
+/// cepstrum. The first coefficient, overall loudness, is left out, so a
+/// change in level alone isn't taken for a new song.
+const MEL_BANDS: usize = 26;
+const COEFFICIENTS: usize = 13;
+const LOWEST_HZ: f64 = 40.0;
+
+/// Frames quieter than this RMS are skipped, so silence between songs
+/// doesn't count as a timbre of its own.
+const SILENCE_RMS: f64 = 1e-4;
+
+/// A suggested split point.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Boundary {
+    pub time: f64,
+    /// How different the audio either side is, from 0 to 1.
+    pub novelty: f64,
+}
+
+/// Computes mel-frequency cepstral coefficients of mono samples as they are
+/// decoded at [`ANALYSIS_SAMPLE_RATE`], averaged over [`BLOCK_SECS`] blocks.
+pub struct TimbreMeter {
+    window: Vec<f64>,
+    filters: Vec<Vec<(usize, f64)>>,
+    pending: Vec<f32>,
+    /// Frames of the current block so far, and how many were loud enough
+    /// to add to `sum`.
+    frames: usize,
+    counted: usize,
+    sum: Vec<f64>,
+    /// One feature vector per block, `None` for silent blocks.
+    blocks: Vec<Option<Vec<f64>>>,
+}
+
+impl TimbreMeter {
+    pub fn new() -> Self {
+        let window = (0..FRAME_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / FRAME_SIZE as f64).cos())
+            .collect();
+        Self {
+            window,
+            filters: mel_filters(ANALYSIS_SAMPLE_RATE),
+            pending: Vec::with_capacity(FRAME_SIZE),
+            frames: 0,
+            counted: 0,
+            sum: vec![0.0; COEFFICIENTS - 1],
+            blocks: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, sample: f32) {
+        self.pending.push(sample);
+        if self.pending.len() < FRAME_SIZE {
+            return;
+        }
+        if let Some(coefficients) = self.frame_coefficients() {
+            for (sum, c) in self.sum.iter_mut().zip(coefficients) {
+                *sum += c;
+            }
+            self.counted += 1;
+        }
+        self.pending.drain(..HOP_SIZE);
+        self.frames += 1;
+        if self.frames == BLOCK_FRAMES {
+            // A block needs at least half its frames to stand for it.
+            let block = (self.counted * 2 >= self.frames).then(|| {
+                self.sum
+                    .iter()
+                    .map(|sum| sum / self.counted as f64)
+                    .collect()
+            });
+            self.blocks.push(block);
+            self.sum.iter_mut().for_each(|sum| *sum = 0.0);
+            self.frames = 0;
+            self.counted = 0;
+        }
+    }
+
+    /// Returns the feature vector of every whole block added; a final
+    /// partial block is dropped.
+    pub fn finish(self) -> Vec<Option<Vec<f64>>> {
+        self.blocks
+    }
+
+    fn frame_coefficients(&self) -> Option<Vec<f64>> {
+        let frame = &self.pending[..FRAME_SIZE];
+        let rms =
+            (frame.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / FRAME_SIZE as f64).sqrt();
+        if rms < SILENCE_RMS {
+            return None;
+        }
+        let mut re: Vec<f64> = frame
+            .iter()
+            .zip(&self.window)
+            .map(|(s, w)| *s as f64 * w)
+            .collect();
+        let mut im = vec![0.0; FRAME_SIZE];
+        tempo::fft(&mut re, &mut im);
+        let power: Vec<f64> = (0..=FRAME_SIZE / 2)
+            .map(|bin| re[bin] * re[bin] + im[bin] * im[bin])
+            .collect();
+        let bands: Vec<f64> = self
+            .filters
+            .iter()
+            .map(|filter| {
+                let energy: f64 = filter
+                    .iter()
+                    .map(|(bin, weight)| power[*bin] * weight)
+                    .sum();
+                (energy + 1e-10).ln()
+            })
+            .collect();
+        // DCT-II of the log band energies, skipping the 0th coefficient.
+        Some(
+            (1..COEFFICIENTS)
+                .map(|k| {
+                    bands
+                        .iter()
+                        .enumerate()
+                        .map(|(n, band)| {
+                            band * (PI * k as f64 * (n as f64 + 0.5) / MEL_BANDS as f64).cos()
+                        })
+                        .sum()
+                })
+                .collect(),
+        )
+    }
+}
+
+fn hz_to_mel(hz: f64) -> f64 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f64) -> f64 {
+    700.0 * (10f64.powf(mel / 2595.0) - 1.0)
+}
+
+/// Triangular filters evenly spaced in mel from [`LOWEST_HZ`] to Nyquist,
+/// as `(bin, weight)` pairs over the bins of a [`FRAME_SIZE`] FFT.
+fn mel_filters(sample_rate: u32) -> Vec<Vec<(usize, f64)>> {
+    let nyquist = sample_rate as f64 / 2.0;
+    let (low, high) = (hz_to_mel(LOWEST_HZ), hz_to_mel(nyquist));
+    let edges: Vec<f64> = (0..MEL_BANDS + 2)
+        .map(|i| mel_to_hz(low + (high - low) * i as f64 / (MEL_BANDS + 1) as f64))
+        .collect();
+    let bin_hz = sample_rate as f64 / FRAME_SIZE as f64;
+    edges
+        .windows(3)
+        .map(|edge| {
+            (0..=FRAME_SIZE / 2)
+                .filter_map(|bin| {
+                    let hz = bin as f64 * bin_hz;
+                    let weight = if hz <= edge[1] {
+                        (hz - edge[0]) / (edge[1] - edge[0])
+                    } else {
+                        (edge[2] - hz) / (edge[2] - edge[1])
+                    };
+                    (weight > 0.0).then_some((bin, weight))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Finds where the timbre of audio changes for good, as between songs of a
+/// medley, from the blocks of a [`TimbreMeter`].
+///
+/// Each dimension of the features is standardised over the whole range and
+/// blocks are compared by cosine similarity. A Gaussian tapered
+/// checkerboard kernel reaching `kernel_secs` to each side is slid along
+/// the diagonal of the resulting self-similarity matrix: its novelty is
+/// high where the audio is alike within each side but unlike across them.
+/// Peaks of the novelty above `threshold`, at least a kernel width apart,
+/// are returned with times relative to the start of the audio. Silent
+/// blocks are left out of the comparison.
+pub fn find_boundaries(
+    blocks: &[Option<Vec<f64>>],
+    kernel_secs: f64,
+    threshold: f64,
+) -> Vec<Boundary> {
+    let half = ((kernel_secs / BLOCK_SECS).round() as usize).max(1);
+    if blocks.len() < 2 * half + 1 {
+        return Vec::new();
+    }
+    let features = standardise(blocks);
+
+    let sigma = half as f64 / 2.0;
+    let taper = |offset: usize| (-(offset as f64 / sigma).powi(2) / 2.0).exp();
+    let novelty: Vec<f64> = (0..blocks.len())
+        .map(|centre| {
+            if centre < half || centre + half > blocks.len() {
+                return 0.0;
+            }
+            // Block `centre` starts the later side, so the boundary falls
+            // at its start: positions below `half` in the window are before
+            // it.
+            let window = &features[centre - half..centre + half];
+            let distance = |i: usize| if i < half { half - i - 1 } else { i - half };
+            let (mut score, mut weight) = (0.0, 0.0);
+            for (i, x) in window.iter().enumerate() {
+                let Some(x) = x else { continue };
+                for (j, y) in window.iter().enumerate().skip(i + 1) {
+                    let Some(y) = y else { continue };
+                    let w = taper(distance(i)) * taper(distance(j));
+                    let similarity = cosine(x, y);
+                    score += if (i < half) == (j < half) {
+                        w * similarity
+                    } else {
+                        -w * similarity
+                    };
+                    weight += w;
+                }
+            }
+            if weight > 0.0 {
+                (score / weight).max(0.0)
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    let mut boundaries: Vec<Boundary> = Vec::new();
+    let mut candidates: Vec<usize> = (0..novelty.len())
+        .filter(|&i| novelty[i] >= threshold)
+        .collect();
+    candidates.sort_by(|a, b| novelty[*b].total_cmp(&novelty[*a]));
+    let mut taken: Vec<usize> = Vec::new();
+    for index in candidates {
+        if taken.iter().all(|&other| index.abs_diff(other) >= 2 * half) {
+            taken.push(index);
+            boundaries.push(Boundary {
+                time: index as f64 * BLOCK_SECS,
+                novelty: novelty[index].min(1.0),
+            });
+        }
+    }
+    boundaries.sort_by(|a, b| a.time.total_cmp(&b.time));
+    boundaries
+}
+
+/// Scales each dimension of the non-silent blocks to zero mean and unit
+/// variance, so that no coefficient dominates the comparison.
+fn standardise(blocks: &[Option<Vec<f64>>]) -> Vec<Option<Vec<f64>>> {
+    let present: Vec<&Vec<f64>> = blocks.iter().flatten().collect();
+    let Some(first) = present.first() else {
+        return blocks.to_vec();
+    };
+    let count = present.len() as f64;
+    let dims = first.len();
+    let means: Vec<f64> = (0..dims)
+        .map(|d| present.iter().map(|v| v[d]).sum::<f64>() / count)
+        .collect();
+    let deviations: Vec<f64> = (0..dims)
+        .map(|d| {
+            let variance = present
+                .iter()
+                .map(|v| (v[d] - means[d]).powi(2))
+                .sum::<f64>()
+                / count;
+            variance.sqrt().max(1e-9)
+        })
+        .collect();
+    blocks
+        .iter()
+        .map(|block| {
+            block.as_ref().map(|v| {
+                v.iter()
+                    .enumerate()
+                    .map(|(d, x)| (x - means[d]) / deviations[d])
+                    .collect()
+            })
+        })
+        .collect()
+}
+
+fn cosine(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = (a.iter().map(|x| x * x).sum::<f64>() * b.iter().map(|y| y * y).sum::<f64>()).sqrt();
+    if norm > 0.0 {
+        dot / norm
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A dark, buzzy tone: a 110 Hz sawtooth's first harmonics, fading off.
+    fn dark(t: f64) -> f64 {
+        (1..=12)
+            .map(|k| (2.0 * PI * 110.0 * k as f64 * t).sin() / k as f64)
+            .sum::<f64>()
+            * 0.2
+    }
+
+    /// A bright, thin tone: a 1760 Hz sine with its octave.
+    fn bright(t: f64) -> f64 {
+        0.3 * (2.0 * PI * 1760.0 * t).sin() + 0.1 * (2.0 * PI * 3520.0 * t).sin()
+    }
+
+    /// A waveform by time in seconds, or silence.
+    type Timbre = Option<fn(f64) -> f64>;
+
+    /// Blocks of `parts`, each a timbre played for some seconds, with a
+    /// little noise so no two blocks are exactly alike.
+    fn blocks(parts: &[(Timbre, f64)]) -> Vec<Option<Vec<f64>>> {
+        let rate = ANALYSIS_SAMPLE_RATE as f64;
+        let mut meter = TimbreMeter::new();
+        let mut i = 0u64;
+        for &(timbre, seconds) in parts {
+            for _ in 0..(seconds * rate) as u64 {
+                let noise = (i.wrapping_mul(2_654_435_761) % 1000) as f64 / 1000.0 - 0.5;
+                let sample = timbre.map_or(0.0, |timbre| timbre(i as f64 / rate) + 0.01 * noise);
+                meter.add(sample as f32);
+                i += 1;
+            }
+        }
+        meter.finish()
+    }
+
+    #[test]
+    fn finds_where_one_timbre_gives_way_to_another() {
+        let blocks = blocks(&[(Some(dark), 20.0), (Some(bright), 20.0)]);
+        let boundaries = find_boundaries(&blocks, 4.0, DEFAULT_THRESHOLD);
+        assert_eq!(boundaries.len(), 1, "{:?}", boundaries);
+        assert!((boundaries[0].time - 20.0).abs() < 1.0, "{:?}", boundaries);
+        assert!(boundaries[0].novelty > 0.5, "{:?}", boundaries);
+    }
+
+    #[test]
+    fn one_timbre_has_no_boundary() {
+        let blocks = blocks(&[(Some(dark), 30.0)]);
+        assert!(find_boundaries(&blocks, 4.0, DEFAULT_THRESHOLD).is_empty());
+    }
+
+    #[test]
+    fn silence_between_songs_is_left_out() {
+        let blocks = blocks(&[(Some(dark), 16.0), (None, 3.0), (Some(bright), 16.0)]);
+        assert!(blocks.iter().any(Option::is_none));
+        let boundaries = find_boundaries(&blocks, 4.0, DEFAULT_THRESHOLD);
+        assert_eq!(boundaries.len(), 1, "{:?}", boundaries);
+        // Anywhere in the gap will do, to within a block.
+        assert!(
+            (15.5..=19.5).contains(&boundaries[0].time),
+            "{:?}",
+            boundaries
+        );
+    }
+
+    #[test]
+    fn needs_a_kernel_width_of_audio() {
+        let blocks = blocks(&[(Some(dark), 3.0), (Some(bright), 3.0)]);
+        assert!(find_boundaries(&blocks, 8.0, DEFAULT_THRESHOLD).is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tauri_plugin_shell::process::CommandChild;
+
+use crate::error::AppError;
+
+enum State {
+    Running(CommandChild),
+    /// The child was killed by `cancel`; kept until the runner notices so it
+    /// can report the cancellation instead of a generic ffmpeg failure.
+    Cancelled,
+}
+
+struct Operation {
+    kind: String,
+    started_at: u64,
+    progress: Option<f64>,
+    state: State,
+}
+
+/// What `list_active` reports about a running operation.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationInfo {
+    pub id: String,
+    /// The command that started the operation, such as `extract_chapter_audio`.
+    pub kind: String,
+    /// Milliseconds since the Unix epoch.
+    pub started_at: u64,
+    /// Percent done, or `None` while ffmpeg hasn't reported progress or the
+    /// length of the output is unknown.
+    pub progress: Option<f64>,
+}
+
+/// ffmpeg processes started on behalf of a frontend-supplied operation id.
+///
+/// Every method takes the lock only for the map update it makes, so no
+/// guard is ever held across an `.await` by the commands sharing it.
+#[derive(Default)]
+pub struct OperationManager {
+    operations: Mutex<HashMap<String, Operation>>,
+}
+
+impl OperationManager {
+    fn operations(&self) -> MutexGuard<'_, HashMap<String, Operation>> {
+        self.operations
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Tracks `child`, started by a command of `kind`, under `id`, killing
+    /// it if the id is already in use.
+    pub fn register(&self, id: &str, kind: &str, child: CommandChild) -> Result<(), AppError> {
+        let mut operations = self.operations();
+        if operations.contains_key(id) {
+            let _ = child.kill();
+            return Err(AppError::InvalidInput(format!(
+                "Operation {} is already running",
+                id
+            )));
+        }
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        operations.insert(
+            id.to_string(),
+            Operation {
+                kind: kind.to_string(),
+                started_at,
+                progress: None,
+                state: State::Running(child),
+            },
+        );
+        Ok(())
+    }
+
+    /// Records `percent` as the progress of `id`, if it is still running.
+    pub fn set_progress(&self, id: &str, percent: f64) {
+        if let Some(operation) = self.operations().get_mut(id) {
+            operation.progress = (percent >= 0.0).then_some(percent);
+        }
+    }
+
+    /// Kills the process running under `id`. Returns `false` if there is no
+    /// such operation.
+    pub fn cancel(&self, id: &str) -> bool {
+        let mut operations = self.operations();
+        let Some(operation) = operations.get_mut(id) else {
+            return false;
+        };
+        if let State::Running(child) = std::mem::replace(&mut operation.state, State::Cancelled) {
+            let _ = child.kill();
+        }
+        true
+    }
+
+    /// Forgets `id` once its process has exited, returning whether it was
+    /// cancelled.
+    pub fn mark_done(&self, id: &str) -> bool {
+        matches!(
+            self.operations().remove(id),
+            Some(Operation {
+                state: State::Cancelled,
+                ..
+            })
+        )
+    }
+
+    /// Number of operations whose process hasn't exited, cancelled ones
+    /// included.
+    pub fn count(&self) -> usize {
+        self.operations().len()
+    }
+
+    /// The operations still running, oldest first. Cancelled ones whose
+    /// process hasn't exited yet are left out.
+    pub fn list_active(&self) -> Vec<OperationInfo> {
+        let mut active: Vec<OperationInfo> = self
+            .operations()
+            .iter()
+            .filter(|(_, operation)| matches!(operation.state, State::Running(_)))
+            .map(|(id, operation)| OperationInfo {
+                id: id.clone(),
+                kind: operation.kind.clone(),
+                started_at: operation.started_at,
+                progress: operation.progress,
+            })
+            .collect();
+        active.sort_by(|a, b| a.started_at.cmp(&b.started_at).then(a.id.cmp(&b.id)));
+        active
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::Arc;
+
+    use tauri_plugin_shell::ShellExt;
+
+    use super::*;
+
+    const THREADS: usize = 8;
+    const PER_THREAD: usize = 12;
+
+    fn app() -> tauri::App<tauri::test::MockRuntime> {
+        tauri::test::mock_builder()
+            .plugin(tauri_plugin_shell::init())
+            .build(tauri::test::mock_context(tauri::test::noop_assets()))
+            .unwrap()
+    }
+
+    fn sleeper(app: &tauri::AppHandle<tauri::test::MockRuntime>) -> CommandChild {
+        app.shell().command("sleep").arg("30").spawn().unwrap().1
+    }
+
+    #[test]
+    fn concurrent_registers_and_cancels_stay_consistent() {
+        let app = app();
+        let manager = Arc::new(OperationManager::default());
+
+        let workers: Vec<_> = (0..THREADS)
+            .map(|thread| {
+                let app = app.handle().clone();
+                let manager = Arc::clone(&manager);
+                std::thread::spawn(move || {
+                    for n in 0..PER_THREAD {
+                        let id = format!("{}-{}", thread, n);
+                        manager.register(&id, "test", sleeper(&app)).unwrap();
+                        manager.set_progress(&id, n as f64);
+                        if n % 2 == 0 {
+                            assert!(manager.cancel(&id));
+                        }
+                        // Readers see a consistent map while others write.
+                        assert!(manager.list_active().len() <= THREADS * PER_THREAD);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(manager.count(), THREADS * PER_THREAD);
+        assert_eq!(manager.list_active().len(), THREADS * PER_THREAD / 2);
+
+        let workers: Vec<_> = (0..THREADS)
+            .map(|thread| {
+                let manager = Arc::clone(&manager);
+                std::thread::spawn(move || {
+                    for n in 0..PER_THREAD {
+                        let id = format!("{}-{}", thread, n);
+                        // Cancel what's left, and race a second cancel with
+                        // the runner finishing.
+                        manager.cancel(&id);
+                        assert!(manager.mark_done(&id));
+                        assert!(!manager.cancel(&id));
+                        assert!(!manager.mark_done(&id));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(manager.count(), 0);
+    }
+
+    #[test]
+    fn only_one_concurrent_register_wins_an_id() {
+        let app = app();
+        let manager = Arc::new(OperationManager::default());
+
+        let workers: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let app = app.handle().clone();
+                let manager = Arc::clone(&manager);
+                std::thread::spawn(move || manager.register("shared", "test", sleeper(&app)))
+            })
+            .collect();
+        let results: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+        assert!(results
+            .iter()
+            .filter(|r| r.is_err())
+            .all(|r| matches!(r, Err(AppError::InvalidInput(_)))));
+        assert_eq!(manager.list_active().len(), 1);
+        assert!(manager.cancel("shared"));
+        assert!(manager.mark_done("shared"));
+    }
+}
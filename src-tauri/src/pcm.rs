@@ -0,0 +1,164 @@
+use serde::Deserialize;
+
+/// Raw sample format ffmpeg decodes to when audio is analyzed in-process.
+///
+/// Samples of every format are read back as `f32` scaled to `-1.0..=1.0`.
+/// 16-bit is half the data of the others and plenty for drawing, while
+/// `f32le` keeps peaks above full scale and the exact levels needed for
+/// metering.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PcmFormat {
+    S16le,
+    S32le,
+    #[default]
+    F32le,
+}
+
+impl PcmFormat {
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            PcmFormat::S16le => 2,
+            PcmFormat::S32le | PcmFormat::F32le => 4,
+        }
+    }
+
+    /// Name of the format as an ffmpeg muxer, and in cache keys.
+    pub fn name(self) -> &'static str {
+        match self {
+            PcmFormat::S16le => "s16le",
+            PcmFormat::S32le => "s32le",
+            PcmFormat::F32le => "f32le",
+        }
+    }
+
+    /// Output arguments that make ffmpeg write this format to stdout.
+    pub fn output_args(self) -> Vec<String> {
+        vec![
+            "-f".to_string(),
+            self.name().to_string(),
+            "-acodec".to_string(),
+            format!("pcm_{}", self.name()),
+            "-".to_string(),
+        ]
+    }
+
+    /// Reads one sample from exactly [`Self::bytes_per_sample`] bytes.
+    fn sample(self, b: &[u8]) -> f32 {
+        match self {
+            PcmFormat::S16le => i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+            PcmFormat::S32le => {
+                (i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 / 2_147_483_648.0) as f32
+            }
+            PcmFormat::F32le => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        }
+    }
+
+    /// Reads all whole samples in `bytes`, ignoring a trailing partial one.
+    pub fn decode(self, bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks_exact(self.bytes_per_sample())
+            .map(|b| self.sample(b))
+            .collect()
+    }
+}
+
+/// Reads samples from PCM that arrives in pieces, such as the chunks of
+/// `stream_ffmpeg_raw`, keeping a sample split across two pieces.
+pub struct PcmDecoder {
+    format: PcmFormat,
+    leftover: Vec<u8>,
+}
+
+impl PcmDecoder {
+    pub fn new(format: PcmFormat) -> Self {
+        Self {
+            format,
+            leftover: Vec::with_capacity(format.bytes_per_sample()),
+        }
+    }
+
+    /// Consumes `bytes` and passes every sample it completes to `on_sample`.
+    pub fn push(&mut self, mut bytes: &[u8], mut on_sample: impl FnMut(f32)) {
+        let size = self.format.bytes_per_sample();
+        if !self.leftover.is_empty() {
+            let needed = (size - self.leftover.len()).min(bytes.len());
+            self.leftover.extend_from_slice(&bytes[..needed]);
+            bytes = &bytes[needed..];
+            if self.leftover.len() == size {
+                on_sample(self.format.sample(&self.leftover));
+                self.leftover.clear();
+            }
+        }
+
+        let samples = bytes.chunks_exact(size);
+        self.leftover.extend_from_slice(samples.remainder());
+        for b in samples {
+            on_sample(self.format.sample(b));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Full scale negative, silence, half scale and full scale positive in
+    /// each format, written out byte by byte, low byte first.
+    const S16LE: [u8; 8] = [0x00, 0x80, 0x00, 0x00, 0x00, 0x40, 0xff, 0x7f];
+    const S32LE: [u8; 16] = [
+        0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0xff, 0xff, 0xff,
+        0x7f,
+    ];
+    const F32LE: [u8; 16] = [
+        0x00, 0x00, 0x80, 0xbf, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x80,
+        0x3f,
+    ];
+
+    #[test]
+    fn decodes_each_format() {
+        assert_eq!(
+            PcmFormat::S16le.decode(&S16LE),
+            [-1.0, 0.0, 0.5, 32767.0 / 32768.0]
+        );
+        assert_eq!(
+            PcmFormat::S32le.decode(&S32LE),
+            [-1.0, 0.0, 0.5, (2_147_483_647.0 / 2_147_483_648.0) as f32]
+        );
+        assert_eq!(PcmFormat::F32le.decode(&F32LE), [-1.0, 0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn ignores_a_trailing_partial_sample() {
+        assert_eq!(PcmFormat::S16le.decode(&S16LE[..3]), [-1.0]);
+        assert_eq!(PcmFormat::F32le.decode(&F32LE[..7]), [-1.0]);
+    }
+
+    #[test]
+    fn keeps_samples_split_across_pieces() {
+        for (format, bytes) in [
+            (PcmFormat::S16le, &S16LE[..]),
+            (PcmFormat::S32le, &S32LE[..]),
+            (PcmFormat::F32le, &F32LE[..]),
+        ] {
+            for piece in 1..=5 {
+                let mut decoder = PcmDecoder::new(format);
+                let mut samples = Vec::new();
+                for chunk in bytes.chunks(piece) {
+                    decoder.push(chunk, |sample| samples.push(sample));
+                }
+                assert_eq!(samples, format.decode(bytes), "{:?} in {}s", format, piece);
+            }
+        }
+    }
+
+    #[test]
+    fn names_the_ffmpeg_format() {
+        assert_eq!(
+            PcmFormat::S32le.output_args(),
+            ["-f", "s32le", "-acodec", "pcm_s32le", "-"]
+        );
+        assert_eq!(PcmFormat::S16le.bytes_per_sample(), 2);
+        assert_eq!(PcmFormat::default(), PcmFormat::F32le);
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::util;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Playlist {
+    /// Absolute paths, or URLs, of the playable entries in playlist order.
+    pub paths: Vec<String>,
+    /// The `#EXTINF` title of each entry in `paths`, if it had one.
+    pub titles: Vec<Option<String>>,
+    /// One message per entry that was skipped, e.g. because the file is gone.
+    pub warnings: Vec<String>,
+}
+
+/// Splits the contents of an M3U playlist into `(location, title)` pairs.
+///
+/// Titles come from the `#EXTINF:<duration>,<title>` line preceding an
+/// entry. Every other `#` line is a comment or an extension we don't use.
+pub fn parse_entries(contents: &str) -> Vec<(String, Option<String>)> {
+    let mut entries = Vec::new();
+    let mut title = None;
+    // `lines` already drops the `\r` of CRLF line endings.
+    for line in contents.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            title = info
+                .split_once(',')
+                .map(|(_, title)| title.trim().to_string())
+                .filter(|title| !title.is_empty());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            entries.push((line.to_string(), title.take()));
+        }
+    }
+    entries
+}
+
+/// Resolves the entries of a playlist in `playlist_dir` into usable media
+/// sources. Relative paths are taken relative to `playlist_dir`; entries
+/// that don't exist or use an unsupported URL scheme are skipped with a
+/// warning.
+pub fn resolve(contents: &str, playlist_dir: &Path) -> Playlist {
+    let mut playlist = Playlist::default();
+    for (location, title) in parse_entries(contents) {
+        let source = if util::is_url(&location) {
+            location.clone()
+        } else {
+            playlist_dir.join(&location).to_string_lossy().into_owned()
+        };
+        match util::validate_media_source(&source) {
+            Ok(source) => {
+                playlist.paths.push(source);
+                playlist.titles.push(title);
+            }
+            Err(e) => playlist
+                .warnings
+                .push(format!("Skipped {}: {}", location, e.message())),
+        }
+    }
+    playlist
+}
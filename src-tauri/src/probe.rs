@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::AppError;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaInfo {
+    pub duration_seconds: f64,
+    /// Codec of the first audio stream.
+    pub codec_name: String,
+    pub sample_rate: u32,
+    pub channels: u8,
+    /// Bit rate of the audio stream, falling back to the container's overall rate.
+    pub bit_rate: Option<u64>,
+    /// More than two channels, e.g. a 5.1 concert recording, which many
+    /// setups can't play without `downmix_to_stereo`.
+    pub surround: bool,
+    /// Bits per sample of the decoded audio. `None` for lossy codecs, which
+    /// have no fixed resolution, and when ffprobe doesn't report it.
+    pub bit_depth: Option<u32>,
+    /// The source is above CD resolution, more than 16 bits or more than
+    /// 48 kHz, as with 24-bit/96 kHz studio masters, so exports at the
+    /// usual settings lose some of it.
+    pub high_resolution: bool,
+    /// The file also holds moving video, not just cover art.
+    #[serde(default)]
+    pub has_video: bool,
+}
+
+/// Sources with more bits per sample than this are high resolution.
+pub const HIGH_RES_BIT_DEPTH: u32 = 16;
+
+/// Sources sampled faster than this, in Hz, are high resolution.
+pub const HIGH_RES_SAMPLE_RATE: u32 = 48000;
+
+/// ffprobe prints most numbers as JSON strings, but not all of them.
+fn parse_number<T: std::str::FromStr>(value: &Value) -> Option<T> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        Value::Number(n) => n.to_string().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Returns the first stream with `codec_type == "audio"`.
+pub fn first_audio_stream(parsed: &Value) -> Option<&Value> {
+    parsed["streams"]
+        .as_array()?
+        .iter()
+        .find(|stream| stream["codec_type"].as_str() == Some("audio"))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioStream {
+    /// Index of the stream within the file, as used by `-map 0:<index>`.
+    pub index: u32,
+    pub codec: String,
+    pub language: Option<String>,
+    pub channels: u8,
+    /// In Hz, or 0 when ffprobe doesn't report it.
+    pub sample_rate: u32,
+    pub title: Option<String>,
+}
+
+/// Reads tag `name` of a stream, leaving out blank values and the `und`
+/// language ffmpeg writes for unknown.
+fn stream_tag(stream: &Value, name: &str) -> Option<String> {
+    stream["tags"][name]
+        .as_str()
+        .map(str::trim)
+        .filter(|value| !value.is_empty() && *value != "und")
+        .map(str::to_string)
+}
+
+/// Lists the audio streams in `ffprobe -show_streams` output, in file order.
+pub fn parse_audio_streams(parsed: &Value) -> Vec<AudioStream> {
+    let Some(streams) = parsed["streams"].as_array() else {
+        return Vec::new();
+    };
+    streams
+        .iter()
+        .filter(|stream| stream["codec_type"].as_str() == Some("audio"))
+        .filter_map(|stream| {
+            Some(AudioStream {
+                index: parse_number(&stream["index"])?,
+                codec: stream["codec_name"]
+                    .as_str()
+                    .unwrap_or("unknown")
+                    .to_string(),
+                language: stream_tag(stream, "language"),
+                channels: parse_number(&stream["channels"]).unwrap_or(0),
+                sample_rate: parse_number(&stream["sample_rate"]).unwrap_or(0),
+                title: stream_tag(stream, "title"),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubtitleStream {
+    /// Index of the stream within the file, as used by `-map 0:<index>`.
+    pub index: u32,
+    pub codec: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    /// Whether the stream is text, which can be converted to SRT or WebVTT,
+    /// rather than pictures such as DVD or Blu-ray subtitles.
+    pub text_based: bool,
+}
+
+/// Subtitle codecs stored as pictures, which can't be turned into text.
+const BITMAP_SUBTITLE_CODECS: [&str; 4] =
+    ["dvd_subtitle", "hdmv_pgs_subtitle", "dvb_subtitle", "xsub"];
+
+/// Lists the subtitle streams in `ffprobe -show_streams` output, in file
+/// order.
+pub fn parse_subtitle_streams(parsed: &Value) -> Vec<SubtitleStream> {
+    let Some(streams) = parsed["streams"].as_array() else {
+        return Vec::new();
+    };
+    streams
+        .iter()
+        .filter(|stream| stream["codec_type"].as_str() == Some("subtitle"))
+        .filter_map(|stream| {
+            let codec = stream["codec_name"].as_str().unwrap_or("unknown");
+            Some(SubtitleStream {
+                index: parse_number(&stream["index"])?,
+                codec: codec.to_string(),
+                language: stream_tag(stream, "language"),
+                title: stream_tag(stream, "title"),
+                text_based: !BITMAP_SUBTITLE_CODECS.contains(&codec),
+            })
+        })
+        .collect()
+}
+
+/// Returns the index of the first video stream that is really an embedded
+/// picture (cover art) rather than moving video.
+pub fn attached_picture_index(parsed: &Value) -> Option<u64> {
+    parsed["streams"]
+        .as_array()?
+        .iter()
+        .filter(|stream| stream["codec_type"].as_str() == Some("video"))
+        .find(|stream| stream["disposition"]["attached_pic"].as_i64() == Some(1))
+        .and_then(|stream| stream["index"].as_u64())
+}
+
+/// Returns the index of the first video stream that is moving video rather
+/// than an embedded picture.
+pub fn video_stream_index(parsed: &Value) -> Option<u64> {
+    parsed["streams"]
+        .as_array()?
+        .iter()
+        .filter(|stream| stream["codec_type"].as_str() == Some("video"))
+        .find(|stream| stream["disposition"]["attached_pic"].as_i64() != Some(1))
+        .and_then(|stream| stream["index"].as_u64())
+}
+
+/// Returns how many frames the stream `stream_index` holds, as reported by
+/// the container or else estimated from its duration and frame rate.
+pub fn video_frame_count(parsed: &Value, stream_index: u64) -> Option<u64> {
+    let stream = parsed["streams"]
+        .as_array()?
+        .iter()
+        .find(|stream| stream["index"].as_u64() == Some(stream_index))?;
+    if let Some(frames) = parse_number::<u64>(&stream["nb_frames"]).filter(|&frames| frames > 0) {
+        return Some(frames);
+    }
+    let (num, den) = stream["avg_frame_rate"].as_str()?.split_once('/')?;
+    let rate = num.parse::<f64>().ok()? / den.parse::<f64>().ok()?;
+    let duration = parse_number::<f64>(&stream["duration"]).or_else(|| format_duration(parsed))?;
+    let frames = (rate * duration).floor();
+    (frames.is_finite() && frames >= 1.0).then_some(frames as u64)
+}
+
+/// What kinds of streams a file holds, deciding which controls the UI shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    /// Audio, possibly with embedded cover art.
+    AudioOnly,
+    VideoWithAudio,
+    VideoOnly,
+    /// Neither audio nor moving video, e.g. a subtitle or data file.
+    Unknown,
+}
+
+/// Classifies `ffprobe -show_streams` output. Embedded pictures don't count
+/// as video, so an MP3 with album art is still audio only.
+pub fn classify(parsed: &Value) -> MediaKind {
+    match (
+        first_audio_stream(parsed).is_some(),
+        video_stream_index(parsed).is_some(),
+    ) {
+        (true, false) => MediaKind::AudioOnly,
+        (true, true) => MediaKind::VideoWithAudio,
+        (false, true) => MediaKind::VideoOnly,
+        (false, false) => MediaKind::Unknown,
+    }
+}
+
+/// Reads the container duration from `ffprobe -show_format` output.
+pub fn format_duration(parsed: &Value) -> Option<f64> {
+    parse_number(&parsed["format"]["duration"])
+}
+
+/// Builds [`MediaInfo`] from `ffprobe -show_format -show_streams` output.
+pub fn parse_media_info(parsed: &Value) -> Result<MediaInfo, AppError> {
+    let stream = first_audio_stream(parsed).ok_or_else(|| {
+        AppError::NoAudioStream("The file does not contain an audio stream".to_string())
+    })?;
+
+    // Some containers only report the duration on the stream.
+    let duration_seconds = format_duration(parsed)
+        .or_else(|| parse_number(&stream["duration"]))
+        .unwrap_or(0.0);
+
+    let channels = parse_number(&stream["channels"]).unwrap_or(0);
+    let sample_rate = parse_number(&stream["sample_rate"]).unwrap_or(0);
+    let bit_depth = stream_bit_depth(stream);
+    Ok(MediaInfo {
+        duration_seconds,
+        codec_name: stream["codec_name"]
+            .as_str()
+            .unwrap_or("unknown")
+            .to_string(),
+        sample_rate,
+        channels,
+        bit_rate: parse_number(&stream["bit_rate"])
+            .or_else(|| parse_number(&parsed["format"]["bit_rate"])),
+        surround: channels > 2,
+        bit_depth,
+        high_resolution: bit_depth.is_some_and(|depth| depth > HIGH_RES_BIT_DEPTH)
+            || sample_rate > HIGH_RES_SAMPLE_RATE,
+        has_video: video_stream_index(parsed).is_some(),
+    })
+}
+
+/// Reads the bits per sample of an ffprobe audio stream. FLAC and ALAC
+/// only report `bits_per_raw_sample`, PCM reports `bits_per_sample`, and
+/// lossy codecs report neither or 0.
+fn stream_bit_depth(stream: &Value) -> Option<u32> {
+    [&stream["bits_per_raw_sample"], &stream["bits_per_sample"]]
+        .into_iter()
+        .filter_map(parse_number::<u32>)
+        .find(|&bits| bits > 0)
+}
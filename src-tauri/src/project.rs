@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::markers::{self, LoopRegion};
+use crate::Chapter;
+
+/// Version written by [`write`], and the newest [`parse`] understands.
+pub const FORMAT_VERSION: u64 = 1;
+
+/// Extension of project bundles.
+pub const EXTENSION: &str = "glp";
+
+/// The media file a project belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectSource {
+    pub path: String,
+    /// `hashing::fast_hash` of the file when the project was exported, to
+    /// notice it was re-encoded or replaced since. `None` if it couldn't
+    /// be read.
+    pub hash: Option<String>,
+}
+
+/// Analysis results kept with a project, so they needn't be recomputed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectAnalysis {
+    #[serde(default)]
+    pub bpm: Option<f64>,
+    /// Time of the first downbeat, the phase anchor of the beat grid.
+    #[serde(default)]
+    pub beat_offset: Option<f64>,
+    /// Key as `detect_key` names it, e.g. "A minor".
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+/// A whole practice setup for one file in a single JSON bundle: the file it
+/// belongs to, its chapters and loop regions with their loop settings, and
+/// analysis results.
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "source": { "path": "/music/song.flac", "hash": "3f5a..." },
+///   "chapters": [ ... ],
+///   "loop_regions": [ ... ],
+///   "analysis": { "bpm": 120.0, "beat_offset": 0.42, "key": "A minor" }
+/// }
+/// ```
+///
+/// Chapters and loop regions are stored as in the marker sidecar.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub version: u64,
+    pub source: ProjectSource,
+    pub chapters: Vec<Chapter>,
+    #[serde(default)]
+    pub loop_regions: Vec<LoopRegion>,
+    #[serde(default)]
+    pub analysis: ProjectAnalysis,
+}
+
+fn validate(project: &Project) -> Result<(), AppError> {
+    if project.source.path.trim().is_empty() {
+        return Err(AppError::InvalidInput(
+            "The project does not name its media file".to_string(),
+        ));
+    }
+    markers::validate(&project.chapters)?;
+    markers::validate_regions(&project.loop_regions)?;
+    if let Some(bpm) = project
+        .analysis
+        .bpm
+        .filter(|bpm| !bpm.is_finite() || *bpm <= 0.0)
+    {
+        return Err(AppError::InvalidInput(format!(
+            "The project has a BPM of {}, which must be positive",
+            bpm
+        )));
+    }
+    Ok(())
+}
+
+/// Serializes `project` after checking its chapters and loop regions.
+/// The version is always written as [`FORMAT_VERSION`].
+pub fn write(project: &Project) -> Result<String, AppError> {
+    validate(project)?;
+    let project = Project {
+        version: FORMAT_VERSION,
+        ..project.clone()
+    };
+    serde_json::to_string_pretty(&project)
+        .map_err(|e| AppError::ParseFailed(format!("Failed to serialize the project: {}", e)))
+}
+
+/// Parses and checks a project bundle written by [`write`].
+///
+/// The version is checked before anything else, so a bundle from a newer
+/// release is reported as such instead of failing on whatever changed.
+pub fn parse(contents: &str) -> Result<Project, AppError> {
+    let document: Value = serde_json::from_str(contents.trim_start_matches('\u{feff}'))
+        .map_err(|e| AppError::ParseFailed(format!("The file is not valid JSON: {}", e)))?;
+    let version = document["version"].as_u64().ok_or_else(|| {
+        AppError::ParseFailed(
+            "The file has no numeric 'version', so it is not a project bundle".to_string(),
+        )
+    })?;
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(AppError::ParseFailed(format!(
+            "The project is version {}, but only versions up to {} are supported. It may have been written by a newer release.",
+            version, FORMAT_VERSION
+        )));
+    }
+    let project: Project = serde_json::from_value(document)
+        .map_err(|e| AppError::ParseFailed(format!("Invalid project bundle: {}", e)))?;
+    validate(&project)?;
+    Ok(project)
+}
@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::config::config_path;
+use crate::error::AppError;
+use crate::util::write_atomic;
+
+const RECENT_FILE: &str = "recent.json";
+
+/// Most entries kept in the recent files list.
+pub const MAX_RECENT_FILES: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentFile {
+    pub path: String,
+    pub title: String,
+    /// Milliseconds since the Unix epoch.
+    pub last_opened: u64,
+}
+
+/// Managed state holding the recent files, most recently opened first.
+#[derive(Default)]
+pub struct RecentFilesState(pub Mutex<Vec<RecentFile>>);
+
+/// Loads the saved list, or an empty one if none was saved or it can't be
+/// read.
+pub fn load(handle: &AppHandle) -> Vec<RecentFile> {
+    config_path(handle, RECENT_FILE)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(handle: &AppHandle, files: &[RecentFile]) -> Result<(), AppError> {
+    let json = serde_json::to_string_pretty(files)
+        .map_err(|e| AppError::ParseFailed(format!("Failed to serialize recent files: {}", e)))?;
+    write_atomic(&config_path(handle, RECENT_FILE)?, json.as_bytes())
+}
+
+/// Moves `path` to the front of `files`, adding it if it isn't listed yet,
+/// and drops the oldest entries beyond [`MAX_RECENT_FILES`].
+pub fn add(files: &mut Vec<RecentFile>, path: &str, title: &str) {
+    files.retain(|file| file.path != path);
+    let last_opened = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    files.insert(
+        0,
+        RecentFile {
+            path: path.to_string(),
+            title: title.to_string(),
+            last_opened,
+        },
+    );
+    files.truncate(MAX_RECENT_FILES);
+}
+
+/// Removes entries whose file no longer exists. Returns whether any were
+/// removed.
+pub fn prune_missing(files: &mut Vec<RecentFile>) -> bool {
+    let before = files.len();
+    files.retain(|file| Path::new(&file.path).is_file());
+    files.len() != before
+}
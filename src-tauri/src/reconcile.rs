@@ -0,0 +1,207 @@
+use serde::Serialize;
+
+use crate::markers::LoopRegion;
+use crate::{Chapter, ChapterSource};
+
+/// Largest difference in seconds between two chapter starts that still
+/// counts as the same chapter, to absorb rounding when a file is remuxed.
+const MATCH_TOLERANCE: f64 = 0.05;
+
+/// How saved markers compare to a file that changed since they were saved.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MarkerDiff {
+    /// Chapters the file has now that weren't saved.
+    pub added: Vec<Chapter>,
+    /// Saved chapters read from the file that it no longer has.
+    pub removed: Vec<Chapter>,
+    /// Ids of saved chapters, of any source, that start or end past the end
+    /// of the file.
+    pub out_of_range_chapters: Vec<String>,
+    /// Ids of saved loop regions that end past the end of the file.
+    pub out_of_range_regions: Vec<String>,
+}
+
+impl MarkerDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.out_of_range_chapters.is_empty()
+            && self.out_of_range_regions.is_empty()
+    }
+}
+
+/// Whether a chapter was read from the media file itself, rather than
+/// imported or made by hand, so a new version of the file replaces it.
+fn from_file(chapter: &Chapter) -> bool {
+    matches!(
+        chapter.source,
+        Some(
+            ChapterSource::Ffprobe
+                | ChapterSource::Chpl
+                | ChapterSource::TextTrack
+                | ChapterSource::VorbisComment
+        )
+    )
+}
+
+fn same_chapter(a: &Chapter, b: &Chapter) -> bool {
+    a.title == b.title && (a.start - b.start).abs() <= MATCH_TOLERANCE
+}
+
+/// Compares the `saved` chapters and `regions` of a file with the chapters
+/// `current` read from it now, and its `duration` when known.
+///
+/// Chapters are matched by title and start, so one that moved counts as
+/// removed and added. Only saved chapters read from the file can be
+/// removed; the user's own and imported ones are kept as they are, and
+/// only checked against the new length.
+pub fn diff(
+    saved: &[Chapter],
+    regions: &[LoopRegion],
+    current: &[Chapter],
+    duration: Option<f64>,
+) -> MarkerDiff {
+    let embedded: Vec<&Chapter> = saved.iter().filter(|c| from_file(c)).collect();
+    let added = current
+        .iter()
+        .filter(|chapter| !embedded.iter().any(|saved| same_chapter(saved, chapter)))
+        .cloned()
+        .collect();
+    let removed = embedded
+        .iter()
+        .filter(|saved| !current.iter().any(|chapter| same_chapter(saved, chapter)))
+        .map(|saved| (*saved).clone())
+        .collect();
+
+    let past_end = |time: f64| duration.is_some_and(|duration| time - duration > MATCH_TOLERANCE);
+    let out_of_range_chapters = saved
+        .iter()
+        .filter(|chapter| past_end(chapter.end.unwrap_or(chapter.start).max(chapter.start)))
+        .map(|chapter| chapter.id.clone())
+        .collect();
+    let out_of_range_regions = regions
+        .iter()
+        .filter(|region| past_end(region.end))
+        .map(|region| region.id.clone())
+        .collect();
+
+    MarkerDiff {
+        added,
+        removed,
+        out_of_range_chapters,
+        out_of_range_regions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(
+        id: &str,
+        title: &str,
+        start: f64,
+        end: Option<f64>,
+        source: Option<ChapterSource>,
+    ) -> Chapter {
+        Chapter {
+            id: id.to_string(),
+            title: title.to_string(),
+            start,
+            end,
+            source,
+            duration: end.map(|end| end - start),
+            ..Default::default()
+        }
+    }
+
+    fn embedded(id: &str, title: &str, start: f64, end: f64) -> Chapter {
+        chapter(id, title, start, Some(end), Some(ChapterSource::Ffprobe))
+    }
+
+    fn region(id: &str, start: f64, end: f64) -> LoopRegion {
+        LoopRegion {
+            id: id.to_string(),
+            name: id.to_uppercase(),
+            start,
+            end,
+            color: None,
+            enabled: true,
+            loop_settings: None,
+        }
+    }
+
+    fn ids(chapters: &[Chapter]) -> Vec<&str> {
+        chapters.iter().map(|c| c.id.as_str()).collect()
+    }
+
+    #[test]
+    fn an_unchanged_file_has_no_diff() {
+        let saved = [
+            embedded("a", "Intro", 0.0, 30.0),
+            embedded("b", "Verse", 30.0, 90.0),
+        ];
+        // Remuxing can nudge the starts a little.
+        let current = [
+            embedded("x", "Intro", 0.02, 30.0),
+            embedded("y", "Verse", 29.97, 90.0),
+        ];
+        assert!(diff(&saved, &[region("r", 10.0, 20.0)], &current, Some(90.0)).is_empty());
+    }
+
+    #[test]
+    fn lists_added_and_removed_chapters() {
+        let saved = [
+            embedded("a", "Intro", 0.0, 30.0),
+            embedded("b", "Verse", 30.0, 90.0),
+            embedded("c", "Solo", 90.0, 120.0),
+        ];
+        let current = [
+            embedded("x", "Intro", 0.0, 30.0),
+            // Moved, so removed and added again.
+            embedded("y", "Verse", 32.0, 90.0),
+            embedded("z", "Outro", 90.0, 100.0),
+        ];
+        let changes = diff(&saved, &[], &current, None);
+        assert_eq!(ids(&changes.removed), ["b", "c"]);
+        assert_eq!(ids(&changes.added), ["y", "z"]);
+        assert!(changes.out_of_range_chapters.is_empty());
+    }
+
+    #[test]
+    fn chapters_not_from_the_file_are_never_removed() {
+        let saved = [
+            chapter("cue", "Intro", 0.0, Some(30.0), Some(ChapterSource::Cue)),
+            // Made by hand.
+            chapter("mine", "Riff", 40.0, None, None),
+            embedded("tag", "Verse", 30.0, 90.0),
+        ];
+        let changes = diff(&saved, &[], &[], Some(200.0));
+        assert_eq!(ids(&changes.removed), ["tag"]);
+        assert!(changes.added.is_empty());
+    }
+
+    #[test]
+    fn flags_markers_past_a_shorter_file() {
+        let saved = [
+            embedded("a", "Intro", 0.0, 30.0),
+            embedded("b", "Verse", 30.0, 90.0),
+            chapter("late", "Coda", 95.0, None, None),
+            chapter("edge", "Riff", 60.0, Some(80.03), None),
+        ];
+        let current = [
+            embedded("x", "Intro", 0.0, 30.0),
+            embedded("y", "Verse", 30.0, 80.0),
+        ];
+        let regions = [region("fits", 10.0, 70.0), region("cut", 70.0, 85.0)];
+        let changes = diff(&saved, &regions, &current, Some(80.0));
+        assert_eq!(changes.out_of_range_chapters, ["b", "late"]);
+        assert_eq!(changes.out_of_range_regions, ["cut"]);
+
+        // Without a length nothing can be out of range.
+        let changes = diff(&saved, &regions, &current, None);
+        assert!(
+            changes.out_of_range_chapters.is_empty() && changes.out_of_range_regions.is_empty()
+        );
+    }
+}
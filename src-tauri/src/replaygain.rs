@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::AppError;
+
+/// Loudness ReplayGain 2.0 gains are relative to, in LUFS.
+const REPLAYGAIN_REFERENCE_LUFS: f64 = -18.0;
+
+/// Loudness `R128_*` gains are relative to, in LUFS (EBU R128).
+const R128_REFERENCE_LUFS: f64 = -23.0;
+
+/// Containers ffmpeg writes arbitrary tags to without re-encoding.
+const TAG_CONTAINERS: [&str; 9] = [
+    "mp3", "flac", "ogg", "opus", "m4a", "m4b", "mp4", "mka", "mkv",
+];
+
+/// Which tags players read the gain from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GainTagConvention {
+    /// `REPLAYGAIN_TRACK_GAIN` and `REPLAYGAIN_TRACK_PEAK`, which most
+    /// players understand.
+    ReplayGain,
+    /// `R128_TRACK_GAIN`, the convention for Opus files.
+    R128,
+}
+
+/// Checks that the container of `file_path`, judged by its extension, can
+/// hold gain tags.
+pub fn check_tag_support(file_path: &str) -> Result<String, AppError> {
+    let extension = Path::new(file_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    if TAG_CONTAINERS.contains(&extension.as_str()) {
+        return Ok(extension);
+    }
+    Err(AppError::InvalidInput(format!(
+        "{} files can't store gain tags. Normalize the file instead.",
+        if extension.is_empty() {
+            "Extensionless".to_string()
+        } else {
+            extension.to_uppercase()
+        }
+    )))
+}
+
+/// Returns the tags that make a player bring a track measured at
+/// `integrated_lufs`, with a true peak of `peak_db` dBFS if known, to the
+/// convention's reference loudness. Tags of the other convention, and the
+/// peak if it isn't known, are included with empty values, which removes
+/// them, so players don't apply stale values from an earlier analysis.
+pub fn tags(
+    convention: GainTagConvention,
+    integrated_lufs: f64,
+    peak_db: Option<f64>,
+) -> Vec<(&'static str, String)> {
+    match convention {
+        GainTagConvention::ReplayGain => {
+            let gain = REPLAYGAIN_REFERENCE_LUFS - integrated_lufs;
+            let peak = peak_db.map_or(String::new(), |peak_db| {
+                format!("{:.6}", 10f64.powf(peak_db / 20.0))
+            });
+            vec![
+                ("REPLAYGAIN_TRACK_GAIN", format!("{:+.2} dB", gain)),
+                ("REPLAYGAIN_TRACK_PEAK", peak),
+                ("R128_TRACK_GAIN", String::new()),
+            ]
+        }
+        GainTagConvention::R128 => {
+            // A Q7.8 fixed-point number of dB, as in the Opus header.
+            let gain = ((R128_REFERENCE_LUFS - integrated_lufs) * 256.0)
+                .round()
+                .clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+            vec![
+                ("R128_TRACK_GAIN", gain.to_string()),
+                ("REPLAYGAIN_TRACK_GAIN", String::new()),
+                ("REPLAYGAIN_TRACK_PEAK", String::new()),
+            ]
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::future::Future;
+use std::time::Duration;
+
+use crate::error::AppError;
+
+/// Default number of retries after a transient failure.
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Largest accepted retry count.
+pub const MAX_RETRIES: u32 = 10;
+
+/// Wait before the first retry, doubled for every further one.
+const BASE_DELAY: Duration = Duration::from_millis(250);
+const MAX_DELAY: Duration = Duration::from_secs(4);
+
+/// Messages, as printed by ffmpeg for the OS error, of failures that can go
+/// away on their own, mostly on network shares and over flaky connections.
+const TRANSIENT_ERRORS: [&str; 8] = [
+    "input/output error",
+    "connection reset",
+    "connection refused",
+    "connection timed out",
+    "network is unreachable",
+    "resource temporarily unavailable",
+    "broken pipe",
+    "stale file handle",
+];
+
+/// Whether `error` is worth retrying. Missing files, unreadable data,
+/// timeouts and cancellations never are, so only tool failures whose output
+/// names a transient I/O error qualify.
+pub fn is_transient(error: &AppError) -> bool {
+    let AppError::FfmpegFailed(message) = error else {
+        return false;
+    };
+    let message = message.to_ascii_lowercase();
+    TRANSIENT_ERRORS
+        .iter()
+        .any(|transient| message.contains(transient))
+}
+
+/// Runs `operation`, running it again up to `retries` more times while it
+/// fails with a transient error, waiting longer before every attempt.
+pub async fn with_retries<T, F, Fut>(retries: u32, mut operation: F) -> Result<T, AppError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, AppError>>,
+{
+    let mut delay = BASE_DELAY;
+    let mut attempt = 0;
+    loop {
+        match operation().await {
+            Err(e) if attempt < retries && is_transient(&e) => {
+                attempt += 1;
+                tracing::warn!(
+                    "Transient failure, retrying in {:?} ({}/{}): {}",
+                    delay,
+                    attempt,
+                    retries,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Runs a fake ffprobe through `with_retries` that fails with each of
+    /// `failures` in turn and then succeeds, returning the result and how
+    /// often it ran.
+    fn run_failing(retries: u32, failures: &[AppError]) -> (Result<&'static str, AppError>, usize) {
+        let calls = Cell::new(0);
+        let result = tauri::async_runtime::block_on(with_retries(retries, || {
+            let call = calls.get();
+            calls.set(call + 1);
+            let outcome = failures.get(call).cloned().map_or(Ok("probed"), Err);
+            async move { outcome }
+        }));
+        (result, calls.get())
+    }
+
+    fn io_error() -> AppError {
+        AppError::FfmpegFailed("FFprobe failed: /mnt/share/a.mp3: Input/output error".to_string())
+    }
+
+    #[test]
+    fn retries_transient_failures_until_success() {
+        let (result, calls) = run_failing(2, &[io_error(), io_error()]);
+        assert_eq!(result.unwrap(), "probed");
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn gives_up_after_the_retries() {
+        let (result, calls) = run_failing(1, &[io_error(), io_error()]);
+        assert!(matches!(result, Err(AppError::FfmpegFailed(_))));
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn does_not_retry_permanent_failures() {
+        for error in [
+            AppError::FileNotFound("File not found: a.mp3".to_string()),
+            AppError::FfmpegFailed("a.mp3: Invalid data found when processing input".to_string()),
+        ] {
+            let (result, calls) = run_failing(2, &[error]);
+            assert!(result.is_err());
+            assert_eq!(calls, 1);
+        }
+    }
+}
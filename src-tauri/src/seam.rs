@@ -0,0 +1,131 @@
+use serde::Serialize;
+
+/// Length of the audio on each side of the seam `check_loop_seam` looks at
+/// unless told otherwise, in milliseconds.
+pub const DEFAULT_WINDOW_MS: f64 = 5.0;
+
+/// Longest accepted window, in milliseconds. The window is only context for
+/// the seam, and a long one would let a click be averaged away.
+pub const MAX_WINDOW_MS: f64 = 100.0;
+
+/// Level given to a difference of exactly zero, in dB.
+const FLOOR_DB: f64 = -120.0;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SeamReport {
+    /// Level in dBFS of the jump at the seam that the slope of the audio
+    /// leading into it doesn't explain, in the worst channel. A click of
+    /// a jump from 0.5 to -0.5 is about 0 dB; a clean seam is far lower.
+    pub discontinuity_db: f64,
+    /// RMS level of the same measure between the samples within the window
+    /// on either side of the seam: how much the audio moves by itself. A
+    /// seam that isn't clearly above this can't be heard.
+    pub typical_db: f64,
+}
+
+fn to_db(amplitude: f64) -> f64 {
+    if amplitude > 0.0 {
+        (20.0 * amplitude.log10()).max(FLOOR_DB)
+    } else {
+        FLOOR_DB
+    }
+}
+
+/// Measures how cleanly the end of a loop of interleaved `samples` of
+/// `channels` channels runs into its start, looking at `window` frames on
+/// each side of the seam. `None` if there are too few frames to tell.
+///
+/// The measure is the second difference of the samples, which is zero for
+/// audio moving in a straight line, small for smooth waveforms and as large
+/// as the jump itself at a click.
+pub fn measure(samples: &[f32], channels: usize, window: usize) -> Option<SeamReport> {
+    let channels = channels.max(1);
+    let frames = samples.len() / channels;
+    if frames < 8 {
+        return None;
+    }
+    let window = window.clamp(3, frames / 2);
+
+    let mut report = SeamReport {
+        discontinuity_db: FLOOR_DB,
+        typical_db: FLOOR_DB,
+    };
+    for channel in 0..channels {
+        // The loop as it plays on repeat, wrapping from the last frame to
+        // the first.
+        let x = |frame: usize| samples[(frame % frames) * channels + channel] as f64;
+        let second_difference = |frame: usize| x(frame) - 2.0 * x(frame + 1) + x(frame + 2);
+
+        // The two second differences that include both sides of the seam,
+        // centred on the last frame and on the first.
+        let seam = second_difference(frames - 2)
+            .abs()
+            .max(second_difference(frames - 1).abs());
+        let around: Vec<f64> = (frames - window..frames - 2)
+            .chain(frames..frames + window - 2)
+            .map(second_difference)
+            .collect();
+        let rms = if around.is_empty() {
+            0.0
+        } else {
+            (around.iter().map(|d| d * d).sum::<f64>() / around.len() as f64).sqrt()
+        };
+
+        report.discontinuity_db = report.discontinuity_db.max(to_db(seam));
+        report.typical_db = report.typical_db.max(to_db(rms));
+    }
+    Some(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `frames` frames of a half-scale sine with a period of 100 frames.
+    fn sine(frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| 0.5 * (2.0 * std::f64::consts::PI * i as f64 / 100.0).sin() as f32)
+            .collect()
+    }
+
+    #[test]
+    fn a_whole_number_of_periods_loops_cleanly() {
+        let report = measure(&sine(44100), 1, 220).unwrap();
+        assert!(
+            report.discontinuity_db < report.typical_db + 3.0,
+            "{:?}",
+            report
+        );
+        assert!(report.discontinuity_db < -45.0, "{:?}", report);
+    }
+
+    #[test]
+    fn a_loop_cut_at_a_peak_clicks() {
+        // Ends a quarter period in, near the crest, and jumps back to zero.
+        let report = measure(&sine(44125), 1, 220).unwrap();
+        assert!(report.discontinuity_db > -10.0, "{:?}", report);
+        assert!(
+            report.discontinuity_db > report.typical_db + 30.0,
+            "{:?}",
+            report
+        );
+    }
+
+    #[test]
+    fn reports_the_worst_channel() {
+        let clicky = sine(44125);
+        let stereo: Vec<f32> = clicky.iter().flat_map(|&right| [0.0, right]).collect();
+        let report = measure(&stereo, 2, 220).unwrap();
+        let mono = measure(&clicky, 1, 220).unwrap();
+        assert_eq!(report.discontinuity_db, mono.discontinuity_db);
+        assert_eq!(report.typical_db, mono.typical_db);
+    }
+
+    #[test]
+    fn needs_a_few_frames() {
+        assert!(measure(&[0.0; 14], 2, 3).is_none());
+        let silence = measure(&[0.0; 16], 2, 3).unwrap();
+        assert_eq!(silence.discontinuity_db, FLOOR_DB);
+        assert_eq!(silence.typical_db, FLOOR_DB);
+    }
+}
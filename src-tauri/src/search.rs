@@ -0,0 +1,126 @@
+use crate::Chapter;
+
+/// Score for every matched character.
+const MATCH_SCORE: i64 = 1;
+/// Extra score when a character directly follows the previous match.
+const CONSECUTIVE_BONUS: i64 = 5;
+/// Extra score for matching the first character of a word.
+const WORD_BOUNDARY_BONUS: i64 = 8;
+/// Extra score when the match starts at the very beginning of the text.
+const PREFIX_BONUS: i64 = 10;
+/// Penalty per skipped character between two matches.
+const GAP_PENALTY: i64 = 1;
+
+/// Scores how well `query` matches `text` as a case-insensitive subsequence,
+/// or returns `None` if it doesn't match at all. Whitespace in the query is
+/// ignored, so "stair way" still finds "Stairway to Heaven".
+///
+/// Higher is better: prefix and word-boundary hits and runs of consecutive
+/// characters score extra, gaps between matched characters cost a little.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+
+    let char_score = |j: usize| {
+        let boundary = j == 0 || !text[j - 1].is_alphanumeric();
+        MATCH_SCORE
+            + if boundary { WORD_BOUNDARY_BONUS } else { 0 }
+            + if j == 0 { PREFIX_BONUS } else { 0 }
+    };
+
+    // best[j] is the best score for the query so far with its last character
+    // matched at text position j.
+    let mut best: Vec<Option<i64>> = (0..text.len())
+        .map(|j| (text[j] == query[0]).then(|| char_score(j)))
+        .collect();
+    for &q in &query[1..] {
+        let mut next = vec![None; text.len()];
+        for j in 0..text.len() {
+            if text[j] != q {
+                continue;
+            }
+            next[j] = (0..j)
+                .filter_map(|k| {
+                    let link = if k + 1 == j {
+                        CONSECUTIVE_BONUS
+                    } else {
+                        -GAP_PENALTY * (j - k - 1) as i64
+                    };
+                    best[k].map(|score| score + link)
+                })
+                .max()
+                .map(|score| score + char_score(j));
+        }
+        best = next;
+    }
+    best.into_iter().flatten().max()
+}
+
+/// Returns the chapters whose titles match `query`, best match first. Ties
+/// keep their original order, so an empty query returns every chapter as is.
+pub fn search(chapters: &[Chapter], query: &str) -> Vec<Chapter> {
+    let mut scored: Vec<(i64, &Chapter)> = chapters
+        .iter()
+        .filter_map(|chapter| fuzzy_score(query, &chapter.title).map(|score| (score, chapter)))
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored
+        .into_iter()
+        .map(|(_, chapter)| chapter.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(title: &str) -> Chapter {
+        Chapter {
+            id: title.to_string(),
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn titles(chapters: &[Chapter]) -> Vec<&str> {
+        chapters.iter().map(|c| c.title.as_str()).collect()
+    }
+
+    #[test]
+    fn matches_case_insensitive_subsequences() {
+        assert!(fuzzy_score("STAIR way", "Stairway to Heaven").is_some());
+        assert!(fuzzy_score("sth", "Stairway to Heaven").is_some());
+        assert_eq!(fuzzy_score("xyz", "Stairway to Heaven"), None);
+    }
+
+    #[test]
+    fn prefers_prefix_and_word_boundary_hits() {
+        let prefix = fuzzy_score("sol", "Solo").unwrap();
+        let boundary = fuzzy_score("sol", "Guitar Solo").unwrap();
+        let inside = fuzzy_score("sol", "Parasol").unwrap();
+        assert!(prefix > boundary);
+        assert!(boundary > inside);
+    }
+
+    #[test]
+    fn ranks_chapters_by_score() {
+        let chapters = [chapter("Parasol"), chapter("Guitar Solo"), chapter("Intro")];
+        assert_eq!(
+            titles(&search(&chapters, "sol")),
+            ["Guitar Solo", "Parasol"]
+        );
+    }
+
+    #[test]
+    fn an_empty_query_returns_every_chapter() {
+        let chapters = [chapter("Intro"), chapter("Verse")];
+        assert_eq!(titles(&search(&chapters, "  ")), ["Intro", "Verse"]);
+    }
+}
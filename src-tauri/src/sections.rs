@@ -0,0 +1,267 @@
+/// Sample rate audio is decoded at for finding sections. Loudness changes
+/// over seconds, so this only has to keep the energy of the signal.
+pub const ANALYSIS_SAMPLE_RATE: u32 = 8000;
+
+/// Shortest section `auto_segment` reports unless told otherwise, in
+/// seconds.
+pub const DEFAULT_MIN_SECTION: f64 = 20.0;
+
+/// `sensitivity` `auto_segment` uses unless told otherwise.
+pub const DEFAULT_SENSITIVITY: f64 = 0.5;
+
+/// Length of the windows the RMS level is measured over, in seconds.
+const WINDOW_SECS: f64 = 0.1;
+
+/// Length of the moving average the levels are smoothed with, in seconds,
+/// so single loud notes and short breaks don't count as a new section.
+const SMOOTHING_SECS: f64 = 3.0;
+
+/// Level given to windows of digital silence, in dB.
+const FLOOR_DB: f64 = -100.0;
+
+/// Difference between the loud and quiet parts of a file, in dB, needed for
+/// any boundary at a sensitivity of 0. At 1 the difference only has to
+/// exceed [`MIN_CONTRAST_DB`].
+const MAX_CONTRAST_DB: f64 = 12.0;
+const MIN_CONTRAST_DB: f64 = 1.0;
+
+/// Measures the RMS level of consecutive [`WINDOW_SECS`] windows of
+/// samples as they are decoded.
+pub struct LevelMeter {
+    window: usize,
+    sum: f64,
+    count: usize,
+    levels: Vec<f64>,
+}
+
+impl LevelMeter {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            window: ((sample_rate as f64 * WINDOW_SECS) as usize).max(1),
+            sum: 0.0,
+            count: 0,
+            levels: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, sample: f32) {
+        self.sum += (sample as f64).powi(2);
+        self.count += 1;
+        if self.count == self.window {
+            self.levels.push(to_db(self.sum / self.count as f64));
+            self.sum = 0.0;
+            self.count = 0;
+        }
+    }
+
+    /// Returns the level in dB of every whole window added; a final partial
+    /// window is dropped.
+    pub fn finish(self) -> Vec<f64> {
+        self.levels
+    }
+}
+
+fn to_db(mean_square: f64) -> f64 {
+    if mean_square > 0.0 {
+        (10.0 * mean_square.log10()).max(FLOOR_DB)
+    } else {
+        FLOOR_DB
+    }
+}
+
+/// A stretch of audio that stays loud or quiet throughout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Section {
+    pub start: f64,
+    pub end: f64,
+    pub loud: bool,
+}
+
+fn smooth(levels: &[f64]) -> Vec<f64> {
+    let half = ((SMOOTHING_SECS / WINDOW_SECS) as usize / 2).max(1);
+    let mut sums = Vec::with_capacity(levels.len() + 1);
+    sums.push(0.0);
+    for level in levels {
+        sums.push(sums[sums.len() - 1] + level);
+    }
+    (0..levels.len())
+        .map(|i| {
+            let (from, to) = (i.saturating_sub(half), (i + half + 1).min(levels.len()));
+            (sums[to] - sums[from]) / (to - from) as f64
+        })
+        .collect()
+}
+
+fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    sorted[((sorted.len() - 1) as f64 * fraction).round() as usize]
+}
+
+/// Splits audio into loud and quiet sections from the window `levels` of a
+/// [`LevelMeter`].
+///
+/// The smoothed level switches between loud and quiet when it crosses the
+/// middle of the file's quiet and loud levels, with some hysteresis so it
+/// doesn't flicker around the middle. Sections shorter than `min_section`
+/// seconds are absorbed by their neighbours, shortest first. `sensitivity`
+/// from 0 to 1 lowers how much louder the loud parts must be than the quiet
+/// ones before the file is split at all; a file without enough contrast is
+/// one section.
+pub fn find_sections(levels: &[f64], min_section: f64, sensitivity: f64) -> Vec<Section> {
+    if levels.is_empty() {
+        return Vec::new();
+    }
+    let whole = vec![Section {
+        start: 0.0,
+        end: levels.len() as f64 * WINDOW_SECS,
+        loud: true,
+    }];
+
+    let smoothed = smooth(levels);
+    let mut sorted = smoothed.clone();
+    sorted.sort_by(f64::total_cmp);
+    let (quiet, loud) = (percentile(&sorted, 0.1), percentile(&sorted, 0.9));
+    let required = MAX_CONTRAST_DB * (1.0 - sensitivity.clamp(0.0, 1.0));
+    if loud - quiet < required.max(MIN_CONTRAST_DB) {
+        return whole;
+    }
+    let middle = (quiet + loud) / 2.0;
+    let hysteresis = (loud - quiet) / 4.0;
+
+    // Runs of windows in one state, as (loud, first window, window count).
+    let mut runs: Vec<(bool, usize, usize)> = Vec::new();
+    let mut state = smoothed[0] >= middle;
+    for (index, &level) in smoothed.iter().enumerate() {
+        if state && level < middle - hysteresis {
+            state = false;
+        } else if !state && level > middle + hysteresis {
+            state = true;
+        }
+        match runs.last_mut() {
+            Some(run) if run.0 == state => run.2 += 1,
+            _ => runs.push((state, index, 1)),
+        }
+    }
+
+    let min_windows = (min_section / WINDOW_SECS).ceil() as usize;
+    while runs.len() > 1 {
+        let (shortest, &(_, _, count)) = runs
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, run)| run.2)
+            .expect("runs is not empty");
+        if count >= min_windows {
+            break;
+        }
+        // Neighbouring runs alternate, so absorbing a run into the one
+        // before it also joins the one after it.
+        if shortest == 0 {
+            let first = runs.remove(0);
+            runs[0].1 = first.1;
+            runs[0].2 += first.2;
+        } else {
+            let run = runs.remove(shortest);
+            runs[shortest - 1].2 += run.2;
+            if shortest < runs.len() {
+                let next = runs.remove(shortest);
+                runs[shortest - 1].2 += next.2;
+            }
+        }
+    }
+
+    // The hysteresis switches state a little after the level crosses the
+    // middle, so boundaries are moved back to the crossing.
+    let mut starts: Vec<usize> = runs.iter().map(|run| run.1).collect();
+    for index in 1..runs.len() {
+        let loud = runs[index].0;
+        while starts[index] > starts[index - 1] + 1
+            && (smoothed[starts[index] - 1] >= middle) == loud
+        {
+            starts[index] -= 1;
+        }
+    }
+    starts.push(levels.len());
+    runs.iter()
+        .zip(starts.windows(2))
+        .map(|(&(loud, _, _), bounds)| Section {
+            start: bounds[0] as f64 * WINDOW_SECS,
+            end: bounds[1] as f64 * WINDOW_SECS,
+            loud,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Window levels of a 220 Hz tone played at `amplitude` for `seconds`
+    /// per part.
+    fn levels(parts: &[(f64, f32)]) -> Vec<f64> {
+        let rate = ANALYSIS_SAMPLE_RATE as f64;
+        let mut meter = LevelMeter::new(ANALYSIS_SAMPLE_RATE);
+        let mut i = 0usize;
+        for &(seconds, amplitude) in parts {
+            for _ in 0..(seconds * rate) as usize {
+                let t = i as f64 / rate;
+                meter.add(amplitude * (2.0 * std::f64::consts::PI * 220.0 * t).sin() as f32);
+                i += 1;
+            }
+        }
+        meter.finish()
+    }
+
+    fn assert_sections(found: &[Section], expected: &[(f64, f64, bool)]) {
+        assert_eq!(found.len(), expected.len(), "found {:?}", found);
+        for (section, &(start, end, loud)) in found.iter().zip(expected) {
+            assert!(
+                (section.start - start).abs() < 1.0 && (section.end - end).abs() < 1.0,
+                "expected {}..{}, found {:?}",
+                start,
+                end,
+                section
+            );
+            assert_eq!(section.loud, loud, "{:?}", section);
+        }
+    }
+
+    #[test]
+    fn splits_loud_quiet_loud() {
+        let levels = levels(&[(60.0, 0.5), (40.0, 0.02), (60.0, 0.5)]);
+        assert_sections(
+            &find_sections(&levels, DEFAULT_MIN_SECTION, DEFAULT_SENSITIVITY),
+            &[
+                (0.0, 60.0, true),
+                (60.0, 100.0, false),
+                (100.0, 160.0, true),
+            ],
+        );
+    }
+
+    #[test]
+    fn short_changes_are_absorbed() {
+        let levels = levels(&[(60.0, 0.5), (18.0, 0.02), (60.0, 0.5)]);
+        assert_sections(
+            &find_sections(&levels, DEFAULT_MIN_SECTION, DEFAULT_SENSITIVITY),
+            &[(0.0, 138.0, true)],
+        );
+        assert_eq!(find_sections(&levels, 10.0, DEFAULT_SENSITIVITY).len(), 3);
+    }
+
+    #[test]
+    fn sensitivity_sets_the_contrast_needed() {
+        // About 6 dB between the parts.
+        let levels = levels(&[(60.0, 0.5), (40.0, 0.25), (60.0, 0.5)]);
+        assert_eq!(find_sections(&levels, DEFAULT_MIN_SECTION, 0.0).len(), 1);
+        assert_eq!(find_sections(&levels, DEFAULT_MIN_SECTION, 0.8).len(), 3);
+    }
+
+    #[test]
+    fn meters_whole_windows_only() {
+        let mut meter = LevelMeter::new(1000);
+        for _ in 0..250 {
+            meter.add(0.0);
+        }
+        assert_eq!(meter.finish(), [FLOOR_DB, FLOOR_DB]);
+        assert!(find_sections(&[], DEFAULT_MIN_SECTION, DEFAULT_SENSITIVITY).is_empty());
+    }
+}
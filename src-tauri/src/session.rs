@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::config::config_path;
+use crate::error::AppError;
+use crate::util::write_atomic;
+
+const SESSION_FILE: &str = "session.json";
+
+/// What the user was looking at when the app last closed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub last_file: Option<String>,
+    pub selected_chapter_id: Option<String>,
+    pub scroll_position: f64,
+    /// Inner `(width, height)` of the main window in logical pixels.
+    pub window_size: Option<(f64, f64)>,
+}
+
+/// Loads the saved session, or a default one if none was saved or it can't
+/// be read. A last file that has since been moved or deleted is dropped
+/// along with the chapter selected in it; everything else is kept.
+pub fn load(handle: &AppHandle) -> Session {
+    let mut session: Session = config_path(handle, SESSION_FILE)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+    if session
+        .last_file
+        .as_deref()
+        .is_some_and(|file| !Path::new(file).is_file())
+    {
+        session.last_file = None;
+        session.selected_chapter_id = None;
+    }
+    session
+}
+
+pub fn save(handle: &AppHandle, session: &Session) -> Result<(), AppError> {
+    let json = serde_json::to_string_pretty(session)
+        .map_err(|e| AppError::ParseFailed(format!("Failed to serialize session: {}", e)))?;
+    write_atomic(&config_path(handle, SESSION_FILE)?, json.as_bytes())
+}
@@ -0,0 +1,210 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+
+use crate::config::{self, config_path, FfmpegConfig};
+use crate::error::AppError;
+use crate::export;
+use crate::retry;
+use crate::transcode;
+use crate::util::write_atomic;
+use crate::waveform;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Where the ffmpeg settings were kept before they moved into
+/// [`SETTINGS_FILE`]. Read once so existing setups carry over.
+const LEGACY_FFMPEG_FILE: &str = "ffmpeg.json";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    /// Follow the operating system.
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+/// Everything the user can configure, saved together in one file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub ffmpeg: FfmpegConfig,
+    /// Largest size of the waveform cache directory in bytes.
+    pub waveform_cache_limit_bytes: u64,
+    /// Format exports use unless another is picked: `mp3`, `ogg` or `wav`.
+    pub default_export_format: String,
+    /// Directory exports are written to when no output path is given.
+    pub export_dir: Option<String>,
+    /// How exported files are named, see [`export::render_template`].
+    pub export_file_template: String,
+    pub theme: Theme,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            ffmpeg: FfmpegConfig::default(),
+            waveform_cache_limit_bytes: waveform::DEFAULT_CACHE_LIMIT_BYTES,
+            default_export_format: "mp3".to_string(),
+            export_dir: None,
+            export_file_template: export::DEFAULT_FILE_TEMPLATE.to_string(),
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Checks the settings before they replace `previous`. Executable paths
+    /// are only checked when they change, so a tool that went missing
+    /// doesn't block unrelated changes.
+    fn validate(&self, previous: &Settings) -> Result<(), AppError> {
+        let paths = [
+            (&self.ffmpeg.ffmpeg_path, &previous.ffmpeg.ffmpeg_path),
+            (&self.ffmpeg.ffprobe_path, &previous.ffmpeg.ffprobe_path),
+        ];
+        for (path, previous) in paths {
+            if let Some(path) = path.as_ref().filter(|_| path != previous) {
+                if !Path::new(path).is_file() {
+                    return Err(AppError::FileNotFound(format!(
+                        "Executable not found: {}",
+                        path
+                    )));
+                }
+            }
+        }
+        if self.ffmpeg.timeout_secs == Some(0) {
+            return Err(AppError::InvalidInput(
+                "The timeout must be at least one second".to_string(),
+            ));
+        }
+        if self
+            .ffmpeg
+            .threads
+            .is_some_and(|threads| threads > config::MAX_THREADS)
+        {
+            return Err(AppError::InvalidInput(format!(
+                "Thread count must be at most {}",
+                config::MAX_THREADS
+            )));
+        }
+        if self
+            .ffmpeg
+            .retries
+            .is_some_and(|retries| retries > retry::MAX_RETRIES)
+        {
+            return Err(AppError::InvalidInput(format!(
+                "Retry count must be at most {}",
+                retry::MAX_RETRIES
+            )));
+        }
+        transcode::codec_for_format(&self.default_export_format)?;
+        if self
+            .export_dir
+            .as_ref()
+            .is_some_and(|dir| dir.trim().is_empty())
+        {
+            return Err(AppError::InvalidInput(
+                "The export directory is empty".to_string(),
+            ));
+        }
+        export::validate_template(&self.export_file_template)?;
+        Ok(())
+    }
+}
+
+/// Managed state holding the current settings. Updates hold the lock while
+/// saving, so concurrent changes are applied and written one at a time.
+#[derive(Default)]
+pub struct SettingsState(pub Mutex<Settings>);
+
+/// Loads the saved settings, falling back to the old ffmpeg config file and
+/// then to defaults if nothing usable was saved.
+pub fn load(handle: &AppHandle) -> Settings {
+    let read = |file_name: &str| {
+        config_path(handle, file_name)
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+    };
+    if let Some(settings) = read(SETTINGS_FILE).and_then(|json| serde_json::from_str(&json).ok()) {
+        return settings;
+    }
+    Settings {
+        ffmpeg: read(LEGACY_FFMPEG_FILE)
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+        ..Settings::default()
+    }
+}
+
+fn save(handle: &AppHandle, settings: &Settings) -> Result<(), AppError> {
+    let json = serde_json::to_string_pretty(settings)
+        .map_err(|e| AppError::ParseFailed(format!("Failed to serialize settings: {}", e)))?;
+    write_atomic(&config_path(handle, SETTINGS_FILE)?, json.as_bytes())
+}
+
+/// Returns a snapshot of the current settings.
+pub fn current(handle: &AppHandle) -> Settings {
+    handle
+        .try_state::<SettingsState>()
+        .map(|state| {
+            state
+                .0
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone()
+        })
+        .unwrap_or_default()
+}
+
+/// Applies `change` to a copy of the current settings and, if the result is
+/// valid and saved, makes it current. Returns the new settings.
+pub fn update(
+    handle: &AppHandle,
+    change: impl FnOnce(&mut Settings) -> Result<(), AppError>,
+) -> Result<Settings, AppError> {
+    let state = handle.state::<SettingsState>();
+    let mut settings = state
+        .0
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut updated = settings.clone();
+    change(&mut updated)?;
+    updated.validate(&settings)?;
+    save(handle, &updated)?;
+    *settings = updated;
+    Ok(settings.clone())
+}
+
+/// Applies a partial update to `settings`: fields in `patch` replace the
+/// current ones, nested objects are merged, and `null` puts a field back to
+/// its default. Unknown fields are rejected.
+pub fn apply_patch(settings: &mut Settings, patch: &Value) -> Result<(), AppError> {
+    let mut merged = serde_json::to_value(&*settings)
+        .map_err(|e| AppError::ParseFailed(format!("Failed to serialize settings: {}", e)))?;
+    merge(&mut merged, patch);
+    *settings = serde_json::from_value(merged)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid settings: {}", e)))?;
+    Ok(())
+}
+
+/// JSON merge patch (RFC 7396). Removing a field lets `#[serde(default)]`
+/// fill it back in when the result is deserialized.
+fn merge(target: &mut Value, patch: &Value) {
+    let (Value::Object(target), Value::Object(patch)) = (&mut *target, patch) else {
+        *target = patch.clone();
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+use crate::config::Tool;
+
+/// Target the app was built for. Sidecars in the source tree carry it in
+/// their name, e.g. `binaries/ffmpeg-x86_64-pc-windows-msvc.exe`; bundling
+/// strips it.
+const TARGET_TRIPLE: &str = env!("TAURI_ENV_TARGET_TRIPLE");
+
+/// Resource subfolder sidecars are looked for in when shipped as resources
+/// rather than with `externalBin`.
+const RESOURCE_DIR: &str = "binaries";
+
+/// ffmpeg binaries that ship with the app, where found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BundledTools {
+    pub ffmpeg: Option<PathBuf>,
+    pub ffprobe: Option<PathBuf>,
+}
+
+impl BundledTools {
+    pub fn get(&self, tool: Tool) -> Option<&PathBuf> {
+        match tool {
+            Tool::Ffmpeg => self.ffmpeg.as_ref(),
+            Tool::Ffprobe => self.ffprobe.as_ref(),
+        }
+    }
+}
+
+/// Managed state holding the bundled binaries found by [`find_all`].
+#[derive(Default)]
+pub struct BundledToolsState(pub Mutex<BundledTools>);
+
+impl BundledToolsState {
+    pub fn get(&self) -> BundledTools {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Replaces the bundled binaries, returning whether they changed.
+    pub fn set(&self, tools: BundledTools) -> bool {
+        let mut current = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let changed = *current != tools;
+        *current = tools;
+        changed
+    }
+}
+
+/// Names a sidecar for `tool` may have: as bundled, and as in the source
+/// tree with the target triple.
+fn file_names(tool: Tool) -> [String; 2] {
+    let name = tool.default_program();
+    let suffix = std::env::consts::EXE_SUFFIX;
+    [
+        format!("{}{}", name, suffix),
+        format!("{}-{}{}", name, TARGET_TRIPLE, suffix),
+    ]
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Looks for a sidecar binary of `tool`: next to the app's executable,
+/// where Tauri puts `externalBin` sidecars, then among its resources.
+/// Files that exist but can't be run are skipped with a warning.
+pub fn find(handle: &AppHandle, tool: Tool) -> Option<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        dirs.push(dir);
+    }
+    if let Ok(dir) = handle.path().resource_dir() {
+        dirs.push(dir.join(RESOURCE_DIR));
+        dirs.push(dir);
+    }
+
+    for dir in dirs {
+        for name in file_names(tool) {
+            let path = dir.join(name);
+            if !path.exists() {
+                continue;
+            }
+            if is_executable(&path) {
+                return Some(path);
+            }
+            warn!("Ignoring bundled {} that isn't executable", path.display());
+        }
+    }
+    None
+}
+
+/// Looks for sidecar binaries of both tools.
+pub fn find_all(handle: &AppHandle) -> BundledTools {
+    let tools = BundledTools {
+        ffmpeg: find(handle, Tool::Ffmpeg),
+        ffprobe: find(handle, Tool::Ffprobe),
+    };
+    info!("Bundled ffmpeg binaries: {:?}", tools);
+    tools
+}
@@ -0,0 +1,187 @@
+use crate::error::AppError;
+
+/// Default level below which audio counts as silence.
+pub const DEFAULT_THRESHOLD_DB: f64 = -40.0;
+/// Default minimum length of a silence worth reporting, in seconds.
+pub const DEFAULT_MIN_DURATION: f64 = 0.5;
+/// Default silence kept at each end by [`trim_filter`], in seconds, so
+/// reverb tails and pick attacks survive the trim.
+pub const DEFAULT_TRIM_PADDING: f64 = 0.05;
+/// Longest padding [`trim_filter`] accepts, in seconds.
+const MAX_TRIM_PADDING: f64 = 5.0;
+
+/// Builds the `silencedetect` filter for the given threshold and duration.
+pub fn filter(threshold_db: f64, min_duration: f64) -> String {
+    format!("silencedetect=noise={}dB:d={}", threshold_db, min_duration)
+}
+
+/// Builds a filter chain that strips leading and trailing audio quieter than
+/// `threshold_db`, keeping up to `padding` seconds of it at each end.
+///
+/// `silenceremove` only trims reliably from the start, so the chain trims,
+/// reverses, trims what was the tail and reverses back. Silence inside the
+/// loop is left alone.
+pub fn trim_filter(threshold_db: f64, padding: f64) -> Result<String, AppError> {
+    if !threshold_db.is_finite() || threshold_db > 0.0 {
+        return Err(AppError::InvalidInput(format!(
+            "Silence threshold must be 0 dB or below, got {}",
+            threshold_db
+        )));
+    }
+    if !(0.0..=MAX_TRIM_PADDING).contains(&padding) {
+        return Err(AppError::InvalidInput(format!(
+            "Padding of {}s is out of range (0..={})",
+            padding, MAX_TRIM_PADDING
+        )));
+    }
+
+    let trim_start = format!(
+        "silenceremove=start_periods=1:start_threshold={}dB:start_silence={}",
+        threshold_db, padding
+    );
+    Ok(format!("{0},areverse,{0},areverse", trim_start))
+}
+
+/// Reads the value following `key` on a `silencedetect` log line.
+fn value_after(line: &str, key: &str) -> Option<f64> {
+    let (_, rest) = line.split_once(key)?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+/// Pairs up the `silence_start` / `silence_end` lines logged by
+/// `silencedetect` into `(start, end)` ranges.
+///
+/// When the input ends while still silent ffmpeg never logs the final
+/// `silence_end`, so that range is closed at `file_duration` if known.
+pub fn parse(stderr: &str, file_duration: Option<f64>) -> Vec<(f64, f64)> {
+    let mut ranges = Vec::new();
+    let mut open_start = None;
+
+    for line in stderr.lines().filter(|l| l.contains("silencedetect")) {
+        if let Some(start) = value_after(line, "silence_start:") {
+            open_start = Some(start.max(0.0));
+        } else if let Some(end) = value_after(line, "silence_end:") {
+            if let Some(start) = open_start.take() {
+                ranges.push((start, end));
+            }
+        }
+    }
+
+    if let (Some(start), Some(end)) = (open_start, file_duration) {
+        if end > start {
+            ranges.push((start, end));
+        }
+    }
+    ranges
+}
+
+/// Finds the longest stretch of `start..end` outside every range of
+/// `silences`, which may overlap, run past the range or come in any order.
+/// `None` if the whole range is silent.
+pub fn longest_sound(silences: &[(f64, f64)], start: f64, end: f64) -> Option<(f64, f64)> {
+    let mut silences: Vec<(f64, f64)> = silences
+        .iter()
+        .filter(|(s, e)| *e > start && *s < end)
+        .copied()
+        .collect();
+    silences.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut best: Option<(f64, f64)> = None;
+    let mut consider = |from: f64, to: f64| {
+        if to > from && best.is_none_or(|(s, e)| to - from > e - s) {
+            best = Some((from, to));
+        }
+    };
+    let mut sound_from = start;
+    for (silence_start, silence_end) in silences {
+        consider(sound_from, silence_start.min(end));
+        sound_from = sound_from.max(silence_end);
+    }
+    consider(sound_from, end);
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trims_both_ends_keeping_the_padding() {
+        for (padding, kept) in [(0.0, "0"), (0.05, "0.05"), (1.5, "1.5"), (5.0, "5")] {
+            let trim = format!(
+                "silenceremove=start_periods=1:start_threshold=-40dB:start_silence={}",
+                kept
+            );
+            assert_eq!(
+                trim_filter(-40.0, padding).unwrap(),
+                format!("{0},areverse,{0},areverse", trim)
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_padding_out_of_range() {
+        for padding in [-0.1, 5.5, f64::NAN] {
+            assert!(matches!(
+                trim_filter(-40.0, padding),
+                Err(AppError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn refuses_a_threshold_above_full_scale() {
+        assert!(trim_filter(3.0, 0.05).is_err());
+    }
+
+    /// What `silencedetect` logs for 20 seconds of audio with a silent gap
+    /// from 8.5 to 11.25 and silence again from 18 to the end.
+    const GAP_IN_THE_MIDDLE: &str = "\
+[silencedetect @ 0x1] silence_start: 8.5
+[silencedetect @ 0x1] silence_end: 11.25 | silence_duration: 2.75
+size=N/A time=00:00:15.00 bitrate=N/A speed= 300x
+[silencedetect @ 0x1] silence_start: 18
+";
+
+    #[test]
+    fn pairs_up_logged_silences() {
+        assert_eq!(
+            parse(GAP_IN_THE_MIDDLE, Some(20.0)),
+            [(8.5, 11.25), (18.0, 20.0)]
+        );
+        assert_eq!(parse(GAP_IN_THE_MIDDLE, None), [(8.5, 11.25)]);
+    }
+
+    #[test]
+    fn finds_the_longest_sound_around_a_gap() {
+        let silences = parse(GAP_IN_THE_MIDDLE, Some(20.0));
+        assert_eq!(longest_sound(&silences, 0.0, 20.0), Some((0.0, 8.5)));
+        // Offset into the file, as suggest_loop_region shifts them.
+        let shifted: Vec<(f64, f64)> = silences.iter().map(|(s, e)| (s + 4.0, e + 4.0)).collect();
+        assert_eq!(longest_sound(&shifted, 8.0, 24.0), Some((15.25, 22.0)));
+    }
+
+    #[test]
+    fn longest_sound_handles_overlaps_and_order() {
+        let silences = [
+            (12.0, 14.0),
+            (2.0, 5.0),
+            (4.0, 6.0),
+            (-1.0, 1.0),
+            (19.0, 30.0),
+        ];
+        assert_eq!(longest_sound(&silences, 0.0, 20.0), Some((6.0, 12.0)));
+        assert_eq!(longest_sound(&[], 3.0, 7.0), Some((3.0, 7.0)));
+        assert_eq!(longest_sound(&[(0.0, 5.0), (4.0, 10.0)], 1.0, 9.0), None);
+    }
+
+    #[test]
+    fn a_sound_snaps_inside_the_beat_grid() {
+        let silences = parse(GAP_IN_THE_MIDDLE, Some(20.0));
+        let (start, end) = longest_sound(&silences, 0.0, 20.0).unwrap();
+        assert_eq!(
+            crate::beatgrid::quantize_within(start, end, 120.0, 0.2, 1).unwrap(),
+            (0.2, 8.2)
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use crate::error::AppError;
+
+pub const DEFAULT_WIDTH: u32 = 1024;
+pub const DEFAULT_HEIGHT: u32 = 512;
+pub const DEFAULT_COLOR_SCHEME: &str = "intensity";
+
+/// Image dimensions accepted by [`filter`]. `showspectrumpic` holds the whole
+/// picture in memory, so this keeps a typo from exhausting it.
+const MIN_DIMENSION: u32 = 64;
+const MAX_WIDTH: u32 = 8192;
+const MAX_HEIGHT: u32 = 4096;
+
+/// Color schemes supported by ffmpeg's `showspectrumpic`.
+const COLOR_SCHEMES: [&str; 15] = [
+    "channel",
+    "intensity",
+    "rainbow",
+    "moreland",
+    "nebulae",
+    "fire",
+    "fiery",
+    "fruit",
+    "cool",
+    "magma",
+    "green",
+    "viridis",
+    "plasma",
+    "cividis",
+    "terrain",
+];
+
+/// Builds the `showspectrumpic` filter rendering a `width`x`height` image.
+pub fn filter(width: u32, height: u32, color_scheme: &str) -> Result<String, AppError> {
+    if !(MIN_DIMENSION..=MAX_WIDTH).contains(&width)
+        || !(MIN_DIMENSION..=MAX_HEIGHT).contains(&height)
+    {
+        return Err(AppError::InvalidInput(format!(
+            "Spectrogram size {}x{} is out of range ({}-{} wide, {}-{} high)",
+            width, height, MIN_DIMENSION, MAX_WIDTH, MIN_DIMENSION, MAX_HEIGHT
+        )));
+    }
+    if !COLOR_SCHEMES.contains(&color_scheme) {
+        return Err(AppError::InvalidInput(format!(
+            "Unknown color scheme '{}'. Use one of: {}",
+            color_scheme,
+            COLOR_SCHEMES.join(", ")
+        )));
+    }
+    Ok(format!(
+        "showspectrumpic=s={}x{}:color={}:legend=1",
+        width, height, color_scheme
+    ))
+}
@@ -0,0 +1,58 @@
+use serde::Deserialize;
+
+/// Text subtitle formats `extract_subtitles` can write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    /// The ffmpeg encoder and muxer, which share a name for both formats.
+    fn ffmpeg_name(self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "webvtt",
+        }
+    }
+}
+
+/// Builds the ffmpeg arguments that convert subtitle stream `stream_index`
+/// of `file_path` to `format` on stdout, keeping only the cues that start
+/// within `range`.
+///
+/// The range is applied on the output with the input timestamps kept, so
+/// cue times stay as in the whole file and line up with its playback. A cue
+/// that starts before the range is left out even if it is still showing
+/// when the range begins.
+pub fn extract_args(
+    file_path: &str,
+    stream_index: u32,
+    range: Option<(f64, f64)>,
+    format: SubtitleFormat,
+) -> Vec<String> {
+    let mut args = vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-copyts".to_string(),
+        "-i".to_string(),
+        file_path.to_string(),
+        "-map".to_string(),
+        format!("0:{}", stream_index),
+    ];
+    if let Some((start, end)) = range {
+        args.push("-ss".to_string());
+        args.push(start.to_string());
+        args.push("-to".to_string());
+        args.push(end.to_string());
+    }
+    args.extend([
+        "-c:s".to_string(),
+        format.ffmpeg_name().to_string(),
+        "-f".to_string(),
+        format.ffmpeg_name().to_string(),
+        "-".to_string(),
+    ]);
+    args
+}
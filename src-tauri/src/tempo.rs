@@ -0,0 +1,501 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+/// Sample rate the audio is decoded at before tempo analysis.
+pub const ANALYSIS_SAMPLE_RATE: u32 = 22050;
+
+/// Samples per analysis frame when building the onset envelope.
+const FRAME_SIZE: usize = 512;
+/// Samples between the starts of consecutive frames.
+const HOP_SIZE: usize = 128;
+
+const MIN_BPM: f64 = 40.0;
+const MAX_BPM: f64 = 240.0;
+
+/// RMS level below which the input is treated as silence.
+const SILENCE_RMS: f64 = 1e-4;
+
+/// How strong a fractional-period correlation must be, relative to the best
+/// peak, to be preferred as the beat.
+const OCTAVE_RATIO: f64 = 0.8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TempoEstimate {
+    pub bpm: f64,
+    /// How strongly the onsets repeat at `bpm`, from 0 (no pulse) to 1.
+    pub confidence: f64,
+}
+
+/// Estimates the tempo of mono `samples` recorded at `sample_rate`.
+///
+/// The signal is reduced to an onset strength envelope (the rectified rise in
+/// log energy between frames) whose autocorrelation peaks at the beat period.
+/// Silent or pulseless input yields a confidence near zero instead of a guess.
+pub fn estimate_tempo(samples: &[f32], sample_rate: u32) -> TempoEstimate {
+    let unknown = TempoEstimate {
+        bpm: 0.0,
+        confidence: 0.0,
+    };
+
+    let rms = (samples.iter().map(|s| (*s as f64).powi(2)).sum::<f64>()
+        / samples.len().max(1) as f64)
+        .sqrt();
+    if rms < SILENCE_RMS {
+        return unknown;
+    }
+
+    let envelope = onset_envelope(samples);
+    let frames_per_second = sample_rate as f64 / HOP_SIZE as f64;
+    let min_lag = (frames_per_second * 60.0 / MAX_BPM).floor() as usize;
+    let max_lag = (frames_per_second * 60.0 / MIN_BPM).ceil() as usize;
+    if envelope.len() <= max_lag + 1 || min_lag < 2 {
+        return unknown;
+    }
+
+    let mean = envelope.iter().sum::<f64>() / envelope.len() as f64;
+    let centered: Vec<f64> = envelope.iter().map(|v| v - mean).collect();
+    let energy = autocorrelation(&centered, 0);
+    if energy <= f64::EPSILON {
+        return unknown;
+    }
+
+    let correlations: Vec<f64> = (0..=max_lag + 1)
+        .map(|lag| autocorrelation(&centered, lag))
+        .collect();
+    let mut best = (min_lag..=max_lag)
+        .max_by(|a, b| correlations[*a].total_cmp(&correlations[*b]))
+        .unwrap_or(min_lag);
+    if correlations[best] <= 0.0 {
+        return unknown;
+    }
+
+    // Beats that don't land on whole frames smear the peak at the true period,
+    // so a multiple of it can edge ahead. Step down to a fraction of the
+    // period while it still correlates nearly as well.
+    'search: loop {
+        for divisor in [2, 3] {
+            let candidate = best / divisor;
+            if candidate < min_lag {
+                continue;
+            }
+            let fraction = (candidate - 1..=candidate + 1)
+                .max_by(|a, b| correlations[*a].total_cmp(&correlations[*b]))
+                .unwrap_or(candidate);
+            if correlations[fraction] >= OCTAVE_RATIO * correlations[best] {
+                best = fraction;
+                continue 'search;
+            }
+        }
+        break;
+    }
+    let peak = correlations[best];
+
+    // Parabolic interpolation around the peak recovers a fractional lag,
+    // otherwise fast tempos would be quantized to whole frames.
+    let (prev, next) = (correlations[best - 1], correlations[best + 1]);
+    let denominator = prev - 2.0 * peak + next;
+    let offset = if denominator.abs() > f64::EPSILON {
+        (0.5 * (prev - next) / denominator).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    let lag = best as f64 + offset;
+
+    TempoEstimate {
+        bpm: frames_per_second * 60.0 / lag,
+        confidence: (peak / energy).clamp(0.0, 1.0),
+    }
+}
+
+/// Beats per bar assumed when looking for the downbeat.
+const BEATS_PER_BAR: usize = 4;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Downbeat {
+    /// Seconds from the start of the analyzed audio to its first downbeat.
+    pub offset: f64,
+    /// How clearly the onsets line up on one beat phase, from 0 to 1.
+    pub confidence: f64,
+}
+
+/// Finds the first downbeat of mono `samples` recorded at `sample_rate`,
+/// given their tempo.
+///
+/// Every phase of the beat period is scored by the onset strength on the
+/// beats it predicts, and the best one anchors the beat grid. Of the
+/// [`BEATS_PER_BAR`] beats in a bar, the one with the strongest onsets over
+/// the whole input is taken as the downbeat, and the earliest beat on it is
+/// returned. The confidence compares the best phase against the average
+/// phase, so a flat envelope scores near zero.
+pub fn detect_downbeat(samples: &[f32], sample_rate: u32, bpm: f64) -> Downbeat {
+    let unknown = Downbeat {
+        offset: 0.0,
+        confidence: 0.0,
+    };
+    if !bpm.is_finite() || bpm <= 0.0 {
+        return unknown;
+    }
+
+    let envelope = onset_envelope(samples);
+    let frames_per_second = sample_rate as f64 / HOP_SIZE as f64;
+    let period = frames_per_second * 60.0 / bpm;
+    if period < 1.0 || envelope.len() as f64 <= period {
+        return unknown;
+    }
+    // Strength of the beats at `phase`, `phase + step`, ... in frames.
+    let strength = |phase: f64, step: f64| -> (f64, usize) {
+        (0..)
+            .map(|beat| (phase + beat as f64 * step).round() as usize)
+            .take_while(|&frame| frame < envelope.len())
+            .fold((0.0, 0), |(sum, count), frame| {
+                (sum + envelope[frame], count + 1)
+            })
+    };
+
+    let scores: Vec<f64> = (0..period.ceil() as usize)
+        .map(|phase| {
+            let (sum, count) = strength(phase as f64, period);
+            sum / count.max(1) as f64
+        })
+        .collect();
+    let Some(best) = (0..scores.len()).max_by(|a, b| scores[*a].total_cmp(&scores[*b])) else {
+        return unknown;
+    };
+    let mean = scores.iter().sum::<f64>() / scores.len() as f64;
+    if scores[best] <= f64::EPSILON {
+        return unknown;
+    }
+
+    let bar = period * BEATS_PER_BAR as f64;
+    let downbeat = (0..BEATS_PER_BAR)
+        .map(|beat| best as f64 + beat as f64 * period)
+        .filter(|&phase| (phase.round() as usize) < envelope.len())
+        .map(|phase| {
+            let (sum, count) = strength(phase, bar);
+            (phase, sum / count.max(1) as f64)
+        })
+        .reduce(|strongest, beat| {
+            if beat.1 > strongest.1 {
+                beat
+            } else {
+                strongest
+            }
+        })
+        .map_or(best as f64, |(phase, _)| phase);
+
+    Downbeat {
+        offset: frame_time(downbeat, sample_rate),
+        confidence: ((scores[best] - mean) / scores[best]).clamp(0.0, 1.0),
+    }
+}
+
+/// `sensitivity` [`detect_onsets`] uses unless told otherwise.
+pub const DEFAULT_ONSET_SENSITIVITY: f64 = 0.5;
+
+/// Shortest time between two onsets; closer peaks count as one onset.
+const MIN_ONSET_GAP_SECS: f64 = 0.03;
+
+/// Frames before and after a peak it must be the largest of.
+const PEAK_WINDOW: usize = 3;
+
+/// Frames before and after a peak the adaptive threshold averages, more of
+/// them before than after so a decaying note doesn't mask the next one.
+const THRESHOLD_BEFORE: usize = 12;
+const THRESHOLD_AFTER: usize = 3;
+
+/// How far above the local average, in standard deviations of the whole
+/// onset function, a peak must rise at a sensitivity of 0.
+const MAX_ONSET_DELTA: f64 = 3.0;
+
+/// Finds note onsets in mono `samples` recorded at `sample_rate` and
+/// returns their times in seconds.
+///
+/// The spectral flux, how much the log magnitude spectrum rises from one
+/// frame to the next summed over all bins, peaks where notes start, even
+/// when a new note is no louder than the one ringing before it. Peaks
+/// count as onsets when they stand out from the average flux around them
+/// by a margin set by `sensitivity`, from 0 (only the clearest) to 1
+/// (nearly every peak).
+pub fn detect_onsets(samples: &[f32], sample_rate: u32, sensitivity: f64) -> Vec<f64> {
+    let rms = (samples.iter().map(|s| (*s as f64).powi(2)).sum::<f64>()
+        / samples.len().max(1) as f64)
+        .sqrt();
+    if rms < SILENCE_RMS {
+        return Vec::new();
+    }
+
+    let flux = spectral_flux(samples);
+    if flux.len() < 2 {
+        return Vec::new();
+    }
+    let mean = flux.iter().sum::<f64>() / flux.len() as f64;
+    let deviation =
+        (flux.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / flux.len() as f64).sqrt();
+    if deviation <= f64::EPSILON {
+        return Vec::new();
+    }
+    let normalized: Vec<f64> = flux.iter().map(|v| (v - mean) / deviation).collect();
+
+    let delta = (1.0 - sensitivity.clamp(0.0, 1.0)) * MAX_ONSET_DELTA;
+    let min_gap = MIN_ONSET_GAP_SECS * sample_rate as f64 / HOP_SIZE as f64;
+    let mut onsets: Vec<usize> = Vec::new();
+    for (frame, &value) in normalized.iter().enumerate() {
+        let around = |before: usize, after: usize| {
+            &normalized[frame.saturating_sub(before)..(frame + after + 1).min(normalized.len())]
+        };
+        let is_peak = around(PEAK_WINDOW, PEAK_WINDOW)
+            .iter()
+            .all(|&other| other <= value);
+        let local = around(THRESHOLD_BEFORE, THRESHOLD_AFTER);
+        let threshold = local.iter().sum::<f64>() / local.len() as f64 + delta;
+        let spaced = onsets
+            .last()
+            .is_none_or(|&last| (frame - last) as f64 >= min_gap);
+        if is_peak && value > threshold && value > 0.0 && spaced {
+            onsets.push(frame);
+        }
+    }
+    onsets
+        .into_iter()
+        .map(|frame| flux_frame_time(frame, sample_rate))
+        .collect()
+}
+
+/// Time in seconds of an onset found at `frame` of the spectral flux.
+///
+/// Flux frame `i` is the change from analysis frame `i` to `i + 1`. The
+/// window tapers both ends of a frame, so the change shows most once the
+/// onset has reached the middle of the later frame.
+fn flux_frame_time(frame: usize, sample_rate: u32) -> f64 {
+    ((frame + 1) * HOP_SIZE + FRAME_SIZE / 2) as f64 / sample_rate as f64
+}
+
+/// Computes the spectral flux of `samples`: for each pair of consecutive
+/// Hann windowed frames, the summed rise in log magnitude across all bins.
+fn spectral_flux(samples: &[f32]) -> Vec<f64> {
+    if samples.len() < FRAME_SIZE {
+        return Vec::new();
+    }
+    let window: Vec<f64> = (0..FRAME_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / FRAME_SIZE as f64).cos())
+        .collect();
+
+    let mut previous: Option<Vec<f64>> = None;
+    let mut flux = Vec::with_capacity((samples.len() - FRAME_SIZE) / HOP_SIZE);
+    for frame in 0..=(samples.len() - FRAME_SIZE) / HOP_SIZE {
+        let start = frame * HOP_SIZE;
+        let mut re: Vec<f64> = samples[start..start + FRAME_SIZE]
+            .iter()
+            .zip(&window)
+            .map(|(s, w)| *s as f64 * w)
+            .collect();
+        let mut im = vec![0.0; FRAME_SIZE];
+        fft(&mut re, &mut im);
+        let magnitudes: Vec<f64> = re[..FRAME_SIZE / 2]
+            .iter()
+            .zip(&im)
+            .map(|(re, im)| (1.0 + 1.0 * re.hypot(*im)).ln())
+            .collect();
+        if let Some(previous) = &previous {
+            flux.push(
+                magnitudes
+                    .iter()
+                    .zip(previous)
+                    .map(|(now, before)| (now - before).max(0.0))
+                    .sum(),
+            );
+        }
+        previous = Some(magnitudes);
+    }
+    flux
+}
+
+/// In-place iterative radix-2 FFT. Both slices must have the same
+/// power-of-two length.
+pub(crate) fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut length = 2;
+    while length <= n {
+        let angle = -2.0 * PI / length as f64;
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + length / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        length <<= 1;
+    }
+}
+
+/// Time in seconds of an onset found at `frame` of the onset envelope.
+///
+/// Envelope frame `i` is the rise from analysis frame `i` to `i + 1`, which
+/// is sharpest when the onset has just entered the later frame, about half
+/// a hop before that frame ends.
+fn frame_time(frame: f64, sample_rate: u32) -> f64 {
+    let sample = (frame + 1.0) * HOP_SIZE as f64 + FRAME_SIZE as f64 - HOP_SIZE as f64 / 2.0;
+    sample / sample_rate as f64
+}
+
+/// Computes the half-wave rectified difference of log frame energy.
+fn onset_envelope(samples: &[f32]) -> Vec<f64> {
+    if samples.len() < FRAME_SIZE {
+        return Vec::new();
+    }
+
+    let log_energy: Vec<f64> = (0..=(samples.len() - FRAME_SIZE) / HOP_SIZE)
+        .map(|frame| {
+            let start = frame * HOP_SIZE;
+            let energy = samples[start..start + FRAME_SIZE]
+                .iter()
+                .map(|s| (*s as f64).powi(2))
+                .sum::<f64>()
+                / FRAME_SIZE as f64;
+            (1.0 + 1e4 * energy).ln()
+        })
+        .collect();
+
+    let flux: Vec<f64> = log_energy
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).max(0.0))
+        .collect();
+
+    // A short moving average spreads each onset over a few frames so that
+    // beats which straddle a hop boundary still line up with each other.
+    (0..flux.len())
+        .map(|i| {
+            let window = &flux[i.saturating_sub(1)..(i + 2).min(flux.len())];
+            window.iter().sum::<f64>() / window.len() as f64
+        })
+        .collect()
+}
+
+fn autocorrelation(values: &[f64], lag: usize) -> f64 {
+    values.iter().zip(&values[lag..]).map(|(a, b)| a * b).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Short decaying clicks at `bpm`, `seconds` long.
+    fn click_track(bpm: f64, seconds: f64) -> Vec<f32> {
+        let rate = ANALYSIS_SAMPLE_RATE as f64;
+        let period = (rate * 60.0 / bpm).round() as usize;
+        let click = (rate * 0.01) as usize;
+        (0..(rate * seconds) as usize)
+            .map(|i| {
+                let phase = i % period;
+                if phase < click {
+                    (1.0 - phase as f64 / click as f64) as f32 * ((i as f32) * 0.9).sin()
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    fn assert_tempo(bpm: f64) {
+        let estimate = estimate_tempo(&click_track(bpm, 20.0), ANALYSIS_SAMPLE_RATE);
+        assert!(
+            (estimate.bpm - bpm).abs() < 2.0,
+            "expected {} BPM, got {}",
+            bpm,
+            estimate.bpm
+        );
+        assert!(
+            estimate.confidence > 0.5,
+            "confidence {}",
+            estimate.confidence
+        );
+    }
+
+    #[test]
+    fn detects_60_bpm() {
+        assert_tempo(60.0);
+    }
+
+    #[test]
+    fn detects_120_bpm() {
+        assert_tempo(120.0);
+    }
+
+    #[test]
+    fn detects_174_bpm() {
+        assert_tempo(174.0);
+    }
+
+    #[test]
+    fn silence_has_no_confidence() {
+        let silence = vec![0.0; ANALYSIS_SAMPLE_RATE as usize * 10];
+        let estimate = estimate_tempo(&silence, ANALYSIS_SAMPLE_RATE);
+        assert!(estimate.confidence < 0.1);
+    }
+
+    /// Plucked notes of different pitches starting at `onsets` seconds,
+    /// each ringing on until the next, over a quiet hum.
+    fn plucks(onsets: &[f64], seconds: f64) -> Vec<f32> {
+        let rate = ANALYSIS_SAMPLE_RATE as f64;
+        (0..(rate * seconds) as usize)
+            .map(|i| {
+                let t = i as f64 / rate;
+                let hum = 0.01 * (2.0 * std::f64::consts::PI * 110.0 * t).sin();
+                let note = onsets
+                    .iter()
+                    .rposition(|&onset| onset <= t)
+                    .map_or(0.0, |n| {
+                        let since = t - onsets[n];
+                        let pitch = 196.0 * (1.0 + n as f64 * 0.26);
+                        0.8 * (-since * 6.0).exp()
+                            * (2.0 * std::f64::consts::PI * pitch * since).sin()
+                    });
+                (hum + note) as f32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_onsets_where_notes_start() {
+        let expected = [0.5, 1.1, 1.75, 2.2, 3.05, 3.4];
+        let onsets = detect_onsets(
+            &plucks(&expected, 4.0),
+            ANALYSIS_SAMPLE_RATE,
+            DEFAULT_ONSET_SENSITIVITY,
+        );
+        assert_eq!(onsets.len(), expected.len(), "found {:?}", onsets);
+        for (found, expected) in onsets.iter().zip(expected) {
+            assert!(
+                (found - expected).abs() < 0.03,
+                "expected an onset at {}, found {}",
+                expected,
+                found
+            );
+        }
+    }
+
+    #[test]
+    fn silence_has_no_onsets() {
+        let silence = vec![0.0; ANALYSIS_SAMPLE_RATE as usize * 2];
+        assert!(detect_onsets(&silence, ANALYSIS_SAMPLE_RATE, 1.0).is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::error::AppError;
+use crate::{chapters, Chapter, ChapterSource};
+
+/// Characters allowed before a line's timestamp: list bullets and brackets.
+const LEADING_JUNK: [char; 9] = ['-', '*', '•', '·', '▶', '►', '>', '[', '('];
+
+/// Characters allowed between a timestamp and its title.
+const SEPARATORS: [char; 8] = [']', ')', '-', '–', '—', ':', '|', '.'];
+
+/// Parses `M:SS`, `MM:SS` or `H:MM:SS` into seconds. Seconds, and minutes
+/// when there are hours, must have two digits so "1:2" isn't mistaken for a
+/// time.
+pub fn parse_timestamp(value: &str) -> Option<f64> {
+    let parts: Vec<&str> = value.split(':').collect();
+    if !(2..=3).contains(&parts.len())
+        || parts
+            .iter()
+            .any(|part| part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return None;
+    }
+    let two_digit = |part: &str| (part.len() == 2).then(|| part.parse::<u32>().ok())?;
+    let seconds = two_digit(parts[parts.len() - 1]).filter(|&s| s < 60)?;
+    let (hours, minutes) = if parts.len() == 3 {
+        let minutes = two_digit(parts[1]).filter(|&m| m < 60)?;
+        (parts[0].parse::<u32>().ok()?, minutes)
+    } else {
+        (0, parts[0].parse().ok()?)
+    };
+    Some(hours as f64 * 3600.0 + minutes as f64 * 60.0 + seconds as f64)
+}
+
+/// Splits one description line into its timestamp and title, or `None` if
+/// it doesn't start with a timestamp.
+fn parse_line(line: &str) -> Option<(f64, &str)> {
+    let line = line.trim_start_matches(|c: char| c.is_whitespace() || LEADING_JUNK.contains(&c));
+    let end = line
+        .find(|c: char| !(c.is_ascii_digit() || c == ':'))
+        .unwrap_or(line.len());
+    let start = parse_timestamp(line[..end].trim_end_matches(':'))?;
+    let title =
+        line[end..].trim_start_matches(|c: char| c.is_whitespace() || SEPARATORS.contains(&c));
+    Some((start, title.trim_end()))
+}
+
+/// Builds chapters from a video description with one `[H:]M:SS title` per
+/// line, as YouTube uses. Lines without a leading timestamp are skipped,
+/// chapters are sorted by time and each ends where the next one starts; the
+/// last keeps no end. Only the first of several lines with the same time is
+/// kept.
+pub fn parse(text: &str) -> Result<Vec<Chapter>, AppError> {
+    let mut entries: Vec<(f64, &str)> = text.lines().filter_map(parse_line).collect();
+    entries.sort_by(|a, b| a.0.total_cmp(&b.0));
+    entries.dedup_by(|later, earlier| later.0 == earlier.0);
+    if entries.is_empty() {
+        return Err(AppError::ParseFailed(
+            "No lines start with a timestamp such as 0:00 or 1:02:10".to_string(),
+        ));
+    }
+
+    let marks = entries
+        .into_iter()
+        .map(|(start, title)| (start, title.to_string(), None))
+        .collect();
+    Ok(chapters::to_chapters(marks, ChapterSource::Description))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_minutes_and_hours() {
+        assert_eq!(parse_timestamp("0:00"), Some(0.0));
+        assert_eq!(parse_timestamp("03:45"), Some(225.0));
+        assert_eq!(parse_timestamp("75:00"), Some(4500.0));
+        assert_eq!(parse_timestamp("1:02:10"), Some(3730.0));
+        assert_eq!(parse_timestamp("1:2"), None);
+        assert_eq!(parse_timestamp("1:60"), None);
+        assert_eq!(parse_timestamp("1:2:03"), None);
+    }
+
+    #[test]
+    fn parses_a_messy_description() {
+        let description = "Recorded live in 1998, thanks for watching!
+Setlist:
+  1:02:10 - Outro (feat. the crowd)
+► 0:00 Intro
+[3:45] Solo | take 2
+  - 12:30: Ballad
+🎸 gear list below
+03:45 duplicate of the solo
+10:00
+";
+        let chapters = parse(description).unwrap();
+        let parsed: Vec<(&str, f64, Option<f64>)> = chapters
+            .iter()
+            .map(|c| (c.title.as_str(), c.start, c.end))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                ("Intro", 0.0, Some(225.0)),
+                ("Solo | take 2", 225.0, Some(600.0)),
+                ("Chapter 3", 600.0, Some(750.0)),
+                ("Ballad", 750.0, Some(3730.0)),
+                ("Outro (feat. the crowd)", 3730.0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn text_without_timestamps_fails() {
+        assert!(matches!(
+            parse("Just a description\nwith no times"),
+            Err(AppError::ParseFailed(_))
+        ));
+    }
+}
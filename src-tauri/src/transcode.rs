@@ -0,0 +1,616 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TranscodeOptions {
+    /// Output format: `mp3`, `ogg` or `wav`.
+    pub format: String,
+    /// Target bit rate in kbit/s. Ignored for uncompressed formats.
+    pub bitrate: Option<u32>,
+    /// Target sample rate in Hz, keeping the source rate when `None`.
+    pub sample_rate: Option<u32>,
+}
+
+/// Maps a user-facing format name to the ffmpeg audio encoder for it.
+pub fn codec_for_format(format: &str) -> Result<&'static str, AppError> {
+    match format.to_ascii_lowercase().as_str() {
+        "mp3" => Ok("libmp3lame"),
+        "ogg" => Ok("libvorbis"),
+        "wav" => Ok("pcm_s16le"),
+        other => Err(AppError::InvalidInput(format!(
+            "Unsupported output format '{}'. Choose one of: mp3, ogg, wav",
+            other
+        ))),
+    }
+}
+
+/// Returns the muxer and MIME type for writing `format` to a pipe, where
+/// ffmpeg can't guess the container from a file extension.
+pub fn pipe_format(format: &str) -> Result<(&'static str, &'static str), AppError> {
+    // Validates the format with the same message as the encoder lookup.
+    codec_for_format(format)?;
+    Ok(match format.to_ascii_lowercase().as_str() {
+        "mp3" => ("mp3", "audio/mpeg"),
+        "ogg" => ("ogg", "audio/ogg"),
+        _ => ("wav", "audio/wav"),
+    })
+}
+
+/// Builds the output options that encode audio as `options` asks.
+pub fn encoder_args(options: &TranscodeOptions) -> Result<Vec<String>, AppError> {
+    let codec = codec_for_format(&options.format)?;
+    let mut args = vec!["-c:a".to_string(), codec.to_string()];
+    if let Some(bitrate) = options.bitrate.filter(|_| !codec.starts_with("pcm_")) {
+        args.push("-b:a".to_string());
+        args.push(format!("{}k", bitrate));
+    }
+    if let Some(sample_rate) = options.sample_rate {
+        args.push("-ar".to_string());
+        args.push(sample_rate.to_string());
+    }
+    Ok(args)
+}
+
+/// Highest sample rate the MP3 encoder accepts; ffmpeg resamples anything
+/// faster down to it.
+const MP3_MAX_SAMPLE_RATE: u32 = 48000;
+
+/// Bits per sample of the PCM `wav` output.
+const WAV_BIT_DEPTH: u32 = 16;
+
+/// How a transcode would reduce the resolution of its source.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolutionLoss {
+    pub source_sample_rate: u32,
+    pub output_sample_rate: u32,
+    pub source_bit_depth: Option<u32>,
+    /// `None` for lossy formats, which have no fixed bit depth.
+    pub output_bit_depth: Option<u32>,
+    /// What is lost, for showing to the user as is.
+    pub message: String,
+}
+
+/// Checks whether transcoding a source of `sample_rate` Hz and `bit_depth`
+/// bits with `options` would lower its sample rate or bit depth. Returns
+/// `None` when the output keeps the full resolution, or when the source
+/// resolution isn't known.
+pub fn resolution_loss(
+    sample_rate: u32,
+    bit_depth: Option<u32>,
+    options: &TranscodeOptions,
+) -> Result<Option<ResolutionLoss>, AppError> {
+    let codec = codec_for_format(&options.format)?;
+    if sample_rate == 0 {
+        return Ok(None);
+    }
+    let output_sample_rate = match options.sample_rate {
+        Some(rate) => rate,
+        None if codec == "libmp3lame" => sample_rate.min(MP3_MAX_SAMPLE_RATE),
+        None => sample_rate,
+    };
+    let output_bit_depth = codec.starts_with("pcm_").then_some(WAV_BIT_DEPTH);
+
+    let mut losses = Vec::new();
+    if output_sample_rate < sample_rate {
+        losses.push(format!(
+            "the sample rate drops from {} Hz to {} Hz",
+            sample_rate, output_sample_rate
+        ));
+    }
+    if let (Some(source), Some(output)) = (bit_depth, output_bit_depth) {
+        if output < source {
+            losses.push(format!(
+                "the bit depth drops from {} to {} bits",
+                source, output
+            ));
+        }
+    }
+    if losses.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(ResolutionLoss {
+        source_sample_rate: sample_rate,
+        output_sample_rate,
+        source_bit_depth: bit_depth,
+        output_bit_depth,
+        message: format!(
+            "Exporting as {} lowers the resolution: {}",
+            options.format.to_ascii_lowercase(),
+            losses.join(" and ")
+        ),
+    }))
+}
+
+/// Builds the ffmpeg arguments that transcode `input_path` to `output_path`.
+pub fn transcode_args(
+    input_path: &str,
+    output_path: &str,
+    options: &TranscodeOptions,
+) -> Result<Vec<String>, AppError> {
+    let mut args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        input_path.to_string(),
+        "-vn".to_string(),
+    ];
+    args.extend(encoder_args(options)?);
+    args.push(output_path.to_string());
+    Ok(args)
+}
+
+/// Fixed encoding settings for devices that only play particular formats,
+/// so users don't have to know which options a device needs.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DevicePreset {
+    /// 44.1 kHz stereo MP3 at 192 kbit/s, which even old players accept.
+    Mp3Player,
+    /// 44.1 kHz stereo AAC at 256 kbit/s for phones.
+    PhoneM4a,
+    /// 44.1 kHz stereo 16-bit WAV, as on an audio CD.
+    CdWav,
+    Custom(TranscodeOptions),
+}
+
+/// Checks that `output_path` ends in `.extension`. Devices pick files by
+/// extension, so a mismatch would be unplayable. `writer` names what
+/// requires it in the error.
+fn require_extension(output_path: &str, extension: &str, writer: &str) -> Result<(), AppError> {
+    let has_extension = std::path::Path::new(output_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension));
+    if !has_extension {
+        return Err(AppError::InvalidInput(format!(
+            "{} writes .{} files, but the output is {}",
+            writer, extension, output_path
+        )));
+    }
+    Ok(())
+}
+
+/// Builds the ffmpeg arguments that convert `input_path` to `output_path`
+/// for `preset`.
+pub fn device_args(
+    input_path: &str,
+    output_path: &str,
+    preset: &DevicePreset,
+) -> Result<Vec<String>, AppError> {
+    let (extension, codec, sample_rate, channels, bitrate) = match preset {
+        DevicePreset::Mp3Player => ("mp3", "libmp3lame", 44100, 2, Some(192)),
+        DevicePreset::PhoneM4a => ("m4a", "aac", 44100, 2, Some(256)),
+        DevicePreset::CdWav => ("wav", "pcm_s16le", 44100, 2, None),
+        DevicePreset::Custom(options) => return transcode_args(input_path, output_path, options),
+    };
+    require_extension(output_path, extension, "This preset")?;
+
+    let mut args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        input_path.to_string(),
+        "-vn".to_string(),
+        "-c:a".to_string(),
+        codec.to_string(),
+        "-ar".to_string(),
+        sample_rate.to_string(),
+        "-ac".to_string(),
+        channels.to_string(),
+    ];
+    if let Some(bitrate) = bitrate {
+        args.push("-b:a".to_string());
+        args.push(format!("{}k", bitrate));
+    }
+    args.push(output_path.to_string());
+    Ok(args)
+}
+
+/// Length of the fade out `export_ringtone` ends a ringtone with unless
+/// told otherwise, in seconds, so it doesn't stop mid-note.
+pub const DEFAULT_RINGTONE_FADE_OUT: f64 = 1.0;
+
+/// Phones a ringtone can be made for, which decides its format and how long
+/// it may be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RingtonePlatform {
+    /// AAC in an `.m4r` file, which is what iPhones import as a ringtone.
+    Iphone,
+    AndroidMp3,
+    AndroidOgg,
+}
+
+impl RingtonePlatform {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Iphone => "m4r",
+            Self::AndroidMp3 => "mp3",
+            Self::AndroidOgg => "ogg",
+        }
+    }
+
+    /// Longest ringtone the platform plays in full, in seconds. Android has
+    /// no fixed limit, but a call rarely rings longer than a minute.
+    pub fn max_duration(self) -> f64 {
+        match self {
+            Self::Iphone => 30.0,
+            Self::AndroidMp3 | Self::AndroidOgg => 60.0,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Iphone => "iPhone",
+            Self::AndroidMp3 | Self::AndroidOgg => "Android",
+        }
+    }
+
+    /// Encoder and, where ffmpeg can't tell it from the extension, muxer.
+    fn codec(self) -> (&'static str, Option<&'static str>) {
+        match self {
+            // `.m4r` is an MPEG-4 file all the same, written as for iPods.
+            Self::Iphone => ("aac", Some("ipod")),
+            Self::AndroidMp3 => ("libmp3lame", None),
+            Self::AndroidOgg => ("libvorbis", None),
+        }
+    }
+}
+
+/// Bit rate ringtones are encoded at, in kbit/s.
+const RINGTONE_KBPS: u32 = 192;
+
+/// Builds the ffmpeg arguments that cut `start..end` of `input_path` into a
+/// ringtone for `platform` at `output_path`, applying `filter` (such as the
+/// fade out) on the way. Ranges longer than the platform allows are
+/// rejected, as is an output without the platform's extension.
+pub fn ringtone_args(
+    input_path: &str,
+    start: f64,
+    end: f64,
+    filter: Option<&str>,
+    output_path: &str,
+    platform: RingtonePlatform,
+) -> Result<Vec<String>, AppError> {
+    let length = end - start;
+    let max_duration = platform.max_duration();
+    if length > max_duration {
+        return Err(AppError::InvalidRange(format!(
+            "{} ringtones can be at most {}s long, but the selection is {:.1}s. Choose a shorter part.",
+            platform.name(),
+            max_duration,
+            length
+        )));
+    }
+    require_extension(output_path, platform.extension(), "This ringtone")?;
+
+    let (codec, muxer) = platform.codec();
+    let mut args = vec![
+        "-y".to_string(),
+        "-v".to_string(),
+        "error".to_string(),
+        "-ss".to_string(),
+        start.to_string(),
+        "-to".to_string(),
+        end.to_string(),
+        "-i".to_string(),
+        input_path.to_string(),
+        "-map".to_string(),
+        "0:a:0".to_string(),
+        "-vn".to_string(),
+    ];
+    if let Some(filter) = filter {
+        args.push("-af".to_string());
+        args.push(filter.to_string());
+    }
+    args.extend([
+        "-c:a".to_string(),
+        codec.to_string(),
+        "-b:a".to_string(),
+        format!("{}k", RINGTONE_KBPS),
+        "-ar".to_string(),
+        "44100".to_string(),
+        "-ac".to_string(),
+        "2".to_string(),
+    ]);
+    if let Some(muxer) = muxer {
+        args.push("-f".to_string());
+        args.push(muxer.to_string());
+    }
+    args.push(output_path.to_string());
+    Ok(args)
+}
+
+/// Bit rate libmp3lame encodes at when none is given, in kbit/s.
+pub const DEFAULT_MP3_KBPS: u32 = 128;
+
+/// Average bit rate of libvorbis at its default quality, in kbit/s.
+pub const DEFAULT_VORBIS_KBPS: u32 = 112;
+
+/// Size of a canonical WAV header.
+const WAV_HEADER_BYTES: u64 = 44;
+
+/// How an export size was estimated, from most to least reliable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeEstimateMethod {
+    /// Uncompressed audio, whose size follows from its format alone.
+    Pcm,
+    /// A constant bit rate.
+    Cbr,
+    /// The nominal rate of a variable bit rate encoder, which real files
+    /// can miss either way depending on the material.
+    Vbr,
+    /// The source's average bit rate, for stream copies.
+    SourceBitrate,
+}
+
+/// Bytes of `duration` seconds of audio at a constant `kbps`, leaving out
+/// container overhead and tags.
+pub fn cbr_bytes(duration: f64, kbps: u32) -> u64 {
+    (duration.max(0.0) * kbps as f64 * 1000.0 / 8.0).round() as u64
+}
+
+/// Bytes of a 16-bit WAV file holding `duration` seconds at `sample_rate`
+/// with `channels` channels.
+pub fn wav_bytes(duration: f64, sample_rate: u32, channels: u8) -> u64 {
+    let frames = (duration.max(0.0) * sample_rate as f64).round() as u64;
+    WAV_HEADER_BYTES + frames * channels as u64 * 2
+}
+
+/// Estimates the size of `duration` seconds encoded as `format` at
+/// `bitrate` kbit/s, the encoder's default when `None`. The source's sample
+/// rate and channel count are kept, as exports do, and matter for WAV only.
+pub fn estimate_size(
+    format: &str,
+    bitrate: Option<u32>,
+    duration: f64,
+    sample_rate: u32,
+    channels: u8,
+) -> Result<(u64, SizeEstimateMethod), AppError> {
+    Ok(match codec_for_format(format)? {
+        "libmp3lame" => (
+            cbr_bytes(duration, bitrate.unwrap_or(DEFAULT_MP3_KBPS)),
+            SizeEstimateMethod::Cbr,
+        ),
+        "libvorbis" => (
+            cbr_bytes(duration, bitrate.unwrap_or(DEFAULT_VORBIS_KBPS)),
+            SizeEstimateMethod::Vbr,
+        ),
+        _ => (
+            wav_bytes(duration, sample_rate, channels),
+            SizeEstimateMethod::Pcm,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn mp3_player_preset() {
+        assert_eq!(
+            device_args("in.flac", "out.mp3", &DevicePreset::Mp3Player).unwrap(),
+            args(&[
+                "-y",
+                "-v",
+                "error",
+                "-i",
+                "in.flac",
+                "-vn",
+                "-c:a",
+                "libmp3lame",
+                "-ar",
+                "44100",
+                "-ac",
+                "2",
+                "-b:a",
+                "192k",
+                "out.mp3",
+            ])
+        );
+    }
+
+    #[test]
+    fn phone_m4a_preset() {
+        assert_eq!(
+            device_args("in.flac", "out.m4a", &DevicePreset::PhoneM4a).unwrap(),
+            args(&[
+                "-y", "-v", "error", "-i", "in.flac", "-vn", "-c:a", "aac", "-ar", "44100", "-ac",
+                "2", "-b:a", "256k", "out.m4a",
+            ])
+        );
+    }
+
+    #[test]
+    fn cd_wav_preset_has_no_bitrate() {
+        assert_eq!(
+            device_args("in.flac", "out.wav", &DevicePreset::CdWav).unwrap(),
+            args(&[
+                "-y",
+                "-v",
+                "error",
+                "-i",
+                "in.flac",
+                "-vn",
+                "-c:a",
+                "pcm_s16le",
+                "-ar",
+                "44100",
+                "-ac",
+                "2",
+                "out.wav",
+            ])
+        );
+    }
+
+    #[test]
+    fn custom_preset_uses_the_transcode_options() {
+        let preset = DevicePreset::Custom(TranscodeOptions {
+            format: "ogg".to_string(),
+            bitrate: Some(128),
+            sample_rate: Some(48000),
+        });
+        assert_eq!(
+            device_args("in.flac", "out.ogg", &preset).unwrap(),
+            args(&[
+                "-y",
+                "-v",
+                "error",
+                "-i",
+                "in.flac",
+                "-vn",
+                "-c:a",
+                "libvorbis",
+                "-b:a",
+                "128k",
+                "-ar",
+                "48000",
+                "out.ogg",
+            ])
+        );
+    }
+
+    #[test]
+    fn presets_require_their_extension() {
+        assert!(matches!(
+            device_args("in.flac", "out.wav", &DevicePreset::Mp3Player),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn cbr_size_is_duration_times_bitrate() {
+        assert_eq!(cbr_bytes(60.0, 128), 960_000);
+        assert_eq!(cbr_bytes(0.5, 320), 20_000);
+        assert_eq!(cbr_bytes(-3.0, 192), 0);
+        assert_eq!(
+            estimate_size("mp3", Some(192), 10.0, 44100, 2).unwrap(),
+            (240_000, SizeEstimateMethod::Cbr)
+        );
+        assert_eq!(
+            estimate_size("mp3", None, 10.0, 44100, 2).unwrap(),
+            (160_000, SizeEstimateMethod::Cbr)
+        );
+    }
+
+    #[test]
+    fn wav_size_follows_the_format() {
+        assert_eq!(
+            estimate_size("wav", None, 1.0, 48000, 2).unwrap(),
+            (44 + 48000 * 4, SizeEstimateMethod::Pcm)
+        );
+    }
+
+    #[test]
+    fn iphone_ringtones_are_aac_in_an_ipod_file() {
+        assert_eq!(
+            ringtone_args(
+                "song.flac",
+                12.5,
+                40.0,
+                Some("afade=t=out:st=26.5:d=1"),
+                "riff.m4r",
+                RingtonePlatform::Iphone
+            )
+            .unwrap(),
+            args(&[
+                "-y",
+                "-v",
+                "error",
+                "-ss",
+                "12.5",
+                "-to",
+                "40",
+                "-i",
+                "song.flac",
+                "-map",
+                "0:a:0",
+                "-vn",
+                "-af",
+                "afade=t=out:st=26.5:d=1",
+                "-c:a",
+                "aac",
+                "-b:a",
+                "192k",
+                "-ar",
+                "44100",
+                "-ac",
+                "2",
+                "-f",
+                "ipod",
+                "riff.m4r",
+            ])
+        );
+    }
+
+    #[test]
+    fn android_ringtones_pick_their_encoder() {
+        for (platform, output, codec) in [
+            (RingtonePlatform::AndroidMp3, "riff.mp3", "libmp3lame"),
+            (RingtonePlatform::AndroidOgg, "riff.OGG", "libvorbis"),
+        ] {
+            let args = ringtone_args("song.flac", 0.0, 60.0, None, output, platform).unwrap();
+            let codec_at = args.iter().position(|a| a == "-c:a").unwrap();
+            assert_eq!(args[codec_at + 1], codec);
+            assert!(!args.iter().any(|a| a == "-af" || a == "-f"), "{:?}", args);
+            assert_eq!(args.last().unwrap(), output);
+        }
+    }
+
+    #[test]
+    fn ringtones_keep_to_the_platform_limit() {
+        for (platform, output, end) in [
+            (RingtonePlatform::Iphone, "riff.m4r", 30.5),
+            (RingtonePlatform::AndroidMp3, "riff.mp3", 60.5),
+        ] {
+            assert!(matches!(
+                ringtone_args("song.flac", 0.0, end, None, output, platform),
+                Err(AppError::InvalidRange(_))
+            ));
+        }
+        assert!(ringtone_args(
+            "song.flac",
+            10.0,
+            40.0,
+            None,
+            "riff.m4r",
+            RingtonePlatform::Iphone
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn ringtones_need_the_platform_extension() {
+        assert!(matches!(
+            ringtone_args(
+                "song.flac",
+                0.0,
+                20.0,
+                None,
+                "riff.m4a",
+                RingtonePlatform::Iphone
+            ),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            ringtone_args(
+                "song.flac",
+                0.0,
+                20.0,
+                None,
+                "riff.mp3",
+                RingtonePlatform::AndroidOgg
+            ),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,327 @@
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::AppError;
+
+/// Checks that `file_path` names an existing, readable regular file.
+///
+/// Commands call this before shelling out so users get a clear message
+/// instead of whatever ffmpeg happens to print for a bad input.
+pub fn validate_media_path(file_path: &str) -> Result<(), AppError> {
+    let path = Path::new(file_path);
+    let metadata = fs::metadata(path).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => AppError::FileNotFound(format!("File not found: {}", file_path)),
+        _ => AppError::Io(format!("Cannot access {}: {}", file_path, e)),
+    })?;
+
+    if metadata.is_dir() {
+        return Err(AppError::InvalidInput(format!(
+            "{} is a folder, not a media file",
+            file_path
+        )));
+    }
+    if !metadata.is_file() {
+        return Err(AppError::InvalidInput(format!(
+            "{} is not a regular file",
+            file_path
+        )));
+    }
+
+    fs::File::open(path)
+        .map(|_| ())
+        .map_err(|e| AppError::Io(format!("Cannot read {}: {}", file_path, e)))
+}
+
+fn read_error(path: &str, e: std::io::Error) -> AppError {
+    match e.kind() {
+        ErrorKind::NotFound => AppError::FileNotFound(format!("File not found: {}", path)),
+        _ => AppError::Io(format!("Failed to read {}: {}", path, e)),
+    }
+}
+
+/// Reads the whole of `path`, reporting a missing file as `FileNotFound`.
+pub fn read_file(path: &str) -> Result<Vec<u8>, AppError> {
+    fs::read(path).map_err(|e| read_error(path, e))
+}
+
+/// Reads the whole of `path` as UTF-8 text, reporting a missing file as
+/// `FileNotFound`.
+pub fn read_to_string(path: &str) -> Result<String, AppError> {
+    fs::read_to_string(path).map_err(|e| read_error(path, e))
+}
+
+/// URL schemes accepted in place of a local path.
+const ALLOWED_URL_SCHEMES: [&str; 3] = ["http", "https", "file"];
+
+/// Live streaming protocols ffmpeg understands but we don't open yet.
+const STREAMING_URL_SCHEMES: [&str; 5] = ["rtsp", "rtsps", "rtmp", "rtmps", "rtp"];
+
+/// Returns the lowercased scheme of `source` if it is a URL rather than a
+/// local path. Windows drive letters are not mistaken for schemes.
+fn url_scheme(source: &str) -> Option<String> {
+    let (scheme, _) = source.split_once("://")?;
+    let valid = scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
+
+/// Whether `source` is a URL rather than a local path.
+pub fn is_url(source: &str) -> bool {
+    url_scheme(source).is_some()
+}
+
+/// Whether `source` is fetched over the network.
+pub fn is_remote(source: &str) -> bool {
+    matches!(url_scheme(source).as_deref(), Some("http" | "https"))
+}
+
+/// Decodes the `%XX` escapes in the path of a URL, e.g. `%20` for a space.
+/// A `%` not followed by two hex digits is kept as it is.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = path
+            .get(index + 1..index + 3)
+            .filter(|hex| bytes[index] == b'%' && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Like [`validate_media_path`], but also accepts `http(s)://` URLs, which
+/// ffprobe can open itself, and `file://` URLs to local files.
+///
+/// Returns the source to hand to ffprobe: `file://` URLs become plain,
+/// percent-decoded paths.
+pub fn validate_media_source(source: &str) -> Result<String, AppError> {
+    let Some(scheme) = url_scheme(source) else {
+        validate_media_path(source)?;
+        return Ok(source.to_string());
+    };
+    if STREAMING_URL_SCHEMES.contains(&scheme.as_str()) {
+        return Err(AppError::InvalidInput(format!(
+            "{} streams are not supported yet",
+            scheme.to_uppercase()
+        )));
+    }
+    if !ALLOWED_URL_SCHEMES.contains(&scheme.as_str()) {
+        return Err(AppError::InvalidInput(format!(
+            "Unsupported URL scheme '{}'. Use a local file or an http(s) URL.",
+            scheme
+        )));
+    }
+    if scheme == "file" {
+        let path = percent_decode(&source["file://".len()..]);
+        // `file:///C:/...` carries an extra slash before the drive letter.
+        let path = match path.strip_prefix('/') {
+            Some(rest) if rest.get(1..2) == Some(":") => rest,
+            _ => &path,
+        };
+        validate_media_path(path)?;
+        return Ok(path.to_string());
+    }
+    Ok(source.to_string())
+}
+
+/// Checks that a `start..end` time range in seconds is usable. An `end` of
+/// `None` means "until the end of the file".
+pub fn validate_range(start: f64, end: Option<f64>) -> Result<(), AppError> {
+    if !start.is_finite() || start < 0.0 {
+        return Err(AppError::InvalidRange(format!(
+            "Invalid range: start ({}) must not be negative",
+            start
+        )));
+    }
+    if let Some(end) = end {
+        if !end.is_finite() || start >= end {
+            return Err(AppError::InvalidRange(format!(
+                "Invalid range: start ({}) must be before end ({})",
+                start, end
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Suffix of files still being written. They are renamed to their final
+/// name once complete, so one left behind by a crash is never mistaken for
+/// a finished file.
+pub const PART_SUFFIX: &str = ".part";
+
+/// Files ending in [`PART_SUFFIX`] untouched for this long are taken to be
+/// left over from an interrupted run. ffmpeg writes its output as it goes,
+/// so the part file of a running job is always fresher than this.
+pub const ORPHANED_PART_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// Returns the path `path` is written to until it is complete.
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.to_path_buf().into_os_string();
+    part.push(PART_SUFFIX);
+    PathBuf::from(part)
+}
+
+/// Writes `contents` to the part file of `path` and renames it into place,
+/// so a crash mid-write never leaves a truncated file behind.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), AppError> {
+    let temp_path = part_path(path);
+
+    let write = || -> std::io::Result<()> {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    };
+    write().map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        AppError::Io(format!("Failed to write {}: {}", path.display(), e))
+    })
+}
+
+/// Removes the part files in `dir` (not its subfolders) that haven't been
+/// written to for [`ORPHANED_PART_AGE`], and returns their paths.
+pub fn remove_orphaned_parts(dir: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| AppError::Io(format!("Failed to read {}: {}", dir.display(), e)))?;
+    let mut removed = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_part = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(PART_SUFFIX));
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let stale = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age >= ORPHANED_PART_AGE);
+        if !is_part || !metadata.is_file() || !stale {
+            continue;
+        }
+        fs::remove_file(&path)
+            .map_err(|e| AppError::Io(format!("Failed to remove {}: {}", path.display(), e)))?;
+        removed.push(path);
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_a_directory() {
+        let dir = std::env::temp_dir();
+        let dir = dir.to_str().unwrap();
+        match validate_media_path(dir) {
+            Err(AppError::InvalidInput(message)) => {
+                assert_eq!(message, format!("{} is a folder, not a media file", dir))
+            }
+            other => panic!("expected InvalidInput, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_a_missing_file() {
+        let missing = std::env::temp_dir().join("guitar-looper-missing.mp3");
+        assert!(matches!(
+            validate_media_path(missing.to_str().unwrap()),
+            Err(AppError::FileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn decodes_file_urls() {
+        match validate_media_source("file:///home/me/My%20Song.mp3") {
+            Err(AppError::FileNotFound(message)) => {
+                assert_eq!(message, "File not found: /home/me/My Song.mp3")
+            }
+            other => panic!("expected FileNotFound, got {:?}", other),
+        }
+
+        let dir = scratch_dir("file-url");
+        let song = dir.join("My Song.mp3");
+        fs::write(&song, b"").unwrap();
+        let url = format!("file://{}", dir.join("My%20Song.mp3").display());
+        assert_eq!(validate_media_source(&url).unwrap(), song.to_str().unwrap());
+        fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(percent_decode("100%25%2Fdone%zz%"), "100%/done%zz%");
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "guitar-looper-util-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn atomic_writes_leave_no_part_file() {
+        let dir = scratch_dir("atomic");
+        let path = dir.join("markers.json");
+        write_atomic(&path, b"{}").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"{}");
+        assert!(!part_path(&path).exists());
+
+        // Renaming onto a folder fails, and the part file goes with it.
+        let folder = dir.join("folder");
+        fs::create_dir(&folder).unwrap();
+        fs::write(folder.join("inside"), b"").unwrap();
+        assert!(matches!(write_atomic(&folder, b"{}"), Err(AppError::Io(_))));
+        assert!(!part_path(&folder).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn removes_only_stale_part_files() {
+        let dir = scratch_dir("orphans");
+        let stale = dir.join("old.mp3.part");
+        let fresh = dir.join("running.mp3.part");
+        let finished = dir.join("done.mp3");
+        for path in [&stale, &fresh, &finished] {
+            fs::write(path, b"audio").unwrap();
+        }
+        let long_ago = std::time::SystemTime::now() - ORPHANED_PART_AGE * 2;
+        for path in [&stale, &finished] {
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(long_ago)
+                .unwrap();
+        }
+
+        assert_eq!(
+            remove_orphaned_parts(&dir).unwrap(),
+            std::slice::from_ref(&stale)
+        );
+        assert!(!stale.exists());
+        assert!(fresh.exists());
+        assert!(finished.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,38 @@
+use serde::Serialize;
+
+/// Most error lines kept in a [`MediaVerification`]. A badly damaged file
+/// can log one per frame.
+const MAX_ERRORS: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaVerification {
+    /// Whether the whole file decoded without errors.
+    pub ok: bool,
+    pub errors: Vec<String>,
+}
+
+/// Collects the error lines ffmpeg logged at `-v error` while decoding.
+///
+/// Identical consecutive lines are reported once, and past [`MAX_ERRORS`]
+/// the rest are summarized in a final line.
+pub fn parse_errors(stderr: &str) -> MediaVerification {
+    let mut errors: Vec<String> = Vec::new();
+    let mut dropped = 0;
+    for line in stderr.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if errors.last().is_some_and(|last| last == line) {
+            continue;
+        }
+        if errors.len() < MAX_ERRORS {
+            errors.push(line.to_string());
+        } else {
+            dropped += 1;
+        }
+    }
+    if dropped > 0 {
+        errors.push(format!("... and {} more errors", dropped));
+    }
+    MediaVerification {
+        ok: errors.is_empty(),
+        errors,
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::{chapters, Chapter, ChapterSource};
+
+/// Parses a Vorbis comment chapter time, `HH:MM:SS.mmm`, into seconds. The
+/// fraction may have any number of digits or be left out.
+pub fn parse_time(value: &str) -> Option<f64> {
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    let [hours, minutes, seconds] = parts[..] else {
+        return None;
+    };
+    let whole = |part: &str| {
+        (!part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| part.parse::<u32>().ok())?
+    };
+    let (secs, fraction) = seconds.split_once('.').unwrap_or((seconds, "0"));
+    let minutes = whole(minutes).filter(|&m| m < 60)?;
+    let secs = whole(secs).filter(|&s| s < 60)?;
+    whole(fraction)?;
+    let fraction: f64 = format!("0.{}", fraction).parse().ok()?;
+    Some(whole(hours)? as f64 * 3600.0 + minutes as f64 * 60.0 + secs as f64 + fraction)
+}
+
+/// Reads `CHAPTERnnn` / `CHAPTERnnnNAME` comments, as Ogg Vorbis and Opus
+/// files store chapters, from an ffprobe `tags` object.
+///
+/// Keys are matched case-insensitively and chapters are ordered by start,
+/// so gaps in the numbering don't matter. Entries with an unreadable time
+/// are skipped. Each chapter ends where the next one starts.
+pub fn parse_chapter_tags(tags: &Value) -> Vec<Chapter> {
+    let Some(tags) = tags.as_object() else {
+        return Vec::new();
+    };
+    let mut entries: BTreeMap<u32, (Option<f64>, String)> = BTreeMap::new();
+    for (key, value) in tags {
+        let Some(value) = value.as_str() else {
+            continue;
+        };
+        let key = key.to_ascii_uppercase();
+        let Some(rest) = key.strip_prefix("CHAPTER") else {
+            continue;
+        };
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let Ok(number) = rest[..digits].parse::<u32>() else {
+            continue;
+        };
+        let entry = entries.entry(number).or_default();
+        match &rest[digits..] {
+            "" => entry.0 = parse_time(value),
+            "NAME" => entry.1 = value.trim().to_string(),
+            _ => {}
+        }
+    }
+
+    let marks = entries
+        .into_values()
+        .filter_map(|(start, title)| Some((start?, title, None)))
+        .collect();
+    chapters::to_chapters(marks, ChapterSource::VorbisComment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_chapter_times() {
+        assert_eq!(parse_time("00:00:00.000"), Some(0.0));
+        assert_eq!(parse_time("01:02:03.5"), Some(3723.5));
+        assert_eq!(parse_time("00:03:45"), Some(225.0));
+        assert_eq!(parse_time("00:61:00.000"), None);
+        assert_eq!(parse_time("3:45"), None);
+    }
+
+    #[test]
+    fn reads_non_contiguous_chapter_tags() {
+        let tags = json!({
+            "ENCODER": "opusenc",
+            "CHAPTER001": "00:00:00.000",
+            "CHAPTER001NAME": "Intro",
+            "chapter005": "00:03:45.250",
+            "chapter005name": "Solo",
+            "CHAPTER012": "00:07:10.000",
+            "CHAPTER020": "not a time",
+            "CHAPTER020NAME": "Broken",
+        });
+        let chapters = parse_chapter_tags(&tags);
+        let parsed: Vec<(&str, f64, Option<f64>)> = chapters
+            .iter()
+            .map(|c| (c.title.as_str(), c.start, c.end))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                ("Intro", 0.0, Some(225.25)),
+                ("Solo", 225.25, Some(430.0)),
+                ("Chapter 3", 430.0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_chapter_tags_means_no_chapters() {
+        assert!(parse_chapter_tags(&json!({ "TITLE": "Live" })).is_empty());
+        assert!(parse_chapter_tags(&Value::Null).is_empty());
+    }
+}
@@ -0,0 +1,314 @@
+use crate::error::AppError;
+use crate::pcm::{PcmDecoder, PcmFormat};
+
+/// Default upper bound on the size of the waveform cache directory.
+pub const DEFAULT_CACHE_LIMIT_BYTES: u64 = 200 * 1024 * 1024;
+
+/// Below this many samples per thread, spreading the work isn't worth
+/// spawning threads for.
+const MIN_SAMPLES_PER_THREAD: usize = 1 << 20;
+
+/// Checks the bucket size every waveform command takes.
+pub fn validate_samples_per_pixel(samples_per_pixel: u32) -> Result<(), AppError> {
+    if samples_per_pixel == 0 {
+        return Err(AppError::InvalidInput(
+            "samples_per_pixel must be greater than zero".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Reduces `samples` to one `[min, max]` pair per bucket of `samples_per_pixel`
+/// samples, flattened and scaled so the loudest peak reaches 1.0.
+pub fn compute_peaks(samples: &[f32], samples_per_pixel: usize) -> Vec<f32> {
+    let mut peaks = compute_raw_peaks(samples, samples_per_pixel);
+    normalize_peaks(&mut peaks);
+    peaks
+}
+
+/// Like [`compute_peaks`], but leaves the pairs unnormalized.
+///
+/// Long inputs are split across the available cores. Every thread's share is
+/// a whole number of buckets, so the result is identical to a single pass.
+pub fn compute_raw_peaks(samples: &[f32], samples_per_pixel: usize) -> Vec<f32> {
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(samples.len() / MIN_SAMPLES_PER_THREAD)
+        .max(1);
+    peaks_on_threads(samples, samples_per_pixel, threads)
+}
+
+/// Computes the pairs of [`compute_raw_peaks`] on `threads` threads.
+fn peaks_on_threads(samples: &[f32], samples_per_pixel: usize, threads: usize) -> Vec<f32> {
+    let buckets_per_thread = samples.len().div_ceil(samples_per_pixel).div_ceil(threads);
+
+    if threads == 1 {
+        bucket_peaks(samples, samples_per_pixel)
+    } else {
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = samples
+                .chunks(buckets_per_thread * samples_per_pixel)
+                .map(|share| scope.spawn(move || bucket_peaks(share, samples_per_pixel)))
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("waveform worker panicked"))
+                .collect()
+        })
+    }
+}
+
+/// Buckets of a full-file waveform covering part of the file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketRange {
+    /// Index of the first bucket, counted from the start of the file.
+    pub first_bucket: u64,
+    /// Where the first bucket starts, in seconds.
+    pub start: f64,
+    /// Where the bucket after the last one starts, in seconds.
+    pub end: f64,
+}
+
+/// Widens `start..end` out to whole buckets of `samples_per_pixel` samples
+/// at `sample_rate`, counted from the start of the file, so peaks computed
+/// for the range line up with those of the whole file.
+pub fn bucket_range(start: f64, end: f64, sample_rate: u32, samples_per_pixel: u32) -> BucketRange {
+    let bucket_secs = samples_per_pixel as f64 / sample_rate as f64;
+    let first_bucket = (start / bucket_secs).floor() as u64;
+    let end_bucket = ((end / bucket_secs).ceil() as u64).max(first_bucket + 1);
+    BucketRange {
+        first_bucket,
+        start: first_bucket as f64 * bucket_secs,
+        end: end_bucket as f64 * bucket_secs,
+    }
+}
+
+/// Reduces `samples` to exactly `count` unnormalized `[min, max]` pairs,
+/// however many samples there are. Bucket boundaries are spread evenly, so
+/// neighbouring buckets differ in size by at most one sample; when there
+/// are fewer samples than buckets, some samples feed more than one bucket.
+/// Returns nothing for an empty slice.
+pub fn fixed_count_peaks(samples: &[f32], count: usize) -> Vec<f32> {
+    if samples.is_empty() {
+        return Vec::new();
+    }
+    let mut peaks = Vec::with_capacity(count * 2);
+    for index in 0..count {
+        let lo = (index * samples.len() / count).min(samples.len() - 1);
+        let hi = ((index + 1) * samples.len() / count).max(lo + 1);
+        let (min, max) = samples[lo..hi]
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), &s| {
+                (min.min(s), max.max(s))
+            });
+        peaks.push(min);
+        peaks.push(max);
+    }
+    peaks
+}
+
+/// Returns the unnormalized `[min, max]` pair of every bucket in `samples`.
+fn bucket_peaks(samples: &[f32], samples_per_pixel: usize) -> Vec<f32> {
+    let mut peaks = Vec::with_capacity(samples.len().div_ceil(samples_per_pixel) * 2);
+    for bucket in samples.chunks(samples_per_pixel) {
+        let (min, max) = bucket.iter().fold((f32::MAX, f32::MIN), |(min, max), &s| {
+            (min.min(s), max.max(s))
+        });
+        peaks.push(min);
+        peaks.push(max);
+    }
+    peaks
+}
+
+/// Scales `peaks` in place so the loudest one reaches 1.0.
+pub fn normalize_peaks(peaks: &mut [f32]) {
+    let loudest = peaks.iter().fold(0.0f32, |acc, p| acc.max(p.abs()));
+    if loudest > 0.0 {
+        for peak in peaks.iter_mut() {
+            *peak /= loudest;
+        }
+    }
+}
+
+/// Scales all of `sets` in place by the loudest peak among them, so their
+/// levels stay comparable.
+pub fn normalize_peak_sets(sets: &mut [Vec<f32>]) {
+    let loudest = sets
+        .iter()
+        .flatten()
+        .fold(0.0f32, |acc, p| acc.max(p.abs()));
+    if loudest > 0.0 {
+        sets.iter_mut().flatten().for_each(|peak| *peak /= loudest);
+    }
+}
+
+/// Most channels [`deinterleave`] callers meter, enough for 7.1.
+pub const MAX_METER_CHANNELS: u8 = 8;
+
+/// Splits interleaved PCM, one sample of each of `channels` channels per
+/// frame, into one buffer per channel. A trailing partial frame is dropped.
+pub fn deinterleave(samples: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    let mut split = vec![Vec::with_capacity(samples.len() / channels.max(1)); channels];
+    for frame in samples.chunks_exact(channels.max(1)) {
+        for (channel, &sample) in split.iter_mut().zip(frame) {
+            channel.push(sample);
+        }
+    }
+    split
+}
+
+/// Builds the same `[min, max]` pairs as [`compute_peaks`] from PCM that
+/// arrives in pieces, without holding all samples at once. The pairs are
+/// not normalized.
+pub struct PeakAccumulator {
+    samples_per_pixel: usize,
+    decoder: PcmDecoder,
+    count: usize,
+    min: f32,
+    max: f32,
+}
+
+impl PeakAccumulator {
+    pub fn new(samples_per_pixel: usize, format: PcmFormat) -> Self {
+        Self {
+            samples_per_pixel,
+            decoder: PcmDecoder::new(format),
+            count: 0,
+            min: f32::MAX,
+            max: f32::MIN,
+        }
+    }
+
+    /// Consumes `bytes` and returns the pairs of every bucket it completed.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<f32> {
+        let mut peaks = Vec::new();
+        self.decoder.push(bytes, |sample| {
+            self.min = self.min.min(sample);
+            self.max = self.max.max(sample);
+            self.count += 1;
+            if self.count == self.samples_per_pixel {
+                peaks.push(self.min);
+                peaks.push(self.max);
+                self.count = 0;
+                self.min = f32::MAX;
+                self.max = f32::MIN;
+            }
+        });
+        peaks
+    }
+
+    /// Returns the pair for the final, partially filled bucket, if any.
+    pub fn finish(self) -> Vec<f32> {
+        if self.count == 0 {
+            Vec::new()
+        } else {
+            vec![self.min, self.max]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A noisy signal whose peaks differ from bucket to bucket.
+    fn signal(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                let t = i as f32;
+                (t * 0.013).sin() * (t * 0.0007).cos() + ((i * 7919) % 101) as f32 / 500.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parallel_peaks_match_a_single_pass() {
+        let samples = signal(1_000_003);
+        // Odd bucket sizes and thread counts, so shares don't end on round
+        // numbers.
+        for samples_per_pixel in [1, 777, 4096] {
+            let single = bucket_peaks(&samples, samples_per_pixel);
+            for threads in [2, 3, 8] {
+                assert_eq!(
+                    peaks_on_threads(&samples, samples_per_pixel, threads),
+                    single
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn channel_peaks_keep_channels_apart() {
+        // Left rises from silence to 0.5, right is a full-scale square
+        // wave, plus a stray sample with no partner.
+        let mut samples = Vec::new();
+        for i in 0..8 {
+            samples.push(i as f32 / 14.0);
+            samples.push(if i % 2 == 0 { 1.0 } else { -1.0 });
+        }
+        samples.push(0.9);
+
+        let channels = deinterleave(&samples, 2);
+        assert_eq!(channels.len(), 2);
+        assert_eq!(
+            channels[0],
+            (0..8).map(|i| i as f32 / 14.0).collect::<Vec<_>>()
+        );
+        assert_eq!(channels[1], [1.0, -1.0].repeat(4));
+
+        let mut peaks: Vec<Vec<f32>> = channels.iter().map(|c| compute_raw_peaks(c, 4)).collect();
+        normalize_peak_sets(&mut peaks);
+        assert_eq!(peaks[0], [0.0, 3.0 / 14.0, 4.0 / 14.0, 0.5]);
+        assert_eq!(peaks[1], [-1.0, 1.0, -1.0, 1.0]);
+    }
+
+    #[test]
+    fn channel_peaks_share_one_scale() {
+        let mut peaks = vec![vec![-0.1, 0.2], vec![-0.4, 0.3]];
+        normalize_peak_sets(&mut peaks);
+        assert_eq!(peaks, [[-0.25, 0.5], [-1.0, 0.75]]);
+    }
+
+    fn encode(samples: &[f32], format: PcmFormat) -> Vec<u8> {
+        samples
+            .iter()
+            .flat_map(|&s| match format {
+                PcmFormat::S16le => ((s * 32767.0) as i16).to_le_bytes().to_vec(),
+                PcmFormat::S32le => ((s as f64 * 2_147_483_647.0) as i32).to_le_bytes().to_vec(),
+                PcmFormat::F32le => s.to_le_bytes().to_vec(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn streamed_peaks_match_for_every_format() {
+        // Kept below full scale, which the integer formats can't exceed.
+        let samples: Vec<f32> = signal(10_007).iter().map(|s| s * 0.8).collect();
+        for format in [PcmFormat::S16le, PcmFormat::S32le, PcmFormat::F32le] {
+            let bytes = encode(&samples, format);
+            let decoded = format.decode(&bytes);
+            for (ours, theirs) in decoded.iter().zip(&samples) {
+                assert!((ours - theirs).abs() < 1e-4, "{:?}", format);
+            }
+
+            // Pieces that split samples, as pipe reads do.
+            let mut accumulator = PeakAccumulator::new(256, format);
+            let mut peaks = Vec::new();
+            for piece in bytes.chunks(1001) {
+                peaks.extend(accumulator.push(piece));
+            }
+            peaks.extend(accumulator.finish());
+            assert_eq!(peaks, compute_raw_peaks(&decoded, 256), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn rejects_empty_buckets() {
+        assert!(matches!(
+            validate_samples_per_pixel(0),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert!(validate_samples_per_pixel(1).is_ok());
+    }
+}
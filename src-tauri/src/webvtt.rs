@@ -0,0 +1,110 @@
+use crate::error::AppError;
+use crate::Chapter;
+
+/// Formats seconds as `HH:MM:SS.mmm`, rounding to the nearest millisecond.
+/// Hours are not wrapped, so a 100 hour file still gets `100:00:00.000`.
+pub fn format_timestamp(seconds: f64) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    let ms = total_ms % 1000;
+    let total_seconds = total_ms / 1000;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        total_seconds / 3600,
+        total_seconds / 60 % 60,
+        total_seconds % 60,
+        ms
+    )
+}
+
+/// Makes a title safe as cue text: `&`, `<` and `>` would start markup, and
+/// a line break or `-->` would end the cue early.
+fn escape(title: &str) -> String {
+    title
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace(['\r', '\n'], " ")
+}
+
+/// Builds a WebVTT chapter track with one cue per chapter, in start order.
+///
+/// Chapters without an end run to the start of the next chapter, or to
+/// `file_duration` for the last one.
+pub fn write(chapters: &[Chapter], file_duration: Option<f64>) -> Result<String, AppError> {
+    let mut ordered: Vec<&Chapter> = chapters.iter().collect();
+    ordered.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let mut track = String::from("WEBVTT\n");
+    for (index, chapter) in ordered.iter().enumerate() {
+        let end = chapter
+            .end
+            .or_else(|| ordered.get(index + 1).map(|next| next.start))
+            .or(file_duration)
+            .ok_or_else(|| {
+                AppError::InvalidRange(format!(
+                    "Chapter '{}' has no end and the file length is unknown",
+                    chapter.title
+                ))
+            })?;
+        if end <= chapter.start {
+            return Err(AppError::InvalidRange(format!(
+                "Chapter '{}' ends ({}) before it starts ({})",
+                chapter.title, end, chapter.start
+            )));
+        }
+
+        track.push_str(&format!(
+            "\n{}\n{} --> {}\n{}\n",
+            index + 1,
+            format_timestamp(chapter.start),
+            format_timestamp(end),
+            escape(&chapter.title)
+        ));
+    }
+    Ok(track)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(title: &str, start: f64, end: Option<f64>) -> Chapter {
+        Chapter {
+            id: title.to_string(),
+            title: title.to_string(),
+            start,
+            end,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn formats_milliseconds_and_rolls_over_hours() {
+        assert_eq!(format_timestamp(0.0), "00:00:00.000");
+        assert_eq!(format_timestamp(61.2345), "00:01:01.235");
+        assert_eq!(format_timestamp(3599.9996), "01:00:00.000");
+        assert_eq!(format_timestamp(3723.5), "01:02:03.500");
+        assert_eq!(format_timestamp(360_000.0), "100:00:00.000");
+    }
+
+    #[test]
+    fn writes_cues_in_start_order() {
+        let chapters = [
+            chapter("Solo <live>", 3540.0, None),
+            chapter("Intro", 0.0, Some(3540.0)),
+        ];
+        assert_eq!(
+            write(&chapters, Some(3661.25)).unwrap(),
+            "WEBVTT\n\n1\n00:00:00.000 --> 00:59:00.000\nIntro\n\n2\n00:59:00.000 --> 01:01:01.250\nSolo &lt;live&gt;\n"
+        );
+    }
+
+    #[test]
+    fn an_open_last_chapter_needs_the_file_length() {
+        let chapters = [chapter("Intro", 0.0, None)];
+        assert!(matches!(
+            write(&chapters, None),
+            Err(AppError::InvalidRange(_))
+        ));
+    }
+}
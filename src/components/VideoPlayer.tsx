@@ -15,6 +15,16 @@ interface Chapter {
   title: string;
   start: number;
   end?: number;
+  source?: "ffprobe" | "chpl" | "text_track" | "cue" | "description" | "vorbis_comment" | "label_track" | "chapter_json" | "chapter_txt" | "auto_segment";
+  duration?: number;
+  language?: string;
+  color?: string;
+  tags?: string[];
+  loop_settings?: {
+    speed: number;
+    pitch_semitones: number;
+    loop_count?: number | null;
+  };
 }
 
 interface VideoPlayerProps {